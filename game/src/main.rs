@@ -40,24 +40,24 @@ struct Graphics<'a> {
 	map: Map,
 }
 
-fn draw(cg: &mut Graphics, scene: &tetrs::Scene) {
+fn draw(cg: &mut Graphics, scene: tetrs::SceneView, ghost_flash: bool, clears: tetrs::ClearedRows) {
 	cg.renderer.set_draw_color(Color::RGB(0, 0, 0));
 	cg.renderer.clear();
 	cg.renderer.copy(&cg.background, None, None).unwrap();
 
-	draw_scene2(cg, scene);
+	draw_scene2(cg, scene, ghost_flash);
+	draw_clears(cg, scene, clears);
 
 	cg.renderer.present();
 }
 
-fn draw_scene1(cg: &mut Graphics, scene: &tetrs::Scene) {
+fn draw_scene1(cg: &mut Graphics, scene: tetrs::SceneView) {
 	let width = scene.width() as i32;
 	let height = scene.height() as i32;
 	// Draw the scene
 	for row in 0..height {
-		let line = scene.line(row as i8);
-		for col in 0..width {
-			let tile = line[col as usize];
+		for (col, tile) in scene.try_line(row).unwrap().enumerate() {
+			let col = col as i32;
 			let x = cg.map.field_x + col * TILE_SIZE;
 			let y = cg.map.field_y + row * TILE_SIZE;
 			let rect = Rect::new(x, y, TILE_SIZE as u32, TILE_SIZE as u32);
@@ -104,13 +104,11 @@ fn draw_scene1(cg: &mut Graphics, scene: &tetrs::Scene) {
 	}
 }
 
-fn draw_scene2(cg: &mut Graphics, scene: &tetrs::Scene) {
-	let width = scene.width() as i32;
+fn draw_scene2(cg: &mut Graphics, scene: tetrs::SceneView, ghost_flash: bool) {
 	let height = scene.height() as i32;
 	for row in 0..height {
-		let line = scene.line(row as i8);
-		for col in 0..width {
-			let tile = line[col as usize];
+		for (col, tile) in scene.try_line(row).unwrap().enumerate() {
+			let col = col as i32;
 			let x = cg.map.field_x + col * TILE_SIZE;
 			let y = cg.map.field_y + row * TILE_SIZE;
 			let rect = Rect::new(x, y, TILE_SIZE as u32, TILE_SIZE as u32);
@@ -121,9 +119,17 @@ fn draw_scene2(cg: &mut Graphics, scene: &tetrs::Scene) {
 					let piece = tile.piece().map(|p| p as usize).unwrap_or(7);
 					let sprite = cg.sprites.pieces[piece];
 					cg.renderer.copy(&cg.atlas, Some(sprite), Some(rect)).unwrap();
+					draw_borders(cg, tile, x, y);
 				},
 				Ghost => {
-					cg.renderer.copy(&cg.atlas, Some(cg.sprites.ghost), Some(rect)).unwrap();
+					// Flash the ghost with the piece colors
+					let sprite = if ghost_flash {
+						cg.sprites.pieces[tile.piece().map(|p| p as usize).unwrap_or(7)]
+					}
+					else {
+						cg.sprites.ghost
+					};
+					cg.renderer.copy(&cg.atlas, Some(sprite), Some(rect)).unwrap();
 				},
 				Background => {
 				},
@@ -132,6 +138,37 @@ fn draw_scene2(cg: &mut Graphics, scene: &tetrs::Scene) {
 	}
 }
 
+/// Borders the sides of the tile which don't connect to another mino of its piece.
+fn draw_borders(cg: &mut Graphics, tile: tetrs::Tile, x: i32, y: i32) {
+	cg.renderer.set_draw_color(Color::RGB(0, 0, 0));
+	let size = TILE_SIZE as u32;
+	if !tile.connects_left() {
+		cg.renderer.fill_rect(Rect::new(x, y, 1, size)).unwrap();
+	}
+	if !tile.connects_right() {
+		cg.renderer.fill_rect(Rect::new(x + TILE_SIZE - 1, y, 1, size)).unwrap();
+	}
+	if !tile.connects_up() {
+		cg.renderer.fill_rect(Rect::new(x, y, size, 1)).unwrap();
+	}
+	if !tile.connects_down() {
+		cg.renderer.fill_rect(Rect::new(x, y + TILE_SIZE - 1, size, 1)).unwrap();
+	}
+}
+
+/// Outlines the rows which clear when the player is dropped.
+fn draw_clears(cg: &mut Graphics, scene: tetrs::SceneView, clears: tetrs::ClearedRows) {
+	let width = scene.width() as i32;
+	let height = scene.height() as i32;
+	cg.renderer.set_draw_color(Color::RGB(255, 255, 255));
+	for row in clears.iter() {
+		// Cleared rows count from the bottom, the scene is drawn from the top
+		let y = cg.map.field_y + (height - 1 - row as i32) * TILE_SIZE;
+		let rect = Rect::new(cg.map.field_x, y, (width * TILE_SIZE) as u32, TILE_SIZE as u32);
+		cg.renderer.draw_rect(rect).unwrap();
+	}
+}
+
 //----------------------------------------------------------------
 
 fn open_controller(gcs: &GameControllerSubsystem) -> Option<GameController> {
@@ -178,8 +215,12 @@ fn open_controller(gcs: &GameControllerSubsystem) -> Option<GameController> {
 
 //----------------------------------------------------------------
 
+#[allow(non_camel_case_types)]
 enum Command {
 	Quit,
+	ChangeTo_WorstBag,
+	ChangeTo_BestBag,
+	ChangeTo_OfficialBag,
 	Down(tetrs::Play),
 	Up(tetrs::Play),
 }
@@ -193,13 +234,17 @@ impl Command {
 			Quit { .. } => {
 				Some(Command::Quit)
 			},
-			KeyDown { keycode, .. } => match keycode {
+			KeyDown { keycode, repeat: false, .. } => match keycode {
+				Some(F1) => { Some(Command::ChangeTo_OfficialBag) },
+				Some(F2) => { Some(Command::ChangeTo_BestBag) },
+				Some(F3) => { Some(Command::ChangeTo_WorstBag) },
 				Some(Left) => { Some(Command::Down(MoveLeft)) },
 				Some(Right) => { Some(Command::Down(MoveRight)) },
 				Some(Down) => { Some(Command::Down(SoftDrop)) },
 				Some(Up) => { Some(Command::Down(RotateCW)) },
 				Some(Space) => { Some(Command::Down(HardDrop)) },
 				Some(LCtrl) => { Some(Command::Down(RotateCCW)) },
+				Some(A) => { Some(Command::Down(Rotate180)) },
 				_ => None,
 			},
 			KeyUp { keycode, .. } => match keycode {
@@ -209,6 +254,7 @@ impl Command {
 				Some(Up) => { Some(Command::Up(RotateCW)) },
 				Some(Space) => { Some(Command::Up(HardDrop)) },
 				Some(LCtrl) => { Some(Command::Up(RotateCCW)) },
+				Some(A) => { Some(Command::Up(Rotate180)) },
 				_ => None,
 			},
 			ControllerButtonDown { button, .. } => match button {
@@ -216,6 +262,7 @@ impl Command {
 				Button::DPadRight => { Some(Command::Down(MoveRight)) },
 				Button::DPadDown => { Some(Command::Down(SoftDrop)) },
 				Button::X => { Some(Command::Down(RotateCCW)) },
+				Button::LeftShoulder => { Some(Command::Down(Rotate180)) },
 				Button::Y => { Some(Command::Down(HardDrop)) },
 				Button::B => { Some(Command::Down(RotateCW)) },
 				Button::A => { Some(Command::Down(HardDrop)) },
@@ -226,6 +273,7 @@ impl Command {
 				Button::DPadRight => { Some(Command::Up(MoveRight)) },
 				Button::DPadDown => { Some(Command::Up(SoftDrop)) },
 				Button::X => { Some(Command::Up(RotateCCW)) },
+				Button::LeftShoulder => { Some(Command::Up(Rotate180)) },
 				Button::Y => { Some(Command::Up(HardDrop)) },
 				Button::B => { Some(Command::Up(RotateCW)) },
 				Button::A => { Some(Command::Up(HardDrop)) },
@@ -297,102 +345,155 @@ fn main() {
 	let mut events = sdl_context.event_pump().unwrap();
 
 	// Tetris game state
-	let mut state = tetrs::State::new(10, 22);
-	let mut bot = tetrs::PlayI { score: 0.0, play: Vec::new(), player: None };
-	let mut play_i = 0;
-	let mut bag = tetrs::OfficialBag::default();
-	let speed = tetrs::Clock {
+	let mut state = tetrs::State::standard();
+	let mut demo = tetrs::PacedReplay::new(Vec::new(), 1, 0);
+	let mut bag: Box<dyn tetrs::Bag> = Box::new(tetrs::OfficialBag::default());
+	// The gravity follows the guideline curve, speeding up every 10 lines
+	let clock = tetrs::Clock {
 		gravity: 40,
 		player: 8,
+		// Half a second at 60 frames per second
+		lock_delay: 30,
+	};
+	let mut levels = tetrs::LevelProgress::new(tetrs::Gravity::Guideline, clock);
+	let mut input = tetrs::Input::new(levels.clock());
+	// Ask for confirmation when dropping far away from where the bot would
+	let guard = tetrs::MisdropGuard {
+		max_distance: Some(3),
+		new_holes: Some(2),
+		confirm_ticks: 30,
 	};
-	let mut timers = speed;
-	let mut action = tetrs::Play::Idle;
+	state.set_misdrop_guard(Some(guard));
+	// Switch to the demo after a minute without input
+	input.set_idle_timeout(Some(60 * 60));
+	let mut ghost_flash = 0;
+	let mut title = "Tetrs";
 
 	'quit: loop {
 		if !state.is_game_over() && state.player().is_none() {
 			use tetrs::Bag;
-			let next_piece = bag.next(state.well()).unwrap();
-			if !state.spawn(next_piece) {
-				bot = tetrs::PlayI::play(&tetrs::Weights::default(), state.well(), *state.player().unwrap());
-				play_i = 0;
+			// A finite bag running out of pieces finishes the game
+			if !state.spawn_from(&mut bag) {
+				// Look ahead at the next piece, near the top keep its spawn clear instead
+				let mut bot = match bag.peek().first() {
+					Some(&next) => {
+						let safety = tetrs::SpawnSafety::avoid_spawn_columns_above(state.dimensions().1 as i32 - 6, next);
+						if state.well().stack_height() > safety.height_threshold {
+							tetrs::PlayI::play_spawn_safe(&tetrs::Weights::default(), state.well(), *state.player().unwrap(), safety)
+						}
+						else {
+							tetrs::PlayI::play2(&tetrs::Weights::default(), state.well(), *state.player().unwrap(), next)
+						}
+					},
+					None => tetrs::PlayI::play(&tetrs::Weights::default(), state.well(), *state.player().unwrap()),
+				};
+				state.set_intended(bot.player);
+				// Drop straight down instead of a row per tick
+				bot.compress(state.well(), *state.player().unwrap());
+				demo = bot.into_paced(1, 0);
 			}
 		}
+		// The demo restarts on game over
+		if state.is_game_over() && input.control_mode() == tetrs::ControlMode::Demo {
+			state = tetrs::State::standard();
+			state.set_misdrop_guard(Some(guard));
+		}
 
 		for e in events.poll_iter() {
 			match Command::from_event(e) {
 				Some(Command::Quit) => break 'quit,
+				// The new bag deals from the next piece on
+				Some(Command::ChangeTo_OfficialBag) => bag = Box::new(tetrs::OfficialBag::default()),
+				Some(Command::ChangeTo_BestBag) => bag = Box::new(tetrs::BestBag::default()),
+				Some(Command::ChangeTo_WorstBag) => bag = Box::new(tetrs::WorstBag::default()),
 				Some(Command::Down(play)) => {
 					// println!("action={:?}", action);
 					match play {
-						tetrs::Play::RotateCW => {
-							state.rotate_cw();
-						},
-						tetrs::Play::RotateCCW => {
-							state.rotate_ccw();
-						},
-						tetrs::Play::HardDrop => {
-							state.hard_drop();
-						},
-						tetrs::Play::MoveLeft | tetrs::Play::MoveRight | tetrs::Play::SoftDrop => {
-							action = play;
-						},
+						tetrs::Play::RotateCW => input.rotate_cw(),
+						tetrs::Play::RotateCCW => input.rotate_ccw(),
+						tetrs::Play::Rotate180 => input.rotate_180(),
+						tetrs::Play::HardDrop => input.hard_drop(),
+						tetrs::Play::MoveLeft => input.move_left_down(),
+						tetrs::Play::MoveRight => input.move_right_down(),
+						tetrs::Play::SoftDrop => input.soft_drop_down(),
 						_ => {},
 					};
 				},
 				Some(Command::Up(play)) => {
-					if play == tetrs::Play::MoveLeft || play == tetrs::Play::MoveRight || play == tetrs::Play::SoftDrop {
-						action = tetrs::Play::Idle;
-					}
+					match play {
+						tetrs::Play::MoveLeft => input.move_left_up(),
+						tetrs::Play::MoveRight => input.move_right_up(),
+						tetrs::Play::SoftDrop => input.soft_drop_up(),
+						_ => {},
+					};
 				},
 				None => {
 				},
 			}
 		}
-		// println!("{:?}", action);
+		// The bot plays the demo one move per tick
+		if input.control_mode() == tetrs::ControlMode::Demo && state.player().is_some() {
+			if demo.is_done() {
+				input.play(&mut state, tetrs::Play::HardDrop);
+			}
+			for play in demo.tick().iter().filter_map(|action| action.to_play()) {
+				input.play(&mut state, play);
+			}
+		}
 
-		// Decrement the timers
-		timers.gravity -= 1;
-		if timers.gravity == 0 {
-			timers.gravity = speed.gravity;
-			// state.gravity();
+		let actions = input.tick(&mut state);
+		if actions.contains(tetrs::Actions::CONFIRM) {
+			ghost_flash = guard.confirm_ticks;
 		}
-		if timers.player > 0 {
-			timers.player -= 1;
+		else if actions.contains(tetrs::Actions::HARD_DROP) {
+			ghost_flash = 0;
 		}
-		else {
-			match action {
-				tetrs::Play::MoveLeft => {
-					state.move_left();
-					timers.player = speed.player;
-				},
-				tetrs::Play::MoveRight => {
-					state.move_right();
-					timers.player = speed.player;
-				},
-				tetrs::Play::SoftDrop => {
-					state.soft_drop();
-					timers.player = speed.player;
-				},
-				_ => {},
-			}
+		// Fresh games for the demo and for the human taking over from it
+		if actions.contains(tetrs::Actions::IDLE_TIMEOUT) {
+			input.start_demo();
+			state = tetrs::State::standard();
+			state.set_misdrop_guard(Some(guard));
+			levels = tetrs::LevelProgress::new(tetrs::Gravity::Guideline, clock);
+			input.set_clock(levels.clock());
+		}
+		if actions.contains(tetrs::Actions::DEMO_INTERRUPTED) {
+			state = tetrs::State::standard();
+			state.set_misdrop_guard(Some(guard));
+			levels = tetrs::LevelProgress::new(tetrs::Gravity::Guideline, clock);
+			input.set_clock(levels.clock());
+		}
+
+		let lines = state.clear_lines(|_| ());
+		if levels.add_lines(lines as u32) {
+			input.set_clock(levels.clock());
 		}
 
-		// if play_i < bot.play.len() {
-		// 	match bot.play[play_i] {
-		// 		tetrs::Play::Idle => (),
-		// 		tetrs::Play::MoveLeft => { state.move_left(); },
-		// 		tetrs::Play::MoveRight => { state.move_right(); },
-		// 		tetrs::Play::RotateCW => { state.rotate_cw(); },
-		// 		tetrs::Play::RotateCCW => { state.rotate_ccw(); },
-		// 		tetrs::Play::SoftDrop => { state.soft_drop(); },
-		// 		tetrs::Play::HardDrop => { state.hard_drop(); },
-		// 	}
-		// 	play_i += 1;
-		// }
-
-		state.clear_lines(|_| ());
-
-		draw(&mut cg, &state.scene());
+		if ghost_flash > 0 {
+			ghost_flash -= 1;
+		}
+		// Preview the rows the player clears when dropped
+		let clears = state.player()
+			.and_then(|&player| state.simulate_lock(tetrs::trace_down(state.well(), player)))
+			.map(|lock| lock.cleared_rows)
+			.unwrap_or_default();
+		draw(&mut cg, state.scene_view(), ghost_flash / 4 % 2 == 1, clears);
+
+		// Without text rendering the window title tells how the game ended
+		let end_title = match state.game_end() {
+			Some(tetrs::GameEnd::OutOfPieces) => "Tetrs - Finished",
+			Some(tetrs::GameEnd::TopOut) => "Tetrs - Game Over (top out)",
+			Some(tetrs::GameEnd::BlockOut) => "Tetrs - Game Over (block out)",
+			Some(tetrs::GameEnd::LockOut) => "Tetrs - Game Over (lock out)",
+			Some(tetrs::GameEnd::TimedOut) => "Tetrs - Game Over (out of time)",
+			Some(_) => "Tetrs - Game Over",
+			None => "Tetrs",
+		};
+		if end_title != title {
+			title = end_title;
+			if let Some(window) = cg.renderer.window_mut() {
+				let _ = window.set_title(title);
+			}
+		}
 
 		thread::sleep(Duration::from_millis(16));
 	}
@@ -1,80 +1,72 @@
 extern crate rand;
 extern crate tetrs;
 
-use rand::{thread_rng, Rng};
+use std::{env, thread};
+use std::fmt::Debug;
+use std::time::Instant;
+
+use rand::{thread_rng, Rand, Rng};
+use tetrs::Evaluator;
+use tetrs::learn::{Genome, Population};
 
 const NUM_GAMES: usize = 100;
-const MAX_MOVES: usize = 2000;
 const POP_SIZE: usize = 100;
-const MAX_ITERS: usize = 200;
+const GENERATIONS: usize = 50;
+// Cheese races dig through this many garbage lines, unfinished races count the piece limit
+const CHEESE_LINES: u8 = 10;
+const CHEESE_PIECES: u32 = 200;
 
 fn main() {
-	let mut rng = thread_rng();
-
-	// Learning from entropy :)
-	let mut best_weights: tetrs::Weights = rng.gen();
-	let mut best_score = fitness(&best_weights);
-	let mut iterations = 0;
-
-	loop {
-		let weights: tetrs::Weights = rng.gen();
-		let score = fitness(&weights);
-		if score < best_score {
-			best_weights = weights;
-			best_score = score;
-			iterations = 0;
-			// println!("{:#?}", weights);
-		}
-		iterations += 1;
-		if iterations > MAX_ITERS {
-			break;
-		}
+	// Reproducible runs with `--seed <n>`
+	let seed = env::args().skip_while(|arg| arg != "--seed").nth(1).map(|seed| seed.parse().expect("invalid seed"));
+	// Optimize the weights of all the phases jointly
+	if env::args().any(|arg| arg == "--phased") {
+		learn::<tetrs::PhasedEvaluator>(tetrs::learn::play_game, seed);
 	}
-	println!("{:#?}", best_weights);
-}
-
-fn fitness(weights: &tetrs::Weights) -> i32 {
-	let mut fitness = 0;
-	for _ in 0..NUM_GAMES {
-		fitness += play_game(weights);
+	// Optimize the weights for digging, see `Weights::downstacking`
+	else if env::args().any(|arg| arg == "--cheese") {
+		learn::<tetrs::Weights>(cheese_game, seed);
+	}
+	else {
+		learn::<tetrs::Weights>(tetrs::learn::play_game, seed);
 	}
-	fitness
 }
 
-fn play_game(weights: &tetrs::Weights) -> i32 {
-	let mut state = tetrs::State::new(10, 11); // Reduce number of rows for speedup
-	let mut bag = tetrs::OfficialBag::default();
-	let mut score = 0;
-	let mut moves = 0;
-	loop {
-		// Spawn a new player
-		use tetrs::Bag;
-		let next_piece = bag.next(state.well()).unwrap();
-		state.spawn(next_piece);
+fn learn<E: Evaluator + Genome + Rand + Sync + Debug>(game: fn(&E, u64) -> i64, seed: Option<u64>) {
+	let seed = seed.unwrap_or_else(|| thread_rng().gen());
+	println!("seed: {}", seed);
+	// Every candidate plays the same games, seeded by `tetrs::learn::game_seeds`
+	let fitness = |genomes: &[E]| -> Vec<f64> {
+		tetrs::learn::evaluate_parallel_with(genomes, NUM_GAMES, seed, game).into_iter().map(|score| score as f64).collect()
+	};
 
-		// Let the AI play a piece
-		let &player = state.player().unwrap();
-		let bot = tetrs::PlayI::play(&weights, state.well(), player);
-
-		// No need to actually play the moves, just teleport the player
-		if let Some(player) = bot.player {
-			state.set_player(player);
-			state.lock();
-		}
-		else {
-			// Game over, didn't find a valid move that wouldn't make us lose
-			break;
-		}
+	let mut population = Population::<E, _>::from_seed(POP_SIZE, seed);
+	report_speedup(&population.individuals()[0].genome, game, seed);
+	population.evaluate_batch(fitness);
+	for _ in 0..GENERATIONS {
+		population.evolve_batch(fitness);
+		println!("generation {}: {}", population.generation(), population.best().fitness);
+	}
+	println!("{:#?}", population.best().genome);
+}
 
-		// Clear the lines
-		state.clear_lines(|_| score += 1);
+// Times a few games of one candidate played serially against the same games played on all cores
+fn report_speedup<E: Evaluator + Sync>(genome: &E, game: fn(&E, u64) -> i64, seed: u64) {
+	let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	let seeds = tetrs::learn::game_seeds(threads * 2, seed);
+	let start = Instant::now();
+	let serial: i64 = seeds.iter().map(|&seed| game(genome, seed)).sum();
+	let serial_time = start.elapsed();
+	let start = Instant::now();
+	let parallel = tetrs::learn::evaluate_parallel_with(&[genome], threads * 2, seed, |genome, seed| game(genome, seed));
+	let parallel_time = start.elapsed();
+	assert_eq!(vec![serial], parallel);
+	println!("parallel speedup: {:.1}x on {} threads", serial_time.as_secs_f64() / parallel_time.as_secs_f64().max(1e-9), threads);
+}
 
-		// Break out if the AI is too good :)
-		moves += 1;
-		if moves >= MAX_MOVES {
-			println!("breakout!");
-			break;
-		}
-	}
-	score
+// Pieces used to clear the cheese board, negated so fewer pieces is fitter
+fn cheese_game<E: Evaluator + Clone>(weights: &E, seed: u64) -> i64 {
+	let mut race = tetrs::CheeseRace::new(seed, CHEESE_LINES);
+	let result = race.run(&mut weights.clone(), CHEESE_PIECES);
+	-(if result.finished { result.pieces } else { CHEESE_PIECES } as i64)
 }
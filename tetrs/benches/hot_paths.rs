@@ -0,0 +1,201 @@
+/*!
+Benchmarks for the hot paths of the engine.
+
+Run with `cargo bench`, a name given on the command line runs only the benchmarks containing it.
+
+The built-in bench harness requires a nightly compiler, this minimal harness runs on stable.
+Every benchmark is calibrated to run for about `TARGET` and reports the mean time per iteration.
+*/
+
+extern crate tetrs;
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tetrs::{Piece, Player, PlayI, Point, Rot, State, Weights, Well};
+
+//----------------------------------------------------------------
+
+const TARGET: Duration = Duration::from_millis(500);
+
+fn bench<F: FnMut()>(filter: &Option<String>, name: &str, mut f: F) {
+	if let Some(ref filter) = *filter {
+		if !name.contains(filter.as_str()) {
+			return;
+		}
+	}
+	// Double the iterations until a run takes long enough to time reliably
+	let mut iters = 1u64;
+	loop {
+		let start = Instant::now();
+		for _ in 0..iters {
+			f();
+		}
+		let elapsed = start.elapsed();
+		if elapsed >= TARGET / 10 {
+			iters = (iters as u128 * TARGET.as_nanos() / elapsed.as_nanos().max(1)).max(1) as u64;
+			break;
+		}
+		iters *= 2;
+	}
+	let start = Instant::now();
+	for _ in 0..iters {
+		f();
+	}
+	let ns = start.elapsed().as_nanos() as f64 / iters as f64;
+	println!("{:<28} {:>12.1} ns/iter ({} iterations)", name, ns, iters);
+}
+
+//----------------------------------------------------------------
+
+// Ragged stack filling about half of the well, holes under the overhangs
+fn half_full() -> Well {
+	"|          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |     #    |\n\
+	 |#   ###   |\n\
+	 |##  ### # |\n\
+	 |## #### ##|\n\
+	 |## ## ####|\n\
+	 |#### #####|\n\
+	 |# ####### |\n\
+	 |## ###### |\n\
+	 |######## #|\n\
+	 |### ######|\n\
+	 |#### #####|\n\
+	 |## #######|".parse().unwrap()
+}
+
+// Checkerboard with a few rows knocked out, lots of holes and caves to flood
+fn dense() -> Well {
+	let mut well = Well::new(10, 22);
+	let cols: Vec<_> = well.col_range().collect();
+	for row in 0..18 {
+		if row % 5 == 4 {
+			continue;
+		}
+		let line = cols.iter().enumerate().filter(|&(col, _)| (col + row as usize).is_multiple_of(2)).fold(0, |line, (_, &col)| line | col);
+		well.set_line(row, line);
+	}
+	well
+}
+
+// Typical well a few pieces into a game, a well left open for a tetris
+fn mid_game() -> Well {
+	"|          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |  #       |\n\
+	 |  ##   ## |\n\
+	 |# ###  ## |\n\
+	 |########  |\n\
+	 |######### |\n\
+	 |######### |".parse().unwrap()
+}
+
+// Four full lines waiting to be cleared below a few rows of rubble
+fn pending_clears() -> Well {
+	"|          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |          |\n\
+	 |   #      |\n\
+	 | ####  #  |\n\
+	 |##########|\n\
+	 |### ######|\n\
+	 |##########|\n\
+	 |##########|\n\
+	 |##### ####|\n\
+	 |##########|\n\
+	 |## #######|".parse().unwrap()
+}
+
+//----------------------------------------------------------------
+
+fn main() {
+	// Cargo passes `--bench`, the first other argument filters the benchmarks
+	let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+	let weights = Weights::default();
+
+	let well = half_full();
+	bench(&filter, "test_all_placements", || {
+		let mut collisions = 0;
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				let sprite = piece.sprite(rot);
+				for x in -2..well.width() {
+					for y in 0..well.height() {
+						collisions += black_box(&well).test(sprite, Point::new(x, y)) as u32;
+					}
+				}
+			}
+		}
+		black_box(collisions);
+	});
+	bench(&filter, "test_fast_all_placements", || {
+		let mut collisions = 0;
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				for x in -2..well.width() {
+					for y in 0..well.height() {
+						collisions += black_box(&well).test_fast(piece, rot, Point::new(x, y)) as u32;
+					}
+				}
+			}
+		}
+		black_box(collisions);
+	});
+
+	let well = dense();
+	bench(&filter, "count_holes_dense", || {
+		black_box(black_box(&well).count_holes());
+	});
+
+	let well = half_full();
+	bench(&filter, "weights_eval", || {
+		black_box(weights.eval(black_box(&well)));
+	});
+
+	let well = mid_game();
+	let player = Player::new(Piece::T, Rot::Zero, Point::new(3, well.height() - 2));
+	bench(&filter, "playi_play_mid_game", || {
+		black_box(PlayI::play(&weights, black_box(&well), player));
+	});
+
+	let well = pending_clears();
+	bench(&filter, "clear_lines_4", || {
+		let mut state = State::with_well(well);
+		black_box(state.clear_lines(|_| ()));
+	});
+}
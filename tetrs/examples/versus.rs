@@ -0,0 +1,152 @@
+/*!
+Two player versus in the terminal, you against the bot.
+
+Run with `cargo run --example versus`, the seed is the optional argument.
+
+Every line you enter is a move for the left well, `left`, `right`, `cw`, `ccw`, `180`, `soft`, `hard` or `firm`.
+An empty line waits a tick, `quit` ends the match. The bot plays one move per tick in the right well.
+Locks clear lines and send garbage to the opponent as in `tetrs::scoring::attack`.
+*/
+
+extern crate tetrs;
+
+use std::env;
+use std::io::prelude::*;
+
+use tetrs::{Action, CompactStyle, Match, MatchStatus, OfficialBag, Play, PlayI, SeededBag, State, Weights};
+
+/// The match with the bot's plan for its current piece.
+struct Versus {
+	game: Match<SeededBag>,
+	weights: Weights,
+	plan: Vec<Action>,
+	locked: u32,
+}
+
+impl Versus {
+	fn new(seed: u64) -> Versus {
+		// Both players get the same pieces
+		let players = vec![
+			(State::standard(), OfficialBag::from_seed(seed)),
+			(State::standard(), OfficialBag::from_seed(seed)),
+		];
+		Versus {
+			game: Match::new(seed, players),
+			weights: Weights::default(),
+			plan: Vec::new(),
+			locked: 0,
+		}
+	}
+
+	/// Plays the human's move, the bot's next move and ticks the match.
+	fn step(&mut self, play: Play) {
+		self.game.play(0, play);
+		self.bot();
+		self.game.tick();
+	}
+
+	// Plans the path of every new piece, then plays it one move per tick
+	fn bot(&mut self) {
+		let state = self.game.player(1).state();
+		let player = match state.player() {
+			Some(&player) => player,
+			None => return,
+		};
+		if self.plan.is_empty() || state.stats().locked != self.locked {
+			self.locked = state.stats().locked;
+			let mut plan = PlayI::play(&self.weights, state.well(), player).actions;
+			plan.push(Action::HardDrop);
+			plan.reverse();
+			self.plan = plan;
+		}
+		if let Some(play) = self.plan.pop().and_then(Action::to_play) {
+			self.game.play(1, play);
+		}
+	}
+
+	/// Draws both wells side by side with the pending garbage below.
+	fn draw(&self) -> String {
+		let scenes: Vec<String> = self.game.players().iter().map(|player| player.state().scene().render_compact(CompactStyle::HalfBlock, true)).collect();
+		let mut out = String::new();
+		for (left, right) in scenes[0].lines().zip(scenes[1].lines()) {
+			out.push_str(&format!("|{}|  |{}|\n", left, right));
+		}
+		let you = self.game.player(0);
+		let bot = self.game.player(1);
+		out.push_str(&format!("pending {:<5} pending {}\n", you.pending(), bot.pending()));
+		out
+	}
+
+	/// Combines the checksums of both states, the same seed and moves give the same checksum.
+	fn checksum(&self) -> u64 {
+		self.game.players().iter().fold(0, |hash, player| hash.rotate_left(17) ^ player.state().checksum())
+	}
+}
+
+/// Runs the match on the lines of input until the match is over, the input ends or after the ticks.
+fn run<I: Iterator<Item = String>>(versus: &mut Versus, lines: I, ticks: usize, mut draw: impl FnMut(&Versus)) {
+	for line in lines.take(ticks) {
+		let line = line.trim();
+		if line == "quit" {
+			break;
+		}
+		let play = if line.is_empty() { Play::Idle } else {
+			match line.parse() {
+				Ok(play) => play,
+				Err(err) => {
+					println!("{}", err);
+					continue;
+				},
+			}
+		};
+		versus.step(play);
+		draw(versus);
+		if versus.game.status() != MatchStatus::Playing {
+			break;
+		}
+	}
+}
+
+fn main() {
+	let seed = env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(1);
+	let mut versus = Versus::new(seed);
+	print!("{}>>> ", versus.draw());
+	std::io::stdout().flush().unwrap();
+	let stdin = std::io::stdin();
+	run(&mut versus, stdin.lock().lines().map_while(Result::ok), usize::MAX, |versus| {
+		print!("{}>>> ", versus.draw());
+		std::io::stdout().flush().unwrap();
+	});
+	match versus.game.status() {
+		MatchStatus::Won(0) => println!("\nYou win!"),
+		MatchStatus::Won(_) => println!("\nThe bot wins!"),
+		MatchStatus::Draw => println!("\nDraw!"),
+		MatchStatus::Playing => println!("\nAbandoned."),
+	}
+	println!("Seed {} checksum {:016x}", seed, versus.checksum());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn script() -> impl Iterator<Item = String> {
+		["left", "cw", "", "hard", "right", "right", "soft", "hard", "ccw", "", "", "firm", "hard"].iter().cycle().map(|s| s.to_string())
+	}
+
+	#[test]
+	fn headless() {
+		let mut versus = Versus::new(7);
+		let mut ticks = 0;
+		run(&mut versus, script(), 200, |versus| {
+			ticks += 1;
+			assert!(versus.draw().lines().count() > 11);
+		});
+		// The bot outlasts the scripted player or the match goes on, the same way every time
+		let mut again = Versus::new(7);
+		run(&mut again, script(), 200, |_| ());
+		assert_eq!(versus.checksum(), again.checksum());
+		assert!(matches!(versus.game.status(), MatchStatus::Won(1) | MatchStatus::Playing));
+		assert!(ticks == 200 || versus.game.status() != MatchStatus::Playing);
+	}
+}
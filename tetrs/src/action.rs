@@ -0,0 +1,205 @@
+/*!
+Engine actions.
+*/
+
+use ::Play;
+
+/// Atomic engine operation, performed with `State::step`.
+///
+/// A `Play` is what the player asked for, the frontends and the `Input` work with those.
+/// An action is what the engine does: the bot paths are made of actions, every action changes the state or fails.
+/// Waiting is not an action, held keys repeating their move are many actions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum Action {
+	MoveLeft,
+	MoveRight,
+	RotateCW,
+	RotateCCW,
+	Rotate180,
+	/// Moves down a row, see `State::soft_drop`.
+	SoftDrop,
+	/// Moves down to the stack without locking, see `State::firm_drop`.
+	SonicDrop,
+	HardDrop,
+	/// Locks the player where it is, see `State::lock`.
+	Lock,
+}
+
+static ACTIONS: [Action; 9] = [
+	Action::MoveLeft,
+	Action::MoveRight,
+	Action::RotateCW,
+	Action::RotateCCW,
+	Action::Rotate180,
+	Action::SoftDrop,
+	Action::SonicDrop,
+	Action::HardDrop,
+	Action::Lock,
+];
+
+impl Action {
+	/// Returns every action in declaration order.
+	pub fn all() -> &'static [Action] {
+		&ACTIONS
+	}
+	/// Lowers the move to its action, `None` for `Play::Idle`.
+	pub fn from_play(play: Play) -> Option<Action> {
+		match play {
+			Play::MoveLeft => Some(Action::MoveLeft),
+			Play::MoveRight => Some(Action::MoveRight),
+			Play::RotateCW => Some(Action::RotateCW),
+			Play::RotateCCW => Some(Action::RotateCCW),
+			Play::Rotate180 => Some(Action::Rotate180),
+			Play::SoftDrop => Some(Action::SoftDrop),
+			Play::FirmDrop => Some(Action::SonicDrop),
+			Play::HardDrop => Some(Action::HardDrop),
+			Play::Idle => None,
+		}
+	}
+	/// Returns the move performing the action, `None` for `Action::Lock` which has no key.
+	pub fn to_play(self) -> Option<Play> {
+		match self {
+			Action::MoveLeft => Some(Play::MoveLeft),
+			Action::MoveRight => Some(Play::MoveRight),
+			Action::RotateCW => Some(Play::RotateCW),
+			Action::RotateCCW => Some(Play::RotateCCW),
+			Action::Rotate180 => Some(Play::Rotate180),
+			Action::SoftDrop => Some(Play::SoftDrop),
+			Action::SonicDrop => Some(Play::FirmDrop),
+			Action::HardDrop => Some(Play::HardDrop),
+			Action::Lock => None,
+		}
+	}
+}
+
+/// Plays back a path of actions paced over the ticks, see `PlayI::paced`.
+///
+/// After the start delay a burst of actions is due every `every` ticks, one tick by default.
+/// The actions are borrowed or owned through `A`, the replay never allocates.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PacedReplay<A> {
+	actions: A,
+	pos: usize,
+	per_tick: u8,
+	interval: u8,
+	delay: u8,
+	tick: u32,
+}
+
+impl<A: AsRef<[Action]>> PacedReplay<A> {
+	/// Creates a replay of the actions, `actions_per_tick` actions per burst after `start_delay` ticks.
+	pub fn new(actions: A, actions_per_tick: u8, start_delay: u8) -> PacedReplay<A> {
+		PacedReplay {
+			actions: actions,
+			pos: 0,
+			per_tick: actions_per_tick,
+			interval: 1,
+			delay: start_delay,
+			tick: 0,
+		}
+	}
+	/// Spaces the bursts the given number of ticks apart.
+	///
+	/// # Panics
+	///
+	/// Panics if the ticks are zero.
+	pub fn every(mut self, ticks: u8) -> PacedReplay<A> {
+		assert!(ticks > 0, "bursts must be at least a tick apart");
+		self.interval = ticks;
+		self
+	}
+	/// Returns all the actions of the path.
+	pub fn actions(&self) -> &[Action] {
+		self.actions.as_ref()
+	}
+	/// Returns the number of actions not played yet.
+	pub fn remaining(&self) -> usize {
+		self.actions().len() - self.pos
+	}
+	/// Returns if every action was played or the replay was stopped.
+	pub fn is_done(&self) -> bool {
+		self.remaining() == 0
+	}
+	/// Rewinds to the first action and restarts the start delay.
+	pub fn reset(&mut self) {
+		self.pos = 0;
+		self.tick = 0;
+	}
+	/// Skips the remaining actions.
+	pub fn stop(&mut self) {
+		self.pos = self.actions().len();
+	}
+	/// Advances a tick and returns the actions due, empty between the bursts.
+	pub fn tick(&mut self) -> &[Action] {
+		let tick = self.tick;
+		self.tick = self.tick.saturating_add(1);
+		if tick < self.delay as u32 || !(tick - self.delay as u32).is_multiple_of(self.interval as u32) {
+			return &[];
+		}
+		let start = self.pos;
+		self.pos = (start + self.per_tick as usize).min(self.actions().len());
+		&self.actions.as_ref()[start..self.pos]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn conversions() {
+		for &play in Play::all() {
+			match Action::from_play(play) {
+				Some(action) => assert_eq!(Some(play), action.to_play()),
+				None => assert_eq!(Play::Idle, play),
+			}
+		}
+		for (i, &action) in Action::all().iter().enumerate() {
+			assert_eq!(i, action as u8 as usize);
+			match action.to_play() {
+				Some(play) => assert_eq!(Some(action), Action::from_play(play)),
+				None => assert_eq!(Action::Lock, action),
+			}
+		}
+	}
+
+	fn schedule(mut replay: PacedReplay<&[Action]>, ticks: usize) -> Vec<Vec<Action>> {
+		(0..ticks).map(|_| replay.tick().to_vec()).collect()
+	}
+
+	#[test]
+	fn pacing() {
+		use self::Action::*;
+		let path = [MoveLeft, MoveLeft, RotateCW, SoftDrop];
+		// One action per tick after two ticks of delay
+		let one = PacedReplay::new(&path[..], 1, 2);
+		assert_eq!(vec![vec![], vec![], vec![MoveLeft], vec![MoveLeft], vec![RotateCW], vec![SoftDrop], vec![]], schedule(one, 7));
+		// One action every third tick
+		let slow = PacedReplay::new(&path[..], 1, 0).every(3);
+		let expected = [vec![MoveLeft], vec![], vec![], vec![MoveLeft], vec![], vec![], vec![RotateCW], vec![], vec![], vec![SoftDrop]];
+		assert_eq!(&expected[..], &schedule(slow, 10)[..]);
+		// Bursts of three, then two ticks of rest
+		let burst = PacedReplay::new(&path[..], 3, 0).every(3);
+		assert_eq!(vec![vec![MoveLeft, MoveLeft, RotateCW], vec![], vec![], vec![SoftDrop], vec![]], schedule(burst, 5));
+	}
+
+	#[test]
+	fn progress() {
+		let path = vec![Action::MoveRight; 5];
+		let mut replay = PacedReplay::new(path, 2, 0);
+		assert_eq!(5, replay.remaining());
+		let first: Vec<Vec<Action>> = (0..4).map(|_| replay.tick().to_vec()).collect();
+		assert!(replay.is_done());
+		// Replays identically after the reset
+		replay.reset();
+		assert_eq!(5, replay.remaining());
+		let again: Vec<Vec<Action>> = (0..4).map(|_| replay.tick().to_vec()).collect();
+		assert_eq!(first, again);
+		replay.reset();
+		replay.tick();
+		replay.stop();
+		assert!(replay.is_done() && replay.tick().is_empty());
+	}
+}
@@ -0,0 +1,214 @@
+/*!
+Explains why a player can't be placed.
+
+Editors and servers validating placements reported by clients use these to turn a rejected player into an actionable message:
+
+```
+use tetrs::{Piece, Player, Point, Rot, Well};
+use tetrs::analysis::{IllegalReason, explain_illegal};
+
+let well = Well::new(10, 22);
+let player = Player::new(Piece::O, Rot::Zero, Point::new(-2, 1));
+assert_eq!(Some(IllegalReason::OutOfBoundsLeft { by: 1 }), explain_illegal(&well, player));
+```
+*/
+
+use ::std::{error, fmt};
+
+use ::{Player, Point, Well, MAX_WIDTH, MAX_HEIGHT, spawn_player, srs_cw, srs_ccw, test_player, trace_down};
+
+/// Reason a player is not a legal placement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum IllegalReason {
+	/// Cells stick out of the left wall by the given number of columns.
+	OutOfBoundsLeft { by: i8 },
+	/// Cells stick out of the right wall by the given number of columns.
+	OutOfBoundsRight { by: i8 },
+	/// Cells stick out of the floor by the given number of rows.
+	OutOfBoundsBelow { by: i8 },
+	/// The cells overlapping blocks of the well, from the top row down and left to right.
+	OverlapsBlocks(Vec<Point>),
+	/// The player doesn't rest on the stack or the floor, it falls the given number of rows.
+	FloatingNotSupported { distance: i8 },
+	/// The player can't be moved there from the spawn position.
+	UnreachableFromSpawn,
+}
+
+impl fmt::Display for IllegalReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			IllegalReason::OutOfBoundsLeft { by } => write!(f, "sticks out of the left wall by {}", by),
+			IllegalReason::OutOfBoundsRight { by } => write!(f, "sticks out of the right wall by {}", by),
+			IllegalReason::OutOfBoundsBelow { by } => write!(f, "sticks out of the floor by {}", by),
+			IllegalReason::OverlapsBlocks(ref cells) => {
+				f.write_str("overlaps blocks at")?;
+				for cell in cells {
+					write!(f, " ({}, {})", cell.x, cell.y)?;
+				}
+				Ok(())
+			},
+			IllegalReason::FloatingNotSupported { distance } => write!(f, "floats {} rows above the stack", distance),
+			IllegalReason::UnreachableFromSpawn => f.write_str("unreachable from the spawn position"),
+		}
+	}
+}
+impl error::Error for IllegalReason {}
+
+/// Explains why the player can't be in the well, `None` if it fits.
+///
+/// The walls and the floor are checked first, then the blocks of the well. Cells above the well are allowed.
+pub fn explain_illegal(well: &Well, player: Player) -> Option<IllegalReason> {
+	let cells: Vec<Point> = player.sprite().cells().map(|cell| Point::new(player.pt.x + cell.x, player.pt.y - cell.y)).collect();
+	let left = cells.iter().map(|cell| cell.x).min().unwrap_or(0);
+	let right = cells.iter().map(|cell| cell.x).max().unwrap_or(0);
+	let bottom = cells.iter().map(|cell| cell.y).min().unwrap_or(0);
+	if left < 0 {
+		return Some(IllegalReason::OutOfBoundsLeft { by: -left });
+	}
+	if right >= well.width() {
+		return Some(IllegalReason::OutOfBoundsRight { by: right - well.width() + 1 });
+	}
+	if bottom < 0 {
+		return Some(IllegalReason::OutOfBoundsBelow { by: -bottom });
+	}
+	let overlaps: Vec<Point> = cells.into_iter().filter(|cell| is_block(well, *cell)).collect();
+	if !overlaps.is_empty() {
+		return Some(IllegalReason::OverlapsBlocks(overlaps));
+	}
+	None
+}
+
+/// Explains why the player isn't a legal place to lock, `None` if it is.
+///
+/// Besides fitting in the well, see `explain_illegal`, the player must rest on the stack or the floor.
+/// The expensive reachability check moves, soft drops and rotates with the SRS kicks from the spawn position, see `spawn_player`.
+pub fn explain_placement(well: &Well, player: Player, reachable: bool) -> Option<IllegalReason> {
+	if let Some(reason) = explain_illegal(well, player) {
+		return Some(reason);
+	}
+	let landed = trace_down(well, player);
+	if landed != player {
+		return Some(IllegalReason::FloatingNotSupported { distance: player.pt.y - landed.pt.y });
+	}
+	if reachable && !is_reachable(well, player) {
+		return Some(IllegalReason::UnreachableFromSpawn);
+	}
+	None
+}
+
+fn is_block(well: &Well, cell: Point) -> bool {
+	cell.y < well.height() && well.col_range().nth(cell.x as usize).is_some_and(|col| well.line(cell.y) & col != 0)
+}
+
+// Positions tracked by the flood fill, the kicks climbing above are not followed
+const STRIDE: usize = (MAX_WIDTH + 3) * 4;
+const SIZE: usize = STRIDE * (MAX_HEIGHT + 4);
+
+fn index(player: Player) -> Option<usize> {
+	if player.pt.y < 0 || player.pt.y as usize >= MAX_HEIGHT + 4 || player.pt.x < -3 {
+		return None;
+	}
+	Some(player.pt.y as usize * STRIDE + (player.pt.x + 3) as usize * 4 + player.rot as usize)
+}
+
+// Flood fills the positions reachable from the spawn position
+fn is_reachable(well: &Well, target: Player) -> bool {
+	let start = spawn_player(well, target.piece);
+	let mut visited = vec![false; SIZE];
+	let mut stack = vec![start];
+	while let Some(player) = stack.pop() {
+		let i = match index(player) { Some(i) => i, None => continue };
+		if visited[i] || test_player(well, player) {
+			continue;
+		}
+		if player == target {
+			return true;
+		}
+		visited[i] = true;
+		stack.push(player.move_down());
+		stack.push(player.move_left());
+		stack.push(player.move_right());
+		stack.push(srs_cw(well, player));
+		stack.push(srs_ccw(well, player));
+	}
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Piece, Rot, State};
+
+	#[test]
+	fn bounds() {
+		let well = Well::new(10, 22);
+		// The I stands in the third column of its box
+		let i = Player::new(Piece::I, Rot::Right, Point::new(-4, 10));
+		assert_eq!(Some(IllegalReason::OutOfBoundsLeft { by: 2 }), explain_illegal(&well, i));
+		let i = Player::new(Piece::I, Rot::Right, Point::new(8, 10));
+		assert_eq!(Some(IllegalReason::OutOfBoundsRight { by: 1 }), explain_illegal(&well, i));
+		let i = Player::new(Piece::I, Rot::Right, Point::new(3, 2));
+		assert_eq!(Some(IllegalReason::OutOfBoundsBelow { by: 1 }), explain_illegal(&well, i));
+		// Sticking out of the top is fine
+		let i = Player::new(Piece::I, Rot::Right, Point::new(3, 23));
+		assert_eq!(None, explain_illegal(&well, i));
+		assert_eq!("sticks out of the floor by 1", IllegalReason::OutOfBoundsBelow { by: 1 }.to_string());
+	}
+
+	#[test]
+	fn overlaps() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0011000000,
+			0b0001100000,
+		]);
+		let t = Player::new(Piece::T, Rot::Zero, Point::new(1, 2));
+		let reason = IllegalReason::OverlapsBlocks(vec![Point::new(2, 1), Point::new(3, 1)]);
+		assert_eq!(Some(reason.clone()), explain_illegal(&well, t));
+		assert_eq!("overlaps blocks at (2, 1) (3, 1)", reason.to_string());
+		assert_eq!(Some(reason), explain_placement(&well, t, true));
+	}
+
+	#[test]
+	fn floating() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1111000000,
+		]);
+		// The O fills the middle rows of its box
+		let o = Player::new(Piece::O, Rot::Zero, Point::new(0, 4));
+		assert_eq!(None, explain_illegal(&well, o));
+		assert_eq!(Some(IllegalReason::FloatingNotSupported { distance: 1 }), explain_placement(&well, o, false));
+		let o = Player::new(Piece::O, Rot::Zero, Point::new(3, 4));
+		assert_eq!(Some(IllegalReason::FloatingNotSupported { distance: 2 }), explain_placement(&well, o, false));
+		assert_eq!(None, explain_placement(&well, trace_down(&well, o), true));
+	}
+
+	#[test]
+	fn unreachable() {
+		// A cave under the roof, the O fits but can't squeeze through the gap
+		let mut well = Well::new(10, 22);
+		well.set_line(2, 0b1111101111 << 22);
+		let o = Player::new(Piece::O, Rot::Zero, Point::new(3, 2));
+		assert_eq!(None, explain_placement(&well, o, false));
+		assert_eq!(Some(IllegalReason::UnreachableFromSpawn), explain_placement(&well, o, true));
+		// The bot's search agrees
+		#[cfg(feature = "bot")]
+		let found = |well: &Well| ::PlayI::candidates(&::Weights::default(), well, spawn_player(well, Piece::O)).iter().any(|candidate| candidate.0 == o);
+		#[cfg(feature = "bot")]
+		assert!(!found(&well));
+		// Tucked under an overhang is reachable by sliding in
+		well.set_line(2, 0b1111110000 << 22);
+		assert_eq!(None, explain_placement(&well, o, true));
+		#[cfg(feature = "bot")]
+		assert!(found(&well));
+		let mut state = State::with_well(well);
+		assert_eq!(Err(IllegalReason::OutOfBoundsBelow { by: 1 }), state.try_set_player(Player::new(Piece::O, Rot::Zero, Point::new(3, 1))));
+		assert_eq!(Ok(()), state.try_set_player(o));
+		assert_eq!(Some(&o), state.player());
+	}
+}
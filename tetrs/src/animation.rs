@@ -0,0 +1,322 @@
+/*!
+Animation timelines for smooth playback.
+*/
+
+use ::std::{error, fmt};
+
+use ::{Action, Piece, Player, Point, Rot, Play, State, test_player};
+
+/// Player pose at a specific tick.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Keyframe {
+	pub tick: u32,
+	pub player: Player,
+	/// Quarter turns clockwise relative to the initial pose, negative for counter-clockwise.
+	pub turns: i32,
+}
+
+/// Player pose interpolated between two keyframes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InterpolatedPose {
+	pub piece: Piece,
+	/// Rotation of the keyframe at or before the sampled tick.
+	pub rot: Rot,
+	/// Horizontal position in cells.
+	pub x: f32,
+	/// Vertical position in cells.
+	pub y: f32,
+	/// Quarter turns clockwise relative to the initial pose.
+	pub turns: f32,
+}
+
+/// Advisory animation of a new piece sliding in from above the visible part of the well.
+///
+/// The player is at its spawn position right away, the animation only affects how it is drawn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SpawnAnimation {
+	/// Origin just above the visible part of the well.
+	pub from: Point,
+	/// The spawn position.
+	pub to: Point,
+	pub ticks: u8,
+}
+
+/// Default duration of the spawn animation in ticks.
+pub const SPAWN_TICKS: u8 = 6;
+
+/// Computes the spawn animation of a piece.
+///
+/// The origin is the spawn position moved up until the lowest occupied row of the piece sits just above the `visible_height` bottom rows.
+/// A piece spawning above the visible rows doesn't move.
+pub fn spawn_path(piece: Piece, rot: Rot, spawn_pt: Point, visible_height: i8, ticks: u8) -> SpawnAnimation {
+	let sprite = Player::new(piece, rot, spawn_pt).sprite();
+	// Rows below the position down to the lowest occupied row
+	let extent = sprite.pix.iter().rposition(|&pix| pix != 0).unwrap_or(0) as i8;
+	let from_y = spawn_pt.y.max(visible_height + extent);
+	SpawnAnimation {
+		from: Point::new(spawn_pt.x, from_y),
+		to: spawn_pt,
+		ticks: ticks,
+	}
+}
+
+impl State {
+	/// Returns the spawn animation of the player with the default duration, `None` if no player.
+	///
+	/// Only the bottom `visible_height` rows of the well are assumed to be drawn.
+	pub fn spawn_animation(&self, visible_height: i8) -> Option<SpawnAnimation> {
+		self.player().map(|player| spawn_path(player.piece, player.rot, player.pt, visible_height, SPAWN_TICKS))
+	}
+}
+
+/// Errors when building a timeline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TimelineError {
+	/// No player or the player collides with the well.
+	Start,
+	/// The move at the given index is blocked.
+	Blocked { index: usize, play: Play },
+	/// The move at the given index follows the player locking.
+	AfterLock { index: usize, play: Play },
+}
+
+impl fmt::Display for TimelineError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TimelineError::Start => f.write_str("initial player collides with the well"),
+			TimelineError::Blocked { index, play } => write!(f, "move {} ({:?}) is blocked", index, play),
+			TimelineError::AfterLock { index, play } => write!(f, "move {} ({:?}) follows the lock", index, play),
+		}
+	}
+}
+impl error::Error for TimelineError {}
+
+/// Keyframes for animating a path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Timeline {
+	keyframes: Vec<Keyframe>,
+}
+
+/// Builds the animation timeline for playing the path from the player of the state.
+///
+/// The moves are played on a copy of the state, with its well and the wall kicks of its rules, see `State::set_rules`.
+/// Every move takes `ticks_per_move` ticks. A rotation which needs a wall kick is split in two halves:
+/// the rotation around the pivot followed by the kick translation.
+///
+/// A soft drop which is blocked locks the player regardless of the lock delay, this is how the bot ends its paths.
+/// Hard drops lock the player as well unless the state's hard drops don't lock, firm drops leave it live.
+/// Any move after the player locked is rejected, as is any other blocked move.
+pub fn timeline(state: &State, path: &[Play], ticks_per_move: u32) -> Result<Timeline, TimelineError> {
+	let initial = match state.player() {
+		Some(&player) if !test_player(state.well(), player) => player,
+		_ => return Err(TimelineError::Start),
+	};
+	let mut scratch = state.clone();
+	scratch.set_lock_delay(0);
+	let mut keyframes = vec![Keyframe { tick: 0, player: initial, turns: 0 }];
+	let mut turns = 0;
+	for (index, &play) in path.iter().enumerate() {
+		let player = match scratch.player() {
+			Some(&player) => player,
+			None => return Err(TimelineError::AfterLock { index: index, play: play }),
+		};
+		let landing = scratch.ghost().unwrap();
+		let start = index as u32 * ticks_per_move;
+		let end = start + ticks_per_move;
+		let moved = match play {
+			Play::SoftDrop => {
+				scratch.soft_drop_lock();
+				true
+			},
+			_ => Action::from_play(play).is_none_or(|action| scratch.step(action)),
+		};
+		if !moved {
+			return Err(TimelineError::Blocked { index: index, play: play });
+		}
+		// A locked player stays where the soft drop landed or the hard drop put it
+		let next = match scratch.player() {
+			Some(&next) => next,
+			None if play == Play::HardDrop => landing,
+			None => player,
+		};
+		let rotated = match play {
+			Play::RotateCW => Some((player.rotate_cw(), 1)),
+			Play::RotateCCW => Some((player.rotate_ccw(), -1)),
+			Play::Rotate180 => Some((player.rotate_180(), 2)),
+			_ => None,
+		};
+		if let Some((rotated, turn)) = rotated {
+			turns += turn;
+			if next.pt != rotated.pt {
+				// Rotate around the pivot first, then translate by the kick
+				keyframes.push(Keyframe { tick: start + ticks_per_move / 2, player: rotated, turns: turns });
+			}
+		}
+		keyframes.push(Keyframe { tick: end, player: next, turns: turns });
+	}
+	Ok(Timeline { keyframes: keyframes })
+}
+
+impl Timeline {
+	/// Returns the keyframes in chronological order.
+	pub fn keyframes(&self) -> &[Keyframe] {
+		&self.keyframes
+	}
+	/// Returns the tick of the last keyframe.
+	pub fn duration(&self) -> u32 {
+		self.keyframes.last().unwrap().tick
+	}
+	/// Returns the final pose.
+	pub fn last(&self) -> Player {
+		self.keyframes.last().unwrap().player
+	}
+	/// Samples the pose at the given tick, clamping to the first and last keyframes.
+	pub fn sample(&self, tick: u32) -> InterpolatedPose {
+		// Last keyframe at or before the tick
+		let i = self.keyframes.iter().rposition(|key| key.tick <= tick).unwrap_or(0);
+		let from = &self.keyframes[i];
+		let to = self.keyframes.get(i + 1).unwrap_or(from);
+		let t = if to.tick > from.tick { (tick - from.tick) as f32 / (to.tick - from.tick) as f32 } else { 0.0 };
+		let lerp = |a: f32, b: f32| a + (b - a) * t;
+		let (a, b) = (from.player.pt, to.player.pt);
+		InterpolatedPose {
+			piece: from.player.piece,
+			rot: from.player.rot,
+			x: lerp(a.x as f32, b.x as f32),
+			y: lerp(a.y as f32, b.y as f32),
+			turns: lerp(from.turns as f32, to.turns as f32),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::Well;
+
+	fn with_player(well: Well, player: Player) -> State {
+		let mut state = State::with_well(well);
+		state.set_player(player);
+		state
+	}
+
+	fn pose(player: Player, turns: f32) -> InterpolatedPose {
+		InterpolatedPose { piece: player.piece, rot: player.rot, x: player.pt.x as f32, y: player.pt.y as f32, turns: turns }
+	}
+
+	#[test]
+	fn known_path() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1100110000,
+			0b1100111111,
+		]);
+		use Play::*;
+		let start = Player::new(Piece::O, Rot::Zero, Point::new(4, 6));
+		let path = [SoftDrop, SoftDrop, MoveLeft, MoveLeft, MoveLeft, SoftDrop, SoftDrop, SoftDrop];
+		let timeline = timeline(&with_player(well, start), &path, 4).unwrap();
+
+		// One keyframe per move plus the initial pose
+		let keyframes = timeline.keyframes();
+		assert_eq!(path.len() + 1, keyframes.len());
+		assert_eq!(Point::new(4, 4), keyframes[2].player.pt);
+		assert_eq!(Point::new(1, 4), keyframes[5].player.pt);
+		// The last soft drop is blocked and locks the player in place
+		assert_eq!(keyframes[7].player, keyframes[8].player);
+		assert_eq!(Player::new(Piece::O, Rot::Zero, Point::new(1, 2)), timeline.last());
+		assert_eq!(32, timeline.duration());
+
+		// Exact at the endpoints and clamped beyond them
+		assert_eq!(pose(start, 0.0), timeline.sample(0));
+		assert_eq!(pose(timeline.last(), 0.0), timeline.sample(32));
+		assert_eq!(pose(timeline.last(), 0.0), timeline.sample(1000));
+		// Halfway through the first soft drop
+		assert_eq!(5.5, timeline.sample(2).y);
+		assert_eq!(2.75, timeline.sample(13).x);
+	}
+
+	#[test]
+	fn kick_decomposition() {
+		// SRS example, the J rotation kicks with the last offset
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000110000,
+			0b0000011100,
+			0b0000001111,
+			0b0111000111,
+			0b1100001111,
+			0b1111001111,
+			0b1111101111,
+		]);
+		let start = Player::new(Piece::J, Rot::Zero, Point::new(2, 5));
+		let mut state = with_player(well, start);
+		let timeline = timeline(&state, &[Play::RotateCCW], 10).unwrap();
+		assert_eq!(&[
+			Keyframe { tick: 0, player: start, turns: 0 },
+			Keyframe { tick: 5, player: Player::new(Piece::J, Rot::Left, Point::new(2, 5)), turns: -1 },
+			Keyframe { tick: 10, player: Player::new(Piece::J, Rot::Left, Point::new(3, 3)), turns: -1 },
+		], timeline.keyframes());
+		assert_eq!(-0.4, timeline.sample(2).turns);
+
+		// The kicks follow the rules of the state, kids mode never kicks down
+		state.set_rules::<::KidsRules>();
+		assert_eq!(Err(TimelineError::Blocked { index: 0, play: Play::RotateCCW }), ::timeline(&state, &[Play::RotateCCW], 10));
+	}
+
+	#[test]
+	fn spawn_origins() {
+		let mut state = State::standard();
+		// Slides in by the number of occupied rows above the spawn point
+		let mut origin = |piece| {
+			state.spawn(piece);
+			let animation = state.spawn_animation(22).unwrap();
+			assert_eq!(state.player().unwrap().pt, animation.to);
+			assert_eq!(SPAWN_TICKS, animation.ticks);
+			animation.from
+		};
+		assert_eq!(Point::new(3, 23), origin(Piece::I));
+		assert_eq!(Point::new(3, 24), origin(Piece::O));
+		assert_eq!(Point::new(3, 23), origin(Piece::T));
+		assert_eq!(10, spawn_path(Piece::T, Rot::Zero, Point::new(3, 21), 22, 10).ticks);
+	}
+
+	#[test]
+	fn spawn_visible_height() {
+		for &visible_height in &[16, 20, 22] {
+			for &piece in &Piece::ALL {
+				let to = Point::new(3, 21);
+				let animation = spawn_path(piece, Rot::Zero, to, visible_height, SPAWN_TICKS);
+				assert_eq!(to.x, animation.from.x);
+				assert!(animation.from.y >= to.y);
+				// The origin is just out of sight, one row lower would show the piece
+				let lowest = |pt: Point| {
+					let mut well = Well::new(10, 23);
+					well.etch(Player::new(piece, Rot::Zero, pt).sprite(), pt);
+					well.lines().iter().position(|&line| line != 0)
+				};
+				let lowest_from = lowest(animation.from).unwrap() as i8;
+				assert!(lowest_from >= visible_height, "{:?} {}", piece, visible_height);
+				if animation.from != to {
+					assert_eq!(visible_height, lowest_from);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn illegal_path() {
+		let well = Well::new(10, 22);
+		let state = with_player(well, Player::new(Piece::T, Rot::Zero, Point::new(0, 10)));
+		assert_eq!(Err(TimelineError::Blocked { index: 1, play: Play::MoveLeft }),
+			timeline(&state, &[Play::MoveLeft, Play::MoveLeft], 4));
+		assert_eq!(Err(TimelineError::AfterLock { index: 1, play: Play::MoveRight }),
+			timeline(&state, &[Play::HardDrop, Play::MoveRight], 4));
+		assert_eq!(Err(TimelineError::Start),
+			timeline(&with_player(well, Player::new(Piece::T, Rot::Zero, Point::new(-3, 10))), &[], 4));
+		assert_eq!(Err(TimelineError::Start), timeline(&State::with_well(well), &[], 4));
+	}
+}
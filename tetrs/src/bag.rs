@@ -1,16 +1,44 @@
 
-use ::rand::{Rng, ThreadRng, thread_rng};
+use ::rand::{Rng, ThreadRng, XorShiftRng, thread_rng};
 
-use ::{Piece, Well, Weights, PlayI};
+use ::{Piece, Well};
+#[cfg(feature = "bot")]
+use ::{Weights, PlayI, Evaluator, spawn_player};
 
 /// The Random Generator.
+///
+/// Object safe, switch bags at runtime with a `Box<dyn Bag>`.
+///
+/// Bags may be finite: `next` returning `None` means the bag ran out of pieces.
+/// The game then ends once the current player locks, see `State::finish`.
+/// A finite bag keeps returning `None` once it ran out.
 pub trait Bag {
-	/// Produce the next piece.
+	/// Produce the next piece, `None` if the bag ran out of pieces.
 	fn next(&mut self, well: &Well) -> Option<Piece>;
 	/// Let the player see the queued up pieces.
 	fn peek(&self) -> &[Piece] {
 		&[]
 	}
+	/// Previews up to the next `n` pieces in the order they are dealt.
+	///
+	/// Bags which pick pieces for the well they're dealt for can't tell ahead and return an empty preview.
+	/// By default the preview is the start of the pieces shown by `peek`.
+	fn preview(&self, n: usize) -> Vec<Piece> {
+		let peek = self.peek();
+		peek[..n.min(peek.len())].to_vec()
+	}
+}
+
+impl<B: Bag + ?Sized> Bag for Box<B> {
+	fn next(&mut self, well: &Well) -> Option<Piece> {
+		(**self).next(well)
+	}
+	fn peek(&self) -> &[Piece] {
+		(**self).peek()
+	}
+	fn preview(&self, n: usize) -> Vec<Piece> {
+		(**self).preview(n)
+	}
 }
 
 /// Official Random Generator.
@@ -20,21 +48,46 @@ pub trait Bag {
 /// > The Random Generator generates a sequence of all seven tetrominoes permuted randomly as if they were drawn from a bag.
 /// > Then it deals all seven tetrominoes to the piece sequence before generating another bag.
 ///
-/// Because of the ability to peek ahead at the next piece, must keep track of the next seven tetrominoes as well.
+/// Because of the ability to preview the next 14 pieces, must keep track of the next two bags as well.
+/// Peeking shows the next 7 pieces.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct OfficialBag<R: Rng> {
 	rng: R,
-	bag: [Piece; 14],
+	bag: [Piece; 21],
 	pos: u8,
+	seed: Option<u64>,
 }
 impl<R: Rng> OfficialBag<R> {
 	pub fn with_rng(rng: R) -> OfficialBag<R> {
-		OfficialBag {
+		let mut bag = OfficialBag {
 			rng: rng,
-			bag: [Piece::O, Piece::I, Piece::S, Piece::Z, Piece::L, Piece::J, Piece::T,
-			      Piece::O, Piece::I, Piece::S, Piece::Z, Piece::L, Piece::J, Piece::T],
-			pos: 255,
+			bag: [Piece::O; 21],
+			pos: 0,
+			seed: None,
+		};
+		// Fill all bags up front so they can be previewed before the first piece is dealt
+		for chunk in bag.bag.chunks_mut(Piece::count()) {
+			chunk.copy_from_slice(&Piece::ALL);
+			bag.rng.shuffle(chunk);
 		}
+		bag
+	}
+	/// Returns the seed if the bag was created with `from_seed`.
+	pub fn seed(&self) -> Option<u64> {
+		self.seed
+	}
+}
+/// Official bag created with `OfficialBag::from_seed`.
+///
+/// Names the bag's type without depending on the `rand` crate.
+pub type SeededBag = OfficialBag<XorShiftRng>;
+
+impl OfficialBag<XorShiftRng> {
+	/// Creates a bag dealing a reproducible sequence of pieces, bags with the same seed deal the same sequence.
+	pub fn from_seed(seed: u64) -> OfficialBag<XorShiftRng> {
+		let mut bag = OfficialBag::with_rng(::garbage::xorshift(seed));
+		bag.seed = Some(seed);
+		bag
 	}
 }
 impl Default for OfficialBag<ThreadRng> {
@@ -44,16 +97,17 @@ impl Default for OfficialBag<ThreadRng> {
 }
 impl<R: Rng> Bag for OfficialBag<R> {
 	fn next(&mut self, _well: &Well) -> Option<Piece> {
-		let (left, right) = self.bag.split_at_mut(7);
-		if self.pos >= 14 {
-			self.rng.shuffle(right);
-		}
 		if self.pos >= 7 {
-			left.copy_from_slice(right);
-			self.rng.shuffle(right);
+			self.bag.copy_within(7.., 0);
+			self.rng.shuffle(&mut self.bag[14..]);
 			self.pos = 0;
+			log!({
+				let mut bag = [Piece::O; 7];
+				bag.copy_from_slice(&self.bag[14..]);
+				::logging::LogRecord::BagRefill { bag: bag }
+			});
 		}
-		let next_piece = left[self.pos as usize];
+		let next_piece = self.bag[self.pos as usize];
 		self.pos += 1;
 		Some(next_piece)
 	}
@@ -61,13 +115,20 @@ impl<R: Rng> Bag for OfficialBag<R> {
 		let pos = self.pos as usize;
 		&self.bag[pos..pos + 7]
 	}
+	/// Previews up to 14 pieces.
+	fn preview(&self, n: usize) -> Vec<Piece> {
+		let pos = self.pos as usize;
+		self.bag[pos..pos + n.min(14)].to_vec()
+	}
 }
 
 /// Pieces bag generously giving the best pieces.
+#[cfg(feature = "bot")]
 #[derive(Clone, Debug, Default)]
 pub struct BestBag {
 	weights: Weights,
 }
+#[cfg(feature = "bot")]
 impl BestBag {
 	pub fn new(weights: Weights) -> BestBag {
 		BestBag {
@@ -75,6 +136,7 @@ impl BestBag {
 		}
 	}
 }
+#[cfg(feature = "bot")]
 impl Bag for BestBag {
 	fn next(&mut self, well: &Well) -> Option<Piece> {
 		let next_piece = PlayI::best_piece(&self.weights, well);
@@ -83,10 +145,12 @@ impl Bag for BestBag {
 }
 
 /// Pieces bag coldly giving the worst pieces.
+#[cfg(feature = "bot")]
 #[derive(Clone, Debug, Default)]
 pub struct WorstBag {
 	weights: Weights,
 }
+#[cfg(feature = "bot")]
 impl WorstBag {
 	pub fn new(weights: Weights) -> WorstBag {
 		WorstBag {
@@ -94,9 +158,258 @@ impl WorstBag {
 		}
 	}
 }
+#[cfg(feature = "bot")]
 impl Bag for WorstBag {
 	fn next(&mut self, well: &Well) -> Option<Piece> {
 		let next_piece = PlayI::worst_piece(&self.weights, well);
 		Some(next_piece)
 	}
 }
+
+/// Pieces bag dealing the pieces like HATETRIS.
+///
+/// For every piece the player's best placement is the one leaving the lowest stack once the completed lines are cleared,
+/// reached from the spawn position with the SRS kicks.
+/// Deals the piece whose best placement leaves the highest stack, ties go to the first piece in the order S, Z, O, I, L, J, T.
+/// A piece which can't be placed at all ends the game and is dealt first.
+///
+/// Unlike the original HATETRIS it does not avoid repeating wells.
+#[cfg(feature = "bot")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HatetrisBag;
+#[cfg(feature = "bot")]
+impl HatetrisBag {
+	pub fn new() -> HatetrisBag {
+		HatetrisBag
+	}
+	// Lowest stack height the player can reach with the piece, infinite if the piece can't be placed
+	fn best_height(well: &Well, piece: Piece) -> f64 {
+		-PlayI::play(&LowStack, well, spawn_player(well, piece)).score
+	}
+}
+#[cfg(feature = "bot")]
+impl Bag for HatetrisBag {
+	fn next(&mut self, well: &Well) -> Option<Piece> {
+		let pieces = [Piece::S, Piece::Z, Piece::O, Piece::I, Piece::L, Piece::J, Piece::T];
+		// Ties go to the piece listed first
+		let (piece, _) = pieces[1..].iter().fold((pieces[0], Self::best_height(well, pieces[0])), |(worst, worst_height), &piece| {
+			let height = Self::best_height(well, piece);
+			if height > worst_height { (piece, height) } else { (worst, worst_height) }
+		});
+		Some(piece)
+	}
+}
+
+// Scores the well by the height of its stack once the completed lines are cleared, lower is better
+#[cfg(feature = "bot")]
+struct LowStack;
+#[cfg(feature = "bot")]
+impl Evaluator for LowStack {
+	fn eval(&self, well: &Well) -> f64 {
+		let full = well.lines().iter().filter(|&&line| line == well.line_mask()).count();
+		-(well.stack_height() as f64 - full as f64)
+	}
+}
+
+/// Finite bag dealing a fixed sequence of pieces, eg. for puzzles.
+///
+/// Peeking shows all the pieces left, the preview shrinks as the end approaches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SequenceBag {
+	pieces: Vec<Piece>,
+	pos: usize,
+}
+impl SequenceBag {
+	pub fn new(pieces: &[Piece]) -> SequenceBag {
+		SequenceBag {
+			pieces: pieces.to_vec(),
+			pos: 0,
+		}
+	}
+	/// Returns the number of pieces left.
+	pub fn remaining(&self) -> usize {
+		self.pieces.len() - self.pos
+	}
+}
+impl Bag for SequenceBag {
+	fn next(&mut self, _well: &Well) -> Option<Piece> {
+		let piece = self.pieces.get(self.pos).cloned();
+		if piece.is_some() {
+			self.pos += 1;
+		}
+		piece
+	}
+	fn peek(&self) -> &[Piece] {
+		&self.pieces[self.pos..]
+	}
+}
+
+//----------------------------------------------------------------
+
+/// Number of pieces drawn by the conformance checks.
+#[cfg(any(test, feature = "testing"))]
+const CONFORMANCE_DRAWS: usize = 100;
+/// Number of pieces previewed by the conformance checks.
+#[cfg(any(test, feature = "testing"))]
+const CONFORMANCE_PREVIEW: usize = 16;
+
+/// Checks the `Bag` contract, panics if a check fails.
+///
+/// The `make` closure must produce identical bags on every call, seed its random number generator to keep the checks deterministic.
+///
+/// * `next` never returns `None`, the checked bags must be infinite.
+/// * `next` only deals valid pieces.
+/// * The pieces shown by `peek` are dealt by subsequent calls to `next` in the same order.
+/// * The pieces shown by `preview` agree with `peek` and are dealt in the same order.
+/// * Freshly made bags deal the same sequence of pieces.
+///
+/// The bags are fed an empty standard well.
+#[cfg(any(test, feature = "testing"))]
+pub fn assert_bag_conformance<B: Bag, F: Fn() -> B>(make: F) {
+	use ::{STANDARD_WIDTH, STANDARD_HEIGHT};
+	let well = Well::new(STANDARD_WIDTH, STANDARD_HEIGHT);
+
+	let mut bag = make();
+	let mut dealt = Vec::new();
+	// Pieces promised by peek which have yet to be dealt
+	let mut promised: Vec<Piece> = bag.peek().to_vec();
+	let mut previewed: Vec<Piece> = bag.preview(CONFORMANCE_PREVIEW);
+	for i in 0..CONFORMANCE_DRAWS {
+		let piece = match bag.next(&well) {
+			Some(piece) => piece,
+			None => panic!("draw {}: next returned None", i),
+		};
+		assert!((piece as usize) < Piece::count(), "draw {}: invalid piece discriminant {}", i, piece as u8);
+		if !promised.is_empty() {
+			assert_eq!(promised[0], piece, "draw {}: dealt piece does not match the peeked piece", i);
+			promised.remove(0);
+		}
+		// Every piece still promised must remain a prefix of the new peek
+		let peek = bag.peek();
+		assert!(peek.len() >= promised.len() && peek[..promised.len()] == promised[..],
+			"draw {}: peek {:?} is inconsistent with the previous peek {:?}", i, peek, promised);
+		promised = peek.to_vec();
+		if !previewed.is_empty() {
+			assert_eq!(previewed[0], piece, "draw {}: dealt piece does not match the previewed piece", i);
+			previewed.remove(0);
+		}
+		let preview = bag.preview(CONFORMANCE_PREVIEW);
+		let common = preview.len().min(peek.len());
+		assert!(preview[..common] == peek[..common], "draw {}: preview {:?} disagrees with peek {:?}", i, preview, peek);
+		assert!(preview.len() >= previewed.len() && preview[..previewed.len()] == previewed[..],
+			"draw {}: preview {:?} is inconsistent with the previous preview {:?}", i, preview, previewed);
+		previewed = preview;
+		dealt.push(piece);
+	}
+
+	let mut bag = make();
+	for (i, &piece) in dealt.iter().enumerate() {
+		assert_eq!(Some(piece), bag.next(&well), "draw {}: freshly made bag deals a different sequence", i);
+	}
+}
+
+/// Defines a test which checks the `Bag` contract with `assert_bag_conformance`.
+///
+/// ```ignore
+/// bag_conformance!(my_bag_conformance, || MyBag::with_seed(42));
+/// ```
+#[cfg(any(test, feature = "testing"))]
+#[macro_export]
+macro_rules! bag_conformance {
+	($name:ident, $make:expr) => {
+		#[test]
+		fn $name() {
+			$crate::assert_bag_conformance($make);
+		}
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::rand::{SeedableRng, XorShiftRng};
+
+	bag_conformance!(official_bag, || OfficialBag::with_rng(XorShiftRng::from_seed([1, 2, 3, 4])));
+	#[cfg(feature = "bot")]
+	bag_conformance!(best_bag, BestBag::default);
+	#[cfg(feature = "bot")]
+	bag_conformance!(worst_bag, WorstBag::default);
+	#[cfg(feature = "bot")]
+	bag_conformance!(hatetris_bag, HatetrisBag::new);
+	bag_conformance!(boxed_bag, || -> Box<dyn Bag> { Box::new(OfficialBag::with_rng(XorShiftRng::from_seed([5, 6, 7, 8]))) });
+
+	#[test]
+	fn seeded() {
+		let well = Well::new(10, 22);
+		let mut a = OfficialBag::from_seed(42);
+		let mut b = OfficialBag::from_seed(42);
+		assert_eq!(Some(42), a.seed());
+		for _ in 0..1000 {
+			assert_eq!(a.next(&well), b.next(&well));
+		}
+		let mut c = OfficialBag::from_seed(43);
+		let mut a = OfficialBag::from_seed(42);
+		assert!((0..1000).any(|_| a.next(&well) != c.next(&well)));
+		assert_eq!(None, OfficialBag::with_rng(XorShiftRng::from_seed([1, 2, 3, 4])).seed());
+	}
+
+	#[test]
+	fn official_preview() {
+		let well = Well::new(10, 22);
+		let mut bag = OfficialBag::with_rng(XorShiftRng::from_seed([9, 8, 7, 6]));
+		for _ in 0..50 {
+			// The preview shows the next pieces in draw order
+			let mut ahead = bag.clone();
+			let dealt: Vec<Piece> = (0..14).map(|_| ahead.next(&well).unwrap()).collect();
+			assert_eq!(&dealt[..7], bag.peek());
+			assert_eq!(&dealt[..7], &bag.preview(7)[..]);
+			assert_eq!(dealt, bag.preview(14));
+			assert_eq!(dealt, bag.preview(100));
+			bag.next(&well);
+		}
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn hatetris() {
+		// Every piece but the I leaves a stack two lines high, the S comes first
+		let mut bag = HatetrisBag::new();
+		assert_eq!(Some(Piece::S), bag.next(&Well::new(10, 22)));
+		// Only the O can't reach into the gap, laid on top it leaves the highest stack
+		let well: Well = "|          |\n|          |\n|          |\n|          |\n|##### ####|".parse().unwrap();
+		assert_eq!(Some(Piece::O), bag.next(&well));
+	}
+
+	#[test]
+	fn sequence() {
+		let well = Well::new(10, 22);
+		let mut bag = SequenceBag::new(&[Piece::T, Piece::I, Piece::O]);
+		assert_eq!(vec![Piece::T, Piece::I], bag.preview(2));
+		assert_eq!(Some(Piece::T), bag.next(&well));
+		assert_eq!(&[Piece::I, Piece::O], bag.peek());
+		assert_eq!(Some(Piece::I), bag.next(&well));
+		assert_eq!(Some(Piece::O), bag.next(&well));
+		assert_eq!(0, bag.remaining());
+		// Runs out for good
+		for _ in 0..3 {
+			assert_eq!(None, bag.next(&well));
+			assert!(bag.peek().is_empty());
+		}
+	}
+
+	#[test]
+	fn switch_bags() {
+		let well = Well::new(10, 22);
+		let mut bag: Box<dyn Bag> = Box::new(OfficialBag::with_rng(XorShiftRng::from_seed([1, 2, 3, 4])));
+		assert_eq!(7, bag.preview(7).len());
+		bag.next(&well);
+		#[cfg(feature = "bot")]
+		{
+			bag = Box::new(WorstBag::default());
+			assert!(bag.next(&well).is_some());
+			assert!(bag.preview(7).is_empty());
+		}
+		bag = Box::new(OfficialBag::with_rng(XorShiftRng::from_seed([4, 3, 2, 1])));
+		assert_eq!(bag.preview(1)[0], bag.next(&well).unwrap());
+	}
+}
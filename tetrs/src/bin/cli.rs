@@ -8,58 +8,75 @@ fn clear_screen() { unsafe {
 	system("@clear||cls\0".as_ptr());
 }}
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 enum Input {
 	None,
-	Left,
-	Right,
-	RotateCW,
-	RotateCCW,
-	SoftDrop,
-	HardDrop,
+	Plays(Vec<tetrs::Play>),
 	Gravity,
 	Quit,
 	Help,
-	Invalid,
+	Invalid(tetrs::ParseSequenceError),
 }
 fn input() -> Input {
 	print!(">>> ");
 	std::io::stdout().flush().unwrap();
 	let mut action = String::new();
 	std::io::stdin().read_line(&mut action).unwrap();
-	match &*action.trim().to_uppercase() {
+	parse_input(&action)
+}
+fn parse_input(line: &str) -> Input {
+	match &*line.trim().to_uppercase() {
 		"" => Input::None,
-		"A" | "Q" | "LEFT" => Input::Left,
-		"D" | "RIGHT" => Input::Right,
-		"CW" | "RR" | "ROT" => Input::RotateCW,
-		"CCW" | "RL" => Input::RotateCCW,
-		"S" | "DOWN" | "SOFT" | "SOFT DROP" => Input::SoftDrop,
-		"W" | "Z" | "DROP" | "HARD DROP" => Input::HardDrop,
+		"SOFT DROP" => Input::Plays(vec![tetrs::Play::SoftDrop]),
+		"HARD DROP" => Input::Plays(vec![tetrs::Play::HardDrop]),
 		"G" | "GRAVITY" => Input::Gravity,
 		"QUIT" | "QUTI" => Input::Quit,
 		"H" | "HELP" => Input::Help,
-		_ => Input::Invalid,
+		line => {
+			// Lower the shortcuts to the move names, keeping the multipliers
+			let words: Vec<String> = line.split_whitespace().map(|word| {
+				let (name, count) = word.split_at(word.find('*').unwrap_or(word.len()));
+				format!("{}{}", alias(name), count)
+			}).collect();
+			match tetrs::parse_play_sequence(&words.join(" ")) {
+				Ok(plays) => Input::Plays(plays),
+				Err(err) => Input::Invalid(err),
+			}
+		},
+	}
+}
+fn alias(name: &str) -> &str {
+	match name {
+		"A" | "Q" => "left",
+		"D" => "right",
+		"RR" | "ROT" => "cw",
+		"RL" => "ccw",
+		"FLIP" => "180",
+		"S" | "DOWN" | "SD" => "soft",
+		"W" | "Z" | "DROP" | "HD" => "hard",
+		name => name,
 	}
 }
 
-fn bot(state: &mut tetrs::State) -> bool {
+fn bot(state: &mut tetrs::State, next: Option<tetrs::Piece>) -> bool {
 	let weights = tetrs::Weights::default();
-	let bot = tetrs::PlayI::play(&weights, state.well(), *state.player().unwrap());
-	if bot.play.len() == 0 {
+	let player = *state.player().unwrap();
+	// Look ahead at the next piece when the bag shows it
+	let bot = match next {
+		Some(next) => tetrs::PlayI::play2(&weights, state.well(), player, next),
+		None => tetrs::PlayI::play(&weights, state.well(), player),
+	};
+	if bot.actions.is_empty() {
 		state.hard_drop();
 		return false;
 	}
 	let mut result = true;
-	for play in bot.play {
-		use tetrs::Play;
-		result &= match play {
-			Play::MoveLeft => state.move_left(),
-			Play::MoveRight => state.move_right(),
-			Play::RotateCW => state.rotate_cw(),
-			Play::RotateCCW => state.rotate_ccw(),
-			Play::SoftDrop => state.soft_drop(),
-			Play::HardDrop => state.hard_drop(),
-			Play::Idle => true,
+	for action in bot.actions {
+		use tetrs::Action;
+		result &= match action {
+			// The last soft drop of the path locks the player
+			Action::SoftDrop => state.soft_drop_lock(),
+			action => state.step(action),
 		};
 		if !result {
 			break;
@@ -68,32 +85,14 @@ fn bot(state: &mut tetrs::State) -> bool {
 	result
 }
 
-static TILESET: [char; 32] = [
-	'O', 'I', 'S', 'Z', 'L', 'J', 'T', 'x',
-	'_', '_', '_', '_', '_', '_', '_', 'x',
-	'O', 'I', 'S', 'Z', 'L', 'J', 'T', '□',
-	'.', '_', ' ', 'x', 'x', 'x', 'x', 'x',
-];
-
 fn draw(scene: &tetrs::Scene) {
-	for row in 0..scene.height() {
-		print!("|");
-		let line = scene.line(row);
-		for &tile in line {
-			let tile: u8 = tile.into();
-			let c = TILESET[(tile >> 3) as usize];
-			print!("{}", c);
-		}
-		print!("|\n");
-	}
-	print!("+");
-	for _ in 0..scene.width() {
-		print!("-");
+	for line in scene.to_string().lines() {
+		println!("|{}|", line);
 	}
-	print!("+\n");
+	println!("+{}+", "-".repeat(scene.width() as usize));
 }
 
-const WELCOME_MESSAGE: &'static str = "
+const WELCOME_MESSAGE: &str = "
 Welcome to Adventure Tetrs!
 After the playing field is shown, you will be asked for input.
 
@@ -105,9 +104,11 @@ Move the piece to the right.
 Rotate the piece clockwise.
 >>> CCW, RL
 Rotate the piece counter-clockwise.
->>> S, DOWN, SOFT, SOFT DROP
+>>> 180, FLIP
+Rotate the piece 180 degrees.
+>>> S, SD, DOWN, SOFT, SOFT DROP
 Soft drop, move the piece down once.
->>> W, Z, DROP, HARD DROP
+>>> W, Z, HD, DROP, HARD DROP
 Hard drop, drops the piece down and locks into place.
 >>> G, GRAVITY
 Apply gravity, same as a soft drop.
@@ -116,49 +117,70 @@ Quit the game.
 >>> H, HELP
 Print this help message.
 
+Moves can be repeated and combined on one line, eg. LEFT*5 or CW*2 HD.
+The moves stop at the first one which fails.
+
 ";
 
+fn print_end(end: tetrs::GameEnd, stats: &tetrs::Stats) {
+	match end {
+		tetrs::GameEnd::OutOfPieces => println!("Finished!"),
+		tetrs::GameEnd::TopOut => println!("Game Over! Topped out."),
+		tetrs::GameEnd::BlockOut => println!("Game Over! Blocked out."),
+		tetrs::GameEnd::LockOut => println!("Game Over! Locked out."),
+		tetrs::GameEnd::TimedOut => println!("Game Over! Out of time."),
+		_ => println!("Game Over!"),
+	}
+	println!("Score: {}", stats.guideline_score(1));
+	println!("Lines: {} ({} singles, {} doubles, {} triples, {} tetrises)", stats.lines(), stats.singles, stats.doubles, stats.triples, stats.tetrises);
+	print!("Pieces: {}", stats.locked);
+	for &piece in &tetrs::Piece::ALL {
+		print!(" {}:{}", piece, stats.spawned(piece));
+	}
+	println!();
+}
+
 fn main() {
 	clear_screen();
 	
 	println!("{}", WELCOME_MESSAGE);
 
-	use tetrs::Bag;
-
-	let mut state = tetrs::State::new(10, 22);
+	let mut state = tetrs::State::standard();
 	let mut bag = tetrs::OfficialBag::default();
-	let mut next_piece = bag.next(state.well()).unwrap();
-	state.spawn(next_piece);
+	state.spawn_from(&mut bag);
+	let mut status = String::new();
 
 	loop {
-		draw(&state.scene());
+		draw(&state.scene_view().to_scene());
+		if !status.is_empty() {
+			println!("{}", status);
+			status.clear();
+		}
 
-		// Check for pieces in the spawning area
-		if state.is_game_over() {
-			println!("Game Over!");
+		// Check for pieces in the spawning area or the end of the bag
+		if let Some(end) = state.game_end() {
+			print_end(end, state.stats());
 			break;
 		}
 
 		match input() {
-			Input::None => bot(&mut state),
+			Input::None => { bot(&mut state, tetrs::Bag::peek(&bag).first().cloned()); },
 			Input::Quit => break,
-			Input::Left => state.move_left(),
-			Input::Right => state.move_right(),
-			Input::RotateCW => state.rotate_cw(),
-			Input::RotateCCW => state.rotate_ccw(),
-			Input::SoftDrop => state.soft_drop(),
-			Input::HardDrop => state.hard_drop(),
-			Input::Gravity => state.gravity(),
-			_ => true,
+			Input::Plays(plays) => {
+				let summary = state.run_steps(plays.into_iter().filter_map(tetrs::Action::from_play));
+				if let Some(action) = summary.rejected() {
+					status = format!("{:?} failed after {} moves, skipped {} moves.", action, summary.applied(), summary.skipped());
+				}
+			},
+			Input::Gravity => { state.gravity_lock(); },
+			Input::Help => status = WELCOME_MESSAGE.to_string(),
+			Input::Invalid(err) => status = format!("Invalid input, {}.", err),
 		};
 
 		// Spawn a new piece as needed
-		if state.player().is_none() {
-			next_piece = bag.next(state.well()).unwrap();
-			if state.spawn(next_piece) {
-				println!("Game Over!");
-				break;
-			}
+		if state.player().is_none() && state.spawn_from(&mut bag) {
+			print_end(state.game_end().unwrap_or(tetrs::GameEnd::TopOut), state.stats());
+			break;
 		}
 
 		state.clear_lines(|_| ());
@@ -167,3 +189,19 @@ fn main() {
 
 	println!("Thanks for playing!");
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tetrs::Play;
+
+	#[test]
+	fn multipliers() {
+		assert_eq!(Input::Plays(vec![Play::MoveLeft; 5]), parse_input("LEFT*5\n"));
+		assert_eq!(Input::Plays(vec![Play::RotateCW, Play::RotateCW, Play::HardDrop]), parse_input("cw*2 hd"));
+		assert_eq!(Input::Plays(vec![Play::MoveLeft, Play::MoveRight, Play::MoveRight]), parse_input("a d*2"));
+		assert_eq!(Input::Plays(vec![Play::HardDrop]), parse_input("hard drop"));
+		assert_eq!(Input::Gravity, parse_input("g"));
+		assert_eq!(Input::Invalid(tetrs::ParseSequenceError { word: 1 }), parse_input("left jump*2"));
+	}
+}
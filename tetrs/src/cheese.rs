@@ -0,0 +1,208 @@
+/*!
+Cheese race, digging through a board of garbage lines.
+*/
+
+use ::rand::{Rng, XorShiftRng};
+
+use ::{OfficialBag, ClearedRows, GarbageRng, Player, State};
+#[cfg(feature = "bot")]
+use ::{Evaluator, PlayI};
+use ::garbage::xorshift;
+
+/// Outcome of a cheese race.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CheeseResult {
+	/// Number of pieces locked.
+	pub pieces: u32,
+	/// Number of garbage lines cleared.
+	pub garbage_cleared: u32,
+	/// Every garbage line was cleared.
+	pub finished: bool,
+	/// Number of garbage lines cleared by the number of pieces locked for them.
+	///
+	/// A garbage line takes the pieces locked since the previous garbage line was cleared,
+	/// garbage lines cleared together with an earlier one take no pieces.
+	pub histogram: Vec<u32>,
+}
+
+impl CheeseResult {
+	/// Returns the garbage efficiency, the pieces locked per garbage line cleared.
+	///
+	/// Lower is better, `None` if no garbage line was cleared.
+	pub fn efficiency(&self) -> Option<f64> {
+		if self.garbage_cleared > 0 {
+			Some(self.pieces as f64 / self.garbage_cleared as f64)
+		}
+		else {
+			None
+		}
+	}
+}
+
+/// Cheese race driver.
+///
+/// The well starts with lines of garbage, every line with its hole in a different column than the line below.
+/// The race is finished once every garbage line is cleared.
+///
+/// The bag and the garbage derive from the seed, replaying the same placements reproduces the race exactly.
+#[derive(Clone, Debug)]
+pub struct CheeseRace {
+	state: State,
+	bag: OfficialBag<XorShiftRng>,
+	// Garbage lines left, always the bottom rows of the well
+	garbage: i8,
+	// Pieces locked since the last garbage line was cleared
+	since: u32,
+	result: CheeseResult,
+}
+
+impl CheeseRace {
+	/// Creates a new race in a standard well with the given number of garbage lines.
+	pub fn new(seed: u64, lines: u8) -> CheeseRace {
+		CheeseRace::with_state(seed, lines, State::standard())
+	}
+	/// Creates a new race with the given number of garbage lines pushed into the state's well.
+	pub fn with_state(seed: u64, lines: u8, mut state: State) -> CheeseRace {
+		let mut master = xorshift(seed);
+		let mut holes = GarbageRng::with_repeat(master.gen(), state.well().width(), 0.0);
+		for _ in 0..lines {
+			state.add_garbage(1, holes.next_column());
+		}
+		CheeseRace {
+			state: state,
+			bag: OfficialBag::with_rng(xorshift(master.gen())),
+			garbage: lines as i8,
+			since: 0,
+			result: CheeseResult::default(),
+		}
+	}
+	/// Returns the game state.
+	pub fn state(&self) -> &State {
+		&self.state
+	}
+	/// Returns the number of garbage lines left.
+	pub fn garbage(&self) -> i8 {
+		self.garbage
+	}
+	/// Returns the result so far.
+	pub fn result(&self) -> &CheeseResult {
+		&self.result
+	}
+	/// Places one piece.
+	///
+	/// The callback is given the state with the spawned player and returns where to lock the player, `None` gives up.
+	///
+	/// Returns `false` once the race is over: finished, topped out or given up.
+	pub fn play<F>(&mut self, f: F) -> bool where F: FnOnce(&State) -> Option<Player> {
+		if self.result.finished || self.state.spawn_from(&mut self.bag) {
+			return false;
+		}
+		match f(&self.state) {
+			Some(player) => self.state.set_player(player),
+			None => return false,
+		}
+		self.state.lock();
+		self.result.pieces += 1;
+		self.since += 1;
+
+		// Garbage stays at the bottom, the cleared rows below the garbage height are garbage
+		let rows = ClearedRows::find(self.state.well());
+		let cleared = rows.iter().filter(|&row| row < self.garbage).count();
+		for _ in 0..cleared {
+			self.record(self.since);
+			self.since = 0;
+		}
+		self.garbage -= cleared as i8;
+		self.state.clear_lines(|_| ());
+
+		self.result.finished = self.garbage == 0;
+		!self.result.finished && !self.state.is_game_over()
+	}
+	/// Races with the bot until the race is over or the number of pieces is reached.
+	#[cfg(feature = "bot")]
+	pub fn run<E: Evaluator>(&mut self, evaluator: &mut E, max_pieces: u32) -> &CheeseResult {
+		while self.result.pieces < max_pieces {
+			let playing = self.play(|state| {
+				evaluator.update(state.well());
+				PlayI::play(evaluator, state.well(), *state.player().unwrap()).player
+			});
+			if !playing {
+				break;
+			}
+		}
+		&self.result
+	}
+	fn record(&mut self, pieces: u32) {
+		let histogram = &mut self.result.histogram;
+		if histogram.len() <= pieces as usize {
+			histogram.resize(pieces as usize + 1, 0);
+		}
+		histogram[pieces as usize] += 1;
+		self.result.garbage_cleared += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Piece, Rot, Point, trace_down};
+
+	#[test]
+	fn board() {
+		let race = CheeseRace::new(1, 10);
+		let well = race.state().well();
+		assert_eq!(10, race.garbage());
+		assert_eq!(90, well.count_blocks());
+		// Every hole differs from the one below
+		for row in 1..10 {
+			assert_ne!(well.line(row - 1), well.line(row));
+			assert_eq!(9, well.line(row).count_ones());
+		}
+	}
+
+	#[test]
+	fn efficiency() {
+		// Garbage with the hole in the first column below the garbage with the hole in the last column
+		let mut state = State::standard();
+		state.add_garbage(1, 9);
+		state.add_garbage(1, 0);
+		let mut race = CheeseRace::with_state(0, 0, state);
+		race.garbage = 2;
+		let drop = |piece: Piece, rot: Rot, x: i8| move |state: &State| {
+			Some(trace_down(state.well(), Player::new(piece, rot, Point::new(x, state.well().height() + 2))))
+		};
+		// Stacking on top clears nothing
+		assert!(race.play(drop(Piece::O, Rot::Zero, 2)));
+		assert_eq!((1, 0, 2), (race.result().pieces, race.result().garbage_cleared, race.garbage()));
+		// Upright I pieces in the holes clear the top garbage line, then the bottom one
+		assert!(race.play(drop(Piece::I, Rot::Right, 7)));
+		assert_eq!((2, 1, 1), (race.result().pieces, race.result().garbage_cleared, race.garbage()));
+		assert!(!race.play(drop(Piece::I, Rot::Right, -2)));
+		let result = race.result();
+		assert!(result.finished);
+		assert_eq!((3, 2), (result.pieces, result.garbage_cleared));
+		// Two pieces for the first line and one for the second
+		assert_eq!(vec![0, 1, 1], result.histogram);
+		assert_eq!(Some(1.5), result.efficiency());
+		// Finished races place no more pieces
+		assert!(!race.play(|_| panic!()));
+		assert_eq!(None, CheeseResult::default().efficiency());
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn downstacking() {
+		use ::Weights;
+		// Pieces to clear the board, unfinished races count the piece limit
+		let race = |weights: Weights| -> u32 {
+			(0..8).map(|seed| {
+				let mut race = CheeseRace::new(seed, 10);
+				let result = race.run(&mut weights.clone(), 200).clone();
+				if result.finished { result.pieces } else { 200 }
+			}).sum()
+		};
+		let default = race(Weights::default());
+		let downstacking = race(Weights::downstacking());
+		assert!(downstacking < default, "downstacking {} default {}", downstacking, default);
+	}
+}
@@ -0,0 +1,467 @@
+/*!
+Line clear classification.
+*/
+
+use ::{Line, Piece, Player, Rot, Well, State, test_player};
+
+/// Set of rows cleared at once, rows counted from the bottom of the well.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClearedRows(u32);
+
+impl ClearedRows {
+	/// Finds the completed rows in the well.
+	pub fn find(well: &Well) -> ClearedRows {
+		let line_mask = well.line_mask();
+		let mut rows = 0;
+		for (row, &line) in well.lines().iter().enumerate() {
+			if line == line_mask {
+				rows |= 1 << row;
+			}
+		}
+		ClearedRows(rows)
+	}
+	/// Returns the number of rows.
+	pub fn len(self) -> usize {
+		self.0.count_ones() as usize
+	}
+	/// Returns if no rows are cleared.
+	pub fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+	/// Returns if the row is cleared.
+	pub fn contains(self, row: i8) -> bool {
+		row >= 0 && (row as usize) < 32 && self.0 & (1 << row) != 0
+	}
+	/// Iterates over the rows from bottom to top.
+	pub fn iter(self) -> impl DoubleEndedIterator<Item = i8> {
+		(0..32i8).filter(move |&row| self.0 & (1 << row) != 0)
+	}
+}
+
+/// Kind of line clear.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ClearKind {
+	/// No lines cleared.
+	None,
+	Single,
+	Double,
+	Triple,
+	Tetris,
+	/// T-spin clearing the given number of lines, zero for a T-spin without lines.
+	TSpin(u8),
+	/// Perfect clear with the given number of lines, takes precedence over the other kinds.
+	PerfectClear(u8),
+}
+
+/// Kind of T-spin by the three corner rule.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TspinKind {
+	None,
+	/// Three corners blocked but only one of the corners the T points at.
+	Mini,
+	/// Both corners the T points at blocked, or spun in with the last wall kick.
+	Full,
+}
+
+/// Line clear info for scoring, see `State::clear_lines_ex`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ClearInfo {
+	/// Number of lines cleared.
+	pub lines: u8,
+	/// T-spin of the locked player.
+	pub tspin: TspinKind,
+	/// Number of consecutive locks clearing lines including this one, zero if no lines were cleared.
+	pub combo: u32,
+	/// The cleared lines left the well empty.
+	pub perfect_clear: bool,
+	/// Effect data if lines were cleared.
+	pub effect: Option<ClearEffect>,
+}
+
+/// Seed for line clear effects such as particles.
+///
+/// Drawn from the state's effect stream when lines are cleared, see `State::set_effect_seed`.
+/// Frontends seeding their effects with it show identical effects for a game and every replay or spectator of it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EffectSeed(pub u32);
+
+/// Cleared row metadata for effects.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ClearedRow {
+	/// The row counted from the bottom of the well, before removing the cleared rows.
+	pub row: i8,
+	/// Columns filled by the just locked player, the other columns were filled before the lock.
+	pub locked: Line,
+}
+
+/// Line clear effect data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ClearEffect {
+	pub seed: EffectSeed,
+	pub rows: ClearedRows,
+	locked: Option<Player>,
+}
+
+impl ClearEffect {
+	pub(crate) fn new(seed: EffectSeed, rows: ClearedRows, locked: Option<Player>) -> ClearEffect {
+		ClearEffect { seed: seed, rows: rows, locked: locked }
+	}
+	/// Iterates over the cleared rows from bottom to top.
+	pub fn cleared_rows(self) -> impl Iterator<Item = ClearedRow> {
+		self.rows.iter().map(move |row| {
+			let locked = match self.locked {
+				Some(player) if row <= player.pt.y && row > player.pt.y - 4 => {
+					Well::render(player.sprite(), player.pt.x)[(player.pt.y - row) as usize]
+				},
+				_ => 0,
+			};
+			ClearedRow { row: row, locked: locked }
+		})
+	}
+}
+
+/// Advances the effect stream, a xorshift generator which must not be zero.
+pub(crate) fn next_effect_seed(rng: &mut u32) -> EffectSeed {
+	let mut x = *rng;
+	x ^= x << 13;
+	x ^= x >> 17;
+	x ^= x << 5;
+	*rng = x;
+	EffectSeed(x)
+}
+
+/// Outcome of locking a player.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SimulatedLock {
+	/// Rows cleared by the lock, before removing them.
+	pub cleared_rows: ClearedRows,
+	/// The well after locking and removing the cleared rows.
+	pub resulting_well: Well,
+	pub clear_kind: ClearKind,
+	/// Holes added by the lock after removing the cleared rows.
+	pub new_holes: u8,
+}
+
+/// Locks the player into a copy of the well and classifies the result.
+///
+/// A T piece is spun in when at least three of the corners around its center are blocked and it can't move left, right or up.
+/// This doesn't depend on how the player got there and works the same for previews and real locks.
+pub(crate) fn resolve(well: &Well, player: Player) -> SimulatedLock {
+	let mut after = *well;
+	after.etch(player.sprite(), player.pt);
+	let cleared_rows = ClearedRows::find(&after);
+	for row in cleared_rows.iter().rev() {
+		after.remove_line(row);
+	}
+	let lines = cleared_rows.len() as u8;
+	let clear_kind = if lines > 0 && after.is_empty() {
+		ClearKind::PerfectClear(lines)
+	}
+	else if is_t_spin(well, player) {
+		ClearKind::TSpin(lines)
+	}
+	else {
+		match lines {
+			0 => ClearKind::None,
+			1 => ClearKind::Single,
+			2 => ClearKind::Double,
+			3 => ClearKind::Triple,
+			_ => ClearKind::Tetris,
+		}
+	};
+	SimulatedLock {
+		cleared_rows: cleared_rows,
+		resulting_well: after,
+		clear_kind: clear_kind,
+		new_holes: (after.count_holes() - well.count_holes()).max(0) as u8,
+	}
+}
+
+fn is_t_spin(well: &Well, player: Player) -> bool {
+	if player.piece != Piece::T {
+		return false;
+	}
+	let up = Player::new(player.piece, player.rot, player.pt.offset(0, 1));
+	if !test_player(well, player.move_left()) || !test_player(well, player.move_right()) || !test_player(well, up) {
+		return false;
+	}
+	// The center of the T sits in the middle of its 3x3 box
+	let (x, y) = (player.pt.x + 2, player.pt.y - 1);
+	let corners = [(x - 1, y - 1), (x + 1, y - 1), (x - 1, y + 1), (x + 1, y + 1)];
+	corners.iter().filter(|&&(col, row)| is_blocked(well, col, row)).count() >= 3
+}
+
+/// Classifies the T-spin of a locked player, `kick` is the index of the wall kick if its last successful move was a rotation.
+///
+/// The well may or may not have the player etched into it, the corners aren't part of the T.
+pub(crate) fn tspin_kind(well: &Well, player: Player, kick: Option<u8>) -> TspinKind {
+	let kick = match kick {
+		Some(kick) if player.piece == Piece::T => kick,
+		_ => return TspinKind::None,
+	};
+	let (x, y) = (player.pt.x + 2, player.pt.y - 1);
+	let corners = [(x - 1, y - 1), (x + 1, y - 1), (x - 1, y + 1), (x + 1, y + 1)];
+	if corners.iter().filter(|&&(col, row)| is_blocked(well, col, row)).count() < 3 {
+		return TspinKind::None;
+	}
+	// The corners on the side the T points at
+	let front = match player.rot {
+		Rot::Zero => [(x - 1, y + 1), (x + 1, y + 1)],
+		Rot::Right => [(x + 1, y - 1), (x + 1, y + 1)],
+		Rot::Two => [(x - 1, y - 1), (x + 1, y - 1)],
+		Rot::Left => [(x - 1, y - 1), (x - 1, y + 1)],
+	};
+	if kick == 4 || front.iter().all(|&(col, row)| is_blocked(well, col, row)) {
+		TspinKind::Full
+	}
+	else {
+		TspinKind::Mini
+	}
+}
+
+// The walls and the floor count as blocked
+fn is_blocked(well: &Well, col: i8, row: i8) -> bool {
+	if col < 0 || col >= well.width() || row < 0 {
+		return true;
+	}
+	row < well.height() && well.line(row) & well.col_range().nth(col as usize).unwrap() != 0
+}
+
+impl State {
+	/// Previews locking the player without changing the state.
+	///
+	/// The player must rest on the stack or the floor: it must not collide and must not be able to move down.
+	/// Drop a floating player with `trace_down` first to preview a hard drop. Returns `None` for any other player.
+	///
+	/// The preview shares the classification with the real lock, see `State::last_clear_kind`.
+	pub fn simulate_lock(&self, player: Player) -> Option<SimulatedLock> {
+		if test_player(self.well(), player) || !test_player(self.well(), player.move_down()) {
+			return None;
+		}
+		Some(resolve(self.well(), player))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{trace_down, Point, SpawnResult, Step};
+
+	// Locks the player for real and compares with the preview
+	fn check(well: Well, player: Player) -> SimulatedLock {
+		let mut state = State::with_well(well);
+		let preview = state.simulate_lock(player).unwrap();
+		state.set_player(player);
+		state.lock();
+		let mut rows = Vec::new();
+		state.clear_lines(|row| rows.push(row as i8));
+		assert_eq!(preview.cleared_rows.iter().collect::<Vec<_>>(), rows);
+		assert_eq!(&preview.resulting_well, state.well());
+		assert_eq!(preview.clear_kind, state.last_clear_kind());
+		preview
+	}
+
+	#[test]
+	fn scripted_drops() {
+		// Tetris with an I piece in the first column
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0111111111,
+			0b0111111111,
+			0b0111111111,
+			0b0111111111,
+			0b1000011000,
+		]);
+		let i = trace_down(&well, Player::new(Piece::I, Rot::Right, Point::new(-2, 6)));
+		let lock = check(well, i);
+		assert_eq!(ClearKind::Tetris, lock.clear_kind);
+		assert_eq!(vec![1, 2, 3, 4], lock.cleared_rows.iter().collect::<Vec<_>>());
+		assert!(lock.cleared_rows.contains(4) && !lock.cleared_rows.contains(0));
+
+		// T-spin double into a slot with an overhang
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1100000000,
+			0b1000111111,
+			0b1101111111,
+		]);
+		let t = Player::new(Piece::T, Rot::Two, Point::new(0, 2));
+		let lock = check(well, t);
+		assert_eq!(ClearKind::TSpin(2), lock.clear_kind);
+		assert_eq!(0, lock.new_holes);
+
+		// Perfect clear with an O piece
+		let well = Well::from_data(4, &[
+			0b0000,
+			0b0000,
+			0b1100,
+			0b1100,
+		]);
+		let o = trace_down(&well, Player::new(Piece::O, Rot::Zero, Point::new(1, 3)));
+		let lock = check(well, o);
+		assert_eq!(ClearKind::PerfectClear(2), lock.clear_kind);
+		assert!(lock.resulting_well.lines().iter().all(|&line| line == 0));
+
+		// A plain drop creating a hole
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1010000000,
+		]);
+		let s = trace_down(&well, Player::new(Piece::S, Rot::Zero, Point::new(-1, 3)));
+		let lock = check(well, s);
+		assert_eq!(ClearKind::None, lock.clear_kind);
+		assert!(lock.cleared_rows.is_empty());
+		assert_eq!(1, lock.new_holes);
+	}
+
+	#[test]
+	fn perfect_clear() {
+		// A single full line at the bottom of an otherwise empty well
+		let mut well = Well::new(10, 22);
+		well.set_line(0, well.line_mask());
+		assert!(!well.is_empty());
+		let mut state = State::with_well(well);
+		let info = state.clear_lines_ex();
+		assert_eq!((1, true), (info.lines, info.perfect_clear));
+		assert!(state.well().is_empty());
+		// Clearing nothing in the empty well is no perfect clear
+		assert!(!state.clear_lines_ex().perfect_clear);
+		well.set_line(1, well.col_range().next().unwrap());
+		assert!(!State::with_well(well).clear_lines_ex().perfect_clear);
+	}
+
+	#[test]
+	fn tspin_triple() {
+		let mut state = State::with_well(Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1100000000,
+			0b1000000000,
+			0b1011111111,
+			0b1001111111,
+			0b1011111111,
+		]));
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::T));
+		for _ in 0..5 {
+			assert_eq!(Step::Moved, state.soft_drop());
+		}
+		for _ in 0..3 {
+			assert!(state.move_left());
+		}
+		// Only the last kick fits the T into the slot under the overhang
+		assert!(state.rotate_cw());
+		assert_eq!(Player::new(Piece::T, Rot::Right, Point::new(-1, 2)), *state.player().unwrap());
+		assert!(state.hard_drop());
+		let info = state.clear_lines_ex();
+		assert_eq!((3, TspinKind::Full, 1, false), (info.lines, info.tspin, info.combo, info.perfect_clear));
+		assert_eq!(ClearKind::TSpin(3), state.last_clear_kind());
+		// Only the T's own columns were just locked
+		let rows: Vec<ClearedRow> = info.effect.unwrap().cleared_rows().collect();
+		assert_eq!(vec![
+			ClearedRow { row: 0, locked: 0b0100_0000_0000_0000 << 16 },
+			ClearedRow { row: 1, locked: 0b0110_0000_0000_0000 << 16 },
+			ClearedRow { row: 2, locked: 0b0100_0000_0000_0000 << 16 },
+		], rows);
+	}
+
+	#[test]
+	fn tspin_mini() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0100000000,
+			0b0000000000,
+		]);
+		// Only one of the corners the T points at is blocked
+		let t = Player::new(Piece::T, Rot::Zero, Point::new(0, 1));
+		assert_eq!(TspinKind::Mini, tspin_kind(&well, t, Some(0)));
+		assert_eq!(TspinKind::Full, tspin_kind(&well, t, Some(4)));
+		assert_eq!(TspinKind::None, tspin_kind(&well, t, None));
+		// Not enough corners blocked
+		let t = Player::new(Piece::T, Rot::Zero, Point::new(3, 1));
+		assert_eq!(TspinKind::None, tspin_kind(&well, t, Some(0)));
+	}
+
+	#[test]
+	fn combo() {
+		// Every flat I clears a line in a narrow well, the O doesn't
+		let mut state = State::new(4, 10);
+		let mut combos = Vec::new();
+		for &piece in &[Piece::I, Piece::I, Piece::O, Piece::I] {
+			state.spawn(piece);
+			state.hard_drop();
+			combos.push(state.clear_lines_ex().combo);
+			// Checking again without a lock doesn't break the combo
+			assert_eq!(combos[combos.len() - 1], state.clear_lines_ex().combo);
+		}
+		assert_eq!(vec![1, 2, 0, 1], combos);
+	}
+
+	// Drops flat I pieces and O pieces in a narrow well, returns the effects and checksums
+	fn play_effects(seed: u32) -> (Vec<Option<ClearEffect>>, Vec<u64>) {
+		let mut state = State::new(4, 10);
+		state.set_effect_seed(seed);
+		let mut effects = Vec::new();
+		let mut checksums = Vec::new();
+		for &piece in &[Piece::I, Piece::O, Piece::I, Piece::I, Piece::O, Piece::I] {
+			state.spawn(piece);
+			state.hard_drop();
+			effects.push(state.clear_lines_ex().effect);
+			checksums.push(state.checksum());
+		}
+		(effects, checksums)
+	}
+
+	#[test]
+	fn effect_seeds() {
+		// A replay shows the same effects as its live original
+		let (live, live_checksums) = play_effects(7);
+		let (replay, replay_checksums) = play_effects(7);
+		assert_eq!(live, replay);
+		assert_eq!(live_checksums, replay_checksums);
+		assert_eq!(vec![true, false, true, true, false, true], live.iter().map(Option::is_some).collect::<Vec<_>>());
+		let seeds: Vec<EffectSeed> = live.iter().filter_map(|effect| effect.map(|effect| effect.seed)).collect();
+		assert!(seeds.windows(2).all(|pair| pair[0] != pair[1]));
+
+		// The effect seed doesn't affect gameplay
+		let (other, other_checksums) = play_effects(8);
+		assert_ne!(live, other);
+		assert_eq!(live_checksums, other_checksums);
+	}
+
+	#[test]
+	fn effect_spectator() {
+		let mut state = State::new(4, 10);
+		state.set_effect_seed(3);
+		let mut spectator = state.clone();
+		state.spawn(Piece::I);
+		state.hard_drop();
+		let info = state.clear_lines_ex();
+		let diff = spectator.diff(&state).unwrap();
+		assert_eq!(Some((None, info.effect)), diff.clear_effect());
+		diff.apply(&mut spectator).unwrap();
+		assert_eq!(info.effect, spectator.last_clear_effect());
+		assert_eq!(state.checksum(), spectator.checksum());
+	}
+
+	#[test]
+	fn not_lockable() {
+		let well = Well::new(10, 22);
+		let state = State::with_well(well);
+		// Floating in the air
+		assert_eq!(None, state.simulate_lock(Player::new(Piece::T, Rot::Zero, Point::new(3, 10))));
+		// Stuck in the wall
+		assert_eq!(None, state.simulate_lock(Player::new(Piece::T, Rot::Zero, Point::new(-3, 1))));
+		// Resting on the floor
+		assert!(state.simulate_lock(Player::new(Piece::T, Rot::Zero, Point::new(3, 1))).is_some());
+	}
+}
@@ -0,0 +1,409 @@
+/*!
+Labeled training data from bot self-play.
+
+The `Exporter` plays seeded games with the bot and writes every sampled decision to a byte stream,
+the `Reader` reads them back.
+
+# Schema
+
+All integers are little endian.
+
+The stream starts with a header:
+
+| Bytes | Field |
+|-------|-------|
+| 4 | Magic `TTDS` |
+| 4 | Schema version, `u32` |
+| 1 | Well width, `i8` |
+| 1 | Well height, `i8` |
+| 1 | Maximum number of candidates per decision, `u8` |
+| 1 | Number of features, `u8` |
+
+Followed by the decisions until the end of the stream, each game's decisions are written together once it ends:
+
+| Bytes | Field |
+|-------|-------|
+| 4 | Game number, `u32` |
+| 4 | Decision number within the game before sampling, `u32` |
+| 4 × features | Features of the well before the placement as listed by `Weights::features`, `i32` |
+| 4 | Chosen placement: piece, rotation, x and y, `u8, u8, i8, i8` |
+| 4 | Pieces placed in the game, `u32` |
+| 4 | Lines cleared in the game, `u32` |
+| 1 | Whether the game topped out before the piece limit, `u8` |
+| 1 | Number of candidates, `u8` |
+| 11 × candidates | Best candidates in order: rotation, x, y and score, `u8, i8, i8, f64` |
+
+The first candidate is the chosen placement. Candidates are of the same piece as the chosen placement.
+*/
+
+use ::std::{error, fmt};
+use ::std::io::{self, Read, Write, BufWriter};
+use ::std::convert::TryFrom;
+
+use ::{OfficialBag, GameEnd, Piece, Player, Point, PlayI, Rot, State, Weights};
+use ::garbage::xorshift;
+
+/// Current schema version.
+pub const SCHEMA_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 4] = b"TTDS";
+const FEATURES: usize = 10;
+
+/// Self-play settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExportConfig {
+	/// Weights of the bot.
+	pub weights: Weights,
+	pub width: i8,
+	pub height: i8,
+	/// Number of best candidates exported per decision.
+	pub top_k: u8,
+	/// Exports every Nth decision of a game, starting with the first.
+	pub sample_every: u32,
+	/// Ends the game after this many pieces.
+	pub max_pieces: u32,
+}
+impl Default for ExportConfig {
+	fn default() -> ExportConfig {
+		ExportConfig {
+			weights: Weights::default(),
+			width: 10,
+			height: 22,
+			top_k: 5,
+			sample_every: 1,
+			max_pieces: 1000,
+		}
+	}
+}
+
+/// Eventual outcome of a game.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Outcome {
+	pub pieces: u32,
+	pub lines: u32,
+	/// The game topped out before reaching the piece limit.
+	pub topped_out: bool,
+}
+
+/// Single labeled decision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decision {
+	pub game: u32,
+	/// Decision number within the game before sampling.
+	pub index: u32,
+	/// Features of the well before the placement.
+	pub features: [i32; FEATURES],
+	/// The chosen placement.
+	pub player: Player,
+	/// The best placements and their scores from best to worst.
+	pub candidates: Vec<(Player, f64)>,
+	pub outcome: Outcome,
+}
+
+/// Dataset header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Header {
+	pub version: u32,
+	pub width: i8,
+	pub height: i8,
+	pub top_k: u8,
+}
+
+/// Errors reading a dataset.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DatasetError {
+	Io(io::Error),
+	/// The stream is not a dataset.
+	BadMagic,
+	/// The dataset has an unsupported schema version.
+	Version(u32),
+	/// The stream contains invalid values.
+	Corrupt,
+}
+impl From<io::Error> for DatasetError {
+	fn from(err: io::Error) -> DatasetError {
+		DatasetError::Io(err)
+	}
+}
+impl fmt::Display for DatasetError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DatasetError::Io(_) => f.write_str("dataset i/o error"),
+			DatasetError::BadMagic => f.write_str("not a dataset"),
+			DatasetError::Version(version) => write!(f, "unsupported dataset version {}", version),
+			DatasetError::Corrupt => f.write_str("corrupt dataset"),
+		}
+	}
+}
+impl error::Error for DatasetError {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match *self {
+			DatasetError::Io(ref err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+/// Exports the decisions of bot self-play.
+pub struct Exporter<W: Write> {
+	out: BufWriter<W>,
+	config: ExportConfig,
+	games: u32,
+}
+
+impl<W: Write> Exporter<W> {
+	/// Creates an exporter and writes the header.
+	pub fn new(sink: W, config: ExportConfig) -> io::Result<Exporter<W>> {
+		assert!(config.sample_every > 0, "sample every must be positive");
+		let mut out = BufWriter::new(sink);
+		out.write_all(MAGIC)?;
+		out.write_all(&SCHEMA_VERSION.to_le_bytes())?;
+		out.write_all(&[config.width as u8, config.height as u8, config.top_k, FEATURES as u8])?;
+		Ok(Exporter {
+			out: out,
+			config: config,
+			games: 0,
+		})
+	}
+	/// Plays a game with the given seed and exports its sampled decisions.
+	///
+	/// Returns the decisions written.
+	pub fn export_game(&mut self, seed: u64) -> io::Result<Vec<Decision>> {
+		let config = self.config;
+		let game = self.games;
+		self.games += 1;
+		let mut state = State::new(config.width, config.height);
+		let mut bag = OfficialBag::with_rng(xorshift(seed));
+		let mut decisions = Vec::new();
+		let mut outcome = Outcome::default();
+		loop {
+			if outcome.pieces >= config.max_pieces {
+				break;
+			}
+			// Spawn returns `true` when the spawned player overlaps the well or the bag ran out of pieces
+			if state.spawn_from(&mut bag) {
+				outcome.topped_out = state.game_end() != Some(GameEnd::OutOfPieces);
+				break;
+			}
+			let candidates = PlayI::candidates(&config.weights, state.well(), *state.player().unwrap());
+			// Only losing placements left, the bot gives up just like `PlayI::play`
+			let player = match candidates.first() {
+				Some(&(player, score)) if score > f64::NEG_INFINITY => player,
+				_ => {
+					outcome.topped_out = true;
+					break;
+				},
+			};
+			if outcome.pieces.is_multiple_of(config.sample_every) {
+				decisions.push(Decision {
+					game: game,
+					index: outcome.pieces,
+					features: Weights::features(state.well()),
+					player: player,
+					candidates: candidates.into_iter().take(config.top_k as usize).collect(),
+					outcome: Outcome::default(),
+				});
+			}
+			state.set_player(player);
+			state.lock();
+			outcome.lines += state.clear_lines(|_| ()) as u32;
+			outcome.pieces += 1;
+		}
+		for decision in &mut decisions {
+			decision.outcome = outcome;
+			write_decision(&mut self.out, decision)?;
+		}
+		Ok(decisions)
+	}
+	/// Flushes the buffered output and returns the sink.
+	pub fn finish(self) -> io::Result<W> {
+		self.out.into_inner().map_err(|err| err.into())
+	}
+}
+
+fn write_decision<W: Write>(out: &mut W, decision: &Decision) -> io::Result<()> {
+	let player = decision.player;
+	out.write_all(&decision.game.to_le_bytes())?;
+	out.write_all(&decision.index.to_le_bytes())?;
+	for feature in &decision.features {
+		out.write_all(&feature.to_le_bytes())?;
+	}
+	out.write_all(&[player.piece as u8, player.rot as u8, player.pt.x as u8, player.pt.y as u8])?;
+	out.write_all(&decision.outcome.pieces.to_le_bytes())?;
+	out.write_all(&decision.outcome.lines.to_le_bytes())?;
+	out.write_all(&[decision.outcome.topped_out as u8, decision.candidates.len() as u8])?;
+	for &(candidate, score) in &decision.candidates {
+		out.write_all(&[candidate.rot as u8, candidate.pt.x as u8, candidate.pt.y as u8])?;
+		out.write_all(&score.to_bits().to_le_bytes())?;
+	}
+	Ok(())
+}
+
+/// Reads the decisions of a dataset.
+pub struct Reader<R: Read> {
+	input: R,
+	header: Header,
+}
+
+impl<R: Read> Reader<R> {
+	/// Reads the header, rejecting streams with a different schema version.
+	pub fn new(mut input: R) -> Result<Reader<R>, DatasetError> {
+		let mut magic = [0; 4];
+		input.read_exact(&mut magic)?;
+		if &magic != MAGIC {
+			return Err(DatasetError::BadMagic);
+		}
+		let version = read_u32(&mut input)?;
+		if version != SCHEMA_VERSION {
+			return Err(DatasetError::Version(version));
+		}
+		let mut bytes = [0; 4];
+		input.read_exact(&mut bytes)?;
+		if bytes[3] as usize != FEATURES {
+			return Err(DatasetError::Corrupt);
+		}
+		Ok(Reader {
+			input: input,
+			header: Header { version: version, width: bytes[0] as i8, height: bytes[1] as i8, top_k: bytes[2] },
+		})
+	}
+	/// Returns the header.
+	pub fn header(&self) -> Header {
+		self.header
+	}
+	/// Reads the next decision, `None` at the end of the stream.
+	pub fn read_decision(&mut self) -> Result<Option<Decision>, DatasetError> {
+		// Only the end of the stream between decisions is a clean end
+		let mut bytes = [0; 4];
+		let mut len = 0;
+		while len < bytes.len() {
+			match self.input.read(&mut bytes[len..]) {
+				Ok(0) if len == 0 => return Ok(None),
+				Ok(0) => return Err(DatasetError::Io(io::ErrorKind::UnexpectedEof.into())),
+				Ok(n) => len += n,
+				Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+				Err(err) => return Err(err.into()),
+			}
+		}
+		let input = &mut self.input;
+		let game = u32::from_le_bytes(bytes);
+		let index = read_u32(input)?;
+		let mut features = [0; FEATURES];
+		for feature in &mut features {
+			*feature = read_u32(input)? as i32;
+		}
+		input.read_exact(&mut bytes)?;
+		let piece = Piece::from_u8(bytes[0]).ok_or(DatasetError::Corrupt)?;
+		let rot = Rot::try_from(bytes[1]).map_err(|_| DatasetError::Corrupt)?;
+		let player = Player::new(piece, rot, Point::new(bytes[2] as i8, bytes[3] as i8));
+		let pieces = read_u32(input)?;
+		let lines = read_u32(input)?;
+		let mut bytes = [0; 2];
+		input.read_exact(&mut bytes)?;
+		let topped_out = bytes[0] != 0;
+		let mut candidates = Vec::with_capacity(bytes[1] as usize);
+		for _ in 0..bytes[1] {
+			let mut pose = [0; 3];
+			input.read_exact(&mut pose)?;
+			let mut score = [0; 8];
+			input.read_exact(&mut score)?;
+			let rot = Rot::try_from(pose[0]).map_err(|_| DatasetError::Corrupt)?;
+			let candidate = Player::new(piece, rot, Point::new(pose[1] as i8, pose[2] as i8));
+			candidates.push((candidate, f64::from_bits(u64::from_le_bytes(score))));
+		}
+		Ok(Some(Decision {
+			game: game,
+			index: index,
+			features: features,
+			player: player,
+			candidates: candidates,
+			outcome: Outcome { pieces: pieces, lines: lines, topped_out: topped_out },
+		}))
+	}
+}
+
+impl<R: Read> Iterator for Reader<R> {
+	type Item = Result<Decision, DatasetError>;
+	fn next(&mut self) -> Option<Result<Decision, DatasetError>> {
+		match self.read_decision() {
+			Ok(Some(decision)) => Some(Ok(decision)),
+			Ok(None) => None,
+			Err(err) => Some(Err(err)),
+		}
+	}
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+	let mut bytes = [0; 4];
+	input.read_exact(&mut bytes)?;
+	Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> ExportConfig {
+		ExportConfig { height: 12, top_k: 4, sample_every: 3, max_pieces: 60, ..ExportConfig::default() }
+	}
+
+	fn export(seeds: &[u64]) -> (Vec<u8>, Vec<Decision>) {
+		let mut exporter = Exporter::new(Vec::new(), config()).unwrap();
+		let mut decisions = Vec::new();
+		for &seed in seeds {
+			decisions.extend(exporter.export_game(seed).unwrap());
+		}
+		(exporter.finish().unwrap(), decisions)
+	}
+
+	#[test]
+	fn round_trip() {
+		let (bytes, written) = export(&[1, 2]);
+		assert!(!written.is_empty());
+		let reader = Reader::new(&bytes[..]).unwrap();
+		assert_eq!(Header { version: SCHEMA_VERSION, width: 10, height: 12, top_k: 4 }, reader.header());
+		let read: Vec<Decision> = reader.map(|decision| decision.unwrap()).collect();
+		assert_eq!(written, read);
+		// Sampling every third decision of each game
+		assert!(read.iter().all(|decision| decision.index % 3 == 0));
+		assert_eq!(0, read[0].index);
+		assert!(read.iter().any(|decision| decision.game == 1));
+
+		// Truncated streams are errors and not a clean end
+		let truncated = Reader::new(&bytes[..bytes.len() - 1]).unwrap();
+		assert!(truncated.last().unwrap().is_err());
+	}
+
+	#[test]
+	fn schema_version() {
+		let (mut bytes, _) = export(&[1]);
+		bytes[4] = 1;
+		match Reader::new(&bytes[..]) {
+			Err(DatasetError::Version(1)) => (),
+			other => panic!("{:?}", other.map(|reader| reader.header())),
+		}
+		bytes[0] = b'X';
+		match Reader::new(&bytes[..]) {
+			Err(DatasetError::BadMagic) => (),
+			other => panic!("{:?}", other.map(|reader| reader.header())),
+		}
+	}
+
+	#[test]
+	fn top_k() {
+		let (_, decisions) = export(&[3]);
+		for decision in &decisions {
+			assert!(decision.candidates.len() <= 4);
+			// The chosen placement is the best candidate and the one `PlayI::play` picks
+			assert_eq!(decision.player, decision.candidates[0].0);
+			assert!(decision.candidates.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+		}
+	}
+
+	#[test]
+	fn deterministic() {
+		assert_eq!(export(&[5, 6]).0, export(&[5, 6]).0);
+		assert_ne!(export(&[5]).0, export(&[6]).0);
+	}
+}
@@ -0,0 +1,154 @@
+/*!
+Error types.
+
+Every fallible API returns its own specific error, match on these to handle the individual cases.
+Applications which want a single error type can convert any of them into `Error` with `?`:
+
+```
+use tetrs::{Event, State, Well};
+
+fn load(events: &[Event], saved: &str, hole: i32) -> Result<State, tetrs::Error> {
+    let saved: Well = saved.parse()?;
+    let mut state = State::replay_events(events, (saved.width(), saved.height()))?;
+    state.try_add_garbage(1, hole)?;
+    Ok(state)
+}
+
+let saved = "|    |\n|    |\n|    |\n|    |";
+assert_eq!(1, load(&[], saved, 2).unwrap().well().stack_height());
+assert!(matches!(load(&[], "", 2), Err(tetrs::Error::ParseWell(tetrs::ParseWellError::Empty))));
+assert_eq!("coordinate 4 is out of bounds", load(&[], saved, 4).unwrap_err().to_string());
+```
+*/
+
+use ::std::{error, fmt};
+
+use ::{CoordError, DimensionMismatch, EventReplayError, ParsePieceError, ParsePlayError, ParseStateError, ParseWellError, ReplayError, SpawnError, TimelineError, ValidationError};
+use ::analysis::IllegalReason;
+#[cfg(feature = "bot")]
+use ::dataset::DatasetError;
+
+/// Any error returned by this crate.
+///
+/// Displays as the wrapped error, which is also its `source`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	ParseWell(ParseWellError),
+	ParsePiece(ParsePieceError),
+	ParsePlay(ParsePlayError),
+	ParseState(ParseStateError),
+	Validation(ValidationError),
+	Coord(CoordError),
+	Timeline(TimelineError),
+	EventReplay(EventReplayError),
+	DimensionMismatch(DimensionMismatch),
+	Spawn(SpawnError),
+	Illegal(IllegalReason),
+	Replay(ReplayError),
+	#[cfg(feature = "bot")]
+	Dataset(DatasetError),
+}
+
+impl Error {
+	fn inner(&self) -> &(dyn error::Error + 'static) {
+		match *self {
+			Error::ParseWell(ref err) => err,
+			Error::ParsePiece(ref err) => err,
+			Error::ParsePlay(ref err) => err,
+			Error::ParseState(ref err) => err,
+			Error::Validation(ref err) => err,
+			Error::Coord(ref err) => err,
+			Error::Timeline(ref err) => err,
+			Error::EventReplay(ref err) => err,
+			Error::DimensionMismatch(ref err) => err,
+			Error::Spawn(ref err) => err,
+			Error::Illegal(ref err) => err,
+			Error::Replay(ref err) => err,
+			#[cfg(feature = "bot")]
+			Error::Dataset(ref err) => err,
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self.inner(), f)
+	}
+}
+
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		Some(self.inner())
+	}
+}
+
+macro_rules! from {
+	($variant:ident($ty:ty)) => {
+		impl From<$ty> for Error {
+			fn from(err: $ty) -> Error {
+				Error::$variant(err)
+			}
+		}
+	};
+}
+
+from!(ParseWell(ParseWellError));
+from!(ParsePiece(ParsePieceError));
+from!(ParsePlay(ParsePlayError));
+from!(ParseState(ParseStateError));
+from!(Validation(ValidationError));
+from!(Coord(CoordError));
+from!(Timeline(TimelineError));
+from!(EventReplay(EventReplayError));
+from!(DimensionMismatch(DimensionMismatch));
+from!(Spawn(SpawnError));
+from!(Illegal(IllegalReason));
+from!(Replay(ReplayError));
+#[cfg(feature = "bot")]
+from!(Dataset(DatasetError));
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::std::error::Error as StdError;
+	use ::{Play, Event, State};
+
+	#[test]
+	fn display() {
+		assert_eq!("line 2: well lines must be enclosed in walls", ParseWellError::BadWalls { line: 2 }.to_string());
+		assert_eq!("expected one of the piece letters O, I, S, Z, L, J or T", ParsePieceError.to_string());
+		assert_eq!("line 1: invalid player", ParseStateError::Player.to_string());
+		assert_eq!("coordinate 300 overflows i8", CoordError::Overflow { value: 300 }.to_string());
+		assert_eq!("move 2 (RotateCW) is blocked", TimelineError::Blocked { index: 2, play: Play::RotateCW }.to_string());
+		assert_eq!("event 3 clears rows which are not full", EventReplayError::NotFull { index: 3 }.to_string());
+		assert_eq!("expected a 10x22 well, found 6x12", DimensionMismatch { expected: (10, 22), found: (6, 12) }.to_string());
+		assert_eq!("cannot spawn over the live T piece", SpawnError { player: ::Player::new(::Piece::T, ::Rot::Zero, ::Point::new(3, 21)) }.to_string());
+		assert_eq!("truncated replay", ReplayError::Truncated.to_string());
+		#[cfg(feature = "bot")]
+		assert_eq!("unsupported dataset version 7", DatasetError::Version(7).to_string());
+		// The top-level error displays as the specific error
+		assert_eq!("coordinate -1 is out of bounds", Error::from(CoordError::OutOfBounds { value: -1 }).to_string());
+	}
+
+	#[test]
+	fn conversions() {
+		let err = Error::from(State::replay_events(&[Event::OutOfPieces, Event::GarbageReceived { count: 1, hole: 20, player: None }], (10, 22)).err().unwrap());
+		assert!(matches!(err, Error::EventReplay(EventReplayError::BadHole { index: 1 })));
+		let err = Error::from(State::standard().diff(&State::new(6, 12)).unwrap_err());
+		assert!(matches!(err, Error::DimensionMismatch(DimensionMismatch { expected: (10, 22), found: (6, 12) })));
+		assert_eq!(err.to_string(), err.source().unwrap().to_string());
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn source_chain() {
+		use ::std::io;
+		let err = Error::from(DatasetError::from(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated")));
+		let dataset = err.source().unwrap();
+		assert_eq!("dataset i/o error", dataset.to_string());
+		let io = dataset.source().unwrap().downcast_ref::<io::Error>().unwrap();
+		assert_eq!(io::ErrorKind::UnexpectedEof, io.kind());
+		assert_eq!("truncated", io.to_string());
+	}
+}
@@ -0,0 +1,64 @@
+/*!
+Cargo feature introspection.
+
+The crate is tested under every entry of `SUPPORTED_FEATURE_SETS`, see `tests/compile_features.rs`.
+*/
+
+/// The optional features of the crate.
+pub const FEATURES: &[&str] = &["std", "bot", "scene", "testing", "log", "soak"];
+
+/// The documented feature combinations.
+///
+/// The empty set is the `no_std` core, every other feature requires `std`.
+/// `bot` and `scene` combine freely, `testing` and `log` are orthogonal to everything else and `soak` requires `bot`.
+/// Use `is_supported_feature_set` to check any other combination against these rules.
+pub const SUPPORTED_FEATURE_SETS: &[&[&str]] = &[
+	&[],
+	&["std"],
+	&["std", "bot"],
+	&["std", "scene"],
+	&["std", "bot", "scene"],
+	&["std", "testing", "log"],
+	&["std", "bot", "scene", "testing", "log"],
+	&["std", "bot", "soak"],
+];
+
+/// Returns the optional features this build was compiled with, in the order of `FEATURES`.
+#[cfg(feature = "std")]
+pub fn enabled_features() -> Vec<&'static str> {
+	let enabled = [
+		cfg!(feature = "std"),
+		cfg!(feature = "bot"),
+		cfg!(feature = "scene"),
+		cfg!(feature = "testing"),
+		cfg!(feature = "log"),
+		cfg!(feature = "soak"),
+	];
+	FEATURES.iter().zip(enabled.iter()).filter(|&(_, &on)| on).map(|(&name, _)| name).collect()
+}
+
+/// Returns whether the combination of features is supported.
+///
+/// Unknown feature names are never supported.
+pub fn is_supported_feature_set(features: &[&str]) -> bool {
+	features.iter().all(|name| FEATURES.contains(name)) &&
+		(!features.contains(&"soak") || features.contains(&"bot")) &&
+		(features.contains(&"std") || features.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn supported() {
+		for set in SUPPORTED_FEATURE_SETS {
+			assert!(is_supported_feature_set(set), "{:?}", set);
+		}
+		#[cfg(feature = "std")]
+		assert!(is_supported_feature_set(&enabled_features()));
+		assert!(!is_supported_feature_set(&["soak"]));
+		assert!(!is_supported_feature_set(&["bot", "scene"]));
+		assert!(!is_supported_feature_set(&["serde"]));
+	}
+}
@@ -0,0 +1,142 @@
+/*!
+Garbage hole columns.
+*/
+
+use ::rand::{Rng, SeedableRng, XorShiftRng};
+
+/// Creates a xorshift random number generator from a 64-bit seed.
+pub(crate) fn xorshift(seed: u64) -> XorShiftRng {
+	// Xorshift must not be seeded with all zeroes, mix in some constant bits
+	let lo = seed as u32;
+	let hi = (seed >> 32) as u32;
+	XorShiftRng::from_seed([lo ^ 0x9E3779B9, hi ^ 0x243F6A88, lo ^ 0xB7E15162, hi ^ 0x85A308D3])
+}
+
+/// Default probability for a garbage line to repeat the previous hole column.
+pub const GARBAGE_REPEAT: f64 = 0.7;
+
+/// Deterministic garbage hole column sequence.
+///
+/// In versus play a single instance is shared by all players, every garbage line received by any player draws
+/// its hole column from it in attack arrival order. Neither player gets luckier garbage and the sequence is reproducible from the seed.
+///
+/// The first hole column is uniformly random. Every next hole column repeats the previous column with the repeat probability,
+/// otherwise it is uniformly random among the other columns.
+#[derive(Clone, Debug)]
+pub struct GarbageRng {
+	rng: XorShiftRng,
+	seed: u64,
+	width: i8,
+	repeat: f64,
+	last: Option<i8>,
+	count: u32,
+}
+
+impl GarbageRng {
+	/// Creates a new garbage hole sequence for wells of the given width.
+	pub fn new(seed: u64, width: i8) -> GarbageRng {
+		GarbageRng::with_repeat(seed, width, GARBAGE_REPEAT)
+	}
+	/// Creates a new garbage hole sequence with the given repeat probability.
+	pub fn with_repeat(seed: u64, width: i8, repeat: f64) -> GarbageRng {
+		assert!(width >= 2, "garbage needs at least 2 columns");
+		GarbageRng {
+			rng: xorshift(seed),
+			seed: seed,
+			width: width,
+			repeat: repeat,
+			last: None,
+			count: 0,
+		}
+	}
+	/// Returns the seed of the sequence.
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+	/// Returns the previously drawn hole column.
+	pub fn last(&self) -> Option<i8> {
+		self.last
+	}
+	/// Returns the number of hole columns drawn so far.
+	pub fn count(&self) -> u32 {
+		self.count
+	}
+	/// Draws the hole column for the next garbage line.
+	pub fn next_column(&mut self) -> i8 {
+		let col = match self.last {
+			Some(last) => {
+				if self.rng.gen::<f64>() < self.repeat {
+					last
+				}
+				else {
+					// Uniform over the other columns by skipping over the previous column
+					let col = self.rng.gen_range(0, self.width - 1);
+					if col >= last { col + 1 } else { col }
+				}
+			},
+			None => self.rng.gen_range(0, self.width),
+		};
+		self.last = Some(col);
+		self.count += 1;
+		log!(::logging::LogRecord::GarbageHole { col: col });
+		col
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn shared_sequence() {
+		// Both players draw from the one sequence in arrival order
+		let mut shared = GarbageRng::new(42, 10);
+		let mut player1 = Vec::new();
+		let mut player2 = Vec::new();
+		for i in 0..20 {
+			let col = shared.next_column();
+			if i % 3 == 0 { player1.push(col) } else { player2.push(col) }
+		}
+		assert_eq!(20, shared.count());
+
+		// Reproducible from the seed
+		let mut replay = GarbageRng::new(42, 10);
+		let expected: Vec<i8> = (0..20).map(|_| replay.next_column()).collect();
+		let mut interleaved = Vec::new();
+		let (mut it1, mut it2) = (player1.iter(), player2.iter());
+		for i in 0..20 {
+			interleaved.push(*if i % 3 == 0 { it1.next() } else { it2.next() }.unwrap());
+		}
+		assert_eq!(expected, interleaved);
+
+		// Pinned sequence, changing it breaks recorded matches
+		assert_eq!(&[1, 1, 1, 1, 9, 9, 9, 4, 4, 4, 6, 6, 6, 6, 6, 7, 6, 1, 4, 3], &expected[..]);
+	}
+
+	#[test]
+	fn repeat_probability() {
+		let mut garbage = GarbageRng::with_repeat(7, 10, 0.7);
+		let mut last = garbage.next_column();
+		let mut repeats = 0;
+		const DRAWS: i32 = 10000;
+		for _ in 0..DRAWS {
+			let col = garbage.next_column();
+			assert!((0..10).contains(&col));
+			if col == last {
+				repeats += 1;
+			}
+			last = col;
+		}
+		let p = repeats as f64 / DRAWS as f64;
+		assert!(p > 0.68 && p < 0.72, "repeat probability {}", p);
+
+		// Never repeats when the repeat probability is zero
+		let mut garbage = GarbageRng::with_repeat(7, 4, 0.0);
+		let mut last = garbage.next_column();
+		for _ in 0..1000 {
+			let col = garbage.next_column();
+			assert_ne!(last, col);
+			last = col;
+		}
+	}
+}
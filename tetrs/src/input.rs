@@ -2,15 +2,134 @@
 Game timers.
 */
 
-use ::{Play, State};
+use ::std::{cmp, mem, ops};
+
+use ::{Bag, Dir, DropStatus, MoveRejectCause, Occurrence, Play, State, Step};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Clock {
+	/// Ticks per row of gravity, `Input` clamps it to at least one.
 	pub gravity: i32,
 	pub player: i32,
+	/// Ticks a player rests on the stack before it locks, zero locks on the first gravity tick it can't drop.
+	pub lock_delay: i32,
+}
+
+/// Soft drop speed.
+///
+/// Soft drop has its own timing channel independent from the horizontal movement timer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SoftDrop {
+	/// Drops the player one row every given number of ticks while held.
+	Repeat(i32),
+	/// Speeds up the gravity timer by the given factor while held, `Input` clamps it to at least one.
+	///
+	/// The guideline soft drop is 20 times the gravity.
+	/// Gravity does not report rejected moves, see `Occurrence::MoveRejected`.
+	Gravity(i32),
+}
+impl Default for SoftDrop {
+	fn default() -> SoftDrop {
+		SoftDrop::Gravity(20)
+	}
+}
+
+// Gravity must advance every tick, a clock or factor below one never drops the player and spins the timers forever
+fn clamp_clock(speed: Clock) -> Clock {
+	Clock { gravity: cmp::max(1, speed.gravity), ..speed }
+}
+fn clamp_soft_drop(soft_drop: SoftDrop) -> SoftDrop {
+	match soft_drop {
+		SoftDrop::Gravity(factor) => SoftDrop::Gravity(cmp::max(1, factor)),
+		_ => soft_drop,
+	}
 }
 
-#[derive(Default)]
+/// Actions applied by a tick of the input, eg. to play sounds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Actions(u8);
+impl Actions {
+	/// The player moved left or right.
+	pub const MOVE: Actions = Actions(0x01);
+	/// The player rotated.
+	pub const ROTATE: Actions = Actions(0x02);
+	/// The player dropped a row by soft drop or gravity.
+	pub const DROP: Actions = Actions(0x04);
+	/// The player was hard dropped.
+	pub const HARD_DROP: Actions = Actions(0x08);
+	/// The hard drop needs confirmation, see `State::guarded_hard_drop`.
+	pub const CONFIRM: Actions = Actions(0x10);
+	/// The player locked.
+	pub const LOCK: Actions = Actions(0x20);
+	/// No user input for the idle timeout, see `Input::set_idle_timeout`.
+	pub const IDLE_TIMEOUT: Actions = Actions(0x40);
+	/// User input interrupted the demo, see `Input::start_demo`.
+	pub const DEMO_INTERRUPTED: Actions = Actions(0x80);
+
+	/// Returns if all the given actions were applied.
+	pub fn contains(self, actions: Actions) -> bool {
+		self.0 & actions.0 == actions.0
+	}
+	/// Returns if no actions were applied.
+	pub fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+}
+impl ops::BitOr for Actions {
+	type Output = Actions;
+	fn bitor(self, rhs: Actions) -> Actions {
+		Actions(self.0 | rhs.0)
+	}
+}
+impl ops::BitOrAssign for Actions {
+	fn bitor_assign(&mut self, rhs: Actions) {
+		self.0 |= rhs.0;
+	}
+}
+
+/// Occurrence reported by `Input::tick_report` and `Input::advance`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TickOccurrence {
+	/// Tick within the batch, counting from zero.
+	pub tick: u32,
+	/// Sequence number shared with the state's events, see `State::take_sequenced_events`.
+	pub sequence: u64,
+	pub occurrence: Occurrence,
+}
+
+/// What happened during a batch of ticks.
+///
+/// The occurrences are listed in the order they happened. Within a tick that order is:
+/// the spawn (`advance` only), then whatever the rotations, moves, hard drop, soft drop and gravity cause in that order,
+/// then the lock delay and finally the cleared lines (`advance` only).
+/// A player coming to rest reports `ContactMade` before `LockDelayStarted` or `Locked`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TickReport {
+	/// The actions applied over all the ticks.
+	pub actions: Actions,
+	pub occurrences: Vec<TickOccurrence>,
+}
+impl TickReport {
+	fn collect(&mut self, tick: u32, state: &mut State) {
+		self.occurrences.extend(state.take_occurrences().into_iter().map(|(sequence, occurrence)| {
+			TickOccurrence { tick: tick, sequence: sequence, occurrence: occurrence }
+		}));
+	}
+}
+
+/// Who is in control of the game.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ControlMode {
+	/// A human is playing.
+	#[default]
+	Human,
+	/// The human stopped giving input, the frontend should switch to the demo.
+	Idle,
+	/// The demo is playing with `Input::play`, any user input interrupts it.
+	Demo,
+}
+
+#[derive(Clone, Debug, Default)]
 struct InputState {
 	move_left: u8,
 	move_right: u8,
@@ -18,32 +137,168 @@ struct InputState {
 	hard_drop: u8,
 	rotate_cw: u8,
 	rotate_ccw: u8,
+	rotate_180: u8,
+	// Obstruction of the held moves by `Dir`, a rejected move is reported once per obstruction
+	rejected: [Option<MoveRejectCause>; 3],
 }
 
+#[derive(Clone, Debug)]
 pub struct Input {
 	speed: Clock,
 	timers: Clock,
+	soft_drop: SoftDrop,
+	soft_drop_timer: i32,
 	state: InputState,
+	mode: ControlMode,
+	idle_timeout: Option<u32>,
+	idle_ticks: u32,
+	interrupted: bool,
 }
 
 impl Input {
 	pub fn new(speed: Clock) -> Input {
+		Input::with_soft_drop(speed, SoftDrop::default())
+	}
+	pub fn with_soft_drop(speed: Clock, soft_drop: SoftDrop) -> Input {
+		let speed = clamp_clock(speed);
 		Input {
 			speed: speed,
 			timers: speed,
+			soft_drop: clamp_soft_drop(soft_drop),
+			soft_drop_timer: 0,
 			state: InputState::default(),
+			mode: ControlMode::Human,
+			idle_timeout: None,
+			idle_ticks: 0,
+			interrupted: false,
 		}
 	}
+	/// Returns the clock.
+	pub fn clock(&self) -> Clock {
+		self.speed
+	}
+	/// Changes the clock, eg. when the level changes with `LevelProgress`.
+	///
+	/// Running timers are cut short to the new clock so faster gravity takes effect right away.
+	pub fn set_clock(&mut self, speed: Clock) {
+		let speed = clamp_clock(speed);
+		self.speed = speed;
+		self.timers.gravity = cmp::min(self.timers.gravity, speed.gravity);
+		self.timers.player = cmp::min(self.timers.player, speed.player);
+	}
+	/// Returns the soft drop speed.
+	pub fn soft_drop(&self) -> SoftDrop {
+		self.soft_drop
+	}
+	/// Changes the soft drop speed.
+	pub fn set_soft_drop(&mut self, soft_drop: SoftDrop) {
+		self.soft_drop = clamp_soft_drop(soft_drop);
+	}
 
-	pub fn move_left_down(&mut self) { self.state.move_left += 1; }
-	pub fn move_left_up(&mut self) { self.state.move_left -= 1; }
-	pub fn move_right_down(&mut self) { self.state.move_right += 1; }
-	pub fn move_right_up(&mut self) { self.state.move_right -= 1; }
-	pub fn soft_drop_down(&mut self) { self.state.soft_drop += 1; }
-	pub fn soft_drop_up(&mut self) { self.state.soft_drop -= 1; }
-	pub fn hard_drop(&mut self) { self.state.hard_drop = 1; }
-	pub fn rotate_cw(&mut self) { self.state.rotate_cw = 1; }
-	pub fn rotate_ccw(&mut self) { self.state.rotate_ccw = 1; }
+	/// Returns who is in control of the game.
+	pub fn control_mode(&self) -> ControlMode {
+		self.mode
+	}
+	/// Sets the number of ticks without user input after which a human session times out, `None` never times out.
+	pub fn set_idle_timeout(&mut self, ticks: Option<u32>) {
+		self.idle_timeout = ticks;
+		self.idle_ticks = 0;
+	}
+	/// Returns the number of ticks since the last user input.
+	pub fn idle_ticks(&self) -> u32 {
+		self.idle_ticks
+	}
+	/// Hands control to the demo until the next user input.
+	pub fn start_demo(&mut self) {
+		self.mode = ControlMode::Demo;
+		self.idle_ticks = 0;
+	}
+	// Every user input goes through here, the demo's moves with `play` do not
+	fn user_input(&mut self) {
+		if self.mode == ControlMode::Demo {
+			self.interrupted = true;
+		}
+		self.mode = ControlMode::Human;
+		self.idle_ticks = 0;
+	}
+
+	pub fn move_left_down(&mut self) {
+		self.user_input();
+		// Move on the first tick after the press
+		if self.state.move_left == 0 {
+			self.timers.player = 0;
+		}
+		self.state.move_left += 1;
+		self.state.rejected[Dir::Left as usize] = None;
+	}
+	pub fn move_left_up(&mut self) {
+		self.user_input();
+		self.state.move_left = self.state.move_left.saturating_sub(1);
+		self.state.rejected[Dir::Left as usize] = None;
+	}
+	pub fn move_right_down(&mut self) {
+		self.user_input();
+		if self.state.move_right == 0 {
+			self.timers.player = 0;
+		}
+		self.state.move_right += 1;
+		self.state.rejected[Dir::Right as usize] = None;
+	}
+	pub fn move_right_up(&mut self) {
+		self.user_input();
+		self.state.move_right = self.state.move_right.saturating_sub(1);
+		self.state.rejected[Dir::Right as usize] = None;
+	}
+	pub fn soft_drop_down(&mut self) {
+		self.user_input();
+		// Drop on the first tick after the press
+		if self.state.soft_drop == 0 {
+			self.soft_drop_timer = 0;
+		}
+		self.state.soft_drop += 1;
+		self.state.rejected[Dir::Down as usize] = None;
+	}
+	pub fn soft_drop_up(&mut self) {
+		self.user_input();
+		self.state.soft_drop = self.state.soft_drop.saturating_sub(1);
+		self.state.rejected[Dir::Down as usize] = None;
+	}
+	// Held moves report a rejected move once until the obstruction changes or the move succeeds
+	fn held_move(&mut self, state: &mut State, dir: Dir) -> bool {
+		let cause = state.move_rejection(dir);
+		let latched = mem::replace(&mut self.state.rejected[dir as usize], cause);
+		let report = cause != latched;
+		match dir {
+			Dir::Left | Dir::Right => state.shift(dir, report),
+			Dir::Down => state.soft_drop_step(report) == Step::Moved,
+		}
+	}
+	pub fn hard_drop(&mut self) { self.user_input(); self.state.hard_drop = 1; }
+	pub fn rotate_cw(&mut self) { self.user_input(); self.state.rotate_cw = 1; }
+	pub fn rotate_ccw(&mut self) { self.user_input(); self.state.rotate_ccw = 1; }
+	pub fn rotate_180(&mut self) { self.user_input(); self.state.rotate_180 = 1; }
+
+	/// Applies a move of the bot or a replay to the state right away.
+	///
+	/// These moves are not user input, they neither reset the idle timer nor interrupt the demo.
+	pub fn play(&mut self, state: &mut State, play: Play) -> Actions {
+		let had_player = state.player().is_some();
+		let mut actions = match play {
+			Play::MoveLeft if state.move_left() => Actions::MOVE,
+			Play::MoveRight if state.move_right() => Actions::MOVE,
+			Play::RotateCW if state.rotate_cw() => Actions::ROTATE,
+			Play::RotateCCW if state.rotate_ccw() => Actions::ROTATE,
+			Play::Rotate180 if state.rotate_180() => Actions::ROTATE,
+			Play::SoftDrop if state.soft_drop_lock() => Actions::DROP,
+			Play::HardDrop if state.hard_drop() => Actions::HARD_DROP,
+			Play::FirmDrop if state.firm_drop() => Actions::DROP,
+			_ => Actions::default(),
+		};
+		if had_player && state.player().is_none() {
+			actions |= Actions::LOCK;
+		}
+		actions
+	}
 
 	/// Fast forward to the next time new user input will be accepted.
 	pub fn ffw(&mut self) -> usize {
@@ -58,14 +313,466 @@ impl Input {
 		drops
 	}
 
-	pub fn tick(&mut self, state: &mut State) {
+	/// Applies the input to the state for one tick and reports what happened, see `TickReport`.
+	///
+	/// Records the state's occurrences for the duration of the tick.
+	pub fn tick_report(&mut self, state: &mut State) -> TickReport {
+		let mut report = TickReport::default();
+		state.record_occurrences(true);
+		report.actions = self.tick(state);
+		report.collect(0, state);
+		state.record_occurrences(false);
+		report
+	}
+	/// Runs the game for the given number of ticks and reports what happened, see `TickReport`.
+	///
+	/// Spawns the next piece from the bag when there is no player and clears the lines after every lock.
+	/// Stops spawning once the game is over, a bag running out of pieces finishes the game.
+	pub fn advance<B: Bag + ?Sized>(&mut self, state: &mut State, bag: &mut B, ticks: u32) -> TickReport {
+		let mut report = TickReport::default();
+		state.record_occurrences(true);
+		for tick in 0..ticks {
+			if state.player().is_none() && !state.is_game_over() {
+				state.spawn_from(bag);
+			}
+			let actions = self.tick(state);
+			if actions.contains(Actions::LOCK) {
+				state.clear_lines(|_| ());
+			}
+			report.actions |= actions;
+			report.collect(tick, state);
+		}
+		state.record_occurrences(false);
+		report
+	}
+	/// Applies the input to the state for one tick.
+	///
+	/// Rotations and hard drops fire once per press.
+	/// Held moves fire on the first tick after the press and then auto-repeat every `Clock::player` ticks.
+	///
+	/// Returns the actions applied.
+	pub fn tick(&mut self, state: &mut State) -> Actions {
+		let mut actions = Actions::default();
+		let had_player = state.player().is_some();
+
+		// Idle detection, the timeout fires once until the next user input
+		if self.interrupted {
+			self.interrupted = false;
+			actions |= Actions::DEMO_INTERRUPTED;
+		}
+		if self.mode == ControlMode::Human {
+			if let Some(timeout) = self.idle_timeout {
+				self.idle_ticks += 1;
+				if self.idle_ticks >= timeout {
+					self.mode = ControlMode::Idle;
+					actions |= Actions::IDLE_TIMEOUT;
+				}
+			}
+		}
+
+		// The tick counts against the move clock if a player is in play at its start
+		state.tick_move_clock();
+		state.set_lock_delay(self.speed.lock_delay.max(0) as u32);
+
+		// One-shot rotations
+		if self.state.rotate_cw > 0 && state.rotate_cw() {
+			actions |= Actions::ROTATE;
+		}
+		if self.state.rotate_ccw > 0 && state.rotate_ccw() {
+			actions |= Actions::ROTATE;
+		}
+		if self.state.rotate_180 > 0 && state.rotate_180() {
+			actions |= Actions::ROTATE;
+		}
+		self.state.rotate_cw = 0;
+		self.state.rotate_ccw = 0;
+		self.state.rotate_180 = 0;
+
+		// Horizontal movement with auto-repeat
 		if self.timers.player > 0 {
 			self.timers.player -= 1;
 		}
-		else {
-			if self.state.move_left > 0 {
+		if self.timers.player <= 0 && self.state.move_left + self.state.move_right > 0 {
+			let dir = if self.state.move_left > 0 { Dir::Left } else { Dir::Right };
+			let moved = self.held_move(state, dir);
+			if moved {
+				actions |= Actions::MOVE;
+			}
+			self.timers.player = self.speed.player;
+		}
+
+		// One-shot hard drop
+		if self.state.hard_drop > 0 {
+			self.state.hard_drop = 0;
+			match state.guarded_hard_drop() {
+				DropStatus::Locked | DropStatus::Dropped => actions |= Actions::HARD_DROP,
+				DropStatus::NeedsConfirmation => actions |= Actions::CONFIRM,
+				DropStatus::NoPlayer => (),
+			}
+		}
+
+		// Soft drop at a fixed rate on its own timer
+		let mut gravity_rate = 1;
+		if self.state.soft_drop > 0 {
+			match self.soft_drop {
+				SoftDrop::Repeat(rate) => {
+					if self.soft_drop_timer > 0 {
+						self.soft_drop_timer -= 1;
+					}
+					if self.soft_drop_timer <= 0 {
+						if self.held_move(state, Dir::Down) {
+							actions |= Actions::DROP;
+						}
+						self.soft_drop_timer = rate;
+					}
+				},
+				SoftDrop::Gravity(factor) => {
+					gravity_rate = factor;
+				},
+			}
+		}
+
+		// Gravity, sped up while soft dropping in gravity mode
+		self.timers.gravity -= gravity_rate;
+		while self.timers.gravity <= 0 {
+			self.timers.gravity += self.speed.gravity;
+			if state.gravity_lock() {
+				actions |= Actions::DROP;
+			}
+		}
+
+		state.tick_lock_delay();
+		state.tick_misdrop_guard();
+
+		if had_player && state.player().is_none() {
+			actions |= Actions::LOCK;
+		}
+		actions
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{State, Piece, Player, Point};
+
+	fn row(state: &State) -> i8 {
+		state.player().unwrap().pt.y
+	}
+
+	#[test]
+	fn soft_drop_gravity() {
+		let mut state = State::new(10, 22);
+		state.spawn(Piece::T);
+		let start = row(&state);
+		let mut input = Input::with_soft_drop(Clock { gravity: 40, player: 8, lock_delay: 0 }, SoftDrop::Gravity(20));
+		input.soft_drop_down();
+		for i in 1..11 {
+			input.tick(&mut state);
+			assert_eq!(start - i / 2, row(&state));
+		}
+	}
+
+	#[test]
+	fn auto_repeat() {
+		for &rate in &[7, 8, 10, 15, 30] {
+			let mut state = State::new(12, 22);
+			state.spawn(Piece::T);
+			let start = state.player().unwrap().pt.x;
+			let mut input = Input::new(Clock { gravity: 1000, player: rate, lock_delay: 0 });
+			input.move_left_down();
+			let mut moves = 0;
+			for _ in 0..30 {
+				if input.tick(&mut state).contains(Actions::MOVE) {
+					moves += 1;
+				}
+			}
+			// Moves on the first tick, then every rate ticks
+			let expected = 1 + 29 / rate as i8;
+			assert_eq!(expected, moves, "rate {}", rate);
+			assert_eq!(start - expected, state.player().unwrap().pt.x);
+		}
+	}
+
+	#[test]
+	fn one_shots() {
+		let mut state = State::new(10, 22);
+		state.spawn(Piece::T);
+		let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+		input.rotate_cw();
+		assert!(input.tick(&mut state).contains(Actions::ROTATE));
+		assert_eq!(::Rot::Right, state.player().unwrap().rot);
+		// Fired once per press
+		assert!(input.tick(&mut state).is_empty());
+		input.hard_drop();
+		let actions = input.tick(&mut state);
+		assert!(actions.contains(Actions::HARD_DROP | Actions::LOCK));
+		assert!(!actions.contains(Actions::ROTATE));
+		assert!(state.player().is_none());
+	}
+
+	#[test]
+	fn lock_delay() {
+		let mut state = State::new(10, 6);
+		state.spawn(Piece::T);
+		let mut input = Input::new(Clock { gravity: 1, player: 8, lock_delay: 10 });
+		// Lands on the fourth tick and rests for the delay
+		for _ in 0..12 {
+			input.tick(&mut state);
+		}
+		assert_eq!(1, row(&state));
+		input.tick(&mut state);
+		assert!(state.player().is_none());
+	}
+
+	#[test]
+	fn soft_drop_repeat() {
+		for &(rate, expected) in &[(1, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9][..]), (3, &[0, 3, 6, 9][..]), (4, &[0, 4, 8][..])] {
+			let mut state = State::new(10, 22);
+			state.spawn(Piece::T);
+			let mut input = Input::with_soft_drop(Clock { gravity: 1000, player: 8, lock_delay: 0 }, SoftDrop::Repeat(rate));
+			input.soft_drop_down();
+			// Drops on the first tick, then every rate ticks
+			let drops: Vec<i32> = (0..10).filter(|_| input.tick(&mut state).contains(Actions::DROP)).collect();
+			assert_eq!(expected, &drops[..], "rate {}", rate);
+		}
+	}
 
+	#[test]
+	fn soft_drop_independent_from_player_speed() {
+		fn run(player: i32, soft_drop: SoftDrop) -> i8 {
+			let mut state = State::new(10, 22);
+			state.spawn(Piece::T);
+			let mut input = Input::with_soft_drop(Clock { gravity: 40, player: player, lock_delay: 0 }, soft_drop);
+			input.soft_drop_down();
+			input.move_left_down();
+			for _ in 0..12 {
+				input.tick(&mut state);
 			}
+			row(&state)
+		}
+		for &soft_drop in &[SoftDrop::Gravity(20), SoftDrop::Repeat(3)] {
+			assert_eq!(run(1, soft_drop), run(8, soft_drop));
+			assert_eq!(run(1, soft_drop), run(30, soft_drop));
+		}
+	}
+
+	#[test]
+	fn idle_resets() {
+		let presses: [fn(&mut Input); 10] = [
+			Input::move_left_down, Input::move_left_up,
+			Input::move_right_down, Input::move_right_up,
+			Input::soft_drop_down, Input::soft_drop_up,
+			Input::hard_drop, Input::rotate_cw, Input::rotate_ccw, Input::rotate_180,
+		];
+		for press in &presses {
+			let mut state = State::new(10, 22);
+			state.spawn(Piece::T);
+			let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+			input.set_idle_timeout(Some(100));
+			for _ in 0..5 {
+				input.tick(&mut state);
+			}
+			assert_eq!(5, input.idle_ticks());
+			press(&mut input);
+			assert_eq!(0, input.idle_ticks());
+		}
+
+		// The bot's moves are not user input
+		let mut state = State::new(10, 22);
+		state.spawn(Piece::T);
+		let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+		input.set_idle_timeout(Some(100));
+		input.tick(&mut state);
+		assert!(input.play(&mut state, Play::MoveLeft).contains(Actions::MOVE));
+		assert!(input.play(&mut state, Play::RotateCW).contains(Actions::ROTATE));
+		assert!(input.play(&mut state, Play::HardDrop).contains(Actions::HARD_DROP | Actions::LOCK));
+		assert_eq!(1, input.idle_ticks());
+	}
+
+	#[test]
+	fn idle_cycle() {
+		let mut state = State::new(10, 22);
+		let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+		input.set_idle_timeout(Some(10));
+		assert_eq!(ControlMode::Human, input.control_mode());
+
+		// The timeout fires exactly once, on the tenth tick
+		let timeouts: Vec<usize> = (1..40).filter(|_| input.tick(&mut state).contains(Actions::IDLE_TIMEOUT)).collect();
+		assert_eq!(vec![10], timeouts);
+		assert_eq!(ControlMode::Idle, input.control_mode());
+
+		// The demo plays without interruption
+		input.start_demo();
+		assert_eq!(ControlMode::Demo, input.control_mode());
+		state.spawn(Piece::I);
+		for _ in 0..20 {
+			input.play(&mut state, Play::MoveRight);
+			assert!(input.tick(&mut state).is_empty());
+		}
+		assert_eq!(ControlMode::Demo, input.control_mode());
+
+		// Any user input interrupts the demo, reported once by the next tick
+		input.rotate_ccw();
+		assert_eq!(ControlMode::Human, input.control_mode());
+		assert!(input.tick(&mut state).contains(Actions::DEMO_INTERRUPTED));
+		assert!(!input.tick(&mut state).contains(Actions::DEMO_INTERRUPTED));
+
+		// Back in human control the idle timer runs again
+		let timeouts = (0..20).filter(|_| input.tick(&mut state).contains(Actions::IDLE_TIMEOUT)).count();
+		assert_eq!(1, timeouts);
+	}
+
+	#[test]
+	fn tick_report_20g() {
+		// Twenty rows per tick without lock delay
+		let mut state = State::new(10, 16);
+		state.spawn(Piece::T);
+		let mut input = Input::with_soft_drop(Clock { gravity: 1, player: 8, lock_delay: 0 }, SoftDrop::Gravity(20));
+		input.soft_drop_down();
+		state.record_events(true);
+		let report = input.tick_report(&mut state);
+		assert!(report.actions.contains(Actions::DROP | Actions::LOCK));
+		let occurrences: Vec<Occurrence> = report.occurrences.iter().map(|entry| entry.occurrence).collect();
+		let landed = Player::new(Piece::T, ::Rot::Zero, Point::new(3, 1));
+		assert_eq!(vec![Occurrence::ContactMade(landed), Occurrence::Locked(landed)], occurrences);
+		assert!(report.occurrences[0].sequence < report.occurrences[1].sequence);
+		assert!(report.occurrences.iter().all(|entry| entry.tick == 0));
+		// The lock event shares its sequence number
+		assert_eq!(vec![(report.occurrences[1].sequence, ::Event::Locked { player: landed, kick: None })], state.take_sequenced_events());
+	}
+
+	#[test]
+	fn advance() {
+		fn run() -> TickReport {
+			let mut state = State::new(10, 10);
+			let mut bag = ::OfficialBag::from_seed(1);
+			let mut input = Input::new(Clock { gravity: 2, player: 8, lock_delay: 5 });
+			input.advance(&mut state, &mut bag, 100)
 		}
+		let report = run();
+		let locks: Vec<&TickOccurrence> = report.occurrences.iter().filter(|entry| matches!(entry.occurrence, Occurrence::Locked(_))).collect();
+		assert!(locks.len() >= 2);
+		assert!(locks[0].tick < locks[1].tick && locks[0].sequence < locks[1].sequence);
+		// Every piece spawns, comes to rest, waits out the lock delay and locks in that order
+		let kinds: Vec<u8> = report.occurrences.iter().map(|entry| match entry.occurrence {
+			Occurrence::Spawned(_) => 0,
+			Occurrence::ContactMade(_) => 1,
+			Occurrence::LockDelayStarted => 2,
+			Occurrence::Locked(_) => 3,
+			_ => 4,
+		}).collect();
+		assert_eq!(&[0, 1, 2, 3, 0, 1, 2, 3], &kinds[..8]);
+		assert!(report.occurrences.windows(2).all(|pair| pair[0].sequence < pair[1].sequence && pair[0].tick <= pair[1].tick));
+		// Locking after resting for the delay
+		let rested = report.occurrences.iter().find(|entry| entry.occurrence == Occurrence::LockDelayStarted).unwrap();
+		assert_eq!(rested.tick + 4, locks[0].tick);
+		// Stable across runs
+		assert_eq!(report, run());
+	}
+
+	#[test]
+	fn advance_replay() {
+		// Held moves and soft drops are recorded like the moves of the state
+		let mut state = State::new(10, 16);
+		state.record_replay(true);
+		let mut bag = ::OfficialBag::from_seed(3);
+		let mut input = Input::with_soft_drop(Clock { gravity: 10, player: 2, lock_delay: 0 }, SoftDrop::Repeat(3));
+		input.move_left_down();
+		input.soft_drop_down();
+		input.advance(&mut state, &mut bag, 40);
+		input.move_left_up();
+		input.move_right_down();
+		input.advance(&mut state, &mut bag, 40);
+		input.soft_drop_up();
+		input.advance(&mut state, &mut bag, 40);
+		assert!(state.stats().soft_drop_rows > 0);
+		let replay = state.take_replay().unwrap();
+		let last = replay.playback().last().unwrap();
+		assert_eq!(state.player(), last.player());
+		assert_eq!(state.well(), last.well());
+		assert_eq!(state.checksum(), last.checksum());
+	}
+
+	#[test]
+	fn set_clock() {
+		let mut state = State::standard();
+		state.set_player(Player::new(Piece::O, ::Rot::Zero, Point::new(4, 20)));
+		let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+		for _ in 0..10 {
+			input.tick(&mut state);
+		}
+		assert_eq!(20, state.player().unwrap().pt.y);
+		// The next level speeds up the gravity without waiting out the slow timer
+		let clock = Clock { gravity: 2, player: 8, lock_delay: 0 };
+		input.set_clock(clock);
+		assert_eq!(clock, input.clock());
+		for _ in 0..4 {
+			input.tick(&mut state);
+		}
+		assert_eq!(18, state.player().unwrap().pt.y);
+	}
+
+	#[test]
+	fn zero_clock() {
+		// A zero or negative clock runs gravity every tick instead of hanging
+		let mut state = State::standard();
+		state.set_player(Player::new(Piece::O, ::Rot::Zero, Point::new(4, 20)));
+		let mut input = Input::with_soft_drop(Clock { gravity: 0, player: 8, lock_delay: 0 }, SoftDrop::Gravity(0));
+		assert_eq!(1, input.clock().gravity);
+		assert_eq!(SoftDrop::Gravity(1), input.soft_drop());
+		input.soft_drop_down();
+		input.tick(&mut state);
+		assert_eq!(19, state.player().unwrap().pt.y);
+		input.soft_drop_up();
+		input.ffw();
+		input.set_clock(Clock { gravity: -5, player: 8, lock_delay: 0 });
+		assert_eq!(1, input.clock().gravity);
+		input.set_soft_drop(SoftDrop::Gravity(-3));
+		assert_eq!(SoftDrop::Gravity(1), input.soft_drop());
+		input.ffw();
+		input.tick(&mut state);
+	}
+
+	#[test]
+	fn move_rejected_once_per_hold() {
+		fn rejections(report: &TickReport) -> Vec<Occurrence> {
+			report.occurrences.iter().map(|entry| entry.occurrence).filter(|occurrence| matches!(occurrence, Occurrence::MoveRejected { .. })).collect()
+		}
+		// The O piece is held against a block of the stack, a full line below holds it up
+		let well = ::Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0100000000,
+			0b0000000000,
+			0b0000000000,
+			0b1111111111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_player(Player::new(Piece::O, ::Rot::Zero, Point::new(1, 5)));
+		let mut input = Input::new(Clock { gravity: 1000, player: 2, lock_delay: 0 });
+		input.move_left_down();
+		let stack = Occurrence::MoveRejected { dir: Dir::Left, cause: MoveRejectCause::Stack };
+		let mut reported = Vec::new();
+		for _ in 0..20 {
+			reported.extend(rejections(&input.tick_report(&mut state)));
+		}
+		assert_eq!(vec![stack], reported);
+
+		// Clearing the full line drops the stack away, the held move goes through until it hits the wall
+		state.clear_lines(|_| ());
+		let mut reported = Vec::new();
+		for _ in 0..20 {
+			reported.extend(rejections(&input.tick_report(&mut state)));
+		}
+		assert_eq!(-1, state.player().unwrap().pt.x);
+		assert_eq!(vec![Occurrence::MoveRejected { dir: Dir::Left, cause: MoveRejectCause::Wall }], reported);
+
+		// Pressing again reports again
+		input.move_left_up();
+		input.move_left_down();
+		assert_eq!(1, rejections(&input.tick_report(&mut state)).len());
 	}
 }
@@ -0,0 +1,456 @@
+/*!
+Evolving evaluator weights with a genetic algorithm.
+
+The `Population` holds candidate genomes, typically `Weights`, together with their fitness.
+Every generation keeps the best candidates and breeds the rest from tournament selected parents
+by crossover and Gaussian mutation.
+
+Only the ratios between the weights affect the bot's decisions, every genome is normalized to unit length before it is evaluated.
+
+```
+use tetrs::Weights;
+use tetrs::learn::{Genome, Population};
+
+// Prefer weights close to the default weights, higher fitness is better
+let target = Weights::default().genes();
+let fitness = |weights: &Weights| -weights.genes().iter().zip(&target).map(|(a, b)| (a - b) * (a - b)).sum::<f64>();
+
+let mut population = Population::<Weights, _>::from_seed(20, 42);
+population.evaluate(fitness);
+for _ in 0..10 {
+    population.evolve(fitness);
+}
+assert!(population.best().fitness > -1.0);
+```
+*/
+
+use ::rand::{Rand, Rng, XorShiftRng};
+use ::rand::distributions::{IndependentSample, Normal};
+
+use ::std::sync::atomic::{AtomicUsize, Ordering};
+use ::std::thread;
+
+use ::{Weights, PhasedEvaluator, Evaluator, PlayI, State, OfficialBag};
+use ::garbage::xorshift;
+
+/// Candidate solutions evolved by the `Population`.
+pub trait Genome: Clone {
+	/// Returns the genes as a flat list.
+	fn genes(&self) -> Vec<f64>;
+	/// Overwrites the genes from a flat list as returned by `genes`.
+	fn set_genes(&mut self, genes: &[f64]);
+	/// Scales the genes to unit length.
+	///
+	/// Genomes made of multiple independent evaluators normalize each of them.
+	fn normalize(&mut self) {
+		let mut genes = self.genes();
+		normalize(&mut genes);
+		self.set_genes(&genes);
+	}
+}
+
+fn normalize(genes: &mut [f64]) {
+	let len = genes.iter().map(|gene| gene * gene).sum::<f64>().sqrt();
+	if len > 0.0 {
+		for gene in genes.iter_mut() {
+			*gene /= len;
+		}
+	}
+}
+
+/// The factors in the order of the fields, the danger zone is not evolved.
+impl Genome for Weights {
+	fn genes(&self) -> Vec<f64> {
+		vec![
+			self.agg_height_f,
+			self.max_height_f,
+			self.complete_lines_f,
+			self.holes_f,
+			self.caves_f,
+			self.bumpiness_f,
+			self.stacking_f,
+			self.well_f,
+			self.row_transitions_f,
+			self.col_transitions_f,
+			self.well_depth_f,
+			self.perfect_clear_f,
+		]
+	}
+	fn set_genes(&mut self, genes: &[f64]) {
+		self.agg_height_f = genes[0];
+		self.max_height_f = genes[1];
+		self.complete_lines_f = genes[2];
+		self.holes_f = genes[3];
+		self.caves_f = genes[4];
+		self.bumpiness_f = genes[5];
+		self.stacking_f = genes[6];
+		self.well_f = genes[7];
+		self.row_transitions_f = genes[8];
+		self.col_transitions_f = genes[9];
+		self.well_depth_f = genes[10];
+		self.perfect_clear_f = genes[11];
+	}
+}
+
+/// The genes of the opening, steady and danger weights in that order, the thresholds are not evolved.
+impl Genome for PhasedEvaluator {
+	fn genes(&self) -> Vec<f64> {
+		let mut genes = self.opening.genes();
+		genes.extend(self.steady.genes());
+		genes.extend(self.danger.genes());
+		genes
+	}
+	fn set_genes(&mut self, genes: &[f64]) {
+		let n = genes.len() / 3;
+		self.opening.set_genes(&genes[..n]);
+		self.steady.set_genes(&genes[n..n * 2]);
+		self.danger.set_genes(&genes[n * 2..]);
+	}
+	fn normalize(&mut self) {
+		self.opening.normalize();
+		self.steady.normalize();
+		self.danger.normalize();
+	}
+}
+
+/// How the genes of two parents are combined.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Crossover {
+	/// Every gene is taken from either parent with equal probability.
+	Uniform,
+	/// Every gene is drawn uniformly from the range spanned by the parents' genes, extended on both sides by the given fraction of its length (BLX-α).
+	Blend(f64),
+}
+
+/// Tuning of the genetic algorithm.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+	/// Number of candidates competing in a tournament, the fittest becomes a parent.
+	pub tournament_size: usize,
+	/// Number of the fittest candidates carried over unchanged into the next generation.
+	pub elitism: usize,
+	/// How the parents' genes are combined.
+	pub crossover: Crossover,
+	/// Probability for every gene of a child to be mutated.
+	pub mutation_rate: f64,
+	/// Standard deviation of the Gaussian noise added to mutated genes.
+	pub mutation_sigma: f64,
+}
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			tournament_size: 3,
+			elitism: 2,
+			crossover: Crossover::Blend(0.5),
+			mutation_rate: 0.2,
+			mutation_sigma: 0.1,
+		}
+	}
+}
+
+/// A genome and its fitness.
+#[derive(Clone, Debug)]
+pub struct Individual<G> {
+	pub genome: G,
+	/// Fitness returned by the fitness callback, higher is better.
+	///
+	/// `NEG_INFINITY` until evaluated.
+	pub fitness: f64,
+}
+
+/// Population of genomes evolved by a genetic algorithm.
+#[derive(Clone, Debug)]
+pub struct Population<G, R> {
+	individuals: Vec<Individual<G>>,
+	config: Config,
+	generation: u32,
+	rng: R,
+}
+impl<G: Genome + Rand> Population<G, XorShiftRng> {
+	/// Creates a population seeded for reproducible runs.
+	///
+	/// Given the same seed and a deterministic fitness callback every generation is identical.
+	pub fn from_seed(size: usize, seed: u64) -> Population<G, XorShiftRng> {
+		Population::new(size, xorshift(seed))
+	}
+}
+impl<G: Genome + Rand, R: Rng> Population<G, R> {
+	/// Creates a population of random genomes with the default config.
+	///
+	/// The individuals are not yet evaluated, call `evaluate` before evolving.
+	///
+	/// # Panics
+	///
+	/// The size must be at least 2.
+	pub fn new(size: usize, mut rng: R) -> Population<G, R> {
+		assert!(size >= 2, "population size must be at least 2");
+		let individuals = (0..size).map(|_| {
+			let mut genome: G = rng.gen();
+			genome.normalize();
+			Individual { genome: genome, fitness: f64::NEG_INFINITY }
+		}).collect();
+		Population {
+			individuals: individuals,
+			config: Config::default(),
+			generation: 0,
+			rng: rng,
+		}
+	}
+}
+impl<G: Genome, R: Rng> Population<G, R> {
+	/// Replaces the config.
+	pub fn with_config(mut self, config: Config) -> Population<G, R> {
+		self.config = config;
+		self
+	}
+	/// Returns the config.
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+	/// Returns the number of generations evolved so far.
+	pub fn generation(&self) -> u32 {
+		self.generation
+	}
+	/// Returns the individuals, fittest first once evaluated.
+	pub fn individuals(&self) -> &[Individual<G>] {
+		&self.individuals
+	}
+	/// Returns the fittest individual.
+	pub fn best(&self) -> &Individual<G> {
+		&self.individuals[0]
+	}
+	/// Evaluates the fitness of every individual and sorts them fittest first.
+	pub fn evaluate<F: FnMut(&G) -> f64>(&mut self, mut fitness: F) {
+		self.evaluate_batch(|genomes| genomes.iter().map(&mut fitness).collect());
+	}
+	/// Evaluates the fitness of all individuals at once, eg. with `evaluate_parallel`.
+	///
+	/// The callback returns the fitness of every genome in the given order.
+	pub fn evaluate_batch<F: FnOnce(&[G]) -> Vec<f64>>(&mut self, fitness: F) {
+		let genomes: Vec<G> = self.individuals.iter().map(|individual| individual.genome.clone()).collect();
+		let fitness = fitness(&genomes);
+		assert_eq!(genomes.len(), fitness.len(), "expected a fitness for every genome");
+		for (individual, fitness) in self.individuals.iter_mut().zip(fitness) {
+			individual.fitness = fitness;
+		}
+		self.sort();
+	}
+	/// Breeds the next generation and evaluates it.
+	///
+	/// The elite keep their fitness and are not evaluated again, the fitness callback should be deterministic.
+	pub fn evolve<F: FnMut(&G) -> f64>(&mut self, mut fitness: F) {
+		self.evolve_batch(|genomes| genomes.iter().map(&mut fitness).collect());
+	}
+	/// Breeds the next generation and evaluates the children at once.
+	pub fn evolve_batch<F: FnOnce(&[G]) -> Vec<f64>>(&mut self, fitness: F) {
+		let size = self.individuals.len();
+		let elitism = self.config.elitism.min(size);
+		let mut children = Vec::with_capacity(size - elitism);
+		while elitism + children.len() < size {
+			let a = self.select();
+			let b = self.select();
+			let mut genome = self.crossover(a, b);
+			self.mutate(&mut genome);
+			genome.normalize();
+			children.push(genome);
+		}
+		let fitness = fitness(&children);
+		assert_eq!(children.len(), fitness.len(), "expected a fitness for every genome");
+		self.individuals.truncate(elitism);
+		self.individuals.extend(children.into_iter().zip(fitness).map(|(genome, fitness)| Individual { genome: genome, fitness: fitness }));
+		self.generation += 1;
+		self.sort();
+	}
+
+	fn sort(&mut self) {
+		// Stable sort keeps the elite in front of equally fit children
+		self.individuals.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap_or(::std::cmp::Ordering::Equal));
+	}
+	// Returns the index of the tournament winner
+	fn select(&mut self) -> usize {
+		let size = self.individuals.len();
+		let mut best = self.rng.gen_range(0, size);
+		for _ in 1..self.config.tournament_size {
+			// Sorted fittest first, the lowest index wins
+			best = best.min(self.rng.gen_range(0, size));
+		}
+		best
+	}
+	fn crossover(&mut self, a: usize, b: usize) -> G {
+		let genes_b = self.individuals[b].genome.genes();
+		let mut genes = self.individuals[a].genome.genes();
+		for (gene, &other) in genes.iter_mut().zip(&genes_b) {
+			*gene = match self.config.crossover {
+				Crossover::Uniform => if self.rng.gen() { *gene } else { other },
+				Crossover::Blend(alpha) => {
+					let lo = gene.min(other);
+					let hi = gene.max(other);
+					let extend = (hi - lo) * alpha;
+					if hi - lo > 0.0 { self.rng.gen_range(lo - extend, hi + extend) } else { lo }
+				},
+			};
+		}
+		let mut child = self.individuals[a].genome.clone();
+		child.set_genes(&genes);
+		child
+	}
+	fn mutate(&mut self, genome: &mut G) {
+		let normal = Normal::new(0.0, self.config.mutation_sigma);
+		let mut genes = genome.genes();
+		for gene in &mut genes {
+			if self.rng.gen::<f64>() < self.config.mutation_rate {
+				*gene += normal.ind_sample(&mut self.rng);
+			}
+		}
+		genome.set_genes(&genes);
+	}
+}
+
+/// Moves after which a game played by `play_game` is cut short.
+pub const MAX_MOVES: usize = 2000;
+
+/// Plays a seeded game in a shortened 10x11 well and returns the number of lines cleared.
+///
+/// The bot's placements are teleported into place, the game ends when the bot finds no placement or after `MAX_MOVES` pieces.
+pub fn play_game<E: Evaluator + Clone>(evaluator: &E, seed: u64) -> i64 {
+	let mut evaluator = evaluator.clone();
+	// Fewer rows end the games sooner
+	let mut state = State::new(10, 11);
+	let mut bag = OfficialBag::from_seed(seed);
+	let mut score = 0;
+	for _ in 0..MAX_MOVES {
+		state.spawn_from(&mut bag);
+		let player = match state.player() {
+			Some(&player) => player,
+			None => break,
+		};
+		evaluator.update(state.well());
+		match PlayI::play(&evaluator, state.well(), player).player {
+			Some(player) => {
+				state.set_player(player);
+				state.lock();
+			},
+			None => break,
+		}
+		state.clear_lines(|_| score += 1);
+	}
+	score
+}
+
+/// Returns the seeds of the games played by every candidate.
+///
+/// Every candidate plays the same piece sequences to reduce fitness noise.
+pub fn game_seeds(games: usize, seed: u64) -> Vec<u64> {
+	let mut rng = xorshift(seed);
+	(0..games).map(|_| rng.gen()).collect()
+}
+
+/// Plays `games_per` games with every candidate and returns their total scores, one thread at a time.
+///
+/// The reference for `evaluate_parallel`, both return identical scores.
+pub fn evaluate_serial<E: Evaluator + Clone>(weights: &[E], games_per: usize, seed: u64) -> Vec<i64> {
+	let seeds = game_seeds(games_per, seed);
+	weights.iter().map(|weights| seeds.iter().map(|&seed| play_game(weights, seed)).sum()).collect()
+}
+
+/// Plays `games_per` games with every candidate on all cores and returns their total scores.
+///
+/// The games are seeded by `game_seeds`, the scores do not depend on the number of threads.
+pub fn evaluate_parallel<E: Evaluator + Clone + Sync>(weights: &[E], games_per: usize, seed: u64) -> Vec<i64> {
+	evaluate_parallel_with(weights, games_per, seed, play_game)
+}
+
+/// Like `evaluate_parallel` with a custom game, eg. a cheese race.
+pub fn evaluate_parallel_with<E: Sync, F: Fn(&E, u64) -> i64 + Sync>(weights: &[E], games_per: usize, seed: u64, game: F) -> Vec<i64> {
+	let seeds = game_seeds(games_per, seed);
+	let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	let jobs = weights.len() * games_per;
+	let next = AtomicUsize::new(0);
+	let mut scores = vec![0; weights.len()];
+	thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads.min(jobs)).map(|_| scope.spawn(|| {
+			// Every job plays a single game, the threads take the next job until all are done
+			let mut results = Vec::new();
+			loop {
+				let job = next.fetch_add(1, Ordering::Relaxed);
+				if job >= jobs {
+					break results;
+				}
+				let (candidate, game_index) = (job / games_per, job % games_per);
+				results.push((candidate, game(&weights[candidate], seeds[game_index])));
+			}
+		})).collect();
+		for worker in workers {
+			for (candidate, score) in worker.join().unwrap() {
+				scores[candidate] += score;
+			}
+		}
+	});
+	scores
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn distance(genes: &[f64], target: &[f64]) -> f64 {
+		genes.iter().zip(target).map(|(a, b)| (a - b) * (a - b)).sum()
+	}
+
+	#[test]
+	fn improves_convex_objective() {
+		let mut target = Weights::narrow().genes();
+		normalize(&mut target);
+		let fitness = |weights: &Weights| -distance(&weights.genes(), &target);
+
+		let mut population = Population::<Weights, _>::from_seed(8, 7);
+		population.evaluate(&fitness);
+		let initial = population.best().fitness;
+		for _ in 0..20 {
+			population.evolve(&fitness);
+		}
+		assert_eq!(20, population.generation());
+		assert!(population.best().fitness > initial, "{} <= {}", population.best().fitness, initial);
+		// Elitism never loses the best individual
+		assert!(population.individuals().iter().all(|individual| individual.fitness <= population.best().fitness));
+	}
+
+	#[test]
+	fn deterministic_seed() {
+		let fitness = |weights: &Weights| weights.holes_f;
+		let run = || {
+			let mut population = Population::<Weights, _>::from_seed(6, 1234)
+				.with_config(Config { crossover: Crossover::Uniform, ..Config::default() });
+			population.evaluate(fitness);
+			for _ in 0..5 {
+				population.evolve(fitness);
+			}
+			population.best().genome
+		};
+		assert_eq!(run(), run());
+	}
+
+	#[test]
+	fn normalized() {
+		let mut population = Population::<PhasedEvaluator, _>::from_seed(4, 99);
+		population.evaluate(|_| 0.0);
+		population.evolve(|_| 0.0);
+		for individual in population.individuals() {
+			let genome = &individual.genome;
+			for weights in &[genome.opening, genome.steady, genome.danger] {
+				let len = weights.genes().iter().map(|gene| gene * gene).sum::<f64>().sqrt();
+				assert!((len - 1.0).abs() < 1e-9, "{}", len);
+			}
+		}
+	}
+
+	#[test]
+	fn parallel_matches_serial() {
+		let mut rng = xorshift(5);
+		let weights: Vec<Weights> = (0..3).map(|_| rng.gen()).collect();
+		let serial = evaluate_serial(&weights, 3, 11);
+		assert_eq!(serial, evaluate_parallel(&weights, 3, 11));
+		assert_eq!(weights.len(), serial.len());
+		assert_eq!(vec![0; 0], evaluate_parallel::<Weights>(&[], 3, 11));
+	}
+}
@@ -0,0 +1,201 @@
+/*!
+Gravity curves and level progression.
+
+Levels count from zero and advance with the cleared lines, every level speeds up the gravity of the `Clock`:
+
+```
+use tetrs::{Clock, Gravity, LevelProgress};
+
+let mut levels = LevelProgress::new(Gravity::Guideline, Clock { gravity: 60, player: 8, lock_delay: 30 });
+assert_eq!(60, levels.clock().gravity);
+assert!(!levels.add_lines(4));
+assert!(levels.add_lines(8));
+assert_eq!(1, levels.level());
+assert_eq!(48, levels.clock().gravity);
+```
+*/
+
+use ::Clock;
+
+/// Frame rate the gravity curves are defined at, one tick of the `Input` per frame.
+pub const FRAMES_PER_SECOND: f64 = 60.0;
+
+/// Highest level of the guideline curve, the gravity stays at 20 cells per frame for the levels above it.
+pub const GUIDELINE_MAX_LEVEL: u32 = 18;
+
+/// Lines to clear per level by default.
+pub const LINES_PER_LEVEL: u32 = 10;
+
+// Frames per row of the NES version by level, the levels past the end drop a row every frame
+static NES_FRAMES: [u8; 29] = [
+	48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+	5, 5, 5, 4, 4, 4, 3, 3, 3,
+	2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+/// Gravity curve, the speed pieces fall at by level.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Gravity {
+	/// The curve of the tetris guideline, see `Gravity::guideline`.
+	Guideline,
+	/// The curve of the NES version, see `Gravity::classic_nes`.
+	ClassicNes,
+}
+
+impl Gravity {
+	/// Returns the guideline gravity for the level in cells per frame.
+	///
+	/// A row falls every `(0.8 - level * 0.007)^level` seconds, level 0 being the first level of the guideline.
+	/// Levels above `GUIDELINE_MAX_LEVEL` fall as fast as the last level, about 20 cells per frame or the height of the well.
+	pub fn guideline(level: u32) -> f64 {
+		let level = level.min(GUIDELINE_MAX_LEVEL) as f64;
+		let seconds = (0.8 - level * 0.007).powf(level);
+		1.0 / (seconds * FRAMES_PER_SECOND)
+	}
+	/// Returns the gravity of the NES version for the level in cells per frame.
+	///
+	/// From 48 frames per row at level 0 down to a row every frame from level 29 on.
+	pub fn classic_nes(level: u32) -> f64 {
+		let frames = NES_FRAMES.get(level as usize).cloned().unwrap_or(1);
+		1.0 / frames as f64
+	}
+	/// Returns the gravity for the level in cells per frame.
+	pub fn cells_per_frame(self, level: u32) -> f64 {
+		match self {
+			Gravity::Guideline => Gravity::guideline(level),
+			Gravity::ClassicNes => Gravity::classic_nes(level),
+		}
+	}
+	/// Returns the ticks per row for the `Clock` at the level, rounded to the nearest tick.
+	///
+	/// The clock drops at most a row per tick, faster gravity is clamped to a single tick.
+	pub fn ticks_per_row(self, level: u32) -> i32 {
+		(1.0 / self.cells_per_frame(level)).round().max(1.0) as i32
+	}
+}
+
+/// Tracks the cleared lines and the level they reach.
+///
+/// The level advances every `LINES_PER_LEVEL` lines by default. Swap the `clock` into the `Input` when the level changes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LevelProgress {
+	gravity: Gravity,
+	clock: Clock,
+	lines_per_level: u32,
+	start_level: u32,
+	lines: u32,
+}
+
+impl LevelProgress {
+	/// Creates a new level progression starting at level 0.
+	///
+	/// The auto repeat and the lock delay of the clock are kept for every level, the gravity follows the curve.
+	pub fn new(gravity: Gravity, clock: Clock) -> LevelProgress {
+		LevelProgress {
+			gravity: gravity,
+			clock: clock,
+			lines_per_level: LINES_PER_LEVEL,
+			start_level: 0,
+			lines: 0,
+		}
+	}
+	/// Sets the lines to clear per level.
+	///
+	/// # Panics
+	///
+	/// Panics if the lines per level is zero.
+	pub fn set_lines_per_level(&mut self, lines_per_level: u32) {
+		assert!(lines_per_level > 0, "a level needs at least one line");
+		self.lines_per_level = lines_per_level;
+	}
+	/// Sets the level the game starts at, the cleared lines count up from it.
+	pub fn set_start_level(&mut self, level: u32) {
+		self.start_level = level;
+	}
+	/// Counts the cleared lines, returns `true` if the level changed.
+	pub fn add_lines(&mut self, lines: u32) -> bool {
+		let level = self.level();
+		self.lines = self.lines.saturating_add(lines);
+		self.level() != level
+	}
+	/// Returns the lines cleared so far.
+	pub fn lines(&self) -> u32 {
+		self.lines
+	}
+	/// Returns the current level.
+	pub fn level(&self) -> u32 {
+		self.start_level.saturating_add(self.lines / self.lines_per_level)
+	}
+	/// Returns the clock for the current level.
+	pub fn clock(&self) -> Clock {
+		Clock {
+			gravity: self.gravity.ticks_per_row(self.level()),
+			..self.clock
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_close(expected: f64, value: f64) {
+		assert!((expected - value).abs() < 1e-3 * expected, "expected {} found {}", expected, value);
+	}
+
+	#[test]
+	fn guideline() {
+		// One row per second at the first level
+		assert_close(0.016667, Gravity::guideline(0));
+		assert_close(0.021017, Gravity::guideline(1));
+		assert_close(0.17753, Gravity::guideline(8));
+		assert_close(1.4570, Gravity::guideline(13));
+		assert_close(20.229, Gravity::guideline(GUIDELINE_MAX_LEVEL));
+		assert_eq!(Gravity::guideline(GUIDELINE_MAX_LEVEL), Gravity::guideline(100));
+		assert!((0..GUIDELINE_MAX_LEVEL).all(|level| Gravity::guideline(level) < Gravity::guideline(level + 1)));
+	}
+
+	#[test]
+	fn classic_nes() {
+		assert_close(0.020833, Gravity::classic_nes(0));
+		assert_close(0.125, Gravity::classic_nes(8));
+		assert_close(0.2, Gravity::classic_nes(12));
+		assert_close(0.5, Gravity::classic_nes(19));
+		assert_close(0.5, Gravity::classic_nes(28));
+		assert_close(1.0, Gravity::classic_nes(29));
+		assert_close(1.0, Gravity::classic_nes(1000));
+	}
+
+	#[test]
+	fn ticks_per_row() {
+		assert_eq!(60, Gravity::Guideline.ticks_per_row(0));
+		assert_eq!(48, Gravity::ClassicNes.ticks_per_row(0));
+		assert_eq!(2, Gravity::ClassicNes.ticks_per_row(19));
+		// Faster than a row per tick is clamped
+		assert_eq!(1, Gravity::Guideline.ticks_per_row(13));
+		assert_eq!(1, Gravity::Guideline.ticks_per_row(GUIDELINE_MAX_LEVEL));
+	}
+
+	#[test]
+	fn progress() {
+		let clock = Clock { gravity: 40, player: 8, lock_delay: 30 };
+		let mut levels = LevelProgress::new(Gravity::ClassicNes, clock);
+		assert_eq!(Clock { gravity: 48, ..clock }, levels.clock());
+		assert!(!levels.add_lines(4));
+		assert!(!levels.add_lines(0));
+		assert!(levels.add_lines(4 + 2));
+		assert_eq!((1, 10), (levels.level(), levels.lines()));
+		assert_eq!(Clock { gravity: 43, ..clock }, levels.clock());
+		// A tetris can skip levels with short levels
+		levels.set_lines_per_level(2);
+		assert_eq!(5, levels.level());
+		assert!(levels.add_lines(4));
+		assert_eq!(7, levels.level());
+
+		let mut levels = LevelProgress::new(Gravity::ClassicNes, clock);
+		levels.set_start_level(18);
+		assert_eq!(3, levels.clock().gravity);
+		assert!(levels.add_lines(10));
+		assert_eq!(2, levels.clock().gravity);
+	}
+}
@@ -1,44 +1,197 @@
 /*!
 Tetris game engine.
+
+# Stability
+
+Enums which are expected to grow new variants are marked `#[non_exhaustive]`, match them with a wildcard arm.
+Where exhaustive enumeration is genuinely needed use the provided helpers, eg. `Play::all()`.
+
+`Piece`, `Rot` and `TileTy` are exhaustive; their discriminants are part of the `Tile` byte encoding.
+
+# Features
+
+* `std` (default): the game `State` and everything built on it. Without it the crate is `no_std` and allocation free,
+  only `Well`, `Player`, `Piece`, `Rot`, `Point`, `Tile`, `Play` with `PlayBuf`, the rules and the SRS tables remain.
+  Every other feature enables `std`.
+* `bot` (default): the player AI `PlayI`, its `Weights`, the `PhasedEvaluator`, the bags which depend on it (`BestBag`, `WorstBag`, `HatetrisBag`)
+  the training data in `dataset` and the genetic algorithm in `learn`.
+* `scene` (default): the visual `Scene` tracked by `State`.
+* `testing`: bag conformance checks for third-party bags.
+* `log`: structured logging hooks.
+* `soak`: the long running soak test in `tests/soak.rs`, the short variant always runs.
+
+The engine core (`Well`, `Player`, `State` movement and locking) builds with any combination of `bot` and `scene`,
+all four combinations are supported. The documented combinations are listed in `SUPPORTED_FEATURE_SETS`,
+`enabled_features` reports what the current build was compiled with.
 */
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![allow(clippy::redundant_field_names)]
+
+// The core modules name `::std` paths which all exist in `core`
+#[cfg(not(any(feature = "std", test)))]
+extern crate core as std;
+
+#[macro_use]
+mod logging;
+#[cfg(feature = "log")]
+pub use self::logging::{LogRecord, LockReason, set_log_sink, stderr_sink, collect_sink, take_collected};
+
+#[cfg(feature = "std")]
 extern crate rand;
 
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+pub use self::error::Error;
+
+mod features;
+pub use self::features::{FEATURES, SUPPORTED_FEATURE_SETS, is_supported_feature_set};
+#[cfg(feature = "std")]
+pub use self::features::enabled_features;
+
+mod action;
+pub use self::action::{Action, PacedReplay};
+
+mod play;
+pub use self::play::{Play, PlayBuf, ParsePlayError, ParseSequenceError, PLAY_BUF_CAPACITY};
+#[cfg(feature = "std")]
+pub use self::play::parse_play_sequence;
+
+#[cfg(feature = "bot")]
 mod bot;
-pub use self::bot::{Weights, PlayI, Play};
+#[cfg(feature = "bot")]
+pub use self::bot::{Weights, WellMetrics, Evaluator, PlayI, SpawnSafety};
+
+#[cfg(feature = "bot")]
+mod phased;
+#[cfg(feature = "bot")]
+pub use self::phased::{Phase, PhaseThresholds, PhasedEvaluator};
+
+#[cfg(feature = "bot")]
+pub mod dataset;
+
+#[cfg(feature = "bot")]
+pub mod learn;
 
+#[cfg(feature = "std")]
+#[macro_use]
 mod bag;
-pub use self::bag::{Bag, OfficialBag, BestBag, WorstBag};
+#[cfg(feature = "std")]
+pub use self::bag::{Bag, OfficialBag, SeededBag, SequenceBag};
+#[cfg(feature = "bot")]
+pub use self::bag::{BestBag, WorstBag, HatetrisBag};
+#[cfg(all(feature = "std", any(test, feature = "testing")))]
+pub use self::bag::assert_bag_conformance;
 
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "std")]
+pub use self::queue::{NextQueue, QueueEvent, QueueEntryHint, HintThresholds, verify_queue};
+
+#[cfg(feature = "std")]
 mod input;
-pub use self::input::{Clock, Input};
+#[cfg(feature = "std")]
+pub use self::input::{Clock, SoftDrop, Input, Actions, ControlMode, TickReport, TickOccurrence};
+
+#[cfg(feature = "std")]
+mod level;
+#[cfg(feature = "std")]
+pub use self::level::{Gravity, LevelProgress, FRAMES_PER_SECOND, GUIDELINE_MAX_LEVEL, LINES_PER_LEVEL};
 
 mod pt;
-pub use self::pt::Point;
+pub use self::pt::{Point, CoordError};
 
 mod piece;
-pub use self::piece::{Piece, Sprite};
+pub use self::piece::{Piece, Sprite, ParsePieceError};
 
 mod rot;
-pub use self::rot::Rot;
+pub use self::rot::{Rot, TryFromRotError};
 
 mod srs;
-pub use self::srs::{SrsData, srs_cw, srs_ccw, srs_data_cw, srs_data_ccw};
+pub use self::srs::{SrsData, srs_cw, srs_ccw, srs_180, srs_data_cw, srs_data_ccw, srs_data_180, arika_data_cw, arika_data_ccw};
 
 mod player;
 pub use self::player::Player;
 
+#[cfg(feature = "std")]
+mod animation;
+#[cfg(feature = "std")]
+pub use self::animation::{timeline, Timeline, Keyframe, InterpolatedPose, TimelineError, spawn_path, SpawnAnimation, SPAWN_TICKS};
+
+#[cfg(feature = "std")]
+mod garbage;
+#[cfg(feature = "std")]
+pub use self::garbage::{GarbageRng, GARBAGE_REPEAT};
+
+#[cfg(feature = "std")]
+mod royale;
+#[cfg(feature = "std")]
+pub use self::royale::{Royale, Seat, Targeting};
+
+#[cfg(feature = "std")]
+mod versus;
+#[cfg(feature = "std")]
+pub use self::versus::{Match, MatchStatus, Contender};
+
+#[cfg(feature = "std")]
+pub mod scoring;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+
+#[cfg(feature = "std")]
+pub mod moves;
+
+#[cfg(feature = "std")]
+mod cheese;
+#[cfg(feature = "std")]
+pub use self::cheese::{CheeseRace, CheeseResult};
+
 mod well;
-pub use self::well::{Well, Line, ParseWellError, MAX_WIDTH, MAX_HEIGHT};
+pub use self::well::{Well, Line, ParseWellError, WellValidation, ValidationError, MAX_WIDTH, MAX_HEIGHT, STANDARD_WIDTH, STANDARD_HEIGHT};
+
+#[cfg(feature = "std")]
+mod telemetry;
+#[cfg(feature = "std")]
+pub use self::telemetry::{Telemetry, TelemetryStats};
 
 mod tile;
-pub use self::tile::{Tile, TileTy, TILE_BG0, TILE_BG1, TILE_BG2};
+pub use self::tile::{Tile, TileTy, TILE_GARBAGE, TILE_BG0, TILE_BG1, TILE_BG2, CONNECTS_LEFT, CONNECTS_RIGHT, CONNECTS_UP, CONNECTS_DOWN};
+
+#[cfg(feature = "std")]
+mod palette;
+#[cfg(feature = "std")]
+pub use self::palette::{Palette, Rgb};
 
+#[cfg(feature = "scene")]
 mod scene;
-pub use self::scene::{Scene};
+#[cfg(feature = "scene")]
+pub use self::scene::{Scene, SceneView, CompactStyle};
+
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+pub use self::snapshot::SnapshotBuffer;
+
+#[cfg(feature = "std")]
+mod replay;
+#[cfg(feature = "std")]
+pub use self::replay::{Replay, ReplayAction, ReplayEntry, ReplayError, Playback};
+
+#[cfg(feature = "std")]
+mod clear;
+#[cfg(feature = "std")]
+pub use self::clear::{ClearedRows, ClearKind, SimulatedLock, ClearInfo, TspinKind, ClearEffect, ClearedRow, EffectSeed};
 
+#[cfg(feature = "std")]
 mod state;
-pub use self::state::{State, test_player, trace_down};
+#[cfg(feature = "std")]
+pub use self::state::{State, Stats, ParseStateError, GameEnd, MisdropGuard, DropStatus, Step, StepOutcome, StepSummary, SpawnResult, SpawnError, MoveBudget, ClockExpiry, SpawnGrace, MAX_LOCK_RESETS, StateDiff, LineChange, DimensionMismatch, Event, EventReplayError, Occurrence, Dir, MoveRejectCause, test_player, trace_down};
+#[cfg(feature = "scene")]
+pub use self::state::TileChange;
 
 mod rules;
-pub use self::rules::{Rules, TheRules};
+pub use self::rules::{Rules, TheRules, ArikaRules, ClassicRules, KidsRules, KIDS_WIDTH, KIDS_HEIGHT, spawn_player};
+#[cfg(feature = "std")]
+pub use self::rules::KIDS_CLOCK;
@@ -0,0 +1,156 @@
+/*!
+Structured logging hooks.
+
+With the `log` feature enabled the engine emits `LogRecord`s at interesting decision points to a user registered sink.
+Without it the `log!` macro expands to nothing and the record expressions are never evaluated.
+*/
+
+#[cfg(feature = "log")]
+macro_rules! log {
+	($record:expr) => { ::logging::emit($record) };
+}
+#[cfg(not(feature = "log"))]
+macro_rules! log {
+	($record:expr) => {};
+}
+
+#[cfg(feature = "log")]
+pub use self::imp::*;
+
+#[cfg(feature = "log")]
+mod imp {
+	use ::std::{mem};
+	use ::std::cell::RefCell;
+	use ::std::sync::atomic::{AtomicUsize, Ordering};
+
+	use ::{Piece, Player, Point};
+
+	/// Why the player was locked.
+	#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+	pub enum LockReason {
+		/// The player could not move down any further.
+		Stuck,
+		/// The player was hard dropped.
+		HardDrop,
+		/// The player was locked with `State::lock`.
+		Manual,
+	}
+
+	/// Structured log record.
+	#[derive(Copy, Clone, Debug, PartialEq)]
+	#[non_exhaustive]
+	pub enum LogRecord {
+		/// A rotation succeeded with the kick at the given index, zero means no kick was needed.
+		Kick { player: Player, index: u8, offset: Point },
+		/// A rotation was rejected, all kicks collide.
+		KickFailed { player: Player },
+		/// The player was locked.
+		Lock { player: Player, reason: LockReason },
+		/// A new player spawned, shifted up the given number of rows by the spawn grace.
+		Spawn { player: Player, nudge: u8 },
+		/// A full line was detected and cleared.
+		Clear { row: i8 },
+		/// The bag was refilled with a new permutation.
+		BagRefill { bag: [Piece; 7] },
+		/// The bot found a better placement.
+		BotBest { player: Player, score: f64 },
+		/// A garbage hole column was drawn.
+		GarbageHole { col: i8 },
+	}
+
+	impl LogRecord {
+		/// Returns the engine module which emitted the record.
+		pub fn module(&self) -> &'static str {
+			match *self {
+				LogRecord::Kick { .. } | LogRecord::KickFailed { .. } => "srs",
+				LogRecord::Spawn { .. } | LogRecord::Lock { .. } | LogRecord::Clear { .. } => "state",
+				LogRecord::BagRefill { .. } => "bag",
+				LogRecord::BotBest { .. } => "bot",
+				LogRecord::GarbageHole { .. } => "garbage",
+			}
+		}
+	}
+
+	// The registered sink as a function pointer, zero if no sink is registered.
+	static SINK: AtomicUsize = AtomicUsize::new(0);
+
+	/// Registers the log sink, `None` stops logging.
+	///
+	/// The sink is global and called from whichever thread emits the record.
+	pub fn set_log_sink(sink: Option<fn(LogRecord)>) {
+		SINK.store(sink.map(|f| f as usize).unwrap_or(0), Ordering::Release);
+	}
+
+	#[inline]
+	pub(crate) fn emit(record: LogRecord) {
+		let sink = SINK.load(Ordering::Acquire);
+		if sink != 0 {
+			// Only valid function pointers are ever stored
+			let sink: fn(LogRecord) = unsafe { mem::transmute(sink) };
+			sink(record);
+		}
+	}
+
+	/// Sink which prints the records to stderr.
+	pub fn stderr_sink(record: LogRecord) {
+		eprintln!("tetrs::{}: {:?}", record.module(), record);
+	}
+
+	thread_local! {
+		static COLLECTED: RefCell<Vec<LogRecord>> = const { RefCell::new(Vec::new()) };
+	}
+
+	/// Sink which collects the records in a thread local buffer, retrieve them with `take_collected`.
+	pub fn collect_sink(record: LogRecord) {
+		COLLECTED.with(|collected| collected.borrow_mut().push(record));
+	}
+
+	/// Takes the records collected on this thread by `collect_sink`.
+	pub fn take_collected() -> Vec<LogRecord> {
+		COLLECTED.with(|collected| mem::take(&mut *collected.borrow_mut()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[cfg(not(feature = "log"))]
+	#[test]
+	fn disabled() {
+		// The record expression is compiled out
+		log!(panic!("log record evaluated"));
+	}
+
+	#[cfg(feature = "log")]
+	#[test]
+	fn scripted() {
+		use super::*;
+		use ::{State, Well, Player, Piece, Rot, Point};
+
+		set_log_sink(Some(collect_sink));
+		let _ = take_collected();
+
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000110000,
+			0b0000011100,
+			0b0000001111,
+			0b0111000111,
+			0b1100001111,
+			0b1111001111,
+			0b1111101111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_player(Player::new(Piece::J, Rot::Zero, Point::new(2, 5)));
+		assert!(state.rotate_ccw());
+		assert!(state.hard_drop());
+		assert_eq!(1, state.clear_lines(|_| ()));
+
+		let records = take_collected();
+		assert_eq!(vec![
+			LogRecord::Kick { player: Player::new(Piece::J, Rot::Left, Point::new(3, 3)), index: 4, offset: Point::new(1, -2) },
+			LogRecord::Lock { player: Player::new(Piece::J, Rot::Left, Point::new(3, 3)), reason: LockReason::HardDrop },
+			LogRecord::Clear { row: 1 },
+		], records);
+		assert_eq!("srs", records[0].module());
+	}
+}
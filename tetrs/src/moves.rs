@@ -0,0 +1,195 @@
+/*!
+Move generator.
+
+Lists the placements a piece can reach without scoring them, eg. to check if a placement is reachable or to generate training data:
+
+```
+use tetrs::{Piece, Well, spawn_player};
+
+let well = Well::new(10, 22);
+let placements = tetrs::moves::enumerate(&well, spawn_player(&well, Piece::O));
+assert_eq!(9, placements.len());
+```
+*/
+
+use ::{Play, Player, Point, Well, MAX_WIDTH, MAX_HEIGHT, srs_cw, srs_ccw, srs_180, test_player};
+
+/// Placement reachable by the player, see `enumerate`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Placement {
+	/// The player resting on the stack or the floor, ready to lock.
+	pub player: Player,
+	/// The moves from the start, the last soft drop lands the player.
+	pub path: Vec<Play>,
+}
+
+/// Lists every placement the player can reach.
+///
+/// Moves left, right, soft drops and rotates with the SRS kicks, the placements are in the order they were found.
+/// The placements cover distinct cells, a rotation of a symmetric piece covering the cells of a placement found before is left out.
+/// Searches the same positions as `PlayI::play` which scores the placements.
+pub fn enumerate(well: &Well, player: Player) -> Vec<Placement> {
+	let mut placements = Vec::new();
+	let mut found = Vec::new();
+	search(well, player, |_| false, false, |path, player| {
+		// The symmetric pieces reach the same cells in different rotations, keep the first
+		let cells = cells(player);
+		if !found.contains(&cells) {
+			found.push(cells);
+			placements.push(Placement {
+				player: player,
+				path: path.iter().map(|&(play, _)| play).collect(),
+			});
+		}
+	});
+	placements
+}
+
+// The cells of the player in the well, in the order of the sprite
+fn cells(player: Player) -> [Point; 4] {
+	let mut cells = [Point::new(0, 0); 4];
+	for (cell, pt) in cells.iter_mut().zip(player.sprite().cells()) {
+		*cell = Point::new(player.pt.x + pt.x, player.pt.y - pt.y);
+	}
+	cells
+}
+
+// The number of states in a single row:
+// `MAX_WIDTH` plus `3` (for overlap with the well) times `4` (the number of rotations)
+const STRIDE: usize = (MAX_WIDTH + 3) * 4;
+// The number of rows starting all the way up to the top
+pub(crate) const SIZE: usize = STRIDE * (MAX_HEIGHT + 4);
+
+// Index of the player in the visited states, `None` if wall kicks carried it above the tracked rows
+pub(crate) fn visited_index(player: Player) -> Option<usize> {
+	if player.pt.y as usize >= MAX_HEIGHT + 4 {
+		return None;
+	}
+	Some(player.pt.y as usize * STRIDE + (player.pt.x as i32 + 3) as usize * 4 + player.rot as u8 as usize)
+}
+
+// Calls back with the path and the placement for every placement reachable without entering positions to avoid
+pub(crate) fn search<A: Fn(Player) -> bool, F: FnMut(&[(Play, Player)], Player)>(well: &Well, player: Player, avoid: A, rotate_180: bool, mut f: F) {
+	// Keep track of which states we've visited
+	// TODO! Use a bit array instead, reduces allocation by a factor of 8
+	let mut visited = [false; SIZE];
+	let mut visit = |next: Player| -> bool {
+		// Kicks climbing above the tracked rows are not worth exploring
+		let i = match visited_index(next) { Some(i) => i, None => return true };
+		if !visited[i] {
+			visited[i] = true;
+			false
+		}
+		else {
+			true
+		}
+	};
+	// Depth-first traversal through the possible game states
+	let mut path = Vec::new();
+	path.push((Play::Idle, player));
+	// While we have unexplored game states
+	while let Some(&(play, player)) = path.last() {
+		match play {
+			Play::Idle => {
+				path.last_mut().unwrap().0 = Play::SoftDrop;
+				let next = player.move_down();
+				if !visit(next) {
+					if test_player(well, next) {
+						f(&path, player);
+					}
+					else if !avoid(next) {
+						path.push((Play::Idle, next));
+					}
+				}
+			},
+			Play::SoftDrop => {
+				path.last_mut().unwrap().0 = Play::MoveLeft;
+				let next = player.move_left();
+				if !visit(next) && !test_player(well, next) && !avoid(next) {
+					path.push((Play::Idle, next));
+				}
+			},
+			Play::MoveLeft => {
+				path.last_mut().unwrap().0 = Play::MoveRight;
+				let next = player.move_right();
+				if !visit(next) && !test_player(well, next) && !avoid(next) {
+					path.push((Play::Idle, next));
+				}
+			},
+			Play::MoveRight => {
+				path.last_mut().unwrap().0 = Play::RotateCW;
+				let next = srs_cw(well, player);
+				if !visit(next) && !avoid(next) {
+					path.push((Play::Idle, next));
+				}
+			},
+			Play::RotateCW => {
+				path.last_mut().unwrap().0 = Play::RotateCCW;
+				let next = srs_ccw(well, player);
+				if !visit(next) && !avoid(next) {
+					path.push((Play::Idle, next));
+				}
+			},
+			Play::RotateCCW if rotate_180 => {
+				path.last_mut().unwrap().0 = Play::Rotate180;
+				let next = srs_180(well, player);
+				if !visit(next) && !avoid(next) {
+					path.push((Play::Idle, next));
+				}
+			},
+			Play::RotateCCW | Play::Rotate180 => {
+				// Exhausted all possible moves, back one up and try again
+				path.pop();
+			},
+			_ => unreachable!(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Action, Piece, Rot, State, spawn_player};
+
+	#[test]
+	fn empty_well() {
+		let well = Well::new(10, 22);
+		let count = |piece| enumerate(&well, spawn_player(&well, piece)).len();
+		assert_eq!(9, count(Piece::O));
+		// Every rotation, the flat ones fit in 8 columns and the upright ones in 9
+		assert_eq!(8 + 9 + 8 + 9, count(Piece::T));
+		// The rotations of the symmetric pieces cover the same cells
+		assert_eq!(7 + 10, count(Piece::I));
+		assert_eq!(8 + 9, count(Piece::S));
+	}
+
+	#[test]
+	fn paths() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1100110000,
+			0b1100111111,
+		]);
+		let start = Player::new(Piece::T, Rot::Zero, Point::new(3, 6));
+		let placements = enumerate(&well, start);
+		for placement in &placements {
+			// Following the path lands the player on the placement
+			let mut state = State::with_well(well);
+			state.set_player(start);
+			for &play in &placement.path {
+				assert!(state.step(Action::from_play(play).unwrap()) || play == Play::SoftDrop);
+			}
+			assert_eq!(Some(&placement.player), state.player());
+			assert!(test_player(&well, placement.player.move_down()));
+		}
+		// Distinct placements, the same as the bot's candidates for the asymmetric T
+		for (i, placement) in placements.iter().enumerate() {
+			assert!(placements[..i].iter().all(|other| cells(other.player) != cells(placement.player)));
+		}
+		#[cfg(feature = "bot")]
+		assert_eq!(placements.len(), ::PlayI::candidates(&::Weights::default(), &well, start).len());
+	}
+}
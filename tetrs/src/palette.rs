@@ -0,0 +1,227 @@
+/*!
+Tile colors.
+*/
+
+use ::{Piece, Tile, TileTy};
+
+/// RGB color.
+pub type Rgb = [u8; 3];
+
+// Color slots, the pieces occupy the slots matching their discriminants
+const SLOT_GARBAGE: usize = 7;
+const SLOT_GHOST: usize = 8;
+const SLOT_BG0: usize = 9;
+const SLOT_COUNT: usize = 12;
+
+// Order in which the slots get their own index when the color budget is limited
+static PRIORITY: [usize; SLOT_COUNT] = [SLOT_BG0, SLOT_GHOST, 0, 1, 2, 3, 4, 5, 6, SLOT_GARBAGE, SLOT_BG0 + 1, SLOT_BG0 + 2];
+
+/// Indexed color palette.
+///
+/// Every tile resolves to an index into the palette colors:
+///
+/// * Player and field tiles resolve to the color of their piece, garbage tiles without a piece to the garbage color.
+/// * Ghost tiles resolve to the ghost color.
+/// * Background tiles resolve to one of the three background shades.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Palette {
+	colors: Vec<Rgb>,
+	map: [u8; SLOT_COUNT],
+}
+
+/// Returns the palette of the reference frontend with every tile color in its own index.
+impl Default for Palette {
+	fn default() -> Palette {
+		Palette::new(
+			[[241, 238, 81], [83, 254, 248], [84, 254, 87], [255, 85, 85], [254, 203, 36], [84, 85, 255], [255, 85, 254]],
+			[170, 170, 170],
+			[50, 50, 50],
+			[[0, 0, 0], [40, 16, 16], [80, 24, 24]])
+	}
+}
+
+impl Palette {
+	/// Creates a palette from the piece colors in `Piece` discriminant order, the garbage, ghost and background shade colors.
+	pub fn new(pieces: [Rgb; 7], garbage: Rgb, ghost: Rgb, background: [Rgb; 3]) -> Palette {
+		let mut colors = pieces.to_vec();
+		colors.push(garbage);
+		colors.push(ghost);
+		colors.extend_from_slice(&background);
+		let mut map = [0; SLOT_COUNT];
+		for (slot, index) in map.iter_mut().enumerate() {
+			*index = slot as u8;
+		}
+		Palette {
+			colors: colors,
+			map: map,
+		}
+	}
+	/// Quantizes the default palette down to at most `max_colors` colors.
+	pub fn indexed(max_colors: u8) -> Palette {
+		Palette::default().quantize(max_colors)
+	}
+	/// Quantizes the palette down to at most `max_colors` colors.
+	///
+	/// Colors get their own index in priority order:
+	/// the background, the ghost, the pieces in `Piece` discriminant order, the garbage and finally the two upper background shades.
+	/// Once the budget is exhausted the background shades map to the background,
+	/// the remaining colors map to the nearest color already in the palette, the earliest in priority order on equal distance.
+	/// The ghost is thus distinct from all background tiles with a budget of at least two colors.
+	pub fn quantize(&self, max_colors: u8) -> Palette {
+		assert!(max_colors > 0, "palette needs at least one color");
+		let mut colors: Vec<Rgb> = Vec::new();
+		let mut map = [0; SLOT_COUNT];
+		for &slot in PRIORITY.iter() {
+			let color = self.colors[self.map[slot] as usize];
+			map[slot] = match colors.iter().position(|&c| c == color) {
+				Some(index) => index as u8,
+				None if colors.len() < max_colors as usize => {
+					colors.push(color);
+					(colors.len() - 1) as u8
+				},
+				// Background shades fall back to the background so they never get confused with tiles
+				None if slot > SLOT_BG0 => map[SLOT_BG0],
+				None => nearest(&colors, color),
+			};
+		}
+		Palette {
+			colors: colors,
+			map: map,
+		}
+	}
+	/// Returns the colors.
+	pub fn colors(&self) -> &[Rgb] {
+		&self.colors
+	}
+	/// Returns the index of the piece color.
+	pub fn piece_index(&self, piece: Piece) -> u8 {
+		self.map[piece as usize]
+	}
+	/// Returns the index of the garbage color.
+	pub fn garbage_index(&self) -> u8 {
+		self.map[SLOT_GARBAGE]
+	}
+	/// Returns the index of the ghost color.
+	pub fn ghost_index(&self) -> u8 {
+		self.map[SLOT_GHOST]
+	}
+	/// Returns the index of the background shade, 0 to 2.
+	pub fn background_index(&self, shade: u8) -> u8 {
+		self.map[SLOT_BG0 + shade.min(2) as usize]
+	}
+}
+
+fn nearest(colors: &[Rgb], color: Rgb) -> u8 {
+	let distance = |c: &Rgb| (0..3).map(|i| (c[i] as i32 - color[i] as i32).pow(2)).sum::<i32>();
+	let mut best = 0;
+	for (index, c) in colors.iter().enumerate() {
+		if distance(c) < distance(&colors[best]) {
+			best = index;
+		}
+	}
+	best as u8
+}
+
+impl Tile {
+	/// Resolves the tile to its index in the palette.
+	pub fn palette_index(self, palette: &Palette) -> u8 {
+		match self.tile_ty() {
+			TileTy::Player | TileTy::Field => match self.piece() {
+				Some(piece) => palette.piece_index(piece),
+				None => palette.garbage_index(),
+			},
+			TileTy::Ghost => palette.ghost_index(),
+			// The background shade is stored in the piece bits
+			TileTy::Background => palette.background_index((u8::from(self) >> 3) & 0b111),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{TILE_BG0, TILE_BG1, TILE_BG2};
+
+	// Index of every tile: the pieces as player, field and ghost, garbage and the background shades
+	fn indices(palette: &Palette) -> Vec<u8> {
+		let mut tiles = Vec::new();
+		for &ty in &[TileTy::Player, TileTy::Field, TileTy::Ghost] {
+			for &piece in &Piece::ALL {
+				tiles.push(Tile::from(ty, 0, Some(piece)));
+			}
+		}
+		tiles.push(Tile::from(TileTy::Field, 0, None));
+		tiles.extend_from_slice(&[TILE_BG0, TILE_BG1, TILE_BG2]);
+		tiles.iter().map(|tile| tile.palette_index(palette)).collect()
+	}
+
+	#[test]
+	fn full() {
+		let palette = Palette::default();
+		assert_eq!(12, palette.colors().len());
+		assert_eq!(&[
+			0, 1, 2, 3, 4, 5, 6,
+			0, 1, 2, 3, 4, 5, 6,
+			8, 8, 8, 8, 8, 8, 8,
+			7, 9, 10, 11,
+		][..], &indices(&palette)[..]);
+	}
+
+	#[test]
+	fn indexed8() {
+		let palette = Palette::indexed(8);
+		assert_eq!(8, palette.colors().len());
+		assert_eq!(&[
+			2, 3, 4, 5, 6, 7, 5,
+			2, 3, 4, 5, 6, 7, 5,
+			1, 1, 1, 1, 1, 1, 1,
+			2, 0, 0, 0,
+		][..], &indices(&palette)[..]);
+		// The background and ghost keep their own colors
+		assert_eq!([0, 0, 0], palette.colors()[0]);
+		assert_eq!([50, 50, 50], palette.colors()[1]);
+	}
+
+	#[test]
+	fn indexed4() {
+		let palette = Palette::indexed(4);
+		assert_eq!(4, palette.colors().len());
+		assert_eq!(&[
+			2, 3, 2, 2, 2, 3, 2,
+			2, 3, 2, 2, 2, 3, 2,
+			1, 1, 1, 1, 1, 1, 1,
+			2, 0, 0, 0,
+		][..], &indices(&palette)[..]);
+		assert_ne!(palette.ghost_index(), palette.background_index(0));
+	}
+
+	#[test]
+	fn degradation_priority() {
+		// Every color collides, the highest priority color is kept for all of them
+		assert_eq!(&[0; 25][..], &indices(&Palette::indexed(1))[..]);
+		assert_eq!([0, 0, 0], Palette::indexed(1).colors()[0]);
+
+		// Two colors, the background and ghost stay distinct before anything else
+		let palette = Palette::indexed(2);
+		assert_eq!(0, palette.background_index(0));
+		assert_eq!(1, palette.ghost_index());
+	}
+
+	#[cfg(feature = "scene")]
+	#[test]
+	fn render_indexed() {
+		use ::{Scene, Player, Rot, Point};
+		let mut scene = Scene::new(4, 4);
+		scene.draw(Player::new(Piece::T, Rot::Zero, Point::new(-1, 1)), TileTy::Field);
+		scene.draw(Player::new(Piece::O, Rot::Zero, Point::new(1, 3)), TileTy::Ghost);
+		let mut out = [0xff; 16];
+		scene.render_indexed(&Palette::indexed(8), &mut out);
+		// Ghost O resting on the field T, the T shares its color with the Z in 8 colors
+		assert_eq!([
+			0, 0, 0, 0,
+			0, 0, 1, 1,
+			0, 5, 1, 1,
+			5, 5, 5, 0,
+		], out);
+	}
+}
@@ -0,0 +1,233 @@
+/*!
+Phase dependent well evaluation.
+*/
+
+use ::{Well, Weights, Evaluator};
+
+/// Game phase selecting the weights used by the `PhasedEvaluator`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Phase {
+	/// Few blocks in the field, build up a clean stack next to the tetris well.
+	Opening,
+	/// Regular stacking.
+	Steady,
+	/// The stack is high, dig out and stay alive.
+	Danger,
+}
+
+/// Field conditions switching between the phases.
+///
+/// Each threshold has a margin: a phase is entered at the threshold but only left after moving past the margin.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PhaseThresholds {
+	/// Opening while the field has fewer blocks than this.
+	pub opening_blocks: u32,
+	/// Additional blocks before the opening is left.
+	pub blocks_margin: u32,
+	/// Danger while the highest column is taller than this.
+	pub danger_height: i32,
+	/// Rows the highest column must drop below the threshold before danger is left.
+	pub height_margin: i32,
+}
+impl Default for PhaseThresholds {
+	fn default() -> PhaseThresholds {
+		PhaseThresholds {
+			opening_blocks: 30,
+			blocks_margin: 8,
+			danger_height: 12,
+			height_margin: 2,
+		}
+	}
+}
+
+/// Evaluator switching between weights depending on the game phase.
+///
+/// The phase is selected by `update` once per piece, every evaluation within a single search uses the same weights.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhasedEvaluator {
+	/// Weights used in the opening.
+	pub opening: Weights,
+	/// Weights used in the steady state.
+	pub steady: Weights,
+	/// Weights used when in danger.
+	pub danger: Weights,
+	/// Conditions switching between the phases.
+	pub thresholds: PhaseThresholds,
+	phase: Phase,
+}
+/// Returns weights keeping the rightmost column open in the opening and favoring line clears in danger.
+impl Default for PhasedEvaluator {
+	fn default() -> PhasedEvaluator {
+		let steady = Weights::default();
+		PhasedEvaluator::new(
+			Weights { well_f: -1.0, ..steady },
+			steady,
+			Weights { max_height_f: -1.0, complete_lines_f: 1.5, ..steady },
+			PhaseThresholds::default())
+	}
+}
+impl ::rand::Rand for PhasedEvaluator {
+	fn rand<R: ::rand::Rng>(rng: &mut R) -> PhasedEvaluator {
+		PhasedEvaluator::new(rng.gen(), rng.gen(), rng.gen(), PhaseThresholds::default())
+	}
+}
+impl PhasedEvaluator {
+	/// Creates a new evaluator, starting in the opening.
+	pub fn new(opening: Weights, steady: Weights, danger: Weights, thresholds: PhaseThresholds) -> PhasedEvaluator {
+		PhasedEvaluator {
+			opening: opening,
+			steady: steady,
+			danger: danger,
+			thresholds: thresholds,
+			phase: Phase::Opening,
+		}
+	}
+	/// Returns the current phase.
+	pub fn phase(&self) -> Phase {
+		self.phase
+	}
+	/// Returns the weights for the current phase.
+	pub fn weights(&self) -> &Weights {
+		match self.phase {
+			Phase::Opening => &self.opening,
+			Phase::Steady => &self.steady,
+			Phase::Danger => &self.danger,
+		}
+	}
+	/// Returns the phase for the given well when coming from the current phase.
+	pub fn classify(&self, well: &Well) -> Phase {
+		let t = &self.thresholds;
+		let blocks = well.count_blocks();
+		let height = well.stack_height();
+
+		let danger_height = if self.phase == Phase::Danger { t.danger_height - t.height_margin } else { t.danger_height };
+		let opening_blocks = if self.phase == Phase::Opening { t.opening_blocks + t.blocks_margin } else { t.opening_blocks };
+		if height > danger_height {
+			Phase::Danger
+		}
+		else if blocks < opening_blocks {
+			Phase::Opening
+		}
+		else {
+			Phase::Steady
+		}
+	}
+}
+impl Evaluator for PhasedEvaluator {
+	fn update(&mut self, well: &Well) {
+		self.phase = self.classify(well);
+	}
+	fn eval(&self, well: &Well) -> f64 {
+		self.weights().eval(well)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{State, OfficialBag, Bag, PlayI};
+
+	#[test]
+	fn phases() {
+		let mut evaluator = PhasedEvaluator::default();
+		evaluator.update(&Well::new(10, 22));
+		assert_eq!(Phase::Opening, evaluator.phase());
+
+		// 40 blocks, 4 rows high
+		let mut well = Well::new(10, 22);
+		for row in 0..4 {
+			well.set_line(row, 0b1111111110 << 22);
+		}
+		well.set_line(4, 0b1111000000 << 22);
+		evaluator.update(&well);
+		assert_eq!(Phase::Steady, evaluator.phase());
+
+		// A tower 14 rows high
+		for row in 4..14 {
+			well.set_line(row, 0b1000000000 << 22);
+		}
+		evaluator.update(&well);
+		assert_eq!(Phase::Danger, evaluator.phase());
+	}
+
+	#[test]
+	fn hysteresis() {
+		let mut evaluator = PhasedEvaluator::default();
+		let blocks = |n: usize| {
+			let mut well = Well::new(10, 22);
+			for i in 0..n {
+				let row = (i / 9) as i8;
+				let line = well.line(row) | (0b1000000000 << 22) >> (i % 9);
+				well.set_line(row, line);
+			}
+			well
+		};
+		let tower = |height: i8| {
+			let mut well = Well::new(10, 22);
+			for row in 0..height {
+				well.set_line(row, 0b1000000000 << 22);
+			}
+			well
+		};
+
+		// Borderline pair around the opening threshold
+		let (below, above) = (blocks(29), blocks(31));
+		for _ in 0..10 {
+			evaluator.update(&above);
+			assert_eq!(Phase::Opening, evaluator.phase());
+			evaluator.update(&below);
+			assert_eq!(Phase::Opening, evaluator.phase());
+		}
+		evaluator.update(&blocks(40));
+		assert_eq!(Phase::Steady, evaluator.phase());
+		for _ in 0..10 {
+			evaluator.update(&above);
+			assert_eq!(Phase::Steady, evaluator.phase());
+		}
+
+		// Borderline pair around the danger threshold
+		let (below, above) = (tower(12), tower(13));
+		evaluator.update(&blocks(40));
+		evaluator.update(&above);
+		assert_eq!(Phase::Danger, evaluator.phase());
+		for _ in 0..10 {
+			evaluator.update(&below);
+			assert_eq!(Phase::Danger, evaluator.phase());
+			evaluator.update(&above);
+			assert_eq!(Phase::Danger, evaluator.phase());
+		}
+		evaluator.update(&tower(10));
+		assert_ne!(Phase::Danger, evaluator.phase());
+	}
+
+	// Number of pieces played before the rightmost column gets a block
+	fn well_kept<E: Evaluator>(mut evaluator: E, seed: u64) -> u32 {
+		let mut state = State::standard();
+		let mut bag = OfficialBag::with_rng(::garbage::xorshift(seed));
+		let well_mask = state.well().col_range().end << 1;
+		for pieces in 0..50 {
+			if state.well().lines().iter().any(|&line| line & well_mask != 0) {
+				return pieces;
+			}
+			state.spawn(bag.next(state.well()).unwrap());
+			evaluator.update(state.well());
+			match PlayI::play(&evaluator, state.well(), *state.player().unwrap()).player {
+				Some(player) => state.set_player(player),
+				None => return pieces,
+			}
+			state.lock();
+			state.clear_lines(|_| ());
+		}
+		50
+	}
+
+	#[test]
+	fn opening_keeps_well() {
+		let (mut flat, mut phased) = (0, 0);
+		for seed in 0..4 {
+			flat += well_kept(Weights::default(), seed);
+			phased += well_kept(PhasedEvaluator::default(), seed);
+		}
+		assert!(phased >= 2 * flat, "phased {} flat {}", phased, flat);
+	}
+}
@@ -1,5 +1,8 @@
 
-use ::std::mem;
+use ::std::{error, fmt};
+use ::std::str::FromStr;
+
+use ::{Point, Rot};
 
 /// Piece sprite.
 ///
@@ -8,6 +11,30 @@ pub struct Sprite {
 	pub pix: [u8; 4],
 }
 
+impl Sprite {
+	/// Returns the offsets of the set pixels.
+	///
+	/// Sprite-local coordinates: `x` is the column from the left and `y` the row from the top, indexing `pix`.
+	pub fn cells(&self) -> impl Iterator<Item = Point> {
+		let pix = self.pix;
+		(0..16i8)
+			.map(|i| Point::new(i % 4, i / 4))
+			.filter(move |pt| pix[pt.y as usize] >> (3 - pt.x) & 1 != 0)
+	}
+	/// Returns the width of the bounding box of the set pixels.
+	pub fn width(&self) -> i8 {
+		let bits = self.pix.iter().fold(0, |acc, &row| acc | row);
+		if bits == 0 { 0 } else { (8 - bits.leading_zeros() - bits.trailing_zeros()) as i8 }
+	}
+	/// Returns the height of the bounding box of the set pixels.
+	pub fn height(&self) -> i8 {
+		match self.pix.iter().position(|&row| row != 0) {
+			Some(top) => (4 - self.pix.iter().rev().position(|&row| row != 0).unwrap() - top) as i8,
+			None => 0,
+		}
+	}
+}
+
 /// All the valid tetrominoes.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
@@ -64,9 +91,128 @@ pub enum Piece {
 	T,
 }
 
+impl Piece {
+	/// Every piece in discriminant order.
+	pub const ALL: [Piece; 7] = [Piece::O, Piece::I, Piece::S, Piece::Z, Piece::L, Piece::J, Piece::T];
+	/// Returns the number of pieces, for sizing arrays indexed by piece.
+	pub const fn count() -> usize {
+		Piece::ALL.len()
+	}
+	/// Returns the piece with the given discriminant, `None` if out of range.
+	pub fn from_u8(v: u8) -> Option<Piece> {
+		match v {
+			0 => Some(Piece::O),
+			1 => Some(Piece::I),
+			2 => Some(Piece::S),
+			3 => Some(Piece::Z),
+			4 => Some(Piece::L),
+			5 => Some(Piece::J),
+			6 => Some(Piece::T),
+			_ => None,
+		}
+	}
+	/// Returns the sprite of the piece in the given rotation with the default rules.
+	pub fn sprite(self, rot: Rot) -> &'static Sprite {
+		use ::{Rules, TheRules};
+		TheRules.piece_sprite(self, rot)
+	}
+}
+
+impl From<Piece> for u8 {
+	fn from(piece: Piece) -> u8 {
+		piece as u8
+	}
+}
+
+/// Prints the letter of the piece.
+impl fmt::Display for Piece {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Piece::O => "O",
+			Piece::I => "I",
+			Piece::S => "S",
+			Piece::Z => "Z",
+			Piece::L => "L",
+			Piece::J => "J",
+			Piece::T => "T",
+		})
+	}
+}
+
+/// Error parsing a `Piece`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParsePieceError;
+impl fmt::Display for ParsePieceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("expected one of the piece letters O, I, S, Z, L, J or T")
+	}
+}
+impl error::Error for ParsePieceError {}
+
+/// Parses the letter of the piece, case-insensitive.
+impl FromStr for Piece {
+	type Err = ParsePieceError;
+	fn from_str(s: &str) -> Result<Piece, ParsePieceError> {
+		match s {
+			"O" | "o" => Ok(Piece::O),
+			"I" | "i" => Ok(Piece::I),
+			"S" | "s" => Ok(Piece::S),
+			"Z" | "z" => Ok(Piece::Z),
+			"L" | "l" => Ok(Piece::L),
+			"J" | "j" => Ok(Piece::J),
+			"T" | "t" => Ok(Piece::T),
+			_ => Err(ParsePieceError),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
 impl ::rand::Rand for Piece {
 	fn rand<R: ::rand::Rng>(rng: &mut R) -> Piece {
 		let entropy = rng.next_u32();
-		unsafe { mem::transmute((entropy % 7) as u8) }
+		Piece::from_u8((entropy % 7) as u8).unwrap()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cells() {
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				let sprite = piece.sprite(rot);
+				let cells: Vec<Point> = sprite.cells().collect();
+				assert_eq!(4, cells.len(), "{:?} {:?}", piece, rot);
+				assert!(cells.iter().all(|pt| sprite.pix[pt.y as usize] >> (3 - pt.x) & 1 != 0));
+				// The bounding box fits the cells, only the I and O pieces fill it
+				let (w, h) = (sprite.width(), sprite.height());
+				assert_eq!(4, w * h - if piece == Piece::I || piece == Piece::O { 0 } else { 2 }, "{:?} {:?}", piece, rot);
+				assert_eq!(w, cells.iter().map(|pt| pt.x).max().unwrap() - cells.iter().map(|pt| pt.x).min().unwrap() + 1);
+				assert_eq!(h, cells.iter().map(|pt| pt.y).max().unwrap() - cells.iter().map(|pt| pt.y).min().unwrap() + 1);
+			}
+		}
+		let t: Vec<Point> = Piece::T.sprite(Rot::Zero).cells().collect();
+		assert_eq!(vec![Point::new(2, 0), Point::new(1, 1), Point::new(2, 1), Point::new(3, 1)], t);
+		assert_eq!((4, 1), (Piece::I.sprite(Rot::Zero).width(), Piece::I.sprite(Rot::Zero).height()));
+		assert_eq!((0, 0), (Sprite { pix: [0; 4] }.width(), Sprite { pix: [0; 4] }.height()));
+	}
+
+	#[test]
+	fn conversions() {
+		for v in 0..Piece::count() as u8 {
+			let piece = Piece::from_u8(v).unwrap();
+			assert_eq!(v, u8::from(piece));
+			let letter = piece.to_string();
+			assert_eq!(Ok(piece), letter.parse());
+			assert_eq!(Ok(piece), letter.to_lowercase().parse());
+		}
+		assert_eq!("T", Piece::T.to_string());
+		assert_eq!(None, Piece::from_u8(7));
+		assert_eq!(None, Piece::from_u8(255));
+		for &s in &["", "X", "TT", " T", "0", "Ｔ"] {
+			assert_eq!(Err(ParsePieceError), s.parse::<Piece>());
+		}
 	}
 }
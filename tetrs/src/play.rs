@@ -0,0 +1,268 @@
+/*!
+Player moves.
+*/
+
+use ::std::{error, fmt, ops};
+use ::std::str::FromStr;
+
+/// Player move.
+///
+/// New moves will be added as the engine grows, match with a wildcard arm.
+/// Tools which need to enumerate every move should use `Play::all()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum Play {
+	Idle,
+	MoveLeft,
+	MoveRight,
+	RotateCW,
+	RotateCCW,
+	SoftDrop,
+	HardDrop,
+	Rotate180,
+	FirmDrop,
+}
+
+static PLAYS: [Play; 9] = [
+	Play::Idle,
+	Play::MoveLeft,
+	Play::MoveRight,
+	Play::RotateCW,
+	Play::RotateCCW,
+	Play::SoftDrop,
+	Play::HardDrop,
+	Play::Rotate180,
+	Play::FirmDrop,
+];
+
+impl Play {
+	/// Returns every move in declaration order.
+	///
+	/// This list is kept in sync with the variants and grows with them.
+	pub fn all() -> &'static [Play] {
+		&PLAYS
+	}
+}
+
+// Names of the moves in declaration order, the variant name and a short name
+static NAMES: [(&str, &str); 9] = [
+	("Idle", "idle"),
+	("MoveLeft", "left"),
+	("MoveRight", "right"),
+	("RotateCW", "cw"),
+	("RotateCCW", "ccw"),
+	("SoftDrop", "soft"),
+	("HardDrop", "hard"),
+	("Rotate180", "180"),
+	("FirmDrop", "firm"),
+];
+
+/// Error parsing a `Play`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParsePlayError;
+impl fmt::Display for ParsePlayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("expected a move name such as MoveLeft or left")
+	}
+}
+impl error::Error for ParsePlayError {}
+
+/// Parses the variant name or the short name of the move, case-insensitive.
+///
+/// The short names are `idle`, `left`, `right`, `cw`, `ccw`, `soft`, `hard`, `180` and `firm`.
+impl FromStr for Play {
+	type Err = ParsePlayError;
+	fn from_str(s: &str) -> Result<Play, ParsePlayError> {
+		PLAYS.iter().zip(NAMES.iter())
+			.find(|&(_, &(name, short))| s.eq_ignore_ascii_case(name) || s.eq_ignore_ascii_case(short))
+			.map(|(&play, _)| play)
+			.ok_or(ParsePlayError)
+	}
+}
+
+/// Error parsing a sequence of moves, see `parse_play_sequence`.
+///
+/// Words are numbered from zero in the text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParseSequenceError {
+	/// The word which is not a move or has an invalid multiplier.
+	pub word: usize,
+}
+impl fmt::Display for ParseSequenceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "word {}: expected a move such as left or left*5", self.word)
+	}
+}
+impl error::Error for ParseSequenceError {}
+
+/// Parses the whitespace separated moves, see `Play::from_str` for the names.
+///
+/// A move followed by `*` and a count from 1 to 255 is repeated, eg. `left*5 cw*2 hard`.
+#[cfg(feature = "std")]
+pub fn parse_play_sequence(s: &str) -> Result<Vec<Play>, ParseSequenceError> {
+	let mut plays = Vec::new();
+	for (i, word) in s.split_whitespace().enumerate() {
+		let err = ParseSequenceError { word: i };
+		let (name, count) = match word.find('*') {
+			Some(pos) => (&word[..pos], word[pos + 1..].parse::<u8>().map_err(|_| err)?),
+			None => (word, 1),
+		};
+		let play: Play = name.parse().map_err(|_| err)?;
+		if count == 0 {
+			return Err(err);
+		}
+		plays.extend((0..count).map(|_| play));
+	}
+	Ok(plays)
+}
+
+/// Number of moves a `PlayBuf` holds.
+pub const PLAY_BUF_CAPACITY: usize = 64;
+
+/// Fixed capacity list of moves, for paths without an allocator.
+///
+/// Derefs to the slice of moves pushed so far.
+#[derive(Copy, Clone)]
+pub struct PlayBuf {
+	plays: [Play; PLAY_BUF_CAPACITY],
+	len: u8,
+}
+impl PlayBuf {
+	/// Creates an empty list.
+	pub const fn new() -> PlayBuf {
+		PlayBuf {
+			plays: [Play::Idle; PLAY_BUF_CAPACITY],
+			len: 0,
+		}
+	}
+	/// Copies the moves into a new list, `None` if there are more than `PLAY_BUF_CAPACITY`.
+	pub fn from_slice(plays: &[Play]) -> Option<PlayBuf> {
+		let mut buf = PlayBuf::new();
+		for &play in plays {
+			if !buf.push(play) {
+				return None;
+			}
+		}
+		Some(buf)
+	}
+	/// Appends the move, returns `false` and drops it if the list is full.
+	pub fn push(&mut self, play: Play) -> bool {
+		let len = self.len as usize;
+		if len >= PLAY_BUF_CAPACITY {
+			return false;
+		}
+		self.plays[len] = play;
+		self.len += 1;
+		true
+	}
+	/// Removes and returns the last move.
+	pub fn pop(&mut self) -> Option<Play> {
+		if self.len == 0 {
+			return None;
+		}
+		self.len -= 1;
+		Some(self.plays[self.len as usize])
+	}
+	/// Removes all the moves.
+	pub fn clear(&mut self) {
+		self.len = 0;
+	}
+	pub fn as_slice(&self) -> &[Play] {
+		&self.plays[..self.len as usize]
+	}
+}
+impl Default for PlayBuf {
+	fn default() -> PlayBuf {
+		PlayBuf::new()
+	}
+}
+impl ops::Deref for PlayBuf {
+	type Target = [Play];
+	fn deref(&self) -> &[Play] {
+		self.as_slice()
+	}
+}
+impl PartialEq for PlayBuf {
+	fn eq(&self, other: &PlayBuf) -> bool {
+		self.as_slice() == other.as_slice()
+	}
+}
+impl Eq for PlayBuf {}
+impl fmt::Debug for PlayBuf {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Debug::fmt(self.as_slice(), f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn play_all() {
+		// Exhaustive match, adding a variant fails to compile here as a reminder to update `Play::all()`.
+		fn index(play: Play) -> usize {
+			match play {
+				Play::Idle => 0,
+				Play::MoveLeft => 1,
+				Play::MoveRight => 2,
+				Play::RotateCW => 3,
+				Play::RotateCCW => 4,
+				Play::SoftDrop => 5,
+				Play::HardDrop => 6,
+				Play::Rotate180 => 7,
+				Play::FirmDrop => 8,
+			}
+		}
+		const COUNT: usize = 9;
+		assert_eq!(COUNT, Play::all().len());
+		for (i, &play) in Play::all().iter().enumerate() {
+			assert_eq!(i, index(play));
+			assert_eq!(i, play as u8 as usize);
+		}
+	}
+
+	#[test]
+	fn parse() {
+		for (&play, &(name, short)) in Play::all().iter().zip(NAMES.iter()) {
+			assert_eq!(format!("{:?}", play), name);
+			assert_eq!(Ok(play), name.parse());
+			assert_eq!(Ok(play), short.to_uppercase().parse());
+		}
+		assert_eq!(Ok(Play::RotateCCW), "rotateccw".parse());
+		for &s in &["", "l", " left", "Move Left"] {
+			assert_eq!(Err(ParsePlayError), s.parse::<Play>());
+		}
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn sequence() {
+		use self::Play::*;
+		assert_eq!(Ok(vec![MoveLeft; 5]), parse_play_sequence("LEFT*5"));
+		assert_eq!(Ok(vec![RotateCW, RotateCW, HardDrop]), parse_play_sequence(" cw*2  Hard\n"));
+		assert_eq!(Ok(vec![]), parse_play_sequence(""));
+		for &(s, word) in &[("left*0", 0), ("left *2", 1), ("cw left*x", 1), ("hard*256", 0), ("jump", 0), ("*3", 0)] {
+			assert_eq!(Err(ParseSequenceError { word: word }), parse_play_sequence(s));
+		}
+		assert_eq!("word 1: expected a move such as left or left*5", ParseSequenceError { word: 1 }.to_string());
+	}
+
+	#[test]
+	fn play_buf() {
+		let mut buf = PlayBuf::new();
+		assert!(buf.is_empty());
+		assert!(buf.push(Play::MoveLeft) && buf.push(Play::SoftDrop));
+		assert_eq!(&[Play::MoveLeft, Play::SoftDrop], &buf[..]);
+		assert_eq!(Some(Play::SoftDrop), buf.pop());
+		assert_eq!(Some(buf), PlayBuf::from_slice(&[Play::MoveLeft]));
+		// Full lists drop further moves
+		let mut full = PlayBuf::from_slice(&[Play::HardDrop; PLAY_BUF_CAPACITY]).unwrap();
+		assert!(!full.push(Play::Idle));
+		assert_eq!(PLAY_BUF_CAPACITY, full.len());
+		assert_eq!(None, PlayBuf::from_slice(&[Play::HardDrop; PLAY_BUF_CAPACITY + 1]));
+		buf.clear();
+		assert_eq!(PlayBuf::default(), buf);
+	}
+}
@@ -1,5 +1,5 @@
 
-use ::{Piece, Rot, Point, Sprite};
+use ::{Piece, Rot, Point, Sprite, CoordError};
 
 /// The player.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -18,15 +18,22 @@ impl Player {
 			pt: pt,
 		}
 	}
+	/// Creates a new player at a position given in wider coordinates.
+	///
+	/// Fails instead of truncating coordinates which don't fit in `i8`.
+	pub fn try_new_i32(piece: Piece, rot: Rot, x: i32, y: i32) -> Result<Player, CoordError> {
+		Point::try_from_i32(x, y).map(|pt| Player::new(piece, rot, pt))
+	}
+	/// Returns the position widened to `i32`.
+	pub fn position_i32(self) -> (i32, i32) {
+		self.pt.to_i32()
+	}
 	/// Returns the player moved left one step.
 	pub fn move_left(self) -> Player {
 		Player {
 			piece: self.piece,
 			rot: self.rot,
-			pt: Point {
-				x: self.pt.x - 1,
-				y: self.pt.y,
-			},
+			pt: self.pt.offset(-1, 0),
 		}
 	}
 	/// Returns the player moved right one step.
@@ -34,10 +41,7 @@ impl Player {
 		Player {
 			piece: self.piece,
 			rot: self.rot,
-			pt: Point {
-				x: self.pt.x + 1,
-				y: self.pt.y,
-			},
+			pt: self.pt.offset(1, 0),
 		}
 	}
 	/// Returns the player moved down one step.
@@ -45,10 +49,7 @@ impl Player {
 		Player {
 			piece: self.piece,
 			rot: self.rot,
-			pt: Point {
-				x: self.pt.x,
-				y: self.pt.y - 1,
-			},
+			pt: self.pt.offset(0, -1),
 		}
 	}
 	/// Returns the player rotated clockwise.
@@ -67,8 +68,15 @@ impl Player {
 			pt: self.pt,
 		}
 	}
+	/// Returns the player rotated 180 degrees.
+	pub fn rotate_180(self) -> Player {
+		Player {
+			piece: self.piece,
+			rot: self.rot.flip(),
+			pt: self.pt,
+		}
+	}
 	pub fn sprite(self) -> &'static Sprite {
-		use ::{Rules, TheRules};
-		TheRules.piece_sprite(self.piece, self.rot)
+		self.piece.sprite(self.rot)
 	}
 }
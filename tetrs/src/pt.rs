@@ -2,10 +2,13 @@
 Point.
 */
 
-use ::std::ops;
+use ::std::{error, fmt, ops};
 
 /// Point.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+///
+/// The arithmetic operators saturate at the bounds of `i8` instead of overflowing.
+/// Saturated points are far outside any well, sprites there always collide.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 #[repr(C)]
 pub struct Point {
 	pub x: i8,
@@ -13,20 +16,160 @@ pub struct Point {
 }
 
 impl Point {
-	pub fn new(x: i8, y: i8) -> Point {
+	/// The origin.
+	pub const ZERO: Point = Point { x: 0, y: 0 };
+
+	pub const fn new(x: i8, y: i8) -> Point {
 		Point {
 			x: x,
 			y: y,
 		}
 	}
+	/// Creates a point from wider coordinates, eg. from frontend math.
+	///
+	/// Fails instead of truncating coordinates which don't fit in `i8`.
+	pub fn try_from_i32(x: i32, y: i32) -> Result<Point, CoordError> {
+		Ok(Point {
+			x: coord(x)?,
+			y: coord(y)?,
+		})
+	}
+	/// Returns the coordinates widened to `i32`.
+	pub fn to_i32(self) -> (i32, i32) {
+		(self.x as i32, self.y as i32)
+	}
+	/// Returns the point moved by the offsets, saturating at the bounds of `i8`.
+	pub fn offset(self, dx: i8, dy: i8) -> Point {
+		Point {
+			x: self.x.saturating_add(dx),
+			y: self.y.saturating_add(dy),
+		}
+	}
+	/// Returns the taxicab distance to the other point.
+	///
+	/// Widened to `i32`, the distance between points at opposite bounds doesn't fit in `i8`.
+	pub fn manhattan(self, other: Point) -> i32 {
+		(self.x as i32 - other.x as i32).abs() + (self.y as i32 - other.y as i32).abs()
+	}
+}
+
+/// Coordinate rejected by a range-checked conversion.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CoordError {
+	/// The value does not fit in the `i8` coordinates.
+	Overflow { value: i32 },
+	/// The value is outside the well or scene.
+	OutOfBounds { value: i32 },
+}
+
+impl fmt::Display for CoordError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			CoordError::Overflow { value } => write!(f, "coordinate {} overflows i8", value),
+			CoordError::OutOfBounds { value } => write!(f, "coordinate {} is out of bounds", value),
+		}
+	}
+}
+impl error::Error for CoordError {}
+
+/// Narrows a coordinate to `i8`.
+pub(crate) fn coord(value: i32) -> Result<i8, CoordError> {
+	if value < i8::MIN as i32 || value > i8::MAX as i32 {
+		return Err(CoordError::Overflow { value: value });
+	}
+	Ok(value as i8)
+}
+
+/// Narrows a coordinate to `i8` and checks it is in the range `0..end`.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn coord_within(value: i32, end: i8) -> Result<i8, CoordError> {
+	let coord = coord(value)?;
+	if coord < 0 || coord >= end {
+		return Err(CoordError::OutOfBounds { value: value });
+	}
+	Ok(coord)
 }
 
 impl ops::Add for Point {
 	type Output = Point;
 	fn add(self, rhs: Point) -> Point {
+		self.offset(rhs.x, rhs.y)
+	}
+}
+impl ops::AddAssign for Point {
+	fn add_assign(&mut self, rhs: Point) {
+		*self = *self + rhs;
+	}
+}
+impl ops::Sub for Point {
+	type Output = Point;
+	fn sub(self, rhs: Point) -> Point {
+		Point {
+			x: self.x.saturating_sub(rhs.x),
+			y: self.y.saturating_sub(rhs.y),
+		}
+	}
+}
+impl ops::Neg for Point {
+	type Output = Point;
+	fn neg(self) -> Point {
+		Point::ZERO - self
+	}
+}
+impl ops::Mul<i8> for Point {
+	type Output = Point;
+	fn mul(self, rhs: i8) -> Point {
 		Point {
-			x: self.x + rhs.x,
-			y: self.y + rhs.y,
+			x: self.x.saturating_mul(rhs),
+			y: self.y.saturating_mul(rhs),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn conversions() {
+		for &(x, y) in &[(0, 0), (-128, 127), (127, -128), (-4, 21)] {
+			let pt = Point::try_from_i32(x, y).unwrap();
+			assert_eq!((x, y), pt.to_i32());
 		}
+		for &value in &[-129, 128, i32::MAX, i32::MIN] {
+			assert_eq!(Err(CoordError::Overflow { value: value }), Point::try_from_i32(value, 0));
+			assert_eq!(Err(CoordError::Overflow { value: value }), Point::try_from_i32(0, value));
+		}
+		assert_eq!(Ok(9), coord_within(9, 10));
+		assert_eq!(Err(CoordError::OutOfBounds { value: 10 }), coord_within(10, 10));
+		assert_eq!(Err(CoordError::OutOfBounds { value: -1 }), coord_within(-1, 10));
+		assert_eq!(Err(CoordError::Overflow { value: 128 }), coord_within(128, 10));
+	}
+
+	#[test]
+	fn arithmetic() {
+		let mut pt = Point::new(3, -2);
+		assert_eq!(Point::new(4, 0), pt + Point::new(1, 2));
+		assert_eq!(Point::new(2, -4), pt - Point::new(1, 2));
+		assert_eq!(Point::new(-3, 2), -pt);
+		assert_eq!(Point::new(6, -4), pt * 2);
+		assert_eq!(Point::new(2, -1), pt.offset(-1, 1));
+		assert_eq!(pt, pt + Point::ZERO);
+		pt += Point::new(-3, 2);
+		assert_eq!(Point::ZERO, pt);
+		assert_eq!(7, Point::new(3, -2).manhattan(Point::new(-1, 1)));
+	}
+
+	#[test]
+	fn saturation() {
+		// Kicks and moves at the edges of the coordinates stick to the bounds
+		assert_eq!(Point::new(127, 5), Point::new(126, 3) + Point::new(2, 2));
+		assert_eq!(Point::new(-128, 0), Point::new(-127, 0) + Point::new(-2, 0));
+		assert_eq!(Point::new(127, -128), Point::new(125, -127).offset(3, -3));
+		assert_eq!(Point::new(-128, 0), Point::new(-127, 0) - Point::new(2, 0));
+		assert_eq!(Point::new(127, 0), Point::new(127, 0) - Point::new(-1, 0));
+		assert_eq!(Point::new(127, -127), -Point::new(-128, 127));
+		assert_eq!(Point::new(-128, 127), Point::new(-100, 100) * 2);
+		assert_eq!(255 + 255, Point::new(-128, 127).manhattan(Point::new(127, -128)));
 	}
 }
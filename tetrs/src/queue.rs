@@ -0,0 +1,391 @@
+/*!
+Piece queue with scenario setup.
+*/
+
+use ::std::collections::VecDeque;
+
+use ::{Bag, Piece, Player, Point, Rot, State, Well, test_player, trace_down};
+
+/// Recorded queue operation, replay them with `verify_queue`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QueueEvent {
+	/// Pieces skipped with `NextQueue::burn`.
+	Burn { count: usize, well: Well },
+	/// Pieces forced with `NextQueue::prepend`.
+	Prepend(Vec<Piece>),
+	/// Piece dealt for the well.
+	Deal { piece: Piece, well: Well },
+	/// The bag ran out of pieces when asked for the well.
+	Exhausted { well: Well },
+}
+
+/// Hint for the frontend to highlight an upcoming piece, see `NextQueue::hints`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum QueueEntryHint {
+	/// Nothing special about the piece.
+	None,
+	/// The piece ends a drought, `length` pieces were dealt since it was last dealt.
+	Drought { length: u32 },
+	/// The piece can clear `count` lines as soon as it's dealt.
+	CompletesLines { count: u8 },
+}
+
+/// Thresholds for `NextQueue::hints`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HintThresholds {
+	/// Piece whose droughts are flagged.
+	pub drought_piece: Piece,
+	/// Pieces dealt without the drought piece before it's flagged.
+	pub drought: u32,
+	/// Lines the next piece must clear before it's flagged.
+	pub completes_lines: u8,
+}
+
+impl Default for HintThresholds {
+	/// Flags an I piece after 12 other pieces and a next piece completing a tetris.
+	fn default() -> HintThresholds {
+		HintThresholds {
+			drought_piece: Piece::I,
+			drought: 12,
+			completes_lines: 4,
+		}
+	}
+}
+
+/// Piece queue in front of a bag.
+///
+/// Deals the forced pieces first, then resumes dealing from the bag.
+/// The preview shrinks as the end of a finite bag approaches.
+/// Every operation is recorded so the exact piece sequence can be verified later.
+#[derive(Clone, Debug)]
+pub struct NextQueue<B: Bag> {
+	bag: B,
+	forced: VecDeque<Piece>,
+	preview: Vec<Piece>,
+	events: Vec<QueueEvent>,
+	droughts: [u32; 7],
+}
+
+impl<B: Bag> NextQueue<B> {
+	pub fn new(bag: B) -> NextQueue<B> {
+		let mut queue = NextQueue {
+			bag: bag,
+			forced: VecDeque::new(),
+			preview: Vec::new(),
+			events: Vec::new(),
+			droughts: [0; 7],
+		};
+		queue.update_preview();
+		queue
+	}
+	/// Returns the bag.
+	pub fn bag(&self) -> &B {
+		&self.bag
+	}
+	/// Returns the recorded operations.
+	pub fn events(&self) -> &[QueueEvent] {
+		&self.events
+	}
+	/// Returns the upcoming pieces, the forced pieces followed by what the bag lets the player see.
+	pub fn preview(&self) -> &[Piece] {
+		&self.preview
+	}
+	/// Returns the number of pieces dealt since the piece was last dealt, skipped pieces included.
+	///
+	/// Counts every piece dealt so far if the piece was never dealt.
+	pub fn drought(&self, piece: Piece) -> u32 {
+		self.droughts[piece as usize]
+	}
+	/// Returns a hint for every piece of the preview.
+	///
+	/// The drought piece is flagged with the length of the drought it ends once the drought reaches the threshold.
+	/// Only the next piece is checked for line clears to stay cheap, dropped straight down in every rotation and column of the well.
+	/// Completing lines takes precedence over ending a drought.
+	pub fn hints(&self, well: &Well, thresholds: &HintThresholds) -> Vec<QueueEntryHint> {
+		let mut length = self.drought(thresholds.drought_piece);
+		self.preview.iter().enumerate().map(|(index, &piece)| {
+			if index == 0 {
+				let count = max_lines(well, piece);
+				if count > 0 && count >= thresholds.completes_lines {
+					return QueueEntryHint::CompletesLines { count: count };
+				}
+			}
+			if piece != thresholds.drought_piece {
+				length += 1;
+				return QueueEntryHint::None;
+			}
+			let drought = length;
+			length = 0;
+			if drought >= thresholds.drought { QueueEntryHint::Drought { length: drought } } else { QueueEntryHint::None }
+		}).collect()
+	}
+	/// Skips the next pieces as if they were dealt for the given well.
+	///
+	/// Forced pieces are skipped first. Every skipped piece is drawn from the bag for fidelity:
+	/// bags doing work per piece such as the `WorstBag` pay their full cost `n` times.
+	///
+	/// Returns `false` if the bag ran out of pieces.
+	pub fn burn(&mut self, n: usize, well: &Well) -> bool {
+		self.events.push(QueueEvent::Burn { count: n, well: *well });
+		let mut burned = true;
+		for _ in 0..n {
+			if self.draw(well).is_none() {
+				burned = false;
+				break;
+			}
+		}
+		self.update_preview();
+		burned
+	}
+	/// Forces the pieces to be dealt next in the given order, ahead of any earlier forced pieces.
+	///
+	/// The bag resumes exactly where it left off once they are dealt.
+	pub fn prepend(&mut self, pieces: &[Piece]) {
+		self.events.push(QueueEvent::Prepend(pieces.to_vec()));
+		for &piece in pieces.iter().rev() {
+			self.forced.push_front(piece);
+		}
+		self.update_preview();
+	}
+	fn draw(&mut self, well: &Well) -> Option<Piece> {
+		let piece = self.forced.pop_front().or_else(|| self.bag.next(well));
+		if let Some(piece) = piece {
+			for drought in self.droughts.iter_mut() {
+				*drought += 1;
+			}
+			self.droughts[piece as usize] = 0;
+		}
+		piece
+	}
+	fn update_preview(&mut self) {
+		self.preview.clear();
+		self.preview.extend(self.forced.iter().cloned());
+		self.preview.extend_from_slice(self.bag.peek());
+	}
+}
+
+impl<B: Bag> Bag for NextQueue<B> {
+	fn next(&mut self, well: &Well) -> Option<Piece> {
+		let piece = self.draw(well);
+		self.events.push(match piece {
+			Some(piece) => QueueEvent::Deal { piece: piece, well: *well },
+			None => QueueEvent::Exhausted { well: *well },
+		});
+		self.update_preview();
+		piece
+	}
+	fn peek(&self) -> &[Piece] {
+		&self.preview
+	}
+	fn preview(&self, n: usize) -> Vec<Piece> {
+		let mut preview: Vec<Piece> = self.forced.iter().cloned().take(n).collect();
+		let rest = n - preview.len();
+		preview.extend(self.bag.preview(rest));
+		preview
+	}
+}
+
+// Most lines the piece clears dropped straight down from the top of the well
+fn max_lines(well: &Well, piece: Piece) -> u8 {
+	let state = State::with_well(*well);
+	let mut lines = 0;
+	for rot in Rot::iter() {
+		for x in -2..well.width() {
+			let player = Player::new(piece, rot, Point::new(x, well.height() - 1));
+			if test_player(well, player) {
+				continue;
+			}
+			if let Some(lock) = state.simulate_lock(trace_down(well, player)) {
+				lines = lines.max(lock.cleared_rows.len() as u8);
+			}
+		}
+	}
+	lines
+}
+
+/// Replays the recorded operations on a fresh bag, returns the index of the first event which deals a different piece
+/// or where the bag runs out of pieces at a different time.
+///
+/// The bag must be made identical to the one the events were recorded with, eg. with the same seed.
+pub fn verify_queue<B: Bag>(bag: B, events: &[QueueEvent]) -> Result<(), usize> {
+	let mut queue = NextQueue::new(bag);
+	for (index, event) in events.iter().enumerate() {
+		match *event {
+			QueueEvent::Burn { count, ref well } => {
+				if !queue.burn(count, well) {
+					return Err(index);
+				}
+			},
+			QueueEvent::Prepend(ref pieces) => queue.prepend(pieces),
+			QueueEvent::Deal { piece, ref well } => {
+				if queue.next(well) != Some(piece) {
+					return Err(index);
+				}
+			},
+			QueueEvent::Exhausted { ref well } => {
+				if queue.next(well).is_some() {
+					return Err(index);
+				}
+			},
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{OfficialBag, SequenceBag};
+	use ::garbage::xorshift;
+
+	fn bag(seed: u64) -> OfficialBag<::rand::XorShiftRng> {
+		OfficialBag::with_rng(xorshift(seed))
+	}
+
+	#[test]
+	fn burn() {
+		let well = Well::new(10, 22);
+		for seed in 0..4 {
+			let mut direct = bag(seed);
+			let dealt: Vec<Piece> = (0..30).map(|_| direct.next(&well).unwrap()).collect();
+			for &n in &[0, 1, 7, 12, 20] {
+				let mut queue = NextQueue::new(bag(seed));
+				assert!(queue.burn(n, &well));
+				assert_eq!(&dealt[n..n + 7], queue.preview());
+				let rest: Vec<Piece> = (n..30).map(|_| queue.next(&well).unwrap()).collect();
+				assert_eq!(&dealt[n..], &rest[..]);
+			}
+		}
+		// The bag is consulted for every burned piece
+		#[cfg(feature = "bot")]
+		{
+			use ::{WorstBag, Weights};
+			let mut direct = WorstBag::new(Weights::default());
+			let mut queue = NextQueue::new(WorstBag::new(Weights::default()));
+			direct.next(&well);
+			queue.burn(1, &well);
+			assert_eq!(direct.next(&well), queue.next(&well));
+		}
+	}
+
+	#[test]
+	fn prepend() {
+		let well = Well::new(10, 22);
+		let mut direct = bag(5);
+		let dealt: Vec<Piece> = (0..10).map(|_| direct.next(&well).unwrap()).collect();
+
+		let mut queue = NextQueue::new(bag(5));
+		queue.next(&well);
+		queue.prepend(&[Piece::I]);
+		queue.prepend(&[Piece::T, Piece::O]);
+		assert_eq!(&[Piece::T, Piece::O, Piece::I], &queue.preview()[..3]);
+		assert_eq!(&dealt[1..8], &queue.preview()[3..]);
+		let forced: Vec<Piece> = (0..3).map(|_| queue.next(&well).unwrap()).collect();
+		assert_eq!(vec![Piece::T, Piece::O, Piece::I], forced);
+		// The bag resumes where it left off
+		let rest: Vec<Piece> = (1..10).map(|_| queue.next(&well).unwrap()).collect();
+		assert_eq!(&dealt[1..], &rest[..]);
+	}
+
+	#[test]
+	fn replay() {
+		let mut queue = NextQueue::new(bag(9));
+		let mut state = State::standard();
+		queue.burn(12, state.well());
+		queue.prepend(&[Piece::T, Piece::I]);
+		for i in 0..20 {
+			if i == 10 {
+				queue.burn(3, state.well());
+			}
+			if state.spawn(queue.next(state.well()).unwrap()).is_blocked() {
+				break;
+			}
+			state.hard_drop();
+			state.clear_lines(|_| ());
+		}
+		let events = queue.events();
+		assert_eq!(Ok(()), verify_queue(bag(9), events));
+		// A different seed or a tampered record fails
+		assert!(verify_queue(bag(10), events).is_err());
+		let mut tampered = events.to_vec();
+		tampered.retain(|event| !matches!(*event, QueueEvent::Prepend(_)));
+		assert_eq!(Err(1), verify_queue(bag(9), &tampered));
+	}
+
+	#[test]
+	fn out_of_pieces() {
+		use ::{SequenceBag, GameEnd};
+		let pieces = [Piece::I, Piece::O, Piece::T, Piece::L, Piece::I];
+		let mut queue = NextQueue::new(SequenceBag::new(&pieces));
+		let mut state = State::standard();
+		state.record_events(true);
+		for locks in 0..pieces.len() {
+			assert!(!state.spawn_from(&mut queue));
+			// The preview shrinks as the end approaches
+			assert_eq!(pieces.len() - 1 - locks, queue.preview().len());
+			for _ in 0..locks * 2 {
+				state.move_right();
+			}
+			state.hard_drop();
+			state.clear_lines(|_| ());
+			assert_eq!(None, state.game_end());
+		}
+		assert!(state.spawn_from(&mut queue));
+		assert_eq!(Some(GameEnd::OutOfPieces), state.game_end());
+		assert_eq!(20, state.well().count_blocks());
+
+		// The end is recorded and verified
+		assert_eq!(Some(&QueueEvent::Exhausted { well: *state.well() }), queue.events().last());
+		assert_eq!(Ok(()), verify_queue(SequenceBag::new(&pieces), queue.events()));
+		assert_eq!(Err(5), verify_queue(SequenceBag::new(&[Piece::I, Piece::O, Piece::T, Piece::L, Piece::I, Piece::T]), queue.events()));
+		let replayed = State::replay_events(&state.take_events(), state.dimensions()).unwrap();
+		assert_eq!(Some(GameEnd::OutOfPieces), replayed.game_end());
+		assert_eq!(state.checksum(), replayed.checksum());
+	}
+
+	bag_conformance!(next_queue, || {
+		let mut queue = NextQueue::new(bag(3));
+		queue.prepend(&[Piece::S, Piece::Z]);
+		queue
+	});
+
+	#[test]
+	fn completes_lines() {
+		// Tetris ready with the leftmost column open
+		let mut well = Well::new(10, 22);
+		for row in 0..4 {
+			well.set_line(row, 0b0111111111 << 22);
+		}
+		let mut queue = NextQueue::new(SequenceBag::new(&[Piece::I, Piece::I, Piece::O]));
+		let thresholds = HintThresholds { drought: 100, ..HintThresholds::default() };
+		assert_eq!(vec![QueueEntryHint::CompletesLines { count: 4 }, QueueEntryHint::None, QueueEntryHint::None], queue.hints(&well, &thresholds));
+		// An O clears nothing, even with a lower threshold
+		queue.next(&well);
+		queue.next(&well);
+		assert_eq!(vec![QueueEntryHint::None], queue.hints(&well, &HintThresholds { completes_lines: 1, ..thresholds }));
+		assert_eq!(vec![QueueEntryHint::None], queue.hints(&Well::new(10, 22), &thresholds));
+	}
+
+	#[test]
+	fn droughts() {
+		let well = Well::new(10, 22);
+		let mut pieces = vec![Piece::I];
+		pieces.extend_from_slice(&[Piece::O; 5]);
+		pieces.extend_from_slice(&[Piece::I, Piece::T, Piece::I]);
+		let mut queue = NextQueue::new(SequenceBag::new(&pieces));
+		let thresholds = HintThresholds { drought: 5, ..HintThresholds::default() };
+		let drought = QueueEntryHint::Drought { length: 5 };
+		let hints = queue.hints(&well, &thresholds);
+		assert_eq!(QueueEntryHint::None, hints[0]);
+		assert_eq!(&[drought, QueueEntryHint::None, QueueEntryHint::None], &hints[6..]);
+		// Exactly at the threshold once the first I is dealt
+		assert_eq!(Some(Piece::I), queue.next(&well));
+		assert_eq!(0, queue.drought(Piece::I));
+		assert_eq!(1, queue.drought(Piece::T));
+		assert!(queue.burn(2, &well));
+		assert_eq!(2, queue.drought(Piece::I));
+		assert_eq!(&[drought, QueueEntryHint::None, QueueEntryHint::None], &queue.hints(&well, &thresholds)[3..]);
+		let thresholds = HintThresholds { drought: 6, ..thresholds };
+		assert!(queue.hints(&well, &thresholds).iter().all(|&hint| hint == QueueEntryHint::None));
+	}
+}
@@ -0,0 +1,406 @@
+/*!
+Replay recording and playback.
+
+Record a replay with `State::record_replay`, every spawn, move, drop, lock, line clear and garbage insertion is appended
+with the tick it happened on. Playing it back re-simulates the game from the initial well.
+
+# Encoding
+
+`Replay::to_bytes` writes a compact binary encoding, all integers are little endian.
+
+| Bytes | Field |
+|-------|-------|
+| 4 | Magic `TTRP` |
+| 4 | Version, `u32` |
+| 1 | Well width, `i8` |
+| 1 | Well height, `i8` |
+| 4 × height | Initial well lines from the bottom up, `u32` |
+| 1 | Initial player present, `u8` |
+| 4 | Initial player if present: piece, rotation, x and y, `u8, u8, i8, i8` |
+| 4 | Ticks recorded, `u32` |
+
+Followed by the actions until the end, each action is the number of ticks since the previous action as an unsigned LEB128 varint,
+a tag byte and the payload of the tag:
+
+| Tag | Action | Payload |
+|-----|--------|---------|
+| 0 | `Spawn` | Piece, `u8` |
+| 1 | `Play` | Move, `u8` |
+| 2 | `Gravity` | |
+| 3 | `Lock` | |
+| 4 | `ClearLines` | |
+| 5 | `Garbage` | Count and hole, `u8, i8` |
+| 6 | `SetPlayer` | Piece, rotation, x and y, `u8, u8, i8, i8` |
+| 7 | `Finish` | |
+*/
+
+use ::std::{error, fmt};
+use ::std::convert::TryFrom;
+
+use ::{Action, State, Well, Player, Piece, Rot, Point, Play, MAX_WIDTH, MAX_HEIGHT};
+
+const MAGIC: [u8; 4] = *b"TTRP";
+const VERSION: u32 = 1;
+
+/// Action recorded in a replay.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ReplayAction {
+	/// `State::spawn` with the given piece.
+	Spawn(Piece),
+	/// A move by `State::move_left`, `State::move_right`, the rotations, `State::soft_drop` or `State::hard_drop`.
+	Play(Play),
+	/// `State::gravity`.
+	Gravity,
+	/// The player locked without a hard drop, eg. by `State::lock` or the lock delay.
+	Lock,
+	/// `State::clear_lines`.
+	ClearLines,
+	/// `State::add_garbage` with the given count and hole.
+	Garbage { count: u8, hole: i8 },
+	/// `State::set_player`, eg. a bot teleporting its placement into the well.
+	SetPlayer(Player),
+	/// `State::finish`.
+	Finish,
+}
+
+/// Recorded action with the tick it happened on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReplayEntry {
+	/// Ticks of `State::tick_move_clock` since recording started.
+	pub tick: u32,
+	pub action: ReplayAction,
+}
+
+/// Recorded game, see `State::record_replay`.
+///
+/// The initial well and player are recorded, settings such as the rules, the lock delay or the move budget are not.
+/// Playback uses the default settings, record games played with them for a faithful replay.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Replay {
+	well: Well,
+	player: Option<Player>,
+	tick: u32,
+	entries: Vec<ReplayEntry>,
+}
+
+/// Invalid replay encoding found by `Replay::from_bytes`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ReplayError {
+	/// The bytes are not a replay.
+	BadMagic,
+	/// The replay has an unsupported version.
+	Version(u32),
+	/// The bytes end in the middle of the replay.
+	Truncated,
+	/// The bytes contain invalid values.
+	Corrupt,
+}
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReplayError::BadMagic => f.write_str("not a replay"),
+			ReplayError::Version(version) => write!(f, "unsupported replay version {}", version),
+			ReplayError::Truncated => f.write_str("truncated replay"),
+			ReplayError::Corrupt => f.write_str("corrupt replay"),
+		}
+	}
+}
+impl error::Error for ReplayError {}
+
+impl Replay {
+	/// Starts a replay from the given well and player.
+	pub(crate) fn new(well: Well, player: Option<Player>) -> Replay {
+		Replay { well: well, player: player, tick: 0, entries: Vec::new() }
+	}
+	pub(crate) fn tick(&mut self) {
+		self.tick += 1;
+	}
+	pub(crate) fn push(&mut self, action: ReplayAction) {
+		self.entries.push(ReplayEntry { tick: self.tick, action: action });
+	}
+	/// Returns the initial well.
+	pub fn well(&self) -> &Well {
+		&self.well
+	}
+	/// Returns the number of ticks recorded, including the ticks after the last action.
+	pub fn ticks(&self) -> u32 {
+		self.tick
+	}
+	/// Returns the recorded actions in the order they happened.
+	pub fn entries(&self) -> &[ReplayEntry] {
+		&self.entries
+	}
+	/// Returns the state before the first action.
+	pub fn initial_state(&self) -> State {
+		let mut state = State::with_well(self.well);
+		if let Some(player) = self.player {
+			state.set_player(player);
+		}
+		state
+	}
+	/// Re-simulates the game, yields the state after every action.
+	pub fn playback(&self) -> Playback<'_> {
+		Playback {
+			entries: self.entries.iter(),
+			state: self.initial_state(),
+		}
+	}
+	/// Encodes the replay, see the module documentation for the format.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		bytes.extend_from_slice(&MAGIC);
+		bytes.extend_from_slice(&VERSION.to_le_bytes());
+		bytes.push(self.well.width() as u8);
+		bytes.push(self.well.height() as u8);
+		for &line in self.well.lines() {
+			bytes.extend_from_slice(&line.to_le_bytes());
+		}
+		bytes.push(self.player.is_some() as u8);
+		if let Some(player) = self.player {
+			write_player(&mut bytes, player);
+		}
+		bytes.extend_from_slice(&self.tick.to_le_bytes());
+		let mut tick = 0;
+		for entry in &self.entries {
+			write_varint(&mut bytes, entry.tick - tick);
+			tick = entry.tick;
+			match entry.action {
+				ReplayAction::Spawn(piece) => bytes.extend_from_slice(&[0, piece as u8]),
+				ReplayAction::Play(play) => bytes.extend_from_slice(&[1, play as u8]),
+				ReplayAction::Gravity => bytes.push(2),
+				ReplayAction::Lock => bytes.push(3),
+				ReplayAction::ClearLines => bytes.push(4),
+				ReplayAction::Garbage { count, hole } => bytes.extend_from_slice(&[5, count, hole as u8]),
+				ReplayAction::SetPlayer(player) => {
+					bytes.push(6);
+					write_player(&mut bytes, player);
+				},
+				ReplayAction::Finish => bytes.push(7),
+			}
+		}
+		bytes
+	}
+	/// Decodes a replay encoded by `to_bytes`.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+		let mut reader = Reader { bytes: bytes };
+		if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+			return Err(ReplayError::BadMagic);
+		}
+		reader.take(MAGIC.len())?;
+		let version = reader.u32()?;
+		if version != VERSION {
+			return Err(ReplayError::Version(version));
+		}
+		let width = reader.u8()? as i8;
+		let height = reader.u8()? as i8;
+		if width < 4 || width > MAX_WIDTH as i8 || height < 4 || height > MAX_HEIGHT as i8 {
+			return Err(ReplayError::Corrupt);
+		}
+		let mut well = Well::new(width, height);
+		for row in 0..height {
+			let line = reader.u32()?;
+			if line & !well.line_mask() != 0 {
+				return Err(ReplayError::Corrupt);
+			}
+			well.set_line(row, line);
+		}
+		let player = match reader.u8()? {
+			0 => None,
+			1 => Some(reader.player()?),
+			_ => return Err(ReplayError::Corrupt),
+		};
+		let ticks = reader.u32()?;
+		let mut replay = Replay::new(well, player);
+		while !reader.bytes.is_empty() {
+			replay.tick = replay.tick.checked_add(reader.varint()?).filter(|&tick| tick <= ticks).ok_or(ReplayError::Corrupt)?;
+			let action = match reader.u8()? {
+				0 => ReplayAction::Spawn(Piece::from_u8(reader.u8()?).ok_or(ReplayError::Corrupt)?),
+				1 => ReplayAction::Play(*Play::all().get(reader.u8()? as usize).ok_or(ReplayError::Corrupt)?),
+				2 => ReplayAction::Gravity,
+				3 => ReplayAction::Lock,
+				4 => ReplayAction::ClearLines,
+				5 => {
+					let count = reader.u8()?;
+					let hole = reader.u8()? as i8;
+					if hole < 0 || hole >= width {
+						return Err(ReplayError::Corrupt);
+					}
+					ReplayAction::Garbage { count: count, hole: hole }
+				},
+				6 => ReplayAction::SetPlayer(reader.player()?),
+				7 => ReplayAction::Finish,
+				_ => return Err(ReplayError::Corrupt),
+			};
+			replay.push(action);
+		}
+		replay.tick = ticks;
+		Ok(replay)
+	}
+}
+
+/// Iterator re-simulating a replay, see `Replay::playback`.
+#[derive(Clone, Debug)]
+pub struct Playback<'a> {
+	entries: ::std::slice::Iter<'a, ReplayEntry>,
+	state: State,
+}
+impl<'a> Iterator for Playback<'a> {
+	type Item = State;
+	fn next(&mut self) -> Option<State> {
+		let entry = self.entries.next()?;
+		let state = &mut self.state;
+		match entry.action {
+			ReplayAction::Spawn(piece) => { state.spawn(piece); },
+			ReplayAction::Play(play) => {
+				if let Some(action) = Action::from_play(play) {
+					state.step(action);
+				}
+			},
+			ReplayAction::Gravity => { state.gravity(); },
+			ReplayAction::Lock => state.lock(),
+			ReplayAction::ClearLines => { state.clear_lines(|_| ()); },
+			ReplayAction::Garbage { count, hole } => { state.add_garbage(count, hole); },
+			ReplayAction::SetPlayer(player) => state.set_player(player),
+			ReplayAction::Finish => state.finish(),
+		}
+		Some(state.clone())
+	}
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.entries.size_hint()
+	}
+}
+
+fn write_player(bytes: &mut Vec<u8>, player: Player) {
+	bytes.extend_from_slice(&[player.piece as u8, player.rot as u8, player.pt.x as u8, player.pt.y as u8]);
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+	while value >= 0x80 {
+		bytes.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+}
+impl<'a> Reader<'a> {
+	fn take(&mut self, n: usize) -> Result<&'a [u8], ReplayError> {
+		if self.bytes.len() < n {
+			return Err(ReplayError::Truncated);
+		}
+		let (head, tail) = self.bytes.split_at(n);
+		self.bytes = tail;
+		Ok(head)
+	}
+	fn u8(&mut self) -> Result<u8, ReplayError> {
+		Ok(self.take(1)?[0])
+	}
+	fn u32(&mut self) -> Result<u32, ReplayError> {
+		let bytes = self.take(4)?;
+		Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+	fn varint(&mut self) -> Result<u32, ReplayError> {
+		let mut value = 0u32;
+		for shift in (0..35).step_by(7) {
+			let byte = self.u8()?;
+			value |= ((byte & 0x7f) as u32).checked_shl(shift).ok_or(ReplayError::Corrupt)?;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(ReplayError::Corrupt)
+	}
+	fn player(&mut self) -> Result<Player, ReplayError> {
+		let bytes = self.take(4)?;
+		let piece = Piece::from_u8(bytes[0]).ok_or(ReplayError::Corrupt)?;
+		let rot = Rot::try_from(bytes[1]).map_err(|_| ReplayError::Corrupt)?;
+		Ok(Player::new(piece, rot, Point::new(bytes[2] as i8, bytes[3] as i8)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn varint() {
+		for &value in &[0, 1, 127, 128, 300, 16384, u32::MAX] {
+			let mut bytes = Vec::new();
+			write_varint(&mut bytes, value);
+			let mut reader = Reader { bytes: &bytes };
+			assert_eq!(Ok(value), reader.varint());
+			assert!(reader.bytes.is_empty());
+		}
+		assert_eq!(Err(ReplayError::Truncated), Reader { bytes: &[0x80] }.varint());
+	}
+
+	#[test]
+	fn bad_bytes() {
+		let mut state = State::new(6, 8);
+		state.record_replay(true);
+		state.spawn(Piece::T);
+		state.hard_drop();
+		state.add_garbage(1, 2);
+		let bytes = state.take_replay().unwrap().to_bytes();
+		assert!(Replay::from_bytes(&bytes).is_ok());
+
+		assert_eq!(Err(ReplayError::BadMagic), Replay::from_bytes(b"TTDS"));
+		let mut version = bytes.clone();
+		version[4] = 9;
+		assert_eq!(Err(ReplayError::Version(9)), Replay::from_bytes(&version));
+		assert_eq!(Err(ReplayError::Truncated), Replay::from_bytes(&bytes[..bytes.len() - 1]));
+		// The garbage hole outside the well
+		let mut hole = bytes.clone();
+		*hole.last_mut().unwrap() = 6;
+		assert_eq!(Err(ReplayError::Corrupt), Replay::from_bytes(&hole));
+		assert_eq!("unsupported replay version 9", ReplayError::Version(9).to_string());
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn bot_game() {
+		use ::{Bag, OfficialBag, PlayI, Weights};
+		let mut state = State::new(10, 16);
+		state.add_garbage(2, 3);
+		state.record_replay(true);
+		let mut bag = OfficialBag::from_seed(17);
+		for i in 0..40 {
+			if state.spawn(bag.next(state.well()).unwrap()).is_blocked() {
+				break;
+			}
+			// Fidget before teleporting the bot's placement into the well
+			if i % 2 == 0 {
+				state.rotate_cw();
+				state.move_left();
+				state.soft_drop();
+				state.gravity();
+			}
+			let bot = PlayI::play(&Weights::default(), state.well(), *state.player().unwrap());
+			match bot.player {
+				Some(player) => state.set_player(player),
+				None => break,
+			}
+			if i % 3 == 0 { state.lock() } else { state.hard_drop(); }
+			if i % 10 == 9 {
+				state.add_garbage(1, (i % 10) as i8);
+			}
+			state.clear_lines(|_| ());
+			state.tick_move_clock();
+		}
+		let replay = state.take_replay().unwrap();
+		assert!(state.take_replay().is_none());
+		assert_eq!(2, replay.well().count_blocks() / 9);
+		assert!(replay.entries().iter().any(|entry| matches!(entry.action, ReplayAction::Play(_))));
+
+		let decoded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+		assert_eq!(replay, decoded);
+		assert_eq!(40, decoded.ticks());
+		let last = decoded.playback().last().unwrap();
+		assert_eq!(state.well(), last.well());
+		assert_eq!(state.checksum(), last.checksum());
+	}
+}
@@ -2,7 +2,9 @@
 Piece rotation.
 */
 
-use ::std::mem;
+use ::std::{cmp, error, fmt, mem, slice};
+use ::std::convert::TryFrom;
+use ::std::iter::Cloned;
 
 /// Rotation state of a piece.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -19,6 +21,8 @@ pub enum Rot {
 }
 
 impl Rot {
+	/// Every rotation in clockwise order from spawn.
+	pub const ALL: [Rot; 4] = [Rot::Zero, Rot::Right, Rot::Two, Rot::Left];
 	/// Rotate clockwise.
 	pub fn cw(self) -> Rot { unsafe {
 		mem::transmute((self as u8).wrapping_add(1) & 3)
@@ -27,12 +31,48 @@ impl Rot {
 	pub fn ccw(self) -> Rot { unsafe {
 		mem::transmute((self as u8).wrapping_sub(1) & 3)
 	}}
-}
-
-impl From<u8> for Rot {
-	fn from(val: u8) -> Rot { unsafe {
+	/// Rotate 180 degrees.
+	pub fn flip(self) -> Rot { unsafe {
+		mem::transmute((self as u8).wrapping_add(2) & 3)
+	}}
+	/// Converts the value taken modulo 4, values out of range wrap around.
+	///
+	/// Use `Rot::try_from` to reject values out of range.
+	pub fn wrapping_from(val: u8) -> Rot { unsafe {
 		mem::transmute(val & 3)
 	}}
+	/// Iterates over every rotation in clockwise order from spawn.
+	pub fn iter() -> Cloned<slice::Iter<'static, Rot>> {
+		Rot::ALL.iter().cloned()
+	}
+	/// Returns the fewest single rotations in either direction to turn into the other rotation.
+	///
+	/// Opposite rotations are two turns apart, rotating 180 degrees counts as two.
+	pub fn distance(self, other: Rot) -> u8 {
+		let cw = (other as u8).wrapping_sub(self as u8) & 3;
+		cmp::min(cw, 4 - cw)
+	}
+}
+
+/// Error converting a value greater than 3 to a rotation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TryFromRotError(pub u8);
+
+impl fmt::Display for TryFromRotError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "rotation {} out of range", self.0)
+	}
+}
+impl error::Error for TryFromRotError {}
+
+impl TryFrom<u8> for Rot {
+	type Error = TryFromRotError;
+	fn try_from(val: u8) -> Result<Rot, TryFromRotError> {
+		if val > 3 {
+			return Err(TryFromRotError(val));
+		}
+		Ok(Rot::wrapping_from(val))
+	}
 }
 
 #[cfg(test)]
@@ -43,5 +83,37 @@ mod tests {
 	fn rotate() {
 		assert_eq!(Rot::Right, Rot::Zero.cw());
 		assert_eq!(Rot::Left, Rot::Zero.ccw());
+		assert_eq!(Rot::Two, Rot::Zero.flip());
+		assert_eq!(Rot::Right, Rot::Left.flip());
+		assert_eq!(Rot::Left.cw().cw(), Rot::Left.flip());
+	}
+
+	#[test]
+	fn try_from() {
+		for val in 0..4 {
+			assert_eq!(Ok(Rot::ALL[val as usize]), Rot::try_from(val));
+		}
+		assert_eq!(Err(TryFromRotError(4)), Rot::try_from(4));
+		assert_eq!(Err(TryFromRotError(255)), Rot::try_from(255));
+		// The infallible conversion wraps around
+		assert_eq!(Rot::Right, Rot::wrapping_from(5));
+	}
+
+	#[test]
+	fn iter() {
+		assert_eq!(&Rot::ALL[..], &Rot::iter().collect::<Vec<_>>()[..]);
+		assert_eq!(4, Rot::iter().count());
+	}
+
+	#[test]
+	fn distance() {
+		for rot in Rot::iter() {
+			assert_eq!(0, rot.distance(rot));
+			assert_eq!(1, rot.distance(rot.cw()));
+			assert_eq!(1, rot.distance(rot.ccw()));
+			assert_eq!(2, rot.distance(rot.flip()));
+		}
+		assert_eq!(1, Rot::Zero.distance(Rot::Left));
+		assert_eq!(1, Rot::Left.distance(Rot::Zero));
 	}
 }
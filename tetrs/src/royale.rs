@@ -0,0 +1,332 @@
+/*!
+Battle royale between multiple seats.
+*/
+
+use ::rand::{Rng, XorShiftRng};
+
+use ::{OfficialBag, GarbageRng, Player, State};
+use ::garbage::xorshift;
+use ::scoring::attack_lines;
+
+/// Chooses which seat receives an attack.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Targeting {
+	/// Attacks the seat with the highest stack, the lowest seat index wins ties.
+	Highest,
+	/// Attacks a uniformly random seat.
+	Random,
+	/// Attacks back at a seat whose last attack targeted the attacker, the one with the highest stack if several.
+	///
+	/// Falls back to a random seat when nobody is attacking.
+	Attackers,
+}
+
+/// Single seat in the royale.
+#[derive(Clone, Debug)]
+pub struct Seat {
+	state: State,
+	bag: OfficialBag<XorShiftRng>,
+	pending: u32,
+	target: Option<usize>,
+	pieces: u32,
+	sent: u32,
+	received: u32,
+	alive: bool,
+}
+
+impl Seat {
+	/// Returns the game state.
+	pub fn state(&self) -> &State {
+		&self.state
+	}
+	/// Returns the garbage lines waiting to be received.
+	pub fn pending(&self) -> u32 {
+		self.pending
+	}
+	/// Returns the seat targeted by the last attack sent.
+	pub fn target(&self) -> Option<usize> {
+		self.target
+	}
+	/// Returns the number of pieces locked.
+	pub fn pieces(&self) -> u32 {
+		self.pieces
+	}
+	/// Returns the garbage lines sent.
+	pub fn sent(&self) -> u32 {
+		self.sent
+	}
+	/// Returns the garbage lines received.
+	pub fn received(&self) -> u32 {
+		self.received
+	}
+	/// Returns if the seat is still in the game.
+	pub fn is_alive(&self) -> bool {
+		self.alive
+	}
+}
+
+/// Battle royale session for 2 to 4 seats.
+///
+/// Seats take turns placing one piece each in seat order. After locking, a seat's attack first cancels its own pending garbage,
+/// the rest is routed to a target chosen by the targeting policy. A seat which cleared no lines receives all its pending garbage,
+/// the hole columns are drawn in arrival order from the garbage sequence shared by all seats.
+///
+/// A seat is eliminated when it can't spawn, places no piece or tops out. Its pending garbage is discarded and not redistributed,
+/// it no longer sends nor receives garbage and its state is never touched again. The last seat alive wins.
+///
+/// Every random choice derives from the master seed, replaying the same placements reproduces the session exactly.
+#[derive(Clone, Debug)]
+pub struct Royale {
+	seats: Vec<Seat>,
+	garbage: GarbageRng,
+	rng: XorShiftRng,
+	targeting: Targeting,
+	eliminated: Vec<usize>,
+}
+
+impl Royale {
+	/// Creates a new royale with the given number of seats playing standard wells.
+	pub fn new(seed: u64, seats: usize, targeting: Targeting) -> Royale {
+		Royale::with_state(seed, seats, targeting, State::standard())
+	}
+	/// Creates a new royale with every seat starting from a copy of the given state.
+	pub fn with_state(seed: u64, seats: usize, targeting: Targeting, state: State) -> Royale {
+		assert!((2..=4).contains(&seats), "royale needs 2 to 4 seats");
+		let mut master = xorshift(seed);
+		let seats = (0..seats).map(|_| Seat {
+			state: state.clone(),
+			bag: OfficialBag::with_rng(xorshift(master.gen())),
+			pending: 0,
+			target: None,
+			pieces: 0,
+			sent: 0,
+			received: 0,
+			alive: true,
+		}).collect();
+		let width = state.well().width();
+		Royale {
+			seats: seats,
+			garbage: GarbageRng::new(master.gen(), width),
+			rng: xorshift(master.gen()),
+			targeting: targeting,
+			eliminated: Vec::new(),
+		}
+	}
+	/// Returns the seats.
+	pub fn seats(&self) -> &[Seat] {
+		&self.seats
+	}
+	/// Returns the seat.
+	pub fn seat(&self, seat: usize) -> &Seat {
+		&self.seats[seat]
+	}
+	/// Returns the eliminated seats in order of elimination.
+	pub fn eliminated(&self) -> &[usize] {
+		&self.eliminated
+	}
+	/// Returns the winner once only one seat is alive.
+	pub fn winner(&self) -> Option<usize> {
+		if self.eliminated.len() + 1 == self.seats.len() {
+			self.seats.iter().position(|seat| seat.alive)
+		}
+		else {
+			None
+		}
+	}
+	/// Plays one turn, every seat alive places a piece.
+	///
+	/// The callback is given the seat index and its state with the spawned player and returns where to lock the player,
+	/// `None` gives up and eliminates the seat.
+	pub fn turn<F>(&mut self, mut f: F) where F: FnMut(usize, &State) -> Option<Player> {
+		for i in 0..self.seats.len() {
+			if self.winner().is_some() {
+				break;
+			}
+			if self.seats[i].alive {
+				self.play(i, &mut f);
+			}
+		}
+	}
+	fn play<F>(&mut self, i: usize, f: &mut F) where F: FnMut(usize, &State) -> Option<Player> {
+		let cleared = {
+			let seat = &mut self.seats[i];
+			// Spawn returns `true` when the spawned player overlaps the well or the bag ran out of pieces
+			let placement = if seat.state.spawn_from(&mut seat.bag) { None } else { f(i, &seat.state) };
+			match placement {
+				Some(player) => {
+					seat.state.set_player(player);
+					seat.state.lock();
+					seat.pieces += 1;
+					seat.state.clear_lines(|_| ())
+				},
+				None => return self.eliminate(i),
+			}
+		};
+
+		// Cancel pending garbage before sending the rest
+		let mut attack = attack_lines(cleared as u8);
+		let cancel = attack.min(self.seats[i].pending);
+		self.seats[i].pending -= cancel;
+		attack -= cancel;
+		if attack > 0 {
+			if let Some(target) = self.choose_target(i) {
+				self.seats[target].pending += attack;
+				self.seats[i].sent += attack;
+				self.seats[i].target = Some(target);
+			}
+		}
+
+		// Receive the garbage when no lines were cleared
+		let mut topped_out = false;
+		if cleared == 0 {
+			let seat = &mut self.seats[i];
+			for _ in 0..seat.pending {
+				topped_out |= seat.state.add_garbage(1, self.garbage.next_column()) < 1;
+			}
+			seat.received += seat.pending;
+			seat.pending = 0;
+		}
+		if topped_out || self.seats[i].state.is_game_over() {
+			self.eliminate(i);
+		}
+	}
+	fn eliminate(&mut self, i: usize) {
+		let seat = &mut self.seats[i];
+		seat.alive = false;
+		// Pending garbage is discarded
+		seat.pending = 0;
+		self.eliminated.push(i);
+	}
+	/// Returns the seat targeted by an attack from the given seat.
+	fn choose_target(&mut self, attacker: usize) -> Option<usize> {
+		let others: Vec<usize> = (0..self.seats.len()).filter(|&i| i != attacker && self.seats[i].alive).collect();
+		if others.is_empty() {
+			return None;
+		}
+		let highest = |candidates: &[usize]| {
+			// Ties keep the first seat as `max_by_key` returns the last maximum
+			candidates.iter().rev().cloned().max_by_key(|&i| self.seats[i].state.well().stack_height())
+		};
+		match self.targeting {
+			Targeting::Highest => highest(&others),
+			Targeting::Random => Some(others[self.rng.gen_range(0, others.len())]),
+			Targeting::Attackers => {
+				let attackers: Vec<usize> = others.iter().cloned().filter(|&i| self.seats[i].target == Some(attacker)).collect();
+				if attackers.is_empty() {
+					Some(others[self.rng.gen_range(0, others.len())])
+				}
+				else {
+					highest(&attackers)
+				}
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Well, Piece, Rot, Point, trace_down};
+
+	// Drops the player straight down from its spawn, quickly topping out
+	fn dumb(_: usize, state: &State) -> Option<Player> {
+		Some(trace_down(state.well(), *state.player().unwrap()))
+	}
+
+	fn tower(height: i8) -> State {
+		let mut well = Well::new(10, 22);
+		for row in 0..height {
+			well.set_line(row, 0b1000000000 << 22);
+		}
+		State::with_well(well)
+	}
+
+	#[test]
+	fn targeting() {
+		let mut royale = Royale::new(1, 4, Targeting::Highest);
+		royale.seats[2].state = tower(6);
+		royale.seats[3].state = tower(6);
+		assert_eq!(Some(2), royale.choose_target(0));
+		assert_eq!(Some(3), royale.choose_target(2));
+		// Eliminated seats are never targeted
+		royale.eliminate(2);
+		assert_eq!(Some(3), royale.choose_target(0));
+
+		let mut royale = Royale::new(1, 4, Targeting::Attackers);
+		royale.seats[1].target = Some(0);
+		royale.seats[3].target = Some(0);
+		royale.seats[3].state = tower(4);
+		royale.seats[2].state = tower(8);
+		assert_eq!(Some(3), royale.choose_target(0));
+		royale.eliminate(3);
+		assert_eq!(Some(1), royale.choose_target(0));
+
+		// Random targets are reproducible from the seed
+		let targets = |seed| {
+			let mut royale = Royale::new(seed, 4, Targeting::Random);
+			(0..20).map(|_| royale.choose_target(0).unwrap()).collect::<Vec<_>>()
+		};
+		assert_eq!(targets(5), targets(5));
+		assert!(!targets(5).contains(&0));
+	}
+
+	#[test]
+	fn attack_routing() {
+		// Four lines ready to clear with an I piece in the first column
+		let mut well = Well::new(10, 22);
+		for row in 0..4 {
+			well.set_line(row, 0b0111111111 << 22);
+		}
+		let mut royale = Royale::with_state(3, 2, Targeting::Highest, State::with_well(well));
+		royale.seats[0].pending = 1;
+		let tetris = Player::new(Piece::I, Rot::Right, Point::new(-2, 3));
+		royale.play(0, &mut |_, _: &State| Some(tetris));
+		// One line cancelled, three sent
+		assert_eq!(0, royale.seat(0).pending());
+		assert_eq!(3, royale.seat(0).sent());
+		assert_eq!(3, royale.seat(1).pending());
+		assert_eq!(Some(1), royale.seat(0).target());
+
+		// The receiving seat clears nothing and takes the garbage
+		royale.play(1, &mut dumb);
+		assert_eq!(0, royale.seat(1).pending());
+		assert_eq!(3, royale.seat(1).received());
+		let well = royale.seat(1).state().well();
+		for row in 0..3 {
+			assert_eq!(9, well.line(row).count_ones());
+		}
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn bot_battle() {
+		use ::{PlayI, Weights};
+		let weights = [
+			Weights::default(),
+			Weights { holes_f: 0.0, ..Weights::default() },
+			Weights { complete_lines_f: 0.0, ..Weights::default() },
+			Weights { agg_height_f: 0.5, max_height_f: 0.5, ..Weights::default() },
+		];
+		let battle = || {
+			let mut royale = Royale::new(42, 4, Targeting::Attackers);
+			let mut snapshots: Vec<Option<(Well, u32, u32, u32)>> = vec![None; 4];
+			for _ in 0..1000 {
+				royale.turn(|i, state| PlayI::play(&weights[i], state.well(), *state.player().unwrap()).player);
+				for &i in royale.eliminated() {
+					let seat = royale.seat(i);
+					let snapshot = (*seat.state().well(), seat.pieces(), seat.sent(), seat.received());
+					// Eliminated seats never change
+					assert_eq!(*snapshots[i].get_or_insert(snapshot), snapshot);
+				}
+				if royale.winner().is_some() {
+					break;
+				}
+			}
+			royale
+		};
+		let royale = battle();
+		assert_eq!(Some(0), royale.winner());
+		assert_eq!(3, royale.eliminated().len());
+		assert_eq!(royale.eliminated(), battle().eliminated());
+	}
+}
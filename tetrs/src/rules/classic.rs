@@ -0,0 +1,272 @@
+/*!
+Classic rotation rules after the Arika Rotation System, or ARS.
+
+Based on https://tetris.wiki/ARS
+*/
+
+use ::{Piece, Rot, Point, Sprite, Player, Well, Rules, TheRules};
+
+/// Classic rotation rules.
+///
+/// The J, L and T pieces spawn pointing down and every piece rotates around the bottom of its box as in the Sega and Arika games.
+/// A blocked rotation only tries one cell to the right and then one to the left, the I piece never kicks.
+/// The center column exception for the J, L and T pieces is not implemented.
+///
+/// The player keeps using the SRS sprites, the ARS rotation states are the SRS ones shifted within their box.
+/// The kicks carry these shifts so the blocks land where ARS puts them.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ClassicRules;
+impl Rules for ClassicRules {
+	fn piece_sprite(&self, piece: Piece, rot: Rot) -> &'static Sprite {
+		TheRules.piece_sprite(piece, rot)
+	}
+	fn rotate_cw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point] {
+		kicks(&CLASSIC_CW, piece, rot)
+	}
+	fn rotate_ccw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point] {
+		kicks(&CLASSIC_CCW, piece, rot)
+	}
+	fn spawn(&self, piece: Piece, well: &Well) -> Player {
+		let rot = match piece {
+			Piece::J | Piece::L | Piece::T => Rot::Two,
+			_ => Rot::Zero,
+		};
+		let shift = OFFSETS[piece as u8 as usize][rot as u8 as usize];
+		Player::new(piece, rot, Point::new(well.width() / 2 - 2 + shift.x, well.height() + shift.y))
+	}
+}
+
+fn kicks(table: &'static [[[Point; 3]; 4]; 7], piece: Piece, rot: Rot) -> &'static [Point] {
+	let kicks = &table[piece as u8 as usize][rot as u8 as usize];
+	if piece == Piece::I { &kicks[..1] } else { &kicks[..] }
+}
+
+macro_rules! pt {
+	(($x:expr, $y:expr)) => { Point::new($x, $y) };
+}
+
+macro_rules! ars {
+	(
+		$a:tt $b:tt $c:tt
+		$d:tt $e:tt $f:tt
+		$g:tt $h:tt $i:tt
+		$j:tt $k:tt $l:tt
+	) => {
+		[[pt!($a), pt!($b), pt!($c)],
+		 [pt!($d), pt!($e), pt!($f)],
+		 [pt!($g), pt!($h), pt!($i)],
+		 [pt!($j), pt!($k), pt!($l)]]
+	}
+}
+
+// Where the ARS rotation states sit relative to the SRS ones, the flat sides rest on the bottom of the box
+static OFFSETS: [[Point; 4]; 7] = [
+	// The O piece
+	[pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 0))],
+	// The I piece
+	[pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 1)), pt!(( 1, 0))],
+	// The S piece
+	[pt!(( 0,-1)), pt!((-1, 0)), pt!(( 0, 0)), pt!(( 0, 0))],
+	// The Z piece
+	[pt!(( 0,-1)), pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 1, 0))],
+	// The L piece
+	[pt!(( 0,-1)), pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 0))],
+	// The J piece
+	[pt!(( 0,-1)), pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 0))],
+	// The T piece
+	[pt!(( 0,-1)), pt!(( 0, 0)), pt!(( 0, 0)), pt!(( 0, 0))],
+];
+
+// The change in offsets followed by one cell to the right and one to the left
+static CLASSIC_CW: [[[Point; 3]; 4]; 7] = [
+	// The O piece
+	ars! {
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+	// The I piece
+	ars! {
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 1,-1) 	( 2,-1) 	( 0,-1)
+		(-1, 0) 	( 0, 0) 	(-2, 0)
+	},
+	// The S piece
+	ars! {
+		(-1, 1) 	( 0, 1) 	(-2, 1)
+		( 1, 0) 	( 2, 0) 	( 0, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+	},
+	// The Z piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 1, 0) 	( 2, 0) 	( 0, 0)
+		(-1,-1) 	( 0,-1) 	(-2,-1)
+	},
+	// The L piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+	},
+	// The J piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+	},
+	// The T piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+	},
+];
+
+static CLASSIC_CCW: [[[Point; 3]; 4]; 7] = [
+	// The O piece
+	ars! {
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+	// The I piece
+	ars! {
+		( 1, 0) 	( 2, 0) 	( 0, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+		(-1, 1) 	( 0, 1) 	(-2, 1)
+	},
+	// The S piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 1,-1) 	( 2,-1) 	( 0,-1)
+		(-1, 0) 	( 0, 0) 	(-2, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+	// The Z piece
+	ars! {
+		( 1, 1) 	( 2, 1) 	( 0, 1)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		(-1, 0) 	( 0, 0) 	(-2, 0)
+	},
+	// The L piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+	// The J piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+	// The T piece
+	ars! {
+		( 0, 1) 	( 1, 1) 	(-1, 1)
+		( 0,-1) 	( 1,-1) 	(-1,-1)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+		( 0, 0) 	( 1, 0) 	(-1, 0)
+	},
+];
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use ::{State, spawn_player};
+
+	// Rows and columns of the blocks of the player
+	fn blocks(player: Player) -> Vec<(i8, i8)> {
+		let sprite = player.sprite();
+		let mut blocks = Vec::new();
+		for y in 0..4 {
+			for x in 0..4 {
+				if sprite.pix[y as usize] >> (3 - x) & 1 != 0 {
+					blocks.push((player.pt.y - y, player.pt.x + x));
+				}
+			}
+		}
+		blocks
+	}
+
+	fn rotate(player: Player, cw: bool) -> Player {
+		let mut state = State::standard();
+		state.set_rules::<ClassicRules>();
+		state.set_player(player);
+		if cw { state.rotate_cw(); } else { state.rotate_ccw(); }
+		*state.player().unwrap()
+	}
+
+	#[test]
+	fn spawn() {
+		let mut state = State::standard();
+		state.set_rules::<ClassicRules>();
+		state.spawn(Piece::T);
+		// Pointing down in the top rows
+		assert_eq!(vec![(21, 4), (21, 5), (21, 6), (20, 5)], blocks(*state.player().unwrap()));
+		// Every piece in the same rows and columns as the standard spawn
+		for &piece in &Piece::ALL {
+			state.spawn(piece);
+			let mut classic = blocks(*state.player().unwrap());
+			let mut standard = blocks(spawn_player(state.well(), piece));
+			classic.sort();
+			standard.sort();
+			let span = |blocks: &[(i8, i8)]| (blocks[0].0, blocks[blocks.len() - 1].0, blocks.iter().map(|b| b.1).min(), blocks.iter().map(|b| b.1).max());
+			assert_eq!(span(&standard), span(&classic), "{:?}", piece);
+		}
+	}
+
+	#[test]
+	fn flat_on_the_bottom() {
+		// The pieces turn around the bottom row of their box
+		for &piece in &[Piece::S, Piece::Z, Piece::L, Piece::J, Piece::T] {
+			for &cw in &[true, false] {
+				let start = ClassicRules.spawn(piece, &::Well::new(10, 22));
+				let bottom = |player: Player| blocks(player).iter().map(|b| b.0).min().unwrap();
+				let mut player = start;
+				for _ in 0..4 {
+					player = rotate(player, cw);
+					assert_eq!(bottom(start), bottom(player), "{:?}", player);
+				}
+				assert_eq!(start, player);
+			}
+		}
+	}
+
+	#[test]
+	fn kicks() {
+		// Against the left wall the T kicks one cell to the right
+		let player = Player::new(Piece::T, Rot::Right, Point::new(-2, 5));
+		assert_eq!(Player::new(Piece::T, Rot::Two, Point::new(-1, 5)), rotate(player, true));
+		// Against the right wall it kicks one cell to the left, rotating to flat on the bottom
+		let player = Player::new(Piece::T, Rot::Left, Point::new(7, 5));
+		assert_eq!(Player::new(Piece::T, Rot::Zero, Point::new(6, 4)), rotate(player, true));
+	}
+
+	#[test]
+	fn refuses_srs_kick() {
+		// The upright I against the left wall rotates with a kick under SRS
+		let player = Player::new(Piece::I, Rot::Right, Point::new(-2, 5));
+		let mut state = State::standard();
+		state.set_player(player);
+		assert!(state.rotate_cw());
+		// The classic I never kicks
+		let mut state = State::standard();
+		state.set_rules::<ClassicRules>();
+		state.set_player(player);
+		assert!(!state.rotate_cw());
+		assert_eq!(player, *state.player().unwrap());
+	}
+}
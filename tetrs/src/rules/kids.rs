@@ -0,0 +1,159 @@
+/*!
+Simplified rules for beginners.
+*/
+
+use ::{Piece, Rot, Point, Sprite, Rules, TheRules};
+#[cfg(feature = "std")]
+use ::{State, Clock};
+
+/// Well width in kids mode.
+pub const KIDS_WIDTH: i8 = 6;
+/// Well height in kids mode.
+pub const KIDS_HEIGHT: i8 = 12;
+/// Input timing in kids mode, slow gravity and relaxed auto repeat.
+#[cfg(feature = "std")]
+pub const KIDS_CLOCK: Clock = Clock { gravity: 90, player: 10, lock_delay: 0 };
+
+// Only shift one cell left or right, never up or down
+static KIDS_KICKS: [Point; 3] = [Point { x: 0, y: 0 }, Point { x: -1, y: 0 }, Point { x: 1, y: 0 }];
+
+/// Beginner rules.
+///
+/// Rotations only kick a single cell left or right, pieces never move vertically when rotating and spins are impossible.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KidsRules;
+impl Rules for KidsRules {
+	fn piece_sprite(&self, piece: Piece, rot: Rot) -> &'static Sprite {
+		TheRules.piece_sprite(piece, rot)
+	}
+	fn rotate_cw_kicks(&self, _piece: Piece, _rot: Rot) -> &'static [Point] {
+		&KIDS_KICKS
+	}
+	fn rotate_ccw_kicks(&self, _piece: Piece, _rot: Rot) -> &'static [Point] {
+		&KIDS_KICKS
+	}
+	fn rotate_180_kicks(&self, _piece: Piece, _rot: Rot) -> &'static [Point] {
+		&KIDS_KICKS
+	}
+}
+
+#[cfg(feature = "std")]
+impl State {
+	/// Creates a new game state for kids mode.
+	///
+	/// A 6 wide and 12 high well with the `KidsRules` wall kicks. Hard drops don't lock, the player locks once gravity pulls it down.
+	/// Pair with an `Input` running at `KIDS_CLOCK` and the bot with `Weights::narrow()`.
+	pub fn kids_mode() -> State {
+		let mut state = State::new(KIDS_WIDTH, KIDS_HEIGHT);
+		state.set_rules::<KidsRules>();
+		state.set_hard_drop_lock(false);
+		state
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use ::{Well, Player};
+
+	#[test]
+	fn no_vertical_kicks() {
+		// Floor and walls everywhere with a few blocks in the way
+		let well = Well::from_data(KIDS_WIDTH, &[
+			0b000000,
+			0b000000,
+			0b000000,
+			0b000000,
+			0b100001,
+			0b110011,
+		]);
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				for x in -3..KIDS_WIDTH {
+					for y in 0..6 {
+						let player = Player::new(piece, rot, Point::new(x, y));
+						if ::test_player(&well, player) {
+							continue;
+						}
+						for turn in 0..3 {
+							let mut state = State::with_well(well);
+							state.set_rules::<KidsRules>();
+							state.set_player(player);
+							match turn {
+								0 => state.rotate_cw(),
+								1 => state.rotate_ccw(),
+								_ => state.rotate_180(),
+							};
+							let rotated = *state.player().unwrap();
+							assert_eq!(y, rotated.pt.y, "{:?}", player);
+							assert!((rotated.pt.x - x).abs() <= 1, "{:?}", player);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn hard_drop_does_not_lock() {
+		let mut state = State::kids_mode();
+		state.spawn(Piece::T);
+		assert!(state.hard_drop());
+		let player = *state.player().unwrap();
+		assert_eq!(1, player.pt.y);
+		assert_eq!(0, state.well().count_blocks());
+		// Still possible to slide before gravity locks it
+		assert!(state.move_left());
+		assert!(!state.gravity_lock());
+		assert!(state.player().is_none());
+		assert_eq!(4, state.well().count_blocks());
+	}
+
+	#[test]
+	fn spawn_centered() {
+		let mut state = State::kids_mode();
+		assert_eq!((KIDS_WIDTH, KIDS_HEIGHT), state.dimensions());
+		// The I and O pieces are exactly centered, the three wide pieces lean right as in the standard well
+		let cols = |state: &State| {
+			let player = *state.player().unwrap();
+			let mut well = Well::new(KIDS_WIDTH, KIDS_HEIGHT);
+			well.etch(player.sprite(), player.pt);
+			let mask = well.lines().iter().fold(0, |acc, &line| acc | line);
+			well.col_range().enumerate().filter(|&(_, col)| mask & col != 0).map(|(i, _)| i).collect::<Vec<_>>()
+		};
+		state.spawn(Piece::I);
+		assert_eq!(vec![1, 2, 3, 4], cols(&state));
+		state.spawn(Piece::O);
+		assert_eq!(vec![2, 3], cols(&state));
+		state.spawn(Piece::T);
+		assert_eq!(vec![2, 3, 4], cols(&state));
+		assert!(!::test_player(state.well(), *state.player().unwrap()));
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn bot_survives() {
+		use ::{Bag, OfficialBag, PlayI, Weights};
+		// Number of pieces placed before topping out
+		fn survive(weights: &Weights, seed: u64) -> u32 {
+			let mut state = State::kids_mode();
+			let mut bag = OfficialBag::with_rng(::garbage::xorshift(seed));
+			for pieces in 0..300 {
+				if state.spawn(bag.next(state.well()).unwrap()).is_blocked() {
+					return pieces;
+				}
+				match PlayI::play(weights, state.well(), *state.player().unwrap()).player {
+					Some(player) => state.set_player(player),
+					None => return pieces,
+				}
+				state.lock();
+				state.clear_lines(|_| ());
+			}
+			300
+		}
+		let narrow: u32 = (0..4).map(|seed| survive(&Weights::narrow(), seed)).sum();
+		let standard: u32 = (0..4).map(|seed| survive(&Weights::default(), seed)).sum();
+		assert!(narrow >= 200, "narrow {} standard {}", narrow, standard);
+		assert!(narrow > standard, "narrow {} standard {}", narrow, standard);
+	}
+}
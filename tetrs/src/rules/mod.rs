@@ -2,7 +2,14 @@
 Customize the rules for the tetris game.
 */
 
-use ::{Piece, Rot, Point, Sprite, srs_data_cw, srs_data_ccw};
+use ::{Piece, Rot, Point, Sprite, Player, Well, srs_data_cw, srs_data_ccw, srs_data_180, arika_data_cw, arika_data_ccw};
+
+mod kids;
+pub use self::kids::{KidsRules, KIDS_WIDTH, KIDS_HEIGHT};
+#[cfg(feature = "std")]
+pub use self::kids::KIDS_CLOCK;
+mod classic;
+pub use self::classic::ClassicRules;
 
 /// Tetris rule customization.
 pub trait Rules: Copy {
@@ -10,9 +17,29 @@ pub trait Rules: Copy {
 	fn piece_sprite(&self, piece: Piece, rot: Rot) -> &'static Sprite;
 	fn rotate_cw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point];
 	fn rotate_ccw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point];
+	/// Wall kicks for rotating 180 degrees, the SRS+ kicks by default, see `srs_data_180`.
+	fn rotate_180_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point] {
+		srs_data_180(piece, rot)
+	}
+	/// Where the piece spawns in the well and with which rotation, before any spawn grace nudges.
+	///
+	/// Centered at the top of the well with zero rotation by default, see `spawn_player`.
+	fn spawn(&self, piece: Piece, well: &Well) -> Player {
+		spawn_player(well, piece)
+	}
+}
+
+/// Returns where the piece spawns in the well, before any spawn grace nudges.
+pub fn spawn_player(well: &Well, piece: Piece) -> Player {
+	let pt = Point {
+		x: well.width() / 2 - 2,
+		y: well.height() - (piece != Piece::O && piece != Piece::I) as i8,
+	};
+	Player::new(piece, Rot::Zero, pt)
 }
 
-#[derive(Copy, Clone)]
+/// The standard rules with SRS wall kicks.
+#[derive(Copy, Clone, Debug, Default)]
 pub struct TheRules;
 impl Rules for TheRules {
 	fn piece_sprite(&self, piece: Piece, rot: Rot) -> &'static Sprite {
@@ -26,6 +53,21 @@ impl Rules for TheRules {
 	}
 }
 
+/// The SRS rules with the Arika wall kicks for the I piece.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ArikaRules;
+impl Rules for ArikaRules {
+	fn piece_sprite(&self, piece: Piece, rot: Rot) -> &'static Sprite {
+		TheRules.piece_sprite(piece, rot)
+	}
+	fn rotate_cw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point] {
+		arika_data_cw(piece, rot)
+	}
+	fn rotate_ccw_kicks(&self, piece: Piece, rot: Rot) -> &'static [Point] {
+		arika_data_ccw(piece, rot)
+	}
+}
+
 //----------------------------------------------------------------
 
 struct Mesh {
@@ -66,6 +108,11 @@ macro_rules! data {
 	};
 }
 
+/// Returns the pixels of the standard sprite by piece and rotation, usable in constant expressions.
+pub(crate) const fn sprite_pix(piece: usize, rot: usize) -> [u8; 4] {
+	DATA[piece].data[rot].pix
+}
+
 static DATA: [Mesh; 7] = [
 	// The O piece
 	Mesh { data: data![
@@ -119,3 +166,72 @@ static DATA: [Mesh; 7] = [
 ];
 
 //----------------------------------------------------------------
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use ::State;
+
+	// Every sprite of the rules has four blocks within the low nibbles
+	fn check_sprites<R: Rules>(rules: R) {
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				let sprite = rules.piece_sprite(piece, rot);
+				assert!(sprite.pix.iter().all(|&row| row & 0xf0 == 0), "{:?} {:?} outside the 4x4 box", piece, rot);
+				let blocks: u32 = sprite.pix.iter().map(|row| row.count_ones()).sum();
+				assert_eq!(4, blocks, "{:?} {:?}", piece, rot);
+				assert_eq!(4, sprite.cells().count());
+			}
+		}
+	}
+
+	#[test]
+	fn sprites() {
+		check_sprites(TheRules);
+		check_sprites(ArikaRules);
+		check_sprites(ClassicRules);
+		check_sprites(KidsRules);
+		// Rotating a piece turns its sprite, the sizes swap every quarter turn
+		for &piece in &Piece::ALL {
+			for &rot in &Rot::ALL {
+				let (sprite, turned) = (TheRules.piece_sprite(piece, rot), TheRules.piece_sprite(piece, rot.cw()));
+				assert_eq!((sprite.width(), sprite.height()), (turned.height(), turned.width()), "{:?} {:?}", piece, rot);
+			}
+		}
+		assert_eq!(Piece::count(), Piece::ALL.len());
+		assert!(Piece::ALL.iter().enumerate().all(|(i, &piece)| piece as usize == i));
+		assert!(Rot::ALL.iter().enumerate().all(|(i, &rot)| rot as usize == i && Rot::wrapping_from(i as u8) == rot));
+	}
+
+	#[test]
+	fn arika_i_kicks() {
+		// The JLSTZ kicks are the SRS kicks
+		for &piece in &[Piece::S, Piece::Z, Piece::L, Piece::J, Piece::T] {
+			for &rot in &Rot::ALL {
+				assert_eq!(TheRules.rotate_cw_kicks(piece, rot), ArikaRules.rotate_cw_kicks(piece, rot));
+				assert_eq!(TheRules.rotate_ccw_kicks(piece, rot), ArikaRules.rotate_ccw_kicks(piece, rot));
+			}
+		}
+		// Blocked in place and one cell to the left and right, the I kicks down under SRS and up under Arika rules
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0001000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000011000,
+			0b0000000000,
+		]);
+		let player = Player::new(Piece::I, Rot::Zero, Point::new(3, 4));
+		let mut state = State::with_well(well);
+		state.set_player(player);
+		assert!(state.rotate_cw());
+		assert_eq!(Player::new(Piece::I, Rot::Right, Point::new(1, 3)), *state.player().unwrap());
+		let mut state = State::with_well(well);
+		state.set_rules::<ArikaRules>();
+		state.set_player(player);
+		assert!(state.rotate_cw());
+		assert_eq!(Player::new(Piece::I, Rot::Right, Point::new(4, 6)), *state.player().unwrap());
+	}
+}
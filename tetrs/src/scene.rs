@@ -2,7 +2,26 @@
 Well scene.
 */
 
-use ::{Player, Tile, TileTy, TILE_BG0, TILE_BG1, TILE_BG2, MAX_HEIGHT, MAX_WIDTH};
+use ::std::fmt::{self, Write};
+
+use ::{CoordError, Piece, Player, Point, Palette, Rot, Tile, TileTy, TILE_BG0, TILE_BG1, TILE_BG2, MAX_HEIGHT, MAX_WIDTH};
+use ::{CONNECTS_LEFT, CONNECTS_RIGHT, CONNECTS_UP, CONNECTS_DOWN, Line, Well, TILE_GARBAGE};
+use ::well::SIZE_OF_WIDTH;
+use ::pt::coord_within;
+
+/// Character styles of `Scene::render_compact`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompactStyle {
+	/// Two rows per character with the half and full block characters.
+	HalfBlock,
+	/// Four rows by two columns per character with the braille dots.
+	Braille,
+}
+
+// Columns of the side panel of `State::scene_with`, a gap column and the piece boxes
+const PANEL_WIDTH: usize = 5;
+// Rows of every piece box, the spawn sprites fit in their top three rows
+const PANEL_BOX_HEIGHT: usize = 3;
 
 /// Well scene.
 ///
@@ -10,7 +29,7 @@ use ::{Player, Tile, TileTy, TILE_BG0, TILE_BG1, TILE_BG2, MAX_HEIGHT, MAX_WIDTH
 ///
 /// This allows a client to visualize the well based on which pieces were dropped here
 /// without requiring any of the game logic to work with this less efficient data structure.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Scene {
 	width: i8,
 	height: i8,
@@ -18,14 +37,23 @@ pub struct Scene {
 }
 impl Scene {
 	pub fn new(width: i8, height: i8) -> Scene {
-		let mut bg = [[TILE_BG0; MAX_WIDTH]; MAX_HEIGHT];
-		bg[height as usize - 2] = [TILE_BG1; MAX_WIDTH];
-		bg[height as usize - 1] = [TILE_BG2; MAX_WIDTH];
-		Scene {
+		let mut scene = Scene {
 			width: width,
 			height: height,
-			tiles: bg,
+			tiles: [[TILE_BG0; MAX_WIDTH]; MAX_HEIGHT],
+		};
+		scene.fix_bg();
+		scene
+	}
+	/// Creates a scene showing the blocks of the well.
+	///
+	/// The well doesn't know which pieces its blocks came from, they are drawn with the grey `TILE_GARBAGE`.
+	pub fn from_well(well: &Well) -> Scene {
+		let mut scene = Scene::new(well.width(), well.height());
+		for row in 0..well.height() {
+			scene.set_row_from_line(row, well.line(row), TILE_GARBAGE);
 		}
+		scene
 	}
 	pub fn width(&self) -> i8 {
 		self.width
@@ -36,53 +64,646 @@ impl Scene {
 	pub fn line(&self, row: i8) -> &[Tile] {
 		&self.tiles[(self.height - 1 - row) as usize][..self.width as usize]
 	}
+	/// Returns the line like `line` with the row range-checked.
+	pub fn try_line(&self, row: i32) -> Result<&[Tile], CoordError> {
+		coord_within(row, self.height).map(|row| self.line(row))
+	}
+	/// Returns the rows starting with the top row, the same order as `line`.
+	pub fn rows(&self) -> impl Iterator<Item = &[Tile]> {
+		self.tiles[..self.height as usize].iter().rev().map(move |tiles| &tiles[..self.width as usize])
+	}
+	/// Returns the tile at the row and column, rows count from the top like `line`.
+	///
+	/// # Panics
+	///
+	/// Panics if the row or the column is outside the scene.
+	pub fn tile(&self, row: i8, col: i8) -> Tile {
+		assert!(row >= 0 && row < self.height && col >= 0 && col < self.width, "tile ({}, {}) outside the {}x{} scene", row, col, self.width, self.height);
+		self.tiles[(self.height - 1 - row) as usize][col as usize]
+	}
+	pub(crate) fn set_tile(&mut self, row: i8, col: i8, tile: Tile) {
+		self.tiles[row as usize][col as usize] = tile;
+	}
+	/// Renders the scene as palette indices, row-major starting with the top row.
+	///
+	/// # Panics
+	///
+	/// The output must hold at least `width * height` bytes.
+	pub fn render_indexed(&self, palette: &Palette, out: &mut [u8]) {
+		let width = self.width as usize;
+		let out = &mut out[..width * self.height as usize];
+		for (row, chunk) in out.chunks_mut(width).enumerate() {
+			for (index, &tile) in chunk.iter_mut().zip(self.line(row as i8)) {
+				*index = tile.palette_index(palette);
+			}
+		}
+	}
+	/// Renders the occupied tiles as a compact string for tiny terminal areas, one line per character row starting with the top.
+	///
+	/// Only the presence of blocks is rendered, not their colors.
+	/// The player and its ghost are included if requested, the stack always is.
+	///
+	/// `HalfBlock` renders `width` characters by `(height + 1) / 2` lines, `Braille` renders `(width + 1) / 2` characters by `(height + 3) / 4` lines.
+	pub fn render_compact(&self, style: CompactStyle, player: bool) -> String {
+		let (cols, rows) = match style {
+			CompactStyle::HalfBlock => (1, 2),
+			CompactStyle::Braille => (2, 4),
+		};
+		let width = self.width as usize;
+		let height = self.height as usize;
+		// Cells outside the scene are empty
+		let filled = |row: usize, col: usize| -> bool {
+			if row >= height || col >= width {
+				return false;
+			}
+			match self.line(row as i8)[col].tile_ty() {
+				TileTy::Field => true,
+				TileTy::Player | TileTy::Ghost => player,
+				TileTy::Background => false,
+			}
+		};
+		let mut out = String::new();
+		for top in (0..height).step_by(rows) {
+			if top > 0 {
+				out.push('\n');
+			}
+			for left in (0..width).step_by(cols) {
+				let chr = match style {
+					CompactStyle::HalfBlock => match (filled(top, left), filled(top + 1, left)) {
+						(true, true) => '█',
+						(true, false) => '▀',
+						(false, true) => '▄',
+						(false, false) => ' ',
+					},
+					CompactStyle::Braille => {
+						// Dots 1-2-3-7 down the left column, 4-5-6-8 down the right column
+						const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+						let mut bits = 0;
+						for (dy, dots) in DOTS.iter().enumerate() {
+							for (dx, &dot) in dots.iter().enumerate() {
+								if filled(top + dy, left + dx) {
+									bits |= dot;
+								}
+							}
+						}
+						::std::char::from_u32(0x2800 + bits).unwrap()
+					},
+				};
+				out.push(chr);
+			}
+		}
+		out
+	}
 	/// Draws the player and its ghost into the scene.
+	///
+	/// Every mino is connected to its neighbors within the piece, see `Tile::connections`.
 	pub fn draw(&mut self, player: Player, tile_ty: TileTy) {
-		// Get the unperturbed mesh
-		let sprite = player.sprite();
-		let mut part_id = 0;
-		// Render the tiles to the scene
-		for y in 0..4 {
-			let mut mask = sprite.pix[y as usize];
-			for x in 0..4 {
-				if mask & 1 != 0 {
-					let row = player.pt.y - y;
-					let col = player.pt.x + x;
-					if col >= 0 && col < self.width && row >= 0 && row < self.height {
-						let tile = Tile::from(tile_ty, part_id, Some(player.piece));
-						self.tiles[row as usize][col as usize] = tile;
-					}
-					part_id += 1;
-				}
-				mask >>= 1;
+		let pix = player.sprite().pix;
+		// The parts are numbered in drawing order, the same as the sprite's cells
+		for (part_id, cell) in player.sprite().cells().enumerate() {
+			let row = player.pt.y - cell.y;
+			let col = player.pt.x + cell.x;
+			if col >= 0 && col < self.width && row >= 0 && row < self.height {
+				let tile = Tile::from(tile_ty, part_id as u8, Some(player.piece));
+				self.tiles[row as usize][col as usize] = tile.with_connections(connections(&pix, cell.x, cell.y));
 			}
 		}
 	}
+	/// Draws the piece in its spawn rotation, eg. for the next piece previews.
+	///
+	/// The origin is the top left of the sprite's 4x4 box in the well's coordinates, like `Player::pt`.
+	/// Tiles outside the scene are clipped.
+	pub fn draw_piece_box(&mut self, piece: Piece, origin: Point, tile_ty: TileTy) {
+		self.draw(Player::new(piece, Rot::Zero, origin), tile_ty);
+	}
+	// Widens the scene by the side panel of `State::scene_with`
+	pub(crate) fn with_panel(&self, next: &[Piece], hold: Option<Piece>) -> Scene {
+		let width = self.width as usize;
+		let mut scene = Scene::new(width.saturating_add(PANEL_WIDTH).min(MAX_WIDTH) as i8, self.height);
+		for (lhs, rhs) in scene.tiles.iter_mut().zip(self.tiles.iter()) {
+			lhs[..width].copy_from_slice(&rhs[..width]);
+		}
+		// One column apart from the well, the hold box on top of the next boxes
+		let boxes = ::std::iter::once(hold).chain(next.iter().map(|&piece| Some(piece)));
+		for (slot, piece) in boxes.enumerate().take(self.height as usize / PANEL_BOX_HEIGHT) {
+			if let Some(piece) = piece {
+				let top = self.height as usize - 1 - slot * PANEL_BOX_HEIGHT;
+				scene.draw_piece_box(piece, Point::new(self.width + 1, top as i8), TileTy::Field);
+			}
+		}
+		scene
+	}
 	pub fn remove_line(&mut self, row: i8) {
-		let top = (self.height - 2) as usize;
-		let _ = self.tiles[row as usize..top];
+		let top = (self.height - 1) as usize;
+		let _ = self.tiles[row as usize..=top];
 		for i in row as usize..top {
 			self.tiles[i] = self.tiles[i + 1];
 		}
 		self.tiles[top] = [TILE_BG0; MAX_WIDTH];
+		self.cut(row);
+		self.fix_bg();
+	}
+	/// Resets the scene to the background.
+	pub fn clear(&mut self) {
+		*self = Scene::new(self.width, self.height);
+	}
+	/// Sets the row from a line of the well, the blocks are drawn with the tile and the empty cells with the background.
+	pub fn set_row_from_line(&mut self, row: i8, line: Line, tile: Tile) {
+		let bg = background(self.height as usize, row as usize);
+		for (col, dest) in self.tiles[row as usize][..self.width as usize].iter_mut().enumerate() {
+			*dest = if line & 1 << (SIZE_OF_WIDTH - 1 - col) != 0 { tile } else { bg };
+		}
+	}
+	/// Inserts a line of tiles, shifting the lines above it up.
+	///
+	/// The top line is dropped, like `Well::insert_line`.
+	pub fn insert_line(&mut self, row: i8, line: [Tile; MAX_WIDTH]) {
+		let top = (self.height - 1) as usize;
+		for i in (row as usize..top).rev() {
+			self.tiles[i + 1] = self.tiles[i];
+		}
+		self.tiles[row as usize] = line;
+		self.cut(row + 1);
+		self.cut(row);
 		self.fix_bg();
 	}
+	// Cuts the connections between the row and the row below it
+	fn cut(&mut self, row: i8) {
+		if row <= 0 || row >= self.height {
+			return;
+		}
+		for tile in self.tiles[row as usize].iter_mut() {
+			*tile = tile.with_connections(tile.connections() & !CONNECTS_DOWN);
+		}
+		for tile in self.tiles[row as usize - 1].iter_mut() {
+			*tile = tile.with_connections(tile.connections() & !CONNECTS_UP);
+		}
+	}
+	// Reshades the background tiles after the lines moved
 	fn fix_bg(&mut self) {
-		let height = self.height as usize;
-		for tile in self.tiles[height - 1].iter_mut() {
-			if tile.tile_ty() == TileTy::Background {
-				*tile = TILE_BG2;
+		let height = self.height.max(0) as usize;
+		for (row, tiles) in self.tiles[..height].iter_mut().enumerate() {
+			let bg = background(height, row);
+			for tile in tiles.iter_mut() {
+				if tile.tile_ty() == TileTy::Background {
+					*tile = bg;
+				}
 			}
 		}
-		for tile in self.tiles[height - 2].iter_mut() {
-			if tile.tile_ty() == TileTy::Background {
-				*tile = TILE_BG1;
+	}
+}
+
+/// Prints a character per tile, a line per row starting with the top row.
+///
+/// The field shows the letter of the piece, `#` for garbage, the player the lowercase letter of its piece, the ghost `+` and the background `.`.
+impl fmt::Display for Scene {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for row in self.rows() {
+			for &tile in row {
+				f.write_char(tile_char(tile))?;
 			}
+			f.write_char('\n')?;
+		}
+		Ok(())
+	}
+}
+
+fn tile_char(tile: Tile) -> char {
+	let letter = || tile.piece().map(|piece| b"OISZLJT"[piece as usize] as char);
+	match tile.tile_ty() {
+		TileTy::Player => letter().map_or('@', |c| c.to_ascii_lowercase()),
+		TileTy::Ghost => '+',
+		TileTy::Field => letter().unwrap_or('#'),
+		TileTy::Background => '.',
+	}
+}
+
+/// Read-only view of a scene with the player and its ghost composited on access.
+///
+/// The view borrows the tiles of the field instead of copying them into a new scene,
+/// the player and ghost tiles are looked up from their sprites as the cells are read.
+#[derive(Copy, Clone, Debug)]
+pub struct SceneView<'a> {
+	field: &'a Scene,
+	ghost: Option<Player>,
+	player: Option<Player>,
+}
+impl<'a> SceneView<'a> {
+	/// Creates a view of the field with the ghost and the player drawn over it.
+	pub fn new(field: &'a Scene, ghost: Option<Player>, player: Option<Player>) -> SceneView<'a> {
+		SceneView {
+			field: field,
+			ghost: ghost,
+			player: player,
+		}
+	}
+	pub fn width(&self) -> i8 {
+		self.field.width
+	}
+	pub fn height(&self) -> i8 {
+		self.field.height
+	}
+	/// Returns the tile at the row and column, rows count from the top like `line`.
+	pub fn tile(&self, row: i8, col: i8) -> Tile {
+		self.composite(self.field.height - 1 - row, col)
+	}
+	/// Returns the tiles of the row, rows count from the top like `Scene::line`.
+	pub fn line(self, row: i8) -> impl Iterator<Item = Tile> + 'a {
+		let row = self.field.height - 1 - row;
+		(0..self.field.width).map(move |col| self.composite(row, col))
+	}
+	/// Returns the line like `line` with the row range-checked.
+	pub fn try_line(self, row: i32) -> Result<impl Iterator<Item = Tile> + 'a, CoordError> {
+		coord_within(row, self.field.height).map(|row| self.line(row))
+	}
+	/// Returns the tiles of all the lines starting with the top row.
+	pub fn iter(self) -> impl Iterator<Item = Tile> + 'a {
+		(0..self.field.height).flat_map(move |row| self.line(row))
+	}
+	/// Materializes the view into an owned scene.
+	pub fn to_scene(&self) -> Scene {
+		let mut scene = self.field.clone();
+		if let Some(ghost) = self.ghost {
+			scene.draw(ghost, TileTy::Ghost);
+		}
+		if let Some(player) = self.player {
+			scene.draw(player, TileTy::Player);
+		}
+		scene
+	}
+	// Tiles indexed bottom-up with the well's coordinates, the player is drawn over the ghost
+	fn composite(&self, row: i8, col: i8) -> Tile {
+		if let Some(tile) = self.player.and_then(|player| overlay(player, TileTy::Player, row, col)) {
+			return tile;
+		}
+		if let Some(tile) = self.ghost.and_then(|ghost| overlay(ghost, TileTy::Ghost, row, col)) {
+			return tile;
 		}
-		for tile in self.tiles[height - 3].iter_mut() {
-			if tile.tile_ty() == TileTy::Background {
-				*tile = TILE_BG0;
+		self.field.tiles[row as usize][col as usize]
+	}
+}
+
+// The top two rows are shaded when there are at least as many rows below them
+const SHADED_MIN_HEIGHT: usize = 4;
+
+// Background tile of the row counted from the bottom
+fn background(height: usize, row: usize) -> Tile {
+	if height < SHADED_MIN_HEIGHT {
+		return TILE_BG0;
+	}
+	match height - 1 - row {
+		0 => TILE_BG2,
+		1 => TILE_BG1,
+		_ => TILE_BG0,
+	}
+}
+
+// Returns the tile `Scene::draw` would draw at the row and column
+fn overlay(player: Player, tile_ty: TileTy, row: i8, col: i8) -> Option<Tile> {
+	let y = player.pt.y - row;
+	let x = col - player.pt.x;
+	if !(0..4).contains(&y) || !(0..4).contains(&x) {
+		return None;
+	}
+	let pix = player.sprite().pix;
+	if pix[y as usize] >> (3 - x) & 1 == 0 {
+		return None;
+	}
+	// The parts are numbered in drawing order, top to bottom and left to right
+	let part_id = pix[..y as usize].iter().map(|mask| mask.count_ones()).sum::<u32>()
+		+ (pix[y as usize] >> (4 - x)).count_ones();
+	Some(Tile::from(tile_ty, part_id as u8, Some(player.piece)).with_connections(connections(&pix, x, y)))
+}
+
+// Returns the neighbors of the sprite's cell which are filled, the cell is counted from the top left
+fn connections(pix: &[u8; 4], x: i8, y: i8) -> u8 {
+	let filled = |x: i8, y: i8| (0..4).contains(&x) && (0..4).contains(&y) && pix[y as usize] >> (3 - x) & 1 != 0;
+	let mut mask = 0;
+	if filled(x - 1, y) {
+		mask |= CONNECTS_LEFT;
+	}
+	if filled(x + 1, y) {
+		mask |= CONNECTS_RIGHT;
+	}
+	if filled(x, y - 1) {
+		mask |= CONNECTS_UP;
+	}
+	if filled(x, y + 1) {
+		mask |= CONNECTS_DOWN;
+	}
+	mask
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Piece, Rot, Point, State};
+
+	fn mid_game() -> Scene {
+		let mut state = State::new(10, 8);
+		for &(piece, x) in &[(Piece::I, 0), (Piece::I, 5), (Piece::O, 7), (Piece::T, 1), (Piece::S, 4)] {
+			state.spawn(piece);
+			state.set_player(Player::new(piece, Rot::Zero, Point::new(x, 7)));
+			state.hard_drop();
+		}
+		state.spawn(Piece::L);
+		state.scene()
+	}
+
+	#[test]
+	fn half_block() {
+		let scene = mid_game();
+		assert_eq!("          \n          \n   ▄  ▄▄▄▄\n▄▄██▀██▄█▀", scene.render_compact(CompactStyle::HalfBlock, false));
+		// The L above its ghost, the ghost rests on the stack
+		assert_eq!("    ▄▄█   \n      ▄   \n   ▄▀▀█▄▄▄\n▄▄██▀██▄█▀", scene.render_compact(CompactStyle::HalfBlock, true));
+	}
+
+	#[test]
+	fn matches_well() {
+		// The field tiles are the blocks of the well, asymmetric pieces are not mirrored
+		let mut state = State::new(10, 8);
+		for &(piece, rot, x) in &[(Piece::S, Rot::Zero, 0), (Piece::J, Rot::Right, 3), (Piece::L, Rot::Two, 5), (Piece::Z, Rot::Left, 7)] {
+			state.spawn(piece);
+			state.set_player(Player::new(piece, rot, Point::new(x, 7)));
+			state.hard_drop();
+		}
+		let scene = state.scene();
+		for row in 0..8 {
+			let line = state.well().line(7 - row);
+			for (col, (mask, tile)) in state.well().col_range().zip(scene.line(row)).enumerate() {
+				assert_eq!(line & mask != 0, tile.tile_ty() == TileTy::Field, "row {} col {}\n{}", row, col, state.well());
+			}
+		}
+	}
+
+	#[test]
+	fn from_well() {
+		// Every block of the well is a grey field tile
+		fn check(well: &Well, scene: &Scene) {
+			for row in 0..well.height() {
+				for (col, (mask, &tile)) in well.col_range().zip(scene.line(row)).enumerate() {
+					let block = well.line(well.height() - 1 - row) & mask != 0;
+					assert_eq!(block, tile == TILE_GARBAGE, "row {} col {}\n{}", row, col, well);
+					assert_eq!(block, tile.tile_ty() == TileTy::Field, "row {} col {}\n{}", row, col, well);
+				}
+			}
+		}
+		// States built from a well show its blocks right away
+		let well: Well = "|          |\n|          |\n|    #     |\n|##  ## ## |\n|### ######|\n|## #######|".parse().unwrap();
+		let mut state = State::with_well(well);
+		check(&well, &state.scene());
+
+		// Resyncing redraws the locked pieces from the well
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(1, 5)));
+		assert!(state.hard_drop());
+		assert_eq!(well.count_blocks() + 4, state.well().count_blocks());
+		state.resync_scene();
+		check(state.well(), &state.scene());
+	}
+
+	#[test]
+	fn piece_box() {
+		let state = State::standard();
+		let scene = state.scene_with(&[Piece::I, Piece::O], None);
+		assert_eq!((15, 22), (scene.width(), scene.height()));
+		// The hold box on top stays empty, the I lies in the second row of the first next box
+		for row in 0..3 {
+			assert!(filled_in(&scene, row).is_empty(), "{}", row);
+		}
+		assert_eq!(vec![11, 12, 13, 14], filled_in(&scene, 4));
+		assert!(scene.line(4)[11..].iter().all(|tile| tile.piece() == Some(Piece::I)));
+		assert_eq!(vec![0, 1, 2, 3], scene.line(4)[11..].iter().map(|tile| tile.part()).collect::<Vec<_>>());
+		assert_eq!(TILE_BG0, scene.line(4)[10]);
+		// The O in the middle columns of the box below
+		assert_eq!(vec![12, 13], filled_in(&scene, 7));
+		assert_eq!(vec![12, 13], filled_in(&scene, 8));
+		assert_eq!(4 + 4, (0..22).map(|row| filled_in(&scene, row).len()).sum::<usize>());
+		// The well is the same as without the panel
+		let plain = state.scene();
+		for row in 0..22 {
+			assert_eq!(plain.line(row), &scene.line(row)[..10]);
+		}
+
+		// The hold piece, clipped at the edges
+		let scene = State::new(25, 6).scene_with(&[Piece::T, Piece::T], Some(Piece::I));
+		assert_eq!(MAX_WIDTH as i8, scene.width());
+		assert_eq!(vec![26, 27], filled_in(&scene, 1));
+		// Only the last column of the T fits, the second T does not fit below it
+		assert!(filled_in(&scene, 3).is_empty());
+		assert_eq!(vec![27], filled_in(&scene, 4));
+		assert!(filled_in(&scene, 5).is_empty());
+		let mut scene = Scene::new(10, 4);
+		scene.draw_piece_box(Piece::I, Point::new(8, 1), TileTy::Ghost);
+		assert_eq!(vec![0, 1], scene.line(3).iter().enumerate().filter(|&(_, tile)| tile.tile_ty() == TileTy::Ghost).map(|(col, _)| col - 8).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn connections() {
+		// The T piece's connections by part, the parts counted top to bottom and left to right
+		let (l, r, u, d) = (CONNECTS_LEFT, CONNECTS_RIGHT, CONNECTS_UP, CONNECTS_DOWN);
+		let expected = [
+			(Rot::Zero, [d, r, l | r | u, l]),
+			(Rot::Right, [d, u | d | r, l, u]),
+			(Rot::Two, [r, l | r | d, l, u]),
+			(Rot::Left, [d, r, l | u | d, u]),
+		];
+		for &(rot, masks) in &expected {
+			let mut scene = Scene::new(6, 6);
+			scene.draw(Player::new(Piece::T, rot, Point::new(1, 4)), TileTy::Player);
+			let mut tiles: Vec<Tile> = (0..6).rev().flat_map(|row| scene.line(row).to_vec()).filter(|tile| tile.tile_ty() == TileTy::Player).collect();
+			tiles.sort_by_key(|tile| tile.part());
+			assert_eq!(&masks[..], &tiles.iter().map(|tile| tile.connections()).collect::<Vec<_>>()[..], "{:?}", rot);
+		}
+
+		// Clearing a line cuts the connections across it
+		let mut scene = Scene::new(6, 6);
+		scene.draw(Player::new(Piece::T, Rot::Right, Point::new(1, 4)), TileTy::Field);
+		scene.remove_line(2);
+		assert_eq!(d, scene.tile(2, 3).connections());
+		assert_eq!((u | r, l), (scene.tile(3, 3).connections(), scene.tile(3, 4).connections()));
+	}
+
+	#[test]
+	fn short_scenes() {
+		for &height in &[4, 5] {
+			// A different piece in the first column of every row, bottom to top
+			let rows = height as usize;
+			let fill = |scene: &mut Scene| for row in 0..rows {
+				scene.tiles[row][0] = Tile::from(TileTy::Field, 0, Some(Piece::ALL[row]));
+			};
+			let column = |scene: &Scene| (0..height).map(|row| scene.tile(height - 1 - row, 0)).map(|tile| if tile.tile_ty() == TileTy::Field { tile.piece() } else { None }).collect::<Vec<_>>();
+			let shades = |scene: &Scene| (0..height).map(|row| scene.tile(height - 1 - row, 1)).collect::<Vec<_>>();
+			let mut expected_shades = vec![TILE_BG0; rows - 2];
+			expected_shades.extend_from_slice(&[TILE_BG1, TILE_BG2]);
+
+			let mut scene = Scene::new(4, height);
+			assert_eq!(expected_shades, shades(&scene));
+
+			// Removing the bottom row shifts every row above it down, including the top row
+			fill(&mut scene);
+			scene.remove_line(0);
+			let mut expected: Vec<_> = Piece::ALL[1..rows].iter().cloned().map(Some).collect();
+			expected.push(None);
+			assert_eq!(expected, column(&scene));
+			assert_eq!(TILE_BG2, scene.tile(0, 0));
+			assert_eq!(expected_shades, shades(&scene));
+
+			// Removing the top row leaves the rows below it in place
+			let mut scene = Scene::new(4, height);
+			fill(&mut scene);
+			scene.remove_line(height - 1);
+			let mut expected: Vec<_> = Piece::ALL[..rows - 1].iter().cloned().map(Some).collect();
+			expected.push(None);
+			assert_eq!(expected, column(&scene));
+			assert_eq!(expected_shades, shades(&scene));
+		}
+		// Too short to shade the top rows
+		for height in 1..4 {
+			let scene = Scene::new(4, height);
+			assert!((0..height).all(|row| scene.line(row).iter().all(|&tile| tile == TILE_BG0)));
+		}
+	}
+
+	#[test]
+	fn insert_line() {
+		let mut scene = Scene::new(4, 6);
+		let top = Tile::from(TileTy::Field, 0, Some(Piece::I));
+		let below = Tile::from(TileTy::Field, 0, Some(Piece::O));
+		scene.set_row_from_line(5, 0b1001 << 28, top);
+		scene.set_row_from_line(4, 0b0110 << 28, below);
+		assert_eq!(&[top, TILE_BG2, TILE_BG2, top], scene.line(0));
+
+		// A garbage line with a hole in the third column, the top row is bumped out
+		scene.insert_line(0, [TILE_BG0; MAX_WIDTH]);
+		scene.set_row_from_line(0, 0b1101 << 28, TILE_GARBAGE);
+		assert_eq!(&[TILE_GARBAGE, TILE_GARBAGE, TILE_BG0, TILE_GARBAGE], scene.line(5));
+		assert_eq!(&[TILE_BG2, below, below, TILE_BG2], scene.line(0));
+		assert_eq!(vec![0, 1, 3], filled_in(&scene, 5));
+		assert!((1..5).all(|row| filled_in(&scene, row).is_empty()));
+
+		// Bits outside the width are ignored
+		scene.set_row_from_line(1, !0, TILE_GARBAGE);
+		assert_eq!(&[TILE_GARBAGE; 4], scene.line(4));
+		assert_eq!(TILE_BG0, scene.tiles[1][4]);
+
+		scene.clear();
+		for row in 0..6 {
+			assert_eq!(Scene::new(4, 6).line(row), scene.line(row));
+		}
+	}
+
+	// Columns of the field tiles in the row
+	fn filled_in(scene: &Scene, row: i8) -> Vec<usize> {
+		scene.line(row).iter().enumerate().filter(|&(_, tile)| tile.tile_ty() == TileTy::Field).map(|(col, _)| col).collect()
+	}
+
+	#[test]
+	fn braille() {
+		let scene = mid_game();
+		assert_eq!("⠀⠀⠀⠀⠀\n⣀⣴⢤⣖⡶", scene.render_compact(CompactStyle::Braille, false));
+		assert_eq!("⠀⠀⠒⡃⠀\n⣀⣴⢭⣗⡶", scene.render_compact(CompactStyle::Braille, true));
+	}
+
+	#[test]
+	fn try_line() {
+		let scene = mid_game();
+		for row in 0..8 {
+			assert_eq!(scene.line(row as i8), scene.try_line(row).unwrap());
+		}
+		for &row in &[-129, -1, 8, 128, i32::MAX] {
+			assert!(scene.try_line(row).is_err(), "{}", row);
+		}
+		assert_eq!(Some(CoordError::Overflow { value: -129 }), scene.try_line(-129).err());
+	}
+
+	// Compares the view against the materialized scene cell by cell
+	fn assert_view(state: &State) {
+		let view = state.scene_view();
+		let scene = view.to_scene();
+		assert_eq!((scene.width(), scene.height()), (view.width(), view.height()));
+		for row in 0..scene.height() {
+			assert_eq!(scene.line(row), &view.line(row).collect::<Vec<_>>()[..]);
+			for col in 0..scene.width() {
+				assert_eq!(scene.line(row)[col as usize], view.tile(row, col));
+			}
+		}
+		let tiles: Vec<Tile> = (0..scene.height()).flat_map(|row| scene.line(row).to_vec()).collect();
+		assert_eq!(tiles, view.iter().collect::<Vec<_>>());
+		assert_eq!(state.scene().tiles, scene.tiles);
+	}
+
+	#[test]
+	fn view() {
+		let mut state = State::standard();
+		let mut cleared = 0;
+		for i in 0..400 {
+			if i % 9 == 8 {
+				state.add_garbage(1, (i % 10) as i8);
+				assert_view(&state);
+			}
+			let piece = Piece::ALL[i % Piece::count()];
+			if state.is_game_over() || state.spawn(piece).is_blocked() {
+				state = State::standard();
+				state.spawn(piece);
+			}
+			assert_view(&state);
+			// Move the player over the columns, preferring a placement which clears lines
+			let y = state.player().unwrap().pt.y;
+			let moves = Rot::iter().flat_map(|rot| (-3..10).map(move |x| Player::new(piece, rot, Point::new(x, y))));
+			let moves: Vec<Player> = moves.filter(|&player| !::test_player(state.well(), player)).collect();
+			let clears = moves.iter().cloned().find(|&player| {
+				state.simulate_lock(::trace_down(state.well(), player)).is_some_and(|lock| !lock.cleared_rows.is_empty())
+			});
+			state.set_player(clears.unwrap_or(moves[i % moves.len()]));
+			assert_view(&state);
+			state.hard_drop();
+			assert_view(&state);
+			cleared += state.clear_lines(|_| ());
+			assert_view(&state);
+		}
+		assert!(cleared > 0);
+	}
+
+	#[test]
+	fn compact_dimensions() {
+		for width in 4..MAX_WIDTH as i8 + 1 {
+			for height in 4..MAX_HEIGHT as i8 + 1 {
+				let scene = Scene::new(width, height);
+				for &(style, cols, rows) in &[(CompactStyle::HalfBlock, 1usize, 2usize), (CompactStyle::Braille, 2, 4)] {
+					let render = scene.render_compact(style, true);
+					let lines: Vec<&str> = render.split('\n').collect();
+					assert_eq!((height as usize).div_ceil(rows), lines.len(), "{}x{} {:?}", width, height, style);
+					assert!(lines.iter().all(|line| line.chars().count() == (width as usize).div_ceil(cols)));
+				}
 			}
 		}
 	}
+
+	#[test]
+	fn display() {
+		let mut state = State::new(6, 6);
+		state.spawn(Piece::O);
+		state.hard_drop();
+		state.add_garbage(1, 5);
+		state.spawn(Piece::T);
+		let scene = state.scene();
+		let printed = scene.to_string();
+		assert_eq!(scene.rows().count(), printed.lines().count());
+		for (row, (tiles, line)) in scene.rows().zip(printed.lines()).enumerate() {
+			assert_eq!(scene.line(row as i8), tiles);
+			assert_eq!(scene.width() as usize, line.chars().count());
+		}
+		// The player, its ghost, the locked O and the garbage are told apart
+		for c in &['t', '+', 'O', '#', '.'] {
+			assert!(printed.contains(*c), "{:?} missing from\n{}", c, printed);
+		}
+		assert_eq!(TileTy::Field, scene.tile(scene.height() - 1, 0).tile_ty());
+		assert_eq!(scene.line(0)[2], scene.tile(0, 2));
+	}
+
+	#[test]
+	#[should_panic]
+	fn tile_bounds() {
+		Scene::new(6, 6).tile(0, 6);
+	}
 }
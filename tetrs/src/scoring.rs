@@ -0,0 +1,186 @@
+/*!
+Guideline attack table.
+
+Computes the garbage lines sent for a line clear, independent of the game state so it can score clears reported by external clients.
+`ScoreState` tracks the back-to-back chain and the combo across clears:
+
+```
+use tetrs::TspinKind;
+use tetrs::scoring::ScoreState;
+
+let mut score = ScoreState::new();
+assert_eq!(4, score.clear(4, TspinKind::None, false));
+// A T-spin double continues the back-to-back chain of the tetris
+assert_eq!(4 + 1 + 0, score.clear(2, TspinKind::Full, false));
+```
+*/
+
+use ::{ClearInfo, TspinKind, Well};
+
+/// Extra garbage lines sent for consecutive line clears, indexed by the combo counting the first clear as one.
+///
+/// Longer combos send as many lines as the last entry.
+pub const COMBO_BONUS: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Extra garbage lines sent for a clear continuing a back-to-back chain.
+pub const B2B_BONUS: u32 = 1;
+
+/// Extra garbage lines sent for a clear leaving the well empty.
+pub const PERFECT_CLEAR_BONUS: u32 = 10;
+
+/// Returns the garbage lines sent for clearing the given number of lines, without T-spins, combos or bonuses.
+pub fn attack_lines(lines: u8) -> u32 {
+	match lines {
+		0 | 1 => 0,
+		2 => 1,
+		3 => 2,
+		_ => 4,
+	}
+}
+
+/// Returns the garbage lines sent for a line clear.
+///
+/// Singles, doubles, triples and tetrises send 0, 1, 2 and 4 lines, see `attack_lines`.
+/// Full T-spins send 2 lines per cleared line instead, mini T-spins one line less than cleared.
+/// The combo, counting the first clear as one, adds the `COMBO_BONUS`.
+/// A clear continuing a back-to-back chain adds the `B2B_BONUS` and a perfect clear the `PERFECT_CLEAR_BONUS`.
+///
+/// Clearing no lines sends nothing.
+pub fn attack(lines: u8, tspin: TspinKind, combo: u32, b2b: bool, perfect_clear: bool) -> u32 {
+	if lines == 0 {
+		return 0;
+	}
+	let base = match tspin {
+		TspinKind::None => attack_lines(lines),
+		TspinKind::Mini => lines as u32 - 1,
+		TspinKind::Full => 2 * lines as u32,
+	};
+	let combo = COMBO_BONUS[(combo.max(1) as usize - 1).min(COMBO_BONUS.len() - 1)];
+	let b2b = if b2b { B2B_BONUS } else { 0 };
+	let perfect_clear = if perfect_clear { PERFECT_CLEAR_BONUS } else { 0 };
+	base + combo + b2b + perfect_clear
+}
+
+/// Returns if the clear starts or continues a back-to-back chain.
+///
+/// Tetrises and T-spins clearing lines are difficult, other clears break the chain.
+pub fn is_difficult(lines: u8, tspin: TspinKind) -> bool {
+	lines > 0 && (lines >= 4 || tspin != TspinKind::None)
+}
+
+/// Tracks the back-to-back chain and the combo for `attack`.
+///
+/// Feed it every lock, locks clearing no lines end the combo but keep the back-to-back chain.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ScoreState {
+	combo: u32,
+	b2b: bool,
+	attack: u32,
+}
+
+impl ScoreState {
+	/// Creates a new score state, without a combo or back-to-back chain.
+	pub fn new() -> ScoreState {
+		ScoreState::default()
+	}
+	/// Returns the number of consecutive locks clearing lines, zero if the last lock didn't clear any lines.
+	pub fn combo(&self) -> u32 {
+		self.combo
+	}
+	/// Returns if the last difficult clear was not followed by an easy clear, the next difficult clear gets the bonus.
+	pub fn is_b2b(&self) -> bool {
+		self.b2b
+	}
+	/// Returns the total garbage lines sent.
+	pub fn total_attack(&self) -> u32 {
+		self.attack
+	}
+	/// Scores a lock and returns the garbage lines sent.
+	pub fn clear(&mut self, lines: u8, tspin: TspinKind, perfect_clear: bool) -> u32 {
+		if lines == 0 {
+			self.combo = 0;
+			return 0;
+		}
+		self.combo += 1;
+		let difficult = is_difficult(lines, tspin);
+		let attack = attack(lines, tspin, self.combo, difficult && self.b2b, perfect_clear);
+		self.b2b = difficult;
+		self.attack += attack;
+		attack
+	}
+	/// Scores a lock from `State::clear_lines_ex` and returns the garbage lines sent.
+	///
+	/// The well after clearing the lines tells a perfect clear. The combo is tracked by the score state, not taken from the info.
+	pub fn clear_info(&mut self, info: &ClearInfo, well: &Well) -> u32 {
+		let perfect_clear = info.lines > 0 && well.is_empty();
+		self.clear(info.lines, info.tspin, perfect_clear)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use self::TspinKind::*;
+
+	#[test]
+	fn table() {
+		// Lines, T-spin, combo, back-to-back, perfect clear and the attack
+		let table = [
+			(0, None, 0, false, false, 0),
+			(0, Full, 5, true, true, 0),
+			(1, None, 1, false, false, 0),
+			(2, None, 1, false, false, 1),
+			(3, None, 1, false, false, 2),
+			(4, None, 1, false, false, 4),
+			(4, None, 1, true, false, 5),
+			(1, Full, 1, false, false, 2),
+			(2, Full, 1, false, false, 4),
+			(3, Full, 1, false, false, 6),
+			(3, Full, 1, true, false, 7),
+			(1, Mini, 1, false, false, 0),
+			(2, Mini, 1, false, false, 1),
+			(1, None, 3, false, false, 1),
+			(2, None, 5, false, false, 3),
+			(1, None, 12, false, false, 5),
+			(1, None, 100, false, false, 5),
+			(4, None, 1, false, true, 14),
+			(2, None, 2, false, true, 11),
+			(2, Full, 4, true, true, 4 + 1 + 1 + 10),
+		];
+		for &(lines, tspin, combo, b2b, perfect_clear, expected) in &table {
+			assert_eq!(expected, attack(lines, tspin, combo, b2b, perfect_clear), "{} {:?} {} {} {}", lines, tspin, combo, b2b, perfect_clear);
+		}
+	}
+
+	#[test]
+	fn chains() {
+		let mut score = ScoreState::new();
+		// Tetris, T-spin double and tetris in a row, the last two continue the chain and the combo
+		assert_eq!(4, score.clear(4, None, false));
+		assert_eq!(4 + 1, score.clear(2, Full, false));
+		assert_eq!(4 + 1 + 1, score.clear(4, None, false));
+		assert_eq!(3, score.combo());
+		// A lock without clearing ends the combo but keeps the chain
+		assert_eq!(0, score.clear(0, None, false));
+		assert!(score.is_b2b());
+		assert_eq!(4 + 1, score.clear(4, None, false));
+		// An easy clear breaks the chain
+		assert_eq!(0, score.clear(1, None, false));
+		assert!(!score.is_b2b());
+		assert_eq!(4 + 1, score.clear(4, None, false));
+		assert_eq!(4 + 5 + 6 + 5 + 5, score.total_attack());
+	}
+
+	#[test]
+	fn perfect_clear() {
+		use ::State;
+		let mut well = Well::new(10, 22);
+		for row in 0..2 {
+			well.set_line(row, well.line_mask());
+		}
+		let mut state = State::with_well(well);
+		let info = state.clear_lines_ex();
+		let mut score = ScoreState::new();
+		assert_eq!(1 + PERFECT_CLEAR_BONUS, score.clear_info(&info, state.well()));
+	}
+}
@@ -0,0 +1,210 @@
+/*!
+Lock-free snapshots for render threads.
+*/
+
+use ::std::sync::Mutex;
+use ::std::sync::atomic::{fence, AtomicU8, AtomicU64, Ordering};
+use ::std::hint;
+
+#[cfg(feature = "scene")]
+use ::Scene;
+
+// Seqlock protected buffer, the sequence is odd while the buffer is being written
+struct Slot {
+	seq: AtomicU64,
+	published: AtomicU64,
+	data: Box<[AtomicU8]>,
+}
+
+/// Double buffered byte snapshots shared between the game thread and render threads.
+///
+/// The game thread publishes complete snapshots into the buffer not being read from,
+/// render threads copy the latest snapshot without locking and retry if the copy was torn by a concurrent publish.
+///
+/// All accesses to the buffers are atomic, a torn copy is detected and discarded but never undefined behavior.
+pub struct SnapshotBuffer {
+	slots: [Slot; 2],
+	latest: AtomicU64,
+	// Publishers are serialized, readers never take this lock
+	writer: Mutex<()>,
+}
+
+impl SnapshotBuffer {
+	/// Creates a buffer for snapshots of the given length in bytes.
+	pub fn new(len: usize) -> SnapshotBuffer {
+		let slot = || Slot {
+			seq: AtomicU64::new(0),
+			published: AtomicU64::new(0),
+			data: (0..len).map(|_| AtomicU8::new(0)).collect(),
+		};
+		SnapshotBuffer {
+			slots: [slot(), slot()],
+			latest: AtomicU64::new(0),
+			writer: Mutex::new(()),
+		}
+	}
+	/// Creates a buffer for snapshots of scenes with the given dimensions, see `publish_scene`.
+	pub fn for_scene(width: i8, height: i8) -> SnapshotBuffer {
+		SnapshotBuffer::new(width as usize * height as usize)
+	}
+	/// Returns the length of the snapshots in bytes.
+	pub fn len(&self) -> usize {
+		self.slots[0].data.len()
+	}
+	/// Returns if the snapshots are empty.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	/// Returns the sequence number of the latest published snapshot, zero if none was published yet.
+	pub fn sequence(&self) -> u64 {
+		self.latest.load(Ordering::Acquire)
+	}
+	/// Publishes a snapshot, returns its sequence number counting from one.
+	///
+	/// # Panics
+	///
+	/// The data must be exactly `len` bytes.
+	pub fn publish(&self, data: &[u8]) -> u64 {
+		assert_eq!(self.len(), data.len(), "snapshot length");
+		self.publish_bytes(data.iter().cloned())
+	}
+	/// Publishes the tiles of the scene, row-major starting with the top row.
+	///
+	/// # Panics
+	///
+	/// The buffer must be created for the dimensions of the scene, see `for_scene`.
+	#[cfg(feature = "scene")]
+	pub fn publish_scene(&self, scene: &Scene) -> u64 {
+		assert_eq!(self.len(), scene.width() as usize * scene.height() as usize, "snapshot length");
+		self.publish_bytes((0..scene.height()).flat_map(|row| scene.line(row).iter().map(|&tile| u8::from(tile))))
+	}
+	fn publish_bytes<I: Iterator<Item = u8>>(&self, bytes: I) -> u64 {
+		// A publisher panicking mid-write leaves the slot odd, readers skip it until the next publish
+		let _writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+		let sequence = self.latest.load(Ordering::Relaxed) + 1;
+		let slot = &self.slots[(sequence & 1) as usize];
+		let seq = slot.seq.load(Ordering::Relaxed) | 1;
+		slot.seq.store(seq, Ordering::Relaxed);
+		fence(Ordering::Release);
+		slot.published.store(sequence, Ordering::Relaxed);
+		for (cell, byte) in slot.data.iter().zip(bytes) {
+			cell.store(byte, Ordering::Relaxed);
+		}
+		slot.seq.store(seq + 1, Ordering::Release);
+		self.latest.store(sequence, Ordering::Release);
+		sequence
+	}
+	/// Copies the latest published snapshot, returns its sequence number or zero if none was published yet.
+	///
+	/// Never blocks on the publisher, a copy torn by a concurrent publish is retried.
+	///
+	/// # Panics
+	///
+	/// The buffer must hold at least `len` bytes.
+	pub fn snapshot(&self, buf: &mut [u8]) -> u64 {
+		let buf = &mut buf[..self.len()];
+		loop {
+			let latest = self.latest.load(Ordering::Acquire);
+			if latest == 0 {
+				return 0;
+			}
+			let slot = &self.slots[(latest & 1) as usize];
+			let seq = slot.seq.load(Ordering::Acquire);
+			if seq & 1 == 0 {
+				let published = slot.published.load(Ordering::Relaxed);
+				for (byte, cell) in buf.iter_mut().zip(slot.data.iter()) {
+					*byte = cell.load(Ordering::Relaxed);
+				}
+				fence(Ordering::Acquire);
+				if slot.seq.load(Ordering::Relaxed) == seq {
+					return published;
+				}
+			}
+			hint::spin_loop();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::std::sync::Arc;
+	use ::std::sync::atomic::AtomicBool;
+	use ::std::thread;
+
+	// Frame filled from its number with a checksum in the last byte
+	fn frame(number: u64, out: &mut [u8]) {
+		let (last, bytes) = out.split_last_mut().unwrap();
+		for (i, byte) in bytes.iter_mut().enumerate() {
+			*byte = (number.wrapping_mul(0x9E3779B97F4A7C15) >> (i % 8 * 8)) as u8 ^ i as u8;
+		}
+		*last = checksum(bytes);
+	}
+	fn checksum(bytes: &[u8]) -> u8 {
+		bytes.iter().fold(0x5Au8, |sum, &byte| sum.rotate_left(1) ^ byte)
+	}
+
+	#[test]
+	fn hammer() {
+		const LEN: usize = 240;
+		const FRAMES: u64 = 20000;
+		let buffer = Arc::new(SnapshotBuffer::new(LEN));
+		let done = Arc::new(AtomicBool::new(false));
+		assert_eq!(0, buffer.snapshot(&mut [0; LEN]));
+
+		let readers: Vec<_> = (0..2).map(|_| {
+			let buffer = buffer.clone();
+			let done = done.clone();
+			thread::spawn(move || {
+				let mut snapshot = [0; LEN];
+				let mut expected = [0; LEN];
+				let mut last = 0;
+				let mut reads = 0;
+				while !done.load(Ordering::Acquire) {
+					let sequence = buffer.snapshot(&mut snapshot);
+					if sequence == 0 {
+						continue;
+					}
+					// Every snapshot is a complete frame and snapshots never go back in time
+					assert_eq!(checksum(&snapshot[..LEN - 1]), snapshot[LEN - 1]);
+					frame(sequence, &mut expected);
+					assert_eq!(&expected[..], &snapshot[..], "frame {}", sequence);
+					assert!(sequence >= last);
+					last = sequence;
+					reads += 1;
+				}
+				reads
+			})
+		}).collect();
+
+		let mut data = [0; LEN];
+		for number in 1..FRAMES + 1 {
+			frame(number, &mut data);
+			assert_eq!(number, buffer.publish(&data));
+		}
+		done.store(true, Ordering::Release);
+		for reader in readers {
+			assert!(reader.join().unwrap() > 0);
+		}
+		let mut snapshot = [0; LEN];
+		assert_eq!(FRAMES, buffer.snapshot(&mut snapshot));
+		assert_eq!(&data[..], &snapshot[..]);
+	}
+
+	#[cfg(feature = "scene")]
+	#[test]
+	fn scene() {
+		let mut state = ::State::new(10, 8);
+		state.spawn(::Piece::T);
+		state.hard_drop();
+		let scene = state.scene();
+		let buffer = SnapshotBuffer::for_scene(10, 8);
+		assert_eq!(1, buffer.publish_scene(&scene));
+		let mut snapshot = vec![0; buffer.len()];
+		assert_eq!(1, buffer.snapshot(&mut snapshot));
+		for (row, chunk) in snapshot.chunks(10).enumerate() {
+			let tiles: Vec<u8> = scene.line(row as i8).iter().map(|&tile| u8::from(tile)).collect();
+			assert_eq!(tiles, chunk);
+		}
+	}
+}
@@ -15,7 +15,7 @@ pub struct SrsData {
 }
 
 macro_rules! pt {
-	(($x:expr, $y:expr)) => { Point { x: $x, y: $y } };
+	(($x:expr, $y:expr)) => { Point::new($x, $y) };
 }
 
 macro_rules! srs {
@@ -64,7 +64,6 @@ pub static SRS_DATA_I: SrsData = SrsData {
 	},
 };
 
-/*
 /// SRS offsets for the I piece under Arika rules.
 pub static SRS_DATA_ARIKA: SrsData = SrsData {
 	cw: srs! {
@@ -80,7 +79,14 @@ pub static SRS_DATA_ARIKA: SrsData = SrsData {
 		( 0, 0) 	( 2, 0) 	(-1, 0) 	( 2, 1) 	(-1,-2)
 	},
 };
-*/
+
+/// SRS+ offsets for rotating any piece 180 degrees, as popularized by TETR.IO.
+static SRS_DATA_180: [[Point; 6]; 4] = [
+	[pt!(( 0, 0)), pt!(( 0, 1)), pt!(( 1, 1)), pt!((-1, 1)), pt!(( 1, 0)), pt!((-1, 0))],
+	[pt!(( 0, 0)), pt!(( 1, 0)), pt!(( 1, 2)), pt!(( 1, 1)), pt!(( 0, 2)), pt!(( 0, 1))],
+	[pt!(( 0, 0)), pt!(( 0,-1)), pt!((-1,-1)), pt!(( 1,-1)), pt!((-1, 0)), pt!(( 1, 0))],
+	[pt!(( 0, 0)), pt!((-1, 0)), pt!((-1, 2)), pt!((-1, 1)), pt!(( 0, 2)), pt!(( 0, 1))],
+];
 
 pub fn srs_data_cw(piece: Piece, rot: Rot) -> &'static [Point; 5] {
 	let src = if piece == Piece::I { &SRS_DATA_I } else { &SRS_DATA_JLSTZ };
@@ -91,17 +97,53 @@ pub fn srs_data_ccw(piece: Piece, rot: Rot) -> &'static [Point; 5] {
 	&src.ccw[rot as u8 as usize]
 }
 
+/// Wall kicks for rotating 180 degrees, the same for every piece.
+pub fn srs_data_180(_piece: Piece, rot: Rot) -> &'static [Point] {
+	&SRS_DATA_180[rot as u8 as usize]
+}
+
+/// Wall kicks of the Arika rules, the I piece kicks with `SRS_DATA_ARIKA`.
+pub fn arika_data_cw(piece: Piece, rot: Rot) -> &'static [Point; 5] {
+	let src = if piece == Piece::I { &SRS_DATA_ARIKA } else { &SRS_DATA_JLSTZ };
+	&src.cw[rot as u8 as usize]
+}
+pub fn arika_data_ccw(piece: Piece, rot: Rot) -> &'static [Point; 5] {
+	let src = if piece == Piece::I { &SRS_DATA_ARIKA } else { &SRS_DATA_JLSTZ };
+	&src.ccw[rot as u8 as usize]
+}
+
 pub fn srs_cw(well: &Well, player: Player) -> Player {
 	let rotated = player.rotate_cw();
-	let sprite = rotated.sprite();
 	let kicks = srs_data_cw(player.piece, player.rot);
-	well.wall_kick(sprite, kicks, rotated.pt).map(|pt| Player::new(rotated.piece, rotated.rot, pt)).unwrap_or(player)
+	kick(well, player, rotated, kicks)
 }
 pub fn srs_ccw(well: &Well, player: Player) -> Player {
 	let rotated = player.rotate_ccw();
-	let sprite = rotated.sprite();
 	let kicks = srs_data_ccw(player.piece, player.rot);
-	well.wall_kick(sprite, kicks, rotated.pt).map(|pt| Player::new(rotated.piece, rotated.rot, pt)).unwrap_or(player)
+	kick(well, player, rotated, kicks)
+}
+pub fn srs_180(well: &Well, player: Player) -> Player {
+	let rotated = player.rotate_180();
+	let kicks = srs_data_180(player.piece, player.rot);
+	kick(well, player, rotated, kicks)
+}
+/// Rotates the player to the rotated player trying the kicks in order, returns the player unchanged if all kicks collide.
+#[inline]
+pub(crate) fn kick(well: &Well, player: Player, rotated: Player, kicks: &[Point]) -> Player {
+	match well.wall_kick(rotated.sprite(), kicks, rotated.pt) {
+		Some(pt) => {
+			let kicked = Player::new(rotated.piece, rotated.rot, pt);
+			log!({
+				let index = kicks.iter().position(|&offset| rotated.pt + offset == pt).unwrap();
+				::logging::LogRecord::Kick { player: kicked, index: index as u8, offset: kicks[index] }
+			});
+			kicked
+		},
+		None => {
+			log!(::logging::LogRecord::KickFailed { player: player });
+			player
+		},
+	}
 }
 
 #[cfg(test)]
@@ -126,4 +168,55 @@ mod tests {
 		let expected = Player::new(Piece::J, Rot::Left, Point::new(3, 3));
 		assert_eq!(expected, player);
 	}
+
+	#[test]
+	fn kicks_at_extremes() {
+		// Kicks from points at the bounds of the coordinates saturate and collide instead of overflowing
+		let well = Well::new(10, 22);
+		for &x in &[127, 126, -128, -127] {
+			for &y in &[127, -128] {
+				for &piece in &Piece::ALL {
+					for rot in Rot::iter() {
+						let player = Player::new(piece, rot, Point::new(x, y));
+						assert_eq!(player, srs_cw(&well, player));
+						assert_eq!(player, srs_ccw(&well, player));
+						assert_eq!(player, srs_180(&well, player));
+					}
+				}
+				let player = Player::new(Piece::T, Rot::Zero, Point::new(x, y));
+				assert_eq!(x.saturating_sub(1), player.move_left().pt.x);
+				assert_eq!(x.saturating_add(1), player.move_right().pt.x);
+				assert_eq!(y.saturating_sub(1), player.move_down().pt.y);
+			}
+		}
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn rotate_180() {
+		// The T fits a cavity two rows high, turning it needs the 180 kick up
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b1111111111,
+			0b1111000111,
+			0b1111000111,
+			0b1111111111,
+			0b1111111111,
+			0b1111111111,
+			0b1111111111,
+		]);
+		let initial = Player::new(Piece::T, Rot::Zero, Point::new(3, 5));
+		assert!(!::test_player(&well, initial));
+		// No quarter turn fits, so two successive clockwise rotations fail
+		assert_eq!(initial, srs_cw(&well, srs_cw(&well, initial)));
+		assert_eq!(initial, srs_ccw(&well, initial));
+		assert_eq!(Player::new(Piece::T, Rot::Two, Point::new(3, 6)), srs_180(&well, initial));
+		// Same through the state
+		let mut state = ::State::with_well(well);
+		state.set_player(initial);
+		assert!(!state.rotate_cw());
+		assert!(!state.rotate_cw());
+		assert!(state.rotate_180());
+		assert_eq!(Player::new(Piece::T, Rot::Two, Point::new(3, 6)), *state.player().unwrap());
+	}
 }
@@ -0,0 +1,436 @@
+/*!
+Differences between game states.
+*/
+
+use std::{error, fmt};
+
+use super::{State, KickFn, SpawnFn, Locked, MisdropGuard, MoveBudget, SpawnGrace, Stats};
+use ::{Player, Line, Well, ClearKind, ClearEffect};
+#[cfg(feature = "scene")]
+use ::Tile;
+
+/// Well line which changed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LineChange {
+	/// Row counted from the bottom of the well.
+	pub row: i8,
+	pub old: Line,
+	pub new: Line,
+}
+
+/// Scene tile which changed.
+#[cfg(feature = "scene")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TileChange {
+	/// Row counted from the bottom of the well.
+	pub row: i8,
+	pub col: i8,
+	pub old: Tile,
+	pub new: Tile,
+}
+
+/// The states have different well dimensions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DimensionMismatch {
+	pub expected: (i8, i8),
+	pub found: (i8, i8),
+}
+
+impl fmt::Display for DimensionMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "expected a {}x{} well, found {}x{}", self.expected.0, self.expected.1, self.found.0, self.found.1)
+	}
+}
+impl error::Error for DimensionMismatch {}
+
+/// Difference between an older and a newer game state.
+///
+/// Covers the well, the scene, the player and every setting and counter tracked by the state.
+/// Applying the diff to the older state reproduces the newer state exactly.
+#[derive(Clone, Debug)]
+pub struct StateDiff {
+	dimensions: (i8, i8),
+	lines: Vec<LineChange>,
+	#[cfg(feature = "scene")]
+	tiles: Vec<TileChange>,
+	player: Option<(Option<Player>, Option<Player>)>,
+	guard: Option<(Option<MisdropGuard>, Option<MisdropGuard>)>,
+	intended: Option<(Option<Player>, Option<Player>)>,
+	confirm: Option<(i32, i32)>,
+	budget: Option<(Option<MoveBudget>, Option<MoveBudget>)>,
+	piece_ticks: Option<(u32, u32)>,
+	last_piece_ticks: Option<(u32, u32)>,
+	clock_used: Option<(u64, u64)>,
+	timed_out: Option<(bool, bool)>,
+	topped_out: Option<(bool, bool)>,
+	blocked_out: Option<(bool, bool)>,
+	locked_out: Option<(bool, bool)>,
+	top_out_check: Option<(bool, bool)>,
+	out_of_pieces: Option<(bool, bool)>,
+	rules: Option<(KickFn, KickFn, KickFn, SpawnFn)>,
+	hard_drop_lock: Option<(bool, bool)>,
+	last_clear: Option<(ClearKind, ClearKind)>,
+	rotated: Option<(Option<u8>, Option<u8>)>,
+	last_locked: Option<(Option<Locked>, Option<Locked>)>,
+	combo: Option<(u32, u32)>,
+	effect_rng: Option<(u32, u32)>,
+	last_effect: Option<(Option<ClearEffect>, Option<ClearEffect>)>,
+	spawn_grace: Option<(SpawnGrace, SpawnGrace)>,
+	spawn_nudge: Option<(u8, u8)>,
+	lock_delay: Option<(u32, u32)>,
+	lock_timer: Option<(u32, u32)>,
+	lock_resets: Option<(u8, u8)>,
+	sequence: Option<(u64, u64)>,
+	contact: Option<(bool, bool)>,
+	stats: Option<(Stats, Stats)>,
+}
+
+fn change<T: Copy + PartialEq>(old: T, new: T) -> Option<(T, T)> {
+	if old != new { Some((old, new)) } else { None }
+}
+
+impl State {
+	/// Computes the difference from this state to the newer state.
+	///
+	/// Both states must have the same well dimensions.
+	pub fn diff(&self, newer: &State) -> Result<StateDiff, DimensionMismatch> {
+		let (width, height) = self.dimensions();
+		if newer.dimensions() != (width, height) {
+			return Err(DimensionMismatch { expected: (width, height), found: newer.dimensions() });
+		}
+		let lines = (0..height).filter_map(|row| {
+			let (old, new) = (self.well.line(row), newer.well.line(row));
+			if old != new { Some(LineChange { row: row, old: old, new: new }) } else { None }
+		}).collect();
+		#[cfg(feature = "scene")]
+		let tiles = {
+			let mut tiles = Vec::new();
+			for row in 0..height {
+				for col in 0..width {
+					let (old, new) = (self.scene.tile(height - 1 - row, col), newer.scene.tile(height - 1 - row, col));
+					if old != new {
+						tiles.push(TileChange { row: row, col: col, old: old, new: new });
+					}
+				}
+			}
+			tiles
+		};
+		// Function pointers are compared by address, equal rules may still show up as a change
+		let same_rules = self.kicks_cw as usize == newer.kicks_cw as usize && self.kicks_ccw as usize == newer.kicks_ccw as usize &&
+			self.kicks_180 as usize == newer.kicks_180 as usize && self.spawner as usize == newer.spawner as usize;
+		Ok(StateDiff {
+			dimensions: (width, height),
+			lines: lines,
+			#[cfg(feature = "scene")]
+			tiles: tiles,
+			player: change(self.player, newer.player),
+			guard: change(self.guard, newer.guard),
+			intended: change(self.intended, newer.intended),
+			confirm: change(self.confirm, newer.confirm),
+			budget: change(self.budget, newer.budget),
+			piece_ticks: change(self.piece_ticks, newer.piece_ticks),
+			last_piece_ticks: change(self.last_piece_ticks, newer.last_piece_ticks),
+			clock_used: change(self.clock_used, newer.clock_used),
+			timed_out: change(self.timed_out, newer.timed_out),
+			topped_out: change(self.topped_out, newer.topped_out),
+			blocked_out: change(self.blocked_out, newer.blocked_out),
+			locked_out: change(self.locked_out, newer.locked_out),
+			top_out_check: change(self.top_out_check, newer.top_out_check),
+			out_of_pieces: change(self.out_of_pieces, newer.out_of_pieces),
+			rules: if same_rules { None } else { Some((newer.kicks_cw, newer.kicks_ccw, newer.kicks_180, newer.spawner)) },
+			hard_drop_lock: change(self.hard_drop_lock, newer.hard_drop_lock),
+			last_clear: change(self.last_clear, newer.last_clear),
+			rotated: change(self.rotated, newer.rotated),
+			last_locked: change(self.last_locked, newer.last_locked),
+			combo: change(self.combo, newer.combo),
+			effect_rng: change(self.effect_rng, newer.effect_rng),
+			last_effect: change(self.last_effect, newer.last_effect),
+			spawn_grace: change(self.spawn_grace, newer.spawn_grace),
+			spawn_nudge: change(self.spawn_nudge, newer.spawn_nudge),
+			lock_delay: change(self.lock_delay, newer.lock_delay),
+			lock_timer: change(self.lock_timer, newer.lock_timer),
+			lock_resets: change(self.lock_resets, newer.lock_resets),
+			sequence: change(self.sequence, newer.sequence),
+			contact: change(self.contact, newer.contact),
+			stats: change(self.stats, newer.stats),
+		})
+	}
+}
+
+/// States are equal if the diff between them is empty.
+///
+/// The recorded events, occurrences and replays are not compared, the rules are compared like in `diff`.
+impl PartialEq for State {
+	fn eq(&self, other: &State) -> bool {
+		self.diff(other).map(|diff| diff.is_empty()).unwrap_or(false)
+	}
+}
+
+impl StateDiff {
+	/// Returns the well dimensions of the compared states.
+	pub fn dimensions(&self) -> (i8, i8) {
+		self.dimensions
+	}
+	/// Returns the changed well lines from bottom to top.
+	pub fn lines(&self) -> &[LineChange] {
+		&self.lines
+	}
+	/// Returns the changed scene tiles from bottom to top.
+	#[cfg(feature = "scene")]
+	pub fn tiles(&self) -> &[TileChange] {
+		&self.tiles
+	}
+	/// Returns the old and new player if it changed.
+	pub fn player(&self) -> Option<(Option<Player>, Option<Player>)> {
+		self.player
+	}
+	/// Returns the old and new line clear effect if it changed.
+	pub fn clear_effect(&self) -> Option<(Option<ClearEffect>, Option<ClearEffect>)> {
+		self.last_effect
+	}
+	/// Returns if the states are identical.
+	pub fn is_empty(&self) -> bool {
+		#[cfg(feature = "scene")]
+		{
+			if !self.tiles.is_empty() {
+				return false;
+			}
+		}
+		self.lines.is_empty() && self.player.is_none() && self.guard.is_none() && self.intended.is_none() &&
+		self.confirm.is_none() && self.budget.is_none() && self.piece_ticks.is_none() && self.last_piece_ticks.is_none() &&
+		self.clock_used.is_none() && self.timed_out.is_none() && self.topped_out.is_none() &&
+		self.blocked_out.is_none() && self.locked_out.is_none() && self.top_out_check.is_none() && self.out_of_pieces.is_none() && self.rules.is_none() && self.hard_drop_lock.is_none() &&
+		self.last_clear.is_none() && self.rotated.is_none() && self.last_locked.is_none() && self.combo.is_none() &&
+		self.effect_rng.is_none() && self.last_effect.is_none() && self.spawn_grace.is_none() && self.spawn_nudge.is_none() &&
+		self.lock_delay.is_none() && self.lock_timer.is_none() && self.lock_resets.is_none() &&
+		self.sequence.is_none() && self.contact.is_none() && self.stats.is_none()
+	}
+	/// Applies the diff to the older state, turning it into the newer state.
+	///
+	/// Only the changed parts are written, applying the diff to any other state with the same dimensions overwrites those parts.
+	pub fn apply(&self, state: &mut State) -> Result<(), DimensionMismatch> {
+		if state.dimensions() != self.dimensions {
+			return Err(DimensionMismatch { expected: self.dimensions, found: state.dimensions() });
+		}
+		for change in &self.lines {
+			state.well.set_line(change.row, change.new);
+		}
+		#[cfg(feature = "scene")]
+		for change in &self.tiles {
+			state.scene.set_tile(change.row, change.col, change.new);
+		}
+		if let Some((_, player)) = self.player { state.player = player; }
+		if let Some((_, guard)) = self.guard { state.guard = guard; }
+		if let Some((_, intended)) = self.intended { state.intended = intended; }
+		if let Some((_, confirm)) = self.confirm { state.confirm = confirm; }
+		if let Some((_, budget)) = self.budget { state.budget = budget; }
+		if let Some((_, ticks)) = self.piece_ticks { state.piece_ticks = ticks; }
+		if let Some((_, ticks)) = self.last_piece_ticks { state.last_piece_ticks = ticks; }
+		if let Some((_, used)) = self.clock_used { state.clock_used = used; }
+		if let Some((_, timed_out)) = self.timed_out { state.timed_out = timed_out; }
+		if let Some((_, topped_out)) = self.topped_out { state.topped_out = topped_out; }
+		if let Some((_, blocked_out)) = self.blocked_out { state.blocked_out = blocked_out; }
+		if let Some((_, locked_out)) = self.locked_out { state.locked_out = locked_out; }
+		if let Some((_, check)) = self.top_out_check { state.top_out_check = check; }
+		if let Some((_, out_of_pieces)) = self.out_of_pieces { state.out_of_pieces = out_of_pieces; }
+		if let Some((cw, ccw, flip, spawner)) = self.rules {
+			state.kicks_cw = cw;
+			state.kicks_ccw = ccw;
+			state.kicks_180 = flip;
+			state.spawner = spawner;
+		}
+		if let Some((_, lock)) = self.hard_drop_lock { state.hard_drop_lock = lock; }
+		if let Some((_, kind)) = self.last_clear { state.last_clear = kind; }
+		if let Some((_, rotated)) = self.rotated { state.rotated = rotated; }
+		if let Some((_, locked)) = self.last_locked { state.last_locked = locked; }
+		if let Some((_, combo)) = self.combo { state.combo = combo; }
+		if let Some((_, rng)) = self.effect_rng { state.effect_rng = rng; }
+		if let Some((_, effect)) = self.last_effect { state.last_effect = effect; }
+		if let Some((_, grace)) = self.spawn_grace { state.spawn_grace = grace; }
+		if let Some((_, nudge)) = self.spawn_nudge { state.spawn_nudge = nudge; }
+		if let Some((_, delay)) = self.lock_delay { state.lock_delay = delay; }
+		if let Some((_, timer)) = self.lock_timer { state.lock_timer = timer; }
+		if let Some((_, resets)) = self.lock_resets { state.lock_resets = resets; }
+		if let Some((_, sequence)) = self.sequence { state.sequence = sequence; }
+		if let Some((_, contact)) = self.contact { state.contact = contact; }
+		if let Some((_, stats)) = self.stats { state.stats = stats; }
+		Ok(())
+	}
+}
+
+/// Lists every change on its own line, the well lines drawn as `#` for blocks and `.` for empty cells.
+impl fmt::Display for StateDiff {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let cols = Well::new(self.dimensions.0, self.dimensions.1).col_range();
+		let draw = |f: &mut fmt::Formatter, line: Line| {
+			for col in cols.clone() {
+				f.write_str(if line & col != 0 { "#" } else { "." })?;
+			}
+			Ok(())
+		};
+		for change in &self.lines {
+			write!(f, "line {}: ", change.row)?;
+			draw(f, change.old)?;
+			f.write_str(" -> ")?;
+			draw(f, change.new)?;
+			f.write_str("\n")?;
+		}
+		#[cfg(feature = "scene")]
+		{
+			if !self.tiles.is_empty() {
+				writeln!(f, "tiles: {} changed", self.tiles.len())?;
+			}
+		}
+		macro_rules! field {
+			($name:ident) => {
+				if let Some((old, new)) = self.$name {
+					writeln!(f, concat!(stringify!($name), ": {:?} -> {:?}"), old, new)?;
+				}
+			};
+		}
+		field!(player);
+		field!(guard);
+		field!(intended);
+		field!(confirm);
+		field!(budget);
+		field!(piece_ticks);
+		field!(last_piece_ticks);
+		field!(clock_used);
+		field!(timed_out);
+		field!(topped_out);
+		field!(blocked_out);
+		field!(locked_out);
+		field!(top_out_check);
+		field!(out_of_pieces);
+		if self.rules.is_some() {
+			writeln!(f, "rules: changed")?;
+		}
+		field!(hard_drop_lock);
+		field!(last_clear);
+		field!(rotated);
+		field!(last_locked);
+		field!(combo);
+		field!(effect_rng);
+		field!(last_effect);
+		field!(spawn_grace);
+		field!(spawn_nudge);
+		field!(lock_delay);
+		field!(lock_timer);
+		field!(lock_resets);
+		field!(sequence);
+		field!(contact);
+		field!(stats);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::rand::Rng;
+	use ::{Piece, Point, Rot, ClockExpiry, KidsRules, TheRules};
+	use ::garbage::xorshift;
+
+	// Plays a random step touching as many parts of the state as possible
+	fn step<R: Rng>(state: &mut State, rng: &mut R) {
+		if state.is_game_over() {
+			*state = State::standard();
+		}
+		if state.player().is_none() {
+			state.clear_lines(|_| ());
+			if rng.gen_range(0, 100) == 0 {
+				state.finish();
+			}
+			else {
+				state.spawn(Piece::ALL[rng.gen_range(0, Piece::count())]);
+			}
+			return;
+		}
+		match rng.gen_range(0, 14) {
+			0 => { state.move_left(); },
+			1 => { state.move_right(); },
+			2 => { state.rotate_cw(); },
+			3 => { state.rotate_ccw(); },
+			4 | 5 => { state.soft_drop(); },
+			6 => { state.hard_drop(); },
+			7 => { state.guarded_hard_drop(); },
+			8 => { state.add_garbage(rng.gen_range(1, 3), rng.gen_range(0, 10)); },
+			9 => {
+				state.tick_misdrop_guard();
+				state.tick_lock_delay();
+			},
+			10 => state.tick_move_clock(),
+			11 => {
+				let guard = MisdropGuard { max_distance: Some(rng.gen_range(0, 3)), new_holes: None, confirm_ticks: 5 };
+				state.set_misdrop_guard(if rng.gen() { Some(guard) } else { None });
+				let intended = Player::new(Piece::ALL[rng.gen_range(0, Piece::count())], Rot::Zero, Point::new(rng.gen_range(0, 7), 1));
+				state.set_intended(Some(intended));
+			},
+			12 => {
+				let expiry = if rng.gen() { ClockExpiry::HardDrop } else { ClockExpiry::GameOver };
+				state.set_move_budget(Some(MoveBudget { total: rng.gen_range(0, 500), expiry: expiry }));
+				state.set_hard_drop_lock(rng.gen());
+				state.set_spawn_grace(if rng.gen() { SpawnGrace::ShiftUpOnce } else { SpawnGrace::Strict });
+				state.set_lock_delay(rng.gen_range(0, 3));
+			},
+			_ => if rng.gen() { state.set_rules::<KidsRules>() } else { state.set_rules::<TheRules>() },
+		}
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut rng = xorshift(7);
+		let mut state = State::standard();
+		for &gap in &[1, 10, 100] {
+			for _ in 0..50 {
+				let mut older = state.clone();
+				for _ in 0..gap {
+					step(&mut state, &mut rng);
+				}
+				let diff = older.diff(&state).unwrap();
+				diff.apply(&mut older).unwrap();
+				assert_eq!(format!("{:?}", state), format!("{:?}", older), "{}", diff);
+				assert!(older.diff(&state).unwrap().is_empty());
+			}
+		}
+	}
+
+	#[test]
+	fn identical() {
+		let mut state = State::standard();
+		state.spawn(Piece::T);
+		let diff = state.diff(&state.clone()).unwrap();
+		assert!(diff.is_empty());
+		assert_eq!("", diff.to_string());
+	}
+
+	#[test]
+	fn display() {
+		let older = State::new(4, 8);
+		let mut newer = older.clone();
+		newer.spawn(Piece::O);
+		newer.hard_drop();
+		let diff = older.diff(&newer).unwrap();
+		assert_eq!(2, diff.lines().len());
+		let mut expected = String::from("line 0: .... -> .##.\nline 1: .... -> .##.\n");
+		if cfg!(feature = "scene") {
+			expected.push_str("tiles: 4 changed\n");
+		}
+		let locked = Player::new(Piece::O, Rot::Zero, Point::new(0, 2));
+		expected.push_str(&format!("last_locked: None -> {:?}\n", Some((locked, None::<u8>))));
+		// Spawned, made contact and locked
+		expected.push_str("sequence: 0 -> 3\n");
+		let stats = Stats { spawned: [1, 0, 0, 0, 0, 0, 0], locked: 1, hard_drop_rows: 6, ..Stats::default() };
+		expected.push_str(&format!("stats: {:?} -> {:?}\n", Stats::default(), stats));
+		assert_eq!(expected, diff.to_string());
+	}
+
+	#[test]
+	fn dimension_mismatch() {
+		let narrow = State::new(8, 22);
+		let mut standard = State::standard();
+		assert_eq!(DimensionMismatch { expected: (10, 22), found: (8, 22) }, standard.diff(&narrow).unwrap_err());
+		let diff = narrow.diff(&narrow).unwrap();
+		assert_eq!(DimensionMismatch { expected: (8, 22), found: (10, 22) }, diff.apply(&mut standard).unwrap_err());
+	}
+}
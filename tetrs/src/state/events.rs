@@ -0,0 +1,326 @@
+/*!
+Gameplay events recorded by the state.
+*/
+
+use ::std::{error, fmt};
+
+use super::State;
+use ::{Player, ClearedRows, test_player};
+
+/// Occurrence while the state is played, record them with `State::record_occurrences`.
+///
+/// Finer grained than the events, for timing feedback such as sounds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Occurrence {
+	/// A player spawned.
+	Spawned(Player),
+	/// The player came to rest on the stack or the floor.
+	ContactMade(Player),
+	/// The lock delay of the resting player started counting.
+	LockDelayStarted,
+	/// Moving or rotating the resting player restarted the lock delay.
+	LockDelayReset,
+	/// The player locked.
+	Locked(Player),
+	/// Lines were cleared.
+	Cleared(ClearedRows),
+	/// The move clock ran out and the game was lost, the player is taken out of play, see `ClockExpiry::GameOver`.
+	TimedOut(Player),
+	/// Moving the player was rejected, for a bump sound when pushing against an obstruction.
+	///
+	/// Only moves by `State::move_left`, `State::move_right` and `State::soft_drop` are reported, gravity is not.
+	/// `Input` reports a held move once per obstruction instead of on every auto-repeat.
+	MoveRejected { dir: Dir, cause: MoveRejectCause },
+}
+
+/// Direction of a player move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Dir {
+	Left,
+	Right,
+	Down,
+}
+
+/// Why a player move was rejected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MoveRejectCause {
+	/// The walls or the floor of the well are in the way.
+	Wall,
+	/// The stack is in the way.
+	Stack,
+	/// There is no player to move.
+	NoPlayer,
+}
+
+/// Gameplay event, record them with `State::record_events`.
+///
+/// The events hold everything needed to rebuild the state with `State::replay_events`,
+/// without the bag or the input timing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+	/// A player spawned at the given pose, after any spawn grace nudge.
+	Spawned(Player),
+	/// The player locked at the given pose, with the wall kick index of its last rotation.
+	Locked { player: Player, kick: Option<u8> },
+	/// Lines were checked after a lock or cleared, with the cleared rows.
+	LinesCleared(ClearedRows),
+	/// Garbage lines were inserted with a hole in the given column, with the player after being pushed up.
+	GarbageReceived { count: u8, hole: i8, player: Option<Player> },
+	/// The bag ran out of pieces, see `State::finish`.
+	OutOfPieces,
+}
+
+/// Inconsistent event found by `State::replay_events`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EventReplayError {
+	/// The lock at the given index is not of the spawned piece.
+	NoPlayer { index: usize },
+	/// The player at the given index overlaps with the well.
+	Overlap { index: usize },
+	/// The rows cleared at the given index are not the full rows of the well.
+	NotFull { index: usize },
+	/// The garbage hole at the given index is outside the well.
+	BadHole { index: usize },
+}
+impl EventReplayError {
+	/// Returns the index of the offending event.
+	pub fn index(self) -> usize {
+		match self {
+			EventReplayError::NoPlayer { index } |
+			EventReplayError::Overlap { index } |
+			EventReplayError::NotFull { index } |
+			EventReplayError::BadHole { index } => index,
+		}
+	}
+}
+impl fmt::Display for EventReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let what = match *self {
+			EventReplayError::NoPlayer { .. } => "locks a piece which was not spawned",
+			EventReplayError::Overlap { .. } => "overlaps with the well",
+			EventReplayError::NotFull { .. } => "clears rows which are not full",
+			EventReplayError::BadHole { .. } => "has a garbage hole outside the well",
+		};
+		write!(f, "event {} {}", self.index(), what)
+	}
+}
+impl error::Error for EventReplayError {}
+
+impl State {
+	/// Starts or stops recording events, stopping drops the events not yet taken.
+	pub fn record_events(&mut self, record: bool) {
+		self.events = if record { Some(self.events.take().unwrap_or_default()) } else { None };
+	}
+	/// Takes the events recorded so far.
+	pub fn take_events(&mut self) -> Vec<Event> {
+		self.take_sequenced_events().into_iter().map(|(_, event)| event).collect()
+	}
+	/// Takes the events recorded so far with their sequence numbers.
+	///
+	/// Events and occurrences are numbered in the order they happen, an event shares its number with the matching occurrence.
+	pub fn take_sequenced_events(&mut self) -> Vec<(u64, Event)> {
+		self.events.as_mut().map(::std::mem::take).unwrap_or_default()
+	}
+	/// Starts or stops recording occurrences, stopping drops the occurrences not yet taken.
+	pub fn record_occurrences(&mut self, record: bool) {
+		self.occurrences = if record { Some(self.occurrences.take().unwrap_or_default()) } else { None };
+	}
+	/// Takes the occurrences recorded so far with their sequence numbers.
+	pub fn take_occurrences(&mut self) -> Vec<(u64, Occurrence)> {
+		self.occurrences.as_mut().map(::std::mem::take).unwrap_or_default()
+	}
+	// Numbers and records the event and the occurrence
+	pub(super) fn emit(&mut self, event: Option<Event>, occurrence: Option<Occurrence>) {
+		self.sequence += 1;
+		let sequence = self.sequence;
+		if let (Some(events), Some(event)) = (self.events.as_mut(), event) {
+			events.push((sequence, event));
+		}
+		if let (Some(occurrences), Some(occurrence)) = (self.occurrences.as_mut(), occurrence) {
+			occurrences.push((sequence, occurrence));
+		}
+	}
+	/// Rebuilds a state of the given dimensions from its recorded events.
+	///
+	/// The events are applied mechanically: spawns place the piece, locks etch it at the recorded pose,
+	/// clears remove the recorded rows and garbage is inserted with the recorded hole.
+	/// The player's moves between spawning and locking are not part of the events, the player is only up to date at locks.
+	///
+	/// Settings such as the rules or the spawn grace are not restored.
+	pub fn replay_events(events: &[Event], dims: (i8, i8)) -> Result<State, EventReplayError> {
+		let mut state = State::new(dims.0, dims.1);
+		for (index, event) in events.iter().enumerate() {
+			match *event {
+				Event::Spawned(player) => {
+					// Only a piece which blocked out spawns overlapping the stack
+					if test_player(&state.well, player) {
+						state.blocked_out = true;
+					}
+					state.set_player(player);
+				},
+				Event::Locked { player, kick } => {
+					if state.player.map(|spawned| spawned.piece) != Some(player.piece) {
+						return Err(EventReplayError::NoPlayer { index: index });
+					}
+					if test_player(&state.well, player) {
+						return Err(EventReplayError::Overlap { index: index });
+					}
+					state.player = Some(player);
+					state.rotated = kick;
+					state.lock_player();
+				},
+				Event::LinesCleared(rows) => {
+					if ClearedRows::find(&state.well) != rows {
+						return Err(EventReplayError::NotFull { index: index });
+					}
+					state.clear_lines(|_| ());
+				},
+				Event::GarbageReceived { count, hole, player } => {
+					if hole < 0 || hole >= state.well.width() {
+						return Err(EventReplayError::BadHole { index: index });
+					}
+					// The recorded player already accounts for being pushed up
+					let had_player = state.player.take().is_some();
+					state.add_garbage(count, hole);
+					if let Some(player) = player {
+						if test_player(&state.well, player) {
+							return Err(EventReplayError::Overlap { index: index });
+						}
+					}
+					else if had_player {
+						state.topped_out = true;
+					}
+					state.player = player;
+				},
+				Event::OutOfPieces => {
+					state.finish();
+				},
+			}
+		}
+		Ok(state)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::Piece;
+
+	// Plays a game one piece per step and compares the replay at every lock boundary
+	fn play<F: FnMut(&mut State, u32) -> bool>(dims: (i8, i8), mut step: F) -> Vec<Event> {
+		let mut state = State::new(dims.0, dims.1);
+		state.record_events(true);
+		let mut events = Vec::new();
+		let mut locks = 0;
+		while step(&mut state, locks) {
+			locks += 1;
+			events.extend(state.take_events());
+			let replayed = State::replay_events(&events, dims).unwrap();
+			assert_eq!(state.checksum(), replayed.checksum(), "lock {}", locks);
+			assert_eq!(state.is_game_over(), replayed.is_game_over());
+		}
+		assert!(locks > 10);
+		events
+	}
+
+	fn scripted() -> Vec<Event> {
+		// Opens by clearing the bottom line
+		let opener = [(Piece::I, -3i8), (Piece::I, 1), (Piece::O, 4)];
+		let pieces = [Piece::I, Piece::O, Piece::T, Piece::L, Piece::J, Piece::I, Piece::S];
+		play((10, 16), |state, i| {
+			if state.is_game_over() || i >= 40 {
+				return false;
+			}
+			if let Some(&(piece, shift)) = opener.get(i as usize) {
+				state.spawn(piece);
+				for _ in 0..shift.abs() {
+					if shift < 0 { state.move_left() } else { state.move_right() };
+				}
+				state.hard_drop();
+				assert_eq!((i == 2) as i32, state.clear_lines(|_| ()));
+				return true;
+			}
+			state.spawn(pieces[i as usize % pieces.len()]);
+			if i % 2 == 0 {
+				state.rotate_cw();
+			}
+			for _ in 0..i % 5 {
+				if i % 3 == 0 { state.move_left() } else { state.move_right() };
+			}
+			// Garbage rising into a player in play
+			if i % 4 == 3 {
+				state.soft_drop();
+				state.add_garbage(1, (i % 10) as i8);
+			}
+			state.hard_drop();
+			state.clear_lines(|_| ());
+			true
+		})
+	}
+
+	#[test]
+	fn replay_scripted() {
+		let events = scripted();
+		assert!(events.iter().any(|event| matches!(*event, Event::GarbageReceived { .. })));
+	}
+
+	#[cfg(feature = "bot")]
+	#[test]
+	fn replay_bot() {
+		use ::{Bag, OfficialBag, PlayI, Weights};
+		let mut bag = OfficialBag::from_seed(4);
+		let events = play((10, 16), |state, i| {
+			if state.is_game_over() || i >= 100 || state.spawn(bag.next(state.well()).unwrap()).is_blocked() {
+				return false;
+			}
+			let bot = PlayI::play(&Weights::default(), state.well(), *state.player().unwrap());
+			match bot.player {
+				Some(player) => state.set_player(player),
+				None => return false,
+			}
+			state.lock();
+			state.clear_lines(|_| ());
+			if i % 9 == 8 {
+				state.add_garbage(2, (i % 10) as i8);
+			}
+			true
+		});
+		assert!(events.iter().any(|event| matches!(*event, Event::LinesCleared(rows) if !rows.is_empty())));
+	}
+
+	#[test]
+	fn tampered() {
+		let events = scripted();
+		let dims = (10, 16);
+
+		let index = events.iter().position(|event| matches!(*event, Event::Locked { .. })).unwrap();
+		let mut tampered = events.clone();
+		if let Event::Locked { ref mut player, .. } = tampered[index] {
+			player.pt.y = -1;
+		}
+		assert_eq!(Err(EventReplayError::Overlap { index: index }), State::replay_events(&tampered, dims).map(|_| ()));
+
+		let index = events.iter().position(|event| matches!(*event, Event::LinesCleared(rows) if !rows.is_empty())).unwrap();
+		let mut tampered = events.clone();
+		tampered[index] = Event::LinesCleared(ClearedRows::default());
+		assert_eq!(Err(EventReplayError::NotFull { index: index }), State::replay_events(&tampered, dims).map(|_| ()));
+
+		let index = events.iter().position(|event| matches!(*event, Event::GarbageReceived { .. })).unwrap();
+		let mut tampered = events.clone();
+		if let Event::GarbageReceived { ref mut hole, .. } = tampered[index] {
+			*hole = 10;
+		}
+		assert_eq!(10, EventReplayError::BadHole { index: 10 }.index());
+		assert_eq!(Err(EventReplayError::BadHole { index: index }), State::replay_events(&tampered, dims).map(|_| ()));
+
+		// Dropping a spawn leaves the lock without a player
+		let index = events.iter().position(|event| matches!(*event, Event::Spawned(_))).unwrap();
+		let mut tampered = events.clone();
+		tampered.remove(index);
+		assert_eq!(Err(EventReplayError::NoPlayer { index: index }), State::replay_events(&tampered, dims).map(|_| ()));
+	}
+}
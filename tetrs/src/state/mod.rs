@@ -0,0 +1,1932 @@
+mod diff;
+pub use self::diff::{StateDiff, LineChange, DimensionMismatch};
+mod events;
+pub use self::events::{Event, EventReplayError, Occurrence, Dir, MoveRejectCause};
+mod stats;
+pub use self::stats::Stats;
+mod save;
+pub use self::save::ParseStateError;
+#[cfg(feature = "scene")]
+pub use self::diff::TileChange;
+
+use ::std::{cmp, error, fmt};
+
+use ::{Action, PacedReplay, Bag, Player, Well, Piece, Rot, Point, Play, spawn_player, CoordError, Rules, ClearKind, ClearedRows, ClearInfo, ClearEffect, Replay, ReplayAction, srs_data_cw, srs_data_ccw, srs_data_180, STANDARD_WIDTH, STANDARD_HEIGHT};
+use ::srs::kick;
+use ::analysis::{IllegalReason, explain_illegal};
+use ::pt::coord_within;
+
+// Kick table lookup of the rules in use
+type KickFn = fn(Piece, Rot) -> &'static [Point];
+// Spawn position lookup of the rules in use
+type SpawnFn = fn(Piece, &Well) -> Player;
+// Locked player with the wall kick index of its last rotation
+type Locked = (Player, Option<u8>);
+#[cfg(feature = "scene")]
+use ::{Scene, SceneView, TileTy, TILE_BG0, TILE_GARBAGE, MAX_WIDTH};
+
+// The scene is indexed with the well's coordinates, a mismatch silently corrupts the scene.
+#[cfg(feature = "scene")]
+macro_rules! debug_assert_dims {
+	($state:expr) => {
+		debug_assert_eq!(
+			($state.well.width(), $state.well.height()),
+			($state.scene.width(), $state.scene.height()),
+			"well and scene dimensions disagree");
+	};
+}
+#[cfg(not(feature = "scene"))]
+macro_rules! debug_assert_dims {
+	($state:expr) => {};
+}
+
+/// Misdrop protection settings.
+///
+/// When an intended placement is registered with `State::set_intended`, `State::guarded_hard_drop` asks for confirmation
+/// before locking the player somewhere else than intended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MisdropGuard {
+	/// Asks for confirmation when landing further than this many cells away from the intended placement.
+	///
+	/// The distance is measured between the occupied cells as the sum of the horizontal and vertical distance.
+	/// Landing with a different shape than intended always asks for confirmation, a rotation occupying the same cells does not.
+	pub max_distance: Option<i32>,
+	/// Asks for confirmation when the drop creates at least this many new holes.
+	pub new_holes: Option<i32>,
+	/// Number of ticks in which a second hard drop confirms the drop.
+	pub confirm_ticks: i32,
+}
+
+/// Result of a guarded hard drop.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DropStatus {
+	/// The player was dropped and locked.
+	Locked,
+	/// The player was dropped without locking, see `State::set_hard_drop_lock`.
+	Dropped,
+	/// The drop differs from the intended placement, hard drop again to confirm.
+	NeedsConfirmation,
+	/// There is no player.
+	NoPlayer,
+}
+
+/// Result of dropping the player one row.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Step {
+	/// The player moved down one row.
+	Moved,
+	/// The player rests on the stack or the floor and stays in play.
+	Landed,
+	/// There is no player.
+	NoPlayer,
+}
+
+/// Outcome of an action in a `StepSummary`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StepOutcome {
+	/// The action was performed.
+	Applied,
+	/// The action failed, see `State::step`.
+	Rejected,
+	/// The action came after the rejected action and was not tried.
+	Skipped,
+}
+
+/// Outcomes of the actions performed by `State::run_steps`, in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StepSummary {
+	pub outcomes: Vec<(Action, StepOutcome)>,
+}
+impl StepSummary {
+	/// Returns the number of actions performed.
+	pub fn applied(&self) -> usize {
+		self.count(StepOutcome::Applied)
+	}
+	/// Returns the action which failed, `None` if every action was performed.
+	pub fn rejected(&self) -> Option<Action> {
+		self.outcomes.iter().find(|&&(_, outcome)| outcome == StepOutcome::Rejected).map(|&(action, _)| action)
+	}
+	/// Returns the number of actions after the rejected action.
+	pub fn skipped(&self) -> usize {
+		self.count(StepOutcome::Skipped)
+	}
+	/// Returns if every action was performed.
+	pub fn is_complete(&self) -> bool {
+		self.rejected().is_none()
+	}
+	fn count(&self, outcome: StepOutcome) -> usize {
+		self.outcomes.iter().filter(|&&(_, o)| o == outcome).count()
+	}
+}
+
+/// Result of spawning a piece.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpawnResult {
+	/// The piece spawned in free space, possibly shifted up by the spawn grace.
+	Ok,
+	/// The piece blocked out, it overlaps with the stack at its spawn position and every position the spawn grace tried.
+	Blocked,
+}
+impl SpawnResult {
+	/// Returns `true` if the piece blocked out.
+	pub fn is_blocked(self) -> bool {
+		self == SpawnResult::Blocked
+	}
+}
+
+/// A live player is in the way of spawning a new piece with `State::try_spawn`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SpawnError {
+	pub player: Player,
+}
+
+impl fmt::Display for SpawnError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "cannot spawn over the live {} piece", self.player.piece)
+	}
+}
+impl error::Error for SpawnError {}
+
+/// How the game ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum GameEnd {
+	/// The stack reached the top two rows of the well, see `State::set_top_out_check`,
+	/// or garbage pushed a block or the player out of the well.
+	TopOut,
+	/// A new piece overlapped the stack where it spawned, see `State::spawn`.
+	BlockOut,
+	/// A piece locked entirely within the top two rows of the well, above the visible field.
+	LockOut,
+	/// The move clock budget ran out.
+	TimedOut,
+	/// The bag ran out of pieces, the game finished normally with the score standing.
+	OutOfPieces,
+}
+
+/// What happens when the move clock budget is exhausted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClockExpiry {
+	/// The current player is hard dropped and locked, as is every further player on its first tick.
+	///
+	/// The player locks even if hard drops don't lock, see `State::set_hard_drop_lock`.
+	HardDrop,
+	/// The game is lost, reported with `Occurrence::TimedOut`.
+	GameOver,
+}
+
+/// What happens when a new piece spawns overlapping the stack.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SpawnGrace {
+	/// The piece is blocked out right away.
+	#[default]
+	Strict,
+	/// The piece is shifted up one row if that clears the stack.
+	ShiftUpOnce,
+	/// The piece is shifted up one row at a time, at most the given number of rows.
+	ShiftUpN(u8),
+}
+
+/// Move clock budget for the whole game.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MoveBudget {
+	/// Total number of ticks the players may be in play.
+	pub total: u64,
+	/// What happens once the budget is exceeded.
+	pub expiry: ClockExpiry,
+}
+
+/// Game state of player and well.
+#[derive(Clone, Debug)]
+pub struct State {
+	player: Option<Player>,
+	well: Well,
+	#[cfg(feature = "scene")]
+	scene: Scene,
+	guard: Option<MisdropGuard>,
+	intended: Option<Player>,
+	confirm: i32,
+	budget: Option<MoveBudget>,
+	piece_ticks: u32,
+	last_piece_ticks: u32,
+	clock_used: u64,
+	timed_out: bool,
+	topped_out: bool,
+	blocked_out: bool,
+	locked_out: bool,
+	top_out_check: bool,
+	out_of_pieces: bool,
+	kicks_cw: KickFn,
+	kicks_ccw: KickFn,
+	kicks_180: KickFn,
+	spawner: SpawnFn,
+	hard_drop_lock: bool,
+	last_clear: ClearKind,
+	rotated: Option<u8>,
+	last_locked: Option<Locked>,
+	combo: u32,
+	effect_rng: u32,
+	last_effect: Option<ClearEffect>,
+	spawn_grace: SpawnGrace,
+	spawn_nudge: u8,
+	lock_delay: u32,
+	lock_timer: u32,
+	lock_resets: u8,
+	events: Option<Vec<(u64, Event)>>,
+	occurrences: Option<Vec<(u64, Occurrence)>>,
+	sequence: u64,
+	contact: bool,
+	stats: Stats,
+	replay: Option<Replay>,
+}
+
+/// Number of times moving or rotating a resting player restarts the lock delay.
+pub const MAX_LOCK_RESETS: u8 = 15;
+
+// Effect stream of new states, xorshift must not be seeded with zero
+const EFFECT_SEED: u32 = 0x9E3779B9;
+
+impl State {
+	/// Creates a new game state.
+	///
+	/// Don't forget to spawn a player!
+	pub fn new(width: i8, height: i8) -> State {
+		State::with_well(Well::new(width, height))
+	}
+	/// Creates a new game state with the standard well dimensions.
+	pub fn standard() -> State {
+		State::new(STANDARD_WIDTH, STANDARD_HEIGHT)
+	}
+	/// Creates a new game state from existing well.
+	///
+	/// The scene shows the blocks already in the well, see `Scene::from_well`.
+	pub fn with_well(well: Well) -> State {
+		let state = State {
+			player: None,
+			#[cfg(feature = "scene")]
+			scene: Scene::from_well(&well),
+			well: well,
+			guard: None,
+			intended: None,
+			confirm: 0,
+			budget: None,
+			piece_ticks: 0,
+			last_piece_ticks: 0,
+			clock_used: 0,
+			timed_out: false,
+			topped_out: false,
+			blocked_out: false,
+			locked_out: false,
+			top_out_check: true,
+			out_of_pieces: false,
+			kicks_cw: |piece, rot| &srs_data_cw(piece, rot)[..],
+			kicks_ccw: |piece, rot| &srs_data_ccw(piece, rot)[..],
+			kicks_180: srs_data_180,
+			spawner: |piece, well| spawn_player(well, piece),
+			hard_drop_lock: true,
+			last_clear: ClearKind::None,
+			rotated: None,
+			last_locked: None,
+			combo: 0,
+			effect_rng: EFFECT_SEED,
+			last_effect: None,
+			spawn_grace: SpawnGrace::Strict,
+			spawn_nudge: 0,
+			lock_delay: 0,
+			lock_timer: 0,
+			lock_resets: 0,
+			events: None,
+			occurrences: None,
+			sequence: 0,
+			contact: false,
+			stats: Stats::default(),
+			replay: None,
+		};
+		debug_assert_dims!(state);
+		state
+	}
+	/// Spawns and rotates the player with the spawn position and the wall kicks of the rules `R`, the standard SRS rules by default.
+	///
+	/// The pieces keep the standard sprites, see `Player::sprite`.
+	pub fn set_rules<R: Rules + Default>(&mut self) {
+		self.kicks_cw = |piece, rot| R::default().rotate_cw_kicks(piece, rot);
+		self.kicks_ccw = |piece, rot| R::default().rotate_ccw_kicks(piece, rot);
+		self.kicks_180 = |piece, rot| R::default().rotate_180_kicks(piece, rot);
+		self.spawner = |piece, well| R::default().spawn(piece, well);
+	}
+	/// Sets the number of ticks a player rests on the stack before it locks, zero by default.
+	///
+	/// When zero a player locks as soon as `gravity_lock` or `soft_drop_lock` is blocked, otherwise `tick_lock_delay` locks it once the delay runs out.
+	/// Moving or rotating a resting player restarts the delay, at most `MAX_LOCK_RESETS` times per player.
+	/// Hard drops lock right away regardless.
+	pub fn set_lock_delay(&mut self, ticks: u32) {
+		self.lock_delay = ticks;
+	}
+	/// Advances the lock delay by one tick, locks the player if it rested on the stack for the whole delay.
+	///
+	/// Returns `true` if the player was locked.
+	pub fn tick_lock_delay(&mut self) -> bool {
+		let player = match self.player { Some(pl) => pl, None => return false };
+		if self.lock_delay == 0 || !test_player(&self.well, player.move_down()) {
+			self.lock_timer = 0;
+			return false;
+		}
+		self.make_contact(player);
+		self.lock_timer += 1;
+		if self.lock_timer == 1 {
+			self.emit(None, Some(Occurrence::LockDelayStarted));
+		}
+		if self.lock_timer < self.lock_delay {
+			return false;
+		}
+		self.record(ReplayAction::Lock);
+		self.lock_player();
+		log!(::logging::LogRecord::Lock { player: player, reason: ::logging::LockReason::Stuck });
+		true
+	}
+	// Moving or rotating during the lock delay restarts it
+	fn reset_lock_delay(&mut self) {
+		if self.lock_timer > 0 && self.lock_resets < MAX_LOCK_RESETS {
+			self.lock_timer = 0;
+			self.lock_resets += 1;
+			self.emit(None, Some(Occurrence::LockDelayReset));
+		}
+	}
+	// The player rests on the stack, reported once until it drops again
+	fn make_contact(&mut self, player: Player) {
+		if !self.contact {
+			self.contact = true;
+			self.emit(None, Some(Occurrence::ContactMade(player)));
+		}
+	}
+	/// Sets whether hard drops lock the player, enabled by default.
+	///
+	/// When disabled a hard drop moves the player to the bottom and leaves locking to gravity, allowing final adjustments.
+	pub fn set_hard_drop_lock(&mut self, lock: bool) {
+		self.hard_drop_lock = lock;
+	}
+	/// Sets whether any block in the top two rows of the well tops out, enabled by default.
+	///
+	/// When disabled the stack may reach into the top two rows as with the guideline rules,
+	/// the game still ends when a piece blocks out or locks out.
+	pub fn set_top_out_check(&mut self, check: bool) {
+		self.top_out_check = check;
+	}
+	/// Sets what happens when a new piece spawns overlapping the stack, strict by default.
+	pub fn set_spawn_grace(&mut self, grace: SpawnGrace) {
+		self.spawn_grace = grace;
+	}
+	/// Returns the number of rows the last spawned piece was shifted up by the spawn grace.
+	pub fn spawn_nudge(&self) -> u8 {
+		self.spawn_nudge
+	}
+	/// Returns the width and height of the well.
+	pub fn dimensions(&self) -> (i8, i8) {
+		(self.well.width(), self.well.height())
+	}
+	/// Returns the current player.
+	pub fn player(&self) -> Option<&Player> {
+		self.player.as_ref()
+	}
+	/// Sets the current player.
+	pub fn set_player(&mut self, player: Player) {
+		self.record(ReplayAction::SetPlayer(player));
+		self.rotated = None;
+		self.contact = false;
+		self.player = Some(player)
+	}
+	/// Sets the current player if it fits in the well, the error explains why it doesn't.
+	///
+	/// See `analysis::explain_illegal`, the player may float or be unreachable.
+	pub fn try_set_player(&mut self, player: Player) -> Result<(), IllegalReason> {
+		match explain_illegal(&self.well, player) {
+			Some(reason) => Err(reason),
+			None => {
+				self.set_player(player);
+				Ok(())
+			},
+		}
+	}
+	/// Returns where the player lands when hard dropped, `None` if no player.
+	pub fn ghost(&self) -> Option<Player> {
+		self.player.map(|player| trace_down(&self.well, player))
+	}
+	/// Returns the well.
+	pub fn well(&self) -> &Well {
+		&self.well
+	}
+	/// Moves the player one block to the left.
+	///
+	/// Does nothing and returns `false` if no player or no space to move left.
+	pub fn move_left(&mut self) -> bool {
+		self.shift(Dir::Left, true)
+	}
+	/// Moves the player one block to the right.
+	///
+	/// Does nothing and returns `false` if no player or no space to move right.
+	pub fn move_right(&mut self) -> bool {
+		self.shift(Dir::Right, true)
+	}
+	/// Returns why moving the player in the direction is rejected, `None` if the player can move.
+	///
+	/// The cause is worked out from the player's blocks at the destination:
+	/// any block outside the well hits a wall or the floor, otherwise the stack is in the way.
+	pub fn move_rejection(&self, dir: Dir) -> Option<MoveRejectCause> {
+		let player = match self.player { Some(pl) => pl, None => return Some(MoveRejectCause::NoPlayer) };
+		let next = match dir {
+			Dir::Left => player.move_left(),
+			Dir::Right => player.move_right(),
+			Dir::Down => player.move_down(),
+		};
+		if !test_player(&self.well, next) {
+			return None;
+		}
+		let sprite = next.sprite();
+		for y in 0..4 {
+			for x in 0..4 {
+				if sprite.pix[y as usize] >> (3 - x) & 1 != 0 {
+					let (col, row) = (next.pt.x + x, next.pt.y - y);
+					if col < 0 || col >= self.well.width() || row < 0 {
+						return Some(MoveRejectCause::Wall);
+					}
+				}
+			}
+		}
+		Some(MoveRejectCause::Stack)
+	}
+	// Moves the player sideways, reports a rejected move as an occurrence if requested
+	pub(crate) fn shift(&mut self, dir: Dir, report: bool) -> bool {
+		self.record(ReplayAction::Play(if dir == Dir::Left { Play::MoveLeft } else { Play::MoveRight }));
+		if let Some(cause) = self.move_rejection(dir) {
+			if report {
+				self.emit(None, Some(Occurrence::MoveRejected { dir: dir, cause: cause }));
+			}
+			return false;
+		}
+		let player = self.player.unwrap();
+		self.player = Some(if dir == Dir::Left { player.move_left() } else { player.move_right() });
+		self.rotated = None;
+		self.reset_lock_delay();
+		true
+	}
+	/// Rotates the player clockwise.
+	///
+	/// Does nothing and returns `false` if no player or no space to rotate clockwise.
+	///
+	/// If there's not enough space a wall kick is attempted.
+	pub fn rotate_cw(&mut self) -> bool {
+		self.record(ReplayAction::Play(Play::RotateCW));
+		let player = match self.player { Some(pl) => pl, None => return false };
+		let kicks = (self.kicks_cw)(player.piece, player.rot);
+		let rotated = player.rotate_cw();
+		let next = kick(&self.well, player, rotated, kicks);
+		self.player = Some(next);
+		if player == next {
+			return false;
+		}
+		self.rotated = kicks.iter().position(|&offset| rotated.pt + offset == next.pt).map(|index| index as u8);
+		self.reset_lock_delay();
+		true
+	}
+	/// Rotates the player counter-clockwise.
+	///
+	/// Does nothing and returns `false` if no player or no space to rotate counter-clockwise.
+	///
+	/// If there's not enough space a wall kick is attempted.
+	pub fn rotate_ccw(&mut self) -> bool {
+		self.record(ReplayAction::Play(Play::RotateCCW));
+		let player = match self.player { Some(pl) => pl, None => return false };
+		let kicks = (self.kicks_ccw)(player.piece, player.rot);
+		let rotated = player.rotate_ccw();
+		let next = kick(&self.well, player, rotated, kicks);
+		self.player = Some(next);
+		if player == next {
+			return false;
+		}
+		self.rotated = kicks.iter().position(|&offset| rotated.pt + offset == next.pt).map(|index| index as u8);
+		self.reset_lock_delay();
+		true
+	}
+	/// Rotates the player 180 degrees.
+	///
+	/// Does nothing and returns `false` if no player or no space to rotate 180 degrees.
+	///
+	/// If there's not enough space a wall kick is attempted.
+	pub fn rotate_180(&mut self) -> bool {
+		self.record(ReplayAction::Play(Play::Rotate180));
+		let player = match self.player { Some(pl) => pl, None => return false };
+		let kicks = (self.kicks_180)(player.piece, player.rot);
+		let rotated = player.rotate_180();
+		let next = kick(&self.well, player, rotated, kicks);
+		self.player = Some(next);
+		if player == next {
+			return false;
+		}
+		self.rotated = kicks.iter().position(|&offset| rotated.pt + offset == next.pt).map(|index| index as u8);
+		self.reset_lock_delay();
+		true
+	}
+	/// Drops the player down one block.
+	///
+	/// A player which can't drop down lands and stays in play, it is locked with `lock`, by the lock delay or by `soft_drop_lock`.
+	pub fn soft_drop(&mut self) -> Step {
+		self.soft_drop_step(true)
+	}
+	// Soft drops the player, reports a rejected drop as an occurrence if requested
+	pub(crate) fn soft_drop_step(&mut self, report: bool) -> Step {
+		self.record(ReplayAction::Play(Play::SoftDrop));
+		let step = self.drop(report);
+		if step == Step::Moved {
+			self.stats.soft_drop_rows += 1;
+		}
+		step
+	}
+	/// Drops the player down one block like `soft_drop`, locks the player if it landed unless a lock delay is set.
+	///
+	/// Returns `true` if the player moved down. Follows a path of the bot where the last soft drop locks the player.
+	pub fn soft_drop_lock(&mut self) -> bool {
+		let step = self.soft_drop();
+		self.lock_landed(step)
+	}
+	// Drops the player, reports a rejected drop as an occurrence if requested
+	fn drop(&mut self, report: bool) -> Step {
+		if report {
+			if let Some(cause) = self.move_rejection(Dir::Down) {
+				self.emit(None, Some(Occurrence::MoveRejected { dir: Dir::Down, cause: cause }));
+			}
+		}
+		let player = match self.player { Some(pl) => pl, None => return Step::NoPlayer };
+		let next = player.move_down();
+		if !test_player(&self.well, next) {
+			self.player = Some(next);
+			self.rotated = None;
+			self.contact = false;
+			Step::Moved
+		}
+		else {
+			self.make_contact(player);
+			Step::Landed
+		}
+	}
+	// Without a lock delay a landed player locks right away
+	fn lock_landed(&mut self, step: Step) -> bool {
+		if step == Step::Landed && self.lock_delay == 0 {
+			if self.player.is_none() {
+				return false;
+			}
+			log!(::logging::LogRecord::Lock { player: self.player.unwrap(), reason: ::logging::LockReason::Stuck });
+			self.record(ReplayAction::Lock);
+			self.lock_player();
+		}
+		step == Step::Moved
+	}
+	/// Drops and locks the player all the way down.
+	///
+	/// Returns `false` if no player.
+	pub fn hard_drop(&mut self) -> bool {
+		self.record(ReplayAction::Play(Play::HardDrop));
+		if let Some(player) = self.ghost() {
+			if let Some(from) = self.player {
+				self.stats.hard_drop_rows += (from.pt.y - player.pt.y) as u32;
+			}
+			if self.player != Some(player) {
+				self.rotated = None;
+				self.contact = false;
+			}
+			self.player = Some(player);
+			self.make_contact(player);
+			if !self.hard_drop_lock {
+				return true;
+			}
+			self.lock_player();
+			log!(::logging::LogRecord::Lock { player: player, reason: ::logging::LockReason::HardDrop });
+			true
+		}
+		else {
+			false
+		}
+	}
+	/// Drops the player all the way down without locking it, also known as a sonic drop.
+	///
+	/// The player stays live and can still slide or rotate until gravity or the lock delay locks it.
+	/// Returns `false` if no player.
+	pub fn firm_drop(&mut self) -> bool {
+		self.record(ReplayAction::Play(Play::FirmDrop));
+		if let Some(player) = self.ghost() {
+			if self.player != Some(player) {
+				self.rotated = None;
+				self.contact = false;
+			}
+			self.player = Some(player);
+			self.make_contact(player);
+			true
+		}
+		else {
+			false
+		}
+	}
+	/// Performs the engine action.
+	///
+	/// Returns `false` if the action failed: no player, no space to move or rotate, or a soft drop which landed.
+	/// Locking a player always succeeds.
+	pub fn step(&mut self, action: Action) -> bool {
+		match action {
+			Action::MoveLeft => self.move_left(),
+			Action::MoveRight => self.move_right(),
+			Action::RotateCW => self.rotate_cw(),
+			Action::RotateCCW => self.rotate_ccw(),
+			Action::Rotate180 => self.rotate_180(),
+			Action::SoftDrop => self.soft_drop() == Step::Moved,
+			Action::SonicDrop => self.firm_drop(),
+			Action::HardDrop => self.hard_drop(),
+			Action::Lock => {
+				let live = self.player.is_some();
+				self.lock();
+				live
+			},
+		}
+	}
+	/// Performs the actions in order until one fails.
+	///
+	/// The actions before the failed action stay performed, the actions after it are not tried and reported as skipped.
+	/// The actions are all taken from the iterator, it must end.
+	pub fn run_steps<I: IntoIterator<Item = Action>>(&mut self, actions: I) -> StepSummary {
+		let mut summary = StepSummary::default();
+		let mut rejected = false;
+		for action in actions {
+			let outcome = if rejected {
+				StepOutcome::Skipped
+			}
+			else if self.step(action) {
+				StepOutcome::Applied
+			}
+			else {
+				rejected = true;
+				StepOutcome::Rejected
+			};
+			summary.outcomes.push((action, outcome));
+		}
+		summary
+	}
+	/// Performs the actions the paced replay has due this tick.
+	///
+	/// Returns the number of actions performed. A soft drop landing the player is not rejected, the bot's paths end by landing the player.
+	/// Any other action which fails stops the replay without performing the actions after it, the rejected action is returned to re-plan.
+	pub fn drive_suggestion<A: AsRef<[Action]>>(&mut self, replay: &mut PacedReplay<A>) -> Result<usize, Action> {
+		let start = replay.actions().len() - replay.remaining();
+		let count = replay.tick().len();
+		for i in start..start + count {
+			let action = replay.actions()[i];
+			let done = match action {
+				Action::SoftDrop => self.soft_drop() != Step::NoPlayer,
+				action => self.step(action),
+			};
+			if !done {
+				replay.stop();
+				return Err(action);
+			}
+		}
+		Ok(count)
+	}
+	/// Enables or disables misdrop protection, disabled by default.
+	pub fn set_misdrop_guard(&mut self, guard: Option<MisdropGuard>) {
+		self.guard = guard;
+		self.confirm = 0;
+	}
+	/// Registers the intended placement for the current player, eg. the bot's suggestion.
+	///
+	/// The intended placement is forgotten when the player locks.
+	pub fn set_intended(&mut self, intended: Option<Player>) {
+		self.intended = intended;
+		self.confirm = 0;
+	}
+	/// Hard drops the player with misdrop protection.
+	///
+	/// Without a misdrop guard or an intended placement this is the same as `hard_drop`.
+	/// Otherwise if the drop differs from the intended placement no drop happens and confirmation is needed:
+	/// another guarded hard drop within the confirmation window locks the player.
+	pub fn guarded_hard_drop(&mut self) -> DropStatus {
+		let player = match self.player { Some(pl) => pl, None => return DropStatus::NoPlayer };
+		if let (Some(guard), Some(intended)) = (self.guard, self.intended) {
+			if self.confirm <= 0 && is_misdrop(&self.well, &guard, trace_down(&self.well, player), intended) {
+				self.confirm = guard.confirm_ticks;
+				return DropStatus::NeedsConfirmation;
+			}
+		}
+		self.hard_drop();
+		if self.hard_drop_lock { DropStatus::Locked } else { DropStatus::Dropped }
+	}
+	/// Advances the misdrop confirmation window by one tick.
+	pub fn tick_misdrop_guard(&mut self) {
+		if self.confirm > 0 {
+			self.confirm -= 1;
+		}
+	}
+	/// Sets the move clock budget, unlimited by default.
+	pub fn set_move_budget(&mut self, budget: Option<MoveBudget>) {
+		self.budget = budget;
+	}
+	/// Returns the move clock budget.
+	pub fn move_budget(&self) -> Option<MoveBudget> {
+		self.budget
+	}
+	/// Returns the ticks the current player has been in play.
+	pub fn piece_ticks(&self) -> u32 {
+		self.piece_ticks
+	}
+	/// Returns the ticks the last locked player was in play.
+	pub fn last_piece_ticks(&self) -> u32 {
+		self.last_piece_ticks
+	}
+	/// Returns the ticks used on the move clock over the whole game.
+	pub fn move_clock_used(&self) -> u64 {
+		self.clock_used
+	}
+	/// Returns `true` if the game was lost by exceeding the move clock budget.
+	pub fn is_timed_out(&self) -> bool {
+		self.timed_out
+	}
+	/// Advances the move clock by one tick.
+	///
+	/// Only ticks with a player in play count, the time between locking and the next spawn (entry delay, line clear delay)
+	/// does not count against the clock. The budget expires on the first tick exceeding it.
+	pub fn tick_move_clock(&mut self) {
+		if let Some(replay) = self.replay.as_mut() {
+			replay.tick();
+		}
+		if self.player.is_none() || self.timed_out {
+			return;
+		}
+		self.piece_ticks += 1;
+		self.clock_used += 1;
+		if let Some(budget) = self.budget {
+			if self.clock_used > budget.total {
+				match budget.expiry {
+					ClockExpiry::HardDrop => {
+						self.hard_drop();
+						self.lock();
+					},
+					ClockExpiry::GameOver => {
+						self.timed_out = true;
+						if let Some(player) = self.player.take() {
+							self.emit(None, Some(Occurrence::TimedOut(player)));
+						}
+					},
+				}
+			}
+		}
+	}
+	/// Applies gravity to the player.
+	///
+	/// Drops the player like `soft_drop` without reporting a rejected drop, a landed player stays in play.
+	pub fn gravity(&mut self) -> Step {
+		self.record(ReplayAction::Gravity);
+		let step = self.drop(false);
+		if step != Step::NoPlayer {
+			self.stats.gravity_ticks += 1;
+		}
+		step
+	}
+	/// Applies gravity like `gravity`, locks the player if it landed unless a lock delay is set.
+	///
+	/// Returns `true` if the player moved down.
+	pub fn gravity_lock(&mut self) -> bool {
+		let step = self.gravity();
+		self.lock_landed(step)
+	}
+	/// Check for line clears.
+	///
+	/// The callback is called for every cleared line with the row being cleared from bottom to top.
+	pub fn clear_lines<F>(&mut self, mut f: F) -> i32 where F: FnMut(i32) {
+		self.record(ReplayAction::ClearLines);
+		let rows = ClearedRows::find(&self.well);
+		for row in rows.iter() {
+			log!(::logging::LogRecord::Clear { row: row });
+			f(row as i32);
+		}
+		// Checks after a lock count towards the combo even without clearing lines
+		if self.last_locked.is_some() || !rows.is_empty() {
+			let occurrence = if rows.is_empty() { None } else { Some(Occurrence::Cleared(rows)) };
+			self.emit(Some(Event::LinesCleared(rows)), occurrence);
+		}
+		self.update_clear(rows);
+		match rows.len() {
+			1 => self.stats.singles += 1,
+			2 => self.stats.doubles += 1,
+			3 => self.stats.triples += 1,
+			4 => self.stats.tetrises += 1,
+			_ => (),
+		}
+		// Remove from the top down so the lower rows keep their index
+		#[cfg(feature = "scene")]
+		for row in rows.iter().rev() {
+			self.scene.remove_line(row);
+		}
+		self.well.clear_full_lines() as i32
+	}
+	/// Check for line clears with the info needed for scoring.
+	///
+	/// Call after every lock, the T-spin is classified for the last locked player and the combo counts consecutive locks clearing lines.
+	/// Both this and `clear_lines` advance the combo once per lock.
+	pub fn clear_lines_ex(&mut self) -> ClearInfo {
+		let tspin = match self.last_locked {
+			Some((player, rotated)) => ::clear::tspin_kind(&self.well, player, rotated),
+			None => ::TspinKind::None,
+		};
+		let lines = self.clear_lines(|_| ()) as u8;
+		ClearInfo {
+			lines: lines,
+			tspin: tspin,
+			combo: self.combo,
+			perfect_clear: lines > 0 && self.well.is_empty(),
+			effect: if lines > 0 { self.last_effect } else { None },
+		}
+	}
+	/// Returns the number of consecutive locks clearing lines, zero if the last lock didn't clear any lines.
+	pub fn combo(&self) -> u32 {
+		self.combo
+	}
+	/// Returns the game statistics.
+	pub fn stats(&self) -> &Stats {
+		&self.stats
+	}
+	/// Resets the game statistics, eg. when starting a new level.
+	pub fn reset_stats(&mut self) {
+		self.stats = Stats::default();
+	}
+	/// Starts or stops recording a replay, starting records the current well and player as the initial state.
+	///
+	/// Stopping drops the replay not yet taken.
+	pub fn record_replay(&mut self, record: bool) {
+		self.replay = if record { Some(self.replay.take().unwrap_or_else(|| Replay::new(self.well, self.player))) } else { None };
+	}
+	/// Takes the replay recorded so far and stops recording.
+	pub fn take_replay(&mut self) -> Option<Replay> {
+		self.replay.take()
+	}
+	fn record(&mut self, action: ReplayAction) {
+		if let Some(replay) = self.replay.as_mut() {
+			replay.push(action);
+		}
+	}
+	/// Seeds the effect stream, see `EffectSeed`.
+	///
+	/// The effect stream is separate from any gameplay randomness, effects never change how a game plays out.
+	pub fn set_effect_seed(&mut self, seed: u32) {
+		self.effect_rng = if seed ^ EFFECT_SEED != 0 { seed ^ EFFECT_SEED } else { EFFECT_SEED };
+	}
+	/// Returns the effect data of the last check clearing lines after a lock.
+	///
+	/// Reset when a lock doesn't clear any lines.
+	pub fn last_clear_effect(&self) -> Option<ClearEffect> {
+		self.last_effect
+	}
+	// Only the first check after a lock counts towards the combo
+	fn update_clear(&mut self, rows: ClearedRows) {
+		let locked = self.last_locked.take();
+		if locked.is_some() {
+			self.combo = if rows.is_empty() { 0 } else { self.combo + 1 };
+		}
+		if !rows.is_empty() {
+			let seed = ::clear::next_effect_seed(&mut self.effect_rng);
+			self.last_effect = Some(ClearEffect::new(seed, rows, locked.map(|(player, _)| player)));
+		}
+		else if locked.is_some() {
+			self.last_effect = None;
+		}
+	}
+	/// Checksum of the well, the player and the combo for comparing games.
+	///
+	/// The effect stream and effect data are left out, they don't affect gameplay and frontends may choose not to replicate them.
+	pub fn checksum(&self) -> u64 {
+		// FNV-1a
+		let mut hash = 0xcbf29ce484222325u64;
+		{
+			let mut write = |byte: u8| hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+			write(self.well.width() as u8);
+			write(self.well.height() as u8);
+			for &line in self.well.lines() {
+				for &byte in &line.to_be_bytes() {
+					write(byte);
+				}
+			}
+			match self.player {
+				Some(player) => {
+					write(1);
+					write(player.piece as u8);
+					write(player.rot as u8);
+					write(player.pt.x as u8);
+					write(player.pt.y as u8);
+				},
+				None => write(0),
+			}
+			for &byte in &[self.combo >> 24, self.combo >> 16, self.combo >> 8, self.combo] {
+				write(byte as u8);
+			}
+		}
+		hash
+	}
+	/// Returns the kind of line clear of the last locked player.
+	///
+	/// Classified when the player locks, before the lines are cleared with `clear_lines`.
+	pub fn last_clear_kind(&self) -> ClearKind {
+		self.last_clear
+	}
+	/// Inserts garbage lines at the bottom of the well with a hole in the given column.
+	///
+	/// The stack is pushed up and the garbage is drawn with `TILE_GARBAGE` in the scene.
+	/// The player is pushed up as far as needed to clear the garbage, at most `count` rows.
+	/// The game is over if a block is pushed out of the top of the well or the player can't be pushed up without its lowest row leaving the well.
+	///
+	/// Returns the number of lines which fit, no further lines are inserted once a block is pushed out of the top of the well.
+	///
+	/// # Panics
+	///
+	/// The hole must be a column of the well, see `try_add_garbage` for external input.
+	pub fn add_garbage(&mut self, count: u8, hole: i8) -> u8 {
+		self.record(ReplayAction::Garbage { count: count, hole: hole });
+		let col_mask = self.well.col_range().nth(hole as usize).unwrap();
+		let line = self.well.line_mask() & !col_mask;
+		let mut inserted = 0;
+		let mut fit = 0;
+		while inserted < count {
+			let bumped = self.well.insert_line(0, line);
+			#[cfg(feature = "scene")]
+			{
+				self.scene.insert_line(0, [TILE_BG0; MAX_WIDTH]);
+				self.scene.set_row_from_line(0, line, TILE_GARBAGE);
+			}
+			inserted += 1;
+			if bumped != 0 {
+				self.topped_out = true;
+				break;
+			}
+			fit += 1;
+		}
+		if let Some(player) = self.player {
+			let height = self.well.height();
+			let bottom = lowest_row(player);
+			let pushed = (0..inserted as i8 + 1)
+				.map(|dy| Player::new(player.piece, player.rot, player.pt.offset(0, dy)))
+				.take_while(|pushed| pushed.pt.y - bottom < height)
+				.find(|&pushed| !test_player(&self.well, pushed));
+			match pushed {
+				Some(pushed) => self.player = Some(pushed),
+				None => {
+					self.player = None;
+					self.topped_out = true;
+				},
+			}
+		}
+		let player = self.player;
+		self.emit(Some(Event::GarbageReceived { count: count, hole: hole, player: player }), None);
+		fit
+	}
+	/// Inserts garbage lines like `add_garbage` with the hole column range-checked.
+	pub fn try_add_garbage(&mut self, count: u8, hole: i32) -> Result<u8, CoordError> {
+		let hole = coord_within(hole, self.well.width())?;
+		Ok(self.add_garbage(count, hole))
+	}
+	/// Etch the player to the well and kill it.
+	pub fn lock(&mut self) {
+		if self.player.is_none() {
+			return;
+		}
+		log!(::logging::LogRecord::Lock { player: self.player.unwrap(), reason: ::logging::LockReason::Manual });
+		self.record(ReplayAction::Lock);
+		self.lock_player();
+	}
+	fn lock_player(&mut self) {
+		if let Some(pl) = self.player {
+			let kick = self.rotated;
+			self.emit(Some(Event::Locked { player: pl, kick: kick }), Some(Occurrence::Locked(pl)));
+			self.contact = false;
+			self.last_clear = ::clear::resolve(&self.well, pl).clear_kind;
+			self.last_locked = Some((pl, self.rotated));
+			self.rotated = None;
+			if pl.pt.y - lowest_row(pl) >= self.well.height() - 2 {
+				self.locked_out = true;
+			}
+			self.well.etch(pl.sprite(), pl.pt);
+			#[cfg(feature = "scene")]
+			self.scene.draw(pl, TileTy::Field);
+			self.stats.locked += 1;
+			self.player = None;
+			self.intended = None;
+			self.confirm = 0;
+			self.last_piece_ticks = self.piece_ticks;
+			self.piece_ticks = 0;
+			self.lock_timer = 0;
+			self.lock_resets = 0;
+		}
+	}
+	/// Spawns a new player with the given piece.
+	///
+	/// The rules decide the spawning location and rotation, by default at the top of the well, centered horizontally with zero rotation.
+	/// If it overlaps with a block in the well the spawn grace shifts the piece up, see `set_spawn_grace`.
+	/// The piece may stick out of the top of the well but its lowest row must stay inside.
+	///
+	/// The piece blocks out if every attempt overlaps with a block in the well,
+	/// the player is then left overlapping the stack at the unshifted spawn position and the game ends with `GameEnd::BlockOut`.
+	///
+	/// Any live player is replaced, see `try_spawn` to catch spawning twice.
+	pub fn spawn(&mut self, piece: Piece) -> SpawnResult {
+		self.record(ReplayAction::Spawn(piece));
+		self.piece_ticks = 0;
+		self.contact = false;
+		self.rotated = None;
+		self.spawn_nudge = 0;
+		self.lock_timer = 0;
+		self.lock_resets = 0;
+		let player = (self.spawner)(piece, &self.well);
+		self.player = Some(player);
+		self.stats.spawned[piece as usize] += 1;
+		let attempts = match self.spawn_grace {
+			SpawnGrace::Strict => 0,
+			SpawnGrace::ShiftUpOnce => 1,
+			SpawnGrace::ShiftUpN(n) => n,
+		};
+		// Nudging further than the height of the well only pushes the piece out of the top
+		let attempts = cmp::min(attempts, self.well.height() as u8) as i8;
+		let bottom = lowest_row(player);
+		for nudge in 0..attempts + 1 {
+			let shifted = Player::new(piece, player.rot, player.pt.offset(0, nudge));
+			if shifted.pt.y - bottom >= self.well.height() {
+				break;
+			}
+			if !test_player(&self.well, shifted) {
+				self.player = Some(shifted);
+				self.spawn_nudge = nudge as u8;
+				log!(::logging::LogRecord::Spawn { player: shifted, nudge: nudge as u8 });
+				self.emit(Some(Event::Spawned(shifted)), Some(Occurrence::Spawned(shifted)));
+				return SpawnResult::Ok;
+			}
+		}
+		self.blocked_out = true;
+		self.emit(Some(Event::Spawned(player)), Some(Occurrence::Spawned(player)));
+		SpawnResult::Blocked
+	}
+	/// Spawns a new player with the given piece like `spawn`, unless there is a live player.
+	pub fn try_spawn(&mut self, piece: Piece) -> Result<SpawnResult, SpawnError> {
+		match self.player {
+			Some(player) => Err(SpawnError { player: player }),
+			None => Ok(self.spawn(piece)),
+		}
+	}
+	/// Spawns the next piece from the bag.
+	///
+	/// Finishes the game with `finish` if the bag ran out of pieces.
+	///
+	/// Returns `true` if the game is over, the piece blocked out or the bag ran out of pieces.
+	pub fn spawn_from<B: Bag + ?Sized>(&mut self, bag: &mut B) -> bool {
+		match bag.next(&self.well) {
+			Some(piece) => self.spawn(piece).is_blocked(),
+			None => {
+				self.finish();
+				true
+			},
+		}
+	}
+	/// Finishes the game because the bag ran out of pieces.
+	///
+	/// The game ends with `GameEnd::OutOfPieces` once the current player, if any, locks.
+	pub fn finish(&mut self) {
+		self.record(ReplayAction::Finish);
+		if !self.out_of_pieces {
+			self.out_of_pieces = true;
+			self.emit(Some(Event::OutOfPieces), None);
+		}
+	}
+	/// Returns how the game ended, `None` while the game is still going.
+	///
+	/// Running out of time takes precedence over blocking out, locking out and topping out, in that order.
+	/// All of them take precedence over running out of pieces.
+	pub fn game_end(&self) -> Option<GameEnd> {
+		let lines = self.well.lines();
+		let height = self.well.height() as usize;
+		if self.timed_out {
+			Some(GameEnd::TimedOut)
+		}
+		else if self.blocked_out {
+			Some(GameEnd::BlockOut)
+		}
+		else if self.locked_out {
+			Some(GameEnd::LockOut)
+		}
+		else if self.topped_out || self.top_out_check && (lines[height - 1] != 0 || lines[height - 2] != 0) {
+			Some(GameEnd::TopOut)
+		}
+		else if self.out_of_pieces && self.player.is_none() {
+			Some(GameEnd::OutOfPieces)
+		}
+		else {
+			None
+		}
+	}
+	/// Tests if the game ended for any reason, see `game_end`.
+	pub fn is_game_over(&self) -> bool {
+		self.game_end().is_some()
+	}
+	/// Returns the scene with the player and its ghost composited on access, without copying the tiles.
+	#[cfg(feature = "scene")]
+	pub fn scene_view(&self) -> SceneView<'_> {
+		match (self.player(), self.ghost()) {
+			// The ghost is where the player will fall
+			(Some(&player), Some(ghost)) => SceneView::new(&self.scene, Some(ghost), Some(player)),
+			_ => SceneView::new(&self.scene, None, None),
+		}
+	}
+	/// Rebuilds the scene from the well, for consumers which changed the well behind the state's back.
+	///
+	/// The tiles of the locked pieces are lost, every block is drawn as in `Scene::from_well`.
+	#[cfg(feature = "scene")]
+	pub fn resync_scene(&mut self) {
+		self.scene = Scene::from_well(&self.well);
+	}
+	/// Returns the scene with the player and its ghost drawn, see `scene_view`.
+	#[cfg(feature = "scene")]
+	pub fn scene(&self) -> Scene {
+		self.scene_view().to_scene()
+	}
+	/// Returns the scene widened with a side panel showing the hold piece and the next pieces.
+	///
+	/// The panel is five columns wide to the right of the well: a gap column and the piece boxes.
+	/// Every box is three rows high, the hold box is the top one and stays empty without a hold piece,
+	/// the next pieces follow below as far as they fit. The pieces are drawn as field tiles in their spawn rotation,
+	/// the panel is clipped to `MAX_WIDTH`.
+	#[cfg(feature = "scene")]
+	pub fn scene_with(&self, next: &[Piece], hold: Option<Piece>) -> Scene {
+		self.scene().with_panel(next, hold)
+	}
+}
+
+fn is_misdrop(well: &Well, guard: &MisdropGuard, landed: Player, intended: Player) -> bool {
+	// Rotations of the O and the I, S and Z upside down occupy the same cells
+	let (landed_cells, intended_cells) = (cells(landed), cells(intended));
+	if landed_cells == intended_cells {
+		return false;
+	}
+	if let Some(max_distance) = guard.max_distance {
+		let dx = landed_cells[0].x as i32 - intended_cells[0].x as i32;
+		let dy = landed_cells[0].y as i32 - intended_cells[0].y as i32;
+		let same_shape = landed_cells.iter().zip(&intended_cells).all(|(a, b)| (a.x as i32 - b.x as i32, a.y as i32 - b.y as i32) == (dx, dy));
+		if !same_shape || dx.abs() + dy.abs() > max_distance {
+			return true;
+		}
+	}
+	if let Some(new_holes) = guard.new_holes {
+		let mut after = *well;
+		after.etch(landed.sprite(), landed.pt);
+		if after.count_holes() - well.count_holes() >= new_holes {
+			return true;
+		}
+	}
+	false
+}
+
+// The cells of the player in the well, sorted from the bottom left
+fn cells(player: Player) -> [Point; 4] {
+	let mut cells = [Point::new(0, 0); 4];
+	for (cell, pt) in cells.iter_mut().zip(player.sprite().cells()) {
+		*cell = Point::new(player.pt.x + pt.x, player.pt.y - pt.y);
+	}
+	cells.sort_by_key(|pt| (pt.y, pt.x));
+	cells
+}
+
+// Index of the lowest occupied row of the player's sprite
+fn lowest_row(player: Player) -> i8 {
+	player.sprite().pix.iter().rposition(|&pix| pix != 0).unwrap() as i8
+}
+
+pub fn test_player(well: &Well, player: Player) -> bool {
+	well.test_fast(player.piece, player.rot, player.pt)
+}
+pub fn trace_down(well: &Well, player: Player) -> Player {
+	let sprite = player.sprite();
+	let pt = well.trace_down(sprite, player.pt);
+	Player::new(player.piece, player.rot, pt)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dimensions() {
+		let state = State::standard();
+		assert_eq!((STANDARD_WIDTH, STANDARD_HEIGHT), state.dimensions());
+		#[cfg(feature = "scene")]
+		assert_eq!((state.scene.width(), state.scene.height()), state.dimensions());
+
+		let state = State::new(6, 12);
+		assert_eq!((6, 12), state.dimensions());
+		#[cfg(feature = "scene")]
+		assert_eq!((state.scene.width(), state.scene.height()), state.dimensions());
+
+		let state = State::with_well(Well::new(4, 23));
+		assert_eq!((4, 23), state.dimensions());
+		#[cfg(feature = "scene")]
+		assert_eq!((state.scene.width(), state.scene.height()), state.dimensions());
+	}
+
+	const GUARD: MisdropGuard = MisdropGuard {
+		max_distance: Some(1),
+		new_holes: None,
+		confirm_ticks: 3,
+	};
+
+	#[test]
+	fn misdrop_confirm() {
+		let mut state = State::standard();
+		state.set_misdrop_guard(Some(GUARD));
+		state.spawn(Piece::O);
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(-1, 1))));
+
+		// Confirm within the window
+		assert_eq!(DropStatus::NeedsConfirmation, state.guarded_hard_drop());
+		assert!(state.player().is_some());
+		state.tick_misdrop_guard();
+		state.tick_misdrop_guard();
+		assert_eq!(DropStatus::Locked, state.guarded_hard_drop());
+		assert!(state.player().is_none());
+		assert_eq!(DropStatus::NoPlayer, state.guarded_hard_drop());
+
+		// Confirmation window expires and re-arms
+		state.spawn(Piece::O);
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(-1, 1))));
+		assert_eq!(DropStatus::NeedsConfirmation, state.guarded_hard_drop());
+		for _ in 0..3 {
+			state.tick_misdrop_guard();
+		}
+		assert_eq!(DropStatus::NeedsConfirmation, state.guarded_hard_drop());
+		assert!(state.player().is_some());
+
+		// Dropping close enough to the intended placement needs no confirmation
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(4, 4))));
+		assert_eq!(DropStatus::Locked, state.guarded_hard_drop());
+	}
+
+	#[test]
+	fn misdrop_bypass() {
+		// Disabled
+		let mut state = State::standard();
+		state.spawn(Piece::O);
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(-1, 1))));
+		assert_eq!(DropStatus::Locked, state.guarded_hard_drop());
+
+		// No intended placement
+		state.set_misdrop_guard(Some(GUARD));
+		state.spawn(Piece::O);
+		assert_eq!(DropStatus::Locked, state.guarded_hard_drop());
+
+		// Hard drops which don't lock leave the player in play
+		state.set_hard_drop_lock(false);
+		state.spawn(Piece::O);
+		assert_eq!(DropStatus::Dropped, state.guarded_hard_drop());
+		assert!(state.player().is_some());
+	}
+
+	#[test]
+	fn misdrop_same_cells() {
+		let well = Well::new(10, 22);
+		let guard = MisdropGuard { max_distance: Some(0), ..GUARD };
+		let etched = |player: Player| {
+			let mut after = well;
+			after.etch(player.sprite(), player.pt);
+			after
+		};
+		// Upside down the O, I, S and Z pieces cover the same cells from another position
+		for &piece in &[Piece::O, Piece::I, Piece::S, Piece::Z] {
+			let landed = trace_down(&well, Player::new(piece, Rot::Two, Point::new(3, 10)));
+			let intended = (-2..3).flat_map(|dx| (-2..3).map(move |dy| Point::new(dx, dy)))
+				.map(|offset| Player::new(piece, Rot::Zero, landed.pt + offset))
+				.find(|&intended| etched(intended) == etched(landed))
+				.unwrap();
+			assert!(!is_misdrop(&well, &guard, landed, intended), "{:?}", piece);
+			assert!(is_misdrop(&well, &guard, landed, intended.move_right()), "{:?}", piece);
+		}
+		assert!(!is_misdrop(&well, &guard, Player::new(Piece::O, Rot::Right, Point::new(3, 1)), Player::new(Piece::O, Rot::Zero, Point::new(3, 1))));
+		// A different shape is always a misdrop, however close
+		let landed = trace_down(&well, Player::new(Piece::T, Rot::Two, Point::new(3, 10)));
+		assert!(is_misdrop(&well, &MisdropGuard { max_distance: Some(5), ..GUARD }, landed, Player::new(Piece::T, Rot::Zero, landed.pt)));
+	}
+
+	#[test]
+	fn misdrop_new_holes() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1011111111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_misdrop_guard(Some(MisdropGuard {
+			max_distance: None,
+			new_holes: Some(1),
+			confirm_ticks: 3,
+		}));
+		// Covering the gap leaves a hole underneath
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(-1, 4)));
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(7, 3))));
+		assert_eq!(DropStatus::NeedsConfirmation, state.guarded_hard_drop());
+
+		// Landing flat creates no holes
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(3, 4)));
+		state.set_intended(Some(Player::new(Piece::O, Rot::Zero, Point::new(7, 3))));
+		assert_eq!(DropStatus::Locked, state.guarded_hard_drop());
+	}
+
+	#[test]
+	fn move_clock() {
+		use ::{Input, Clock};
+		let mut state = State::standard();
+		let mut input = Input::new(Clock { gravity: 1000, player: 8, lock_delay: 0 });
+		state.spawn(Piece::T);
+		for _ in 0..5 {
+			input.tick(&mut state);
+		}
+		assert_eq!(5, state.piece_ticks());
+		assert!(state.hard_drop());
+		assert_eq!(5, state.last_piece_ticks());
+		assert_eq!(0, state.piece_ticks());
+
+		// Entry delay without a player does not count
+		for _ in 0..3 {
+			input.tick(&mut state);
+		}
+		assert_eq!(5, state.move_clock_used());
+
+		state.spawn(Piece::I);
+		for _ in 0..7 {
+			input.tick(&mut state);
+		}
+		assert!(state.hard_drop());
+		assert_eq!(7, state.last_piece_ticks());
+		assert_eq!(12, state.move_clock_used());
+	}
+
+	#[test]
+	fn move_clock_hard_drop() {
+		let mut state = State::standard();
+		state.set_move_budget(Some(MoveBudget { total: 10, expiry: ClockExpiry::HardDrop }));
+		state.spawn(Piece::T);
+		for _ in 0..10 {
+			state.tick_move_clock();
+		}
+		assert!(state.player().is_some());
+		// The eleventh tick exceeds the budget
+		state.tick_move_clock();
+		assert!(state.player().is_none());
+		assert_eq!(11, state.last_piece_ticks());
+		assert_eq!(4, state.well().count_blocks());
+
+		// Every further player drops on its first tick
+		state.spawn(Piece::O);
+		state.tick_move_clock();
+		assert!(state.player().is_none());
+		assert_eq!(1, state.last_piece_ticks());
+		assert!(!state.is_game_over());
+
+		// Locks even if hard drops don't
+		state.set_hard_drop_lock(false);
+		state.spawn(Piece::I);
+		state.tick_move_clock();
+		assert!(state.player().is_none());
+		assert_eq!(12, state.well().count_blocks());
+	}
+
+	#[test]
+	fn ghost() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000110000,
+			0b0000111111,
+		]);
+		let mut state = State::with_well(well);
+		assert_eq!(None, state.ghost());
+		state.set_player(Player::new(Piece::T, Rot::Right, Point::new(2, 5)));
+		let ghost = state.ghost().unwrap();
+		assert_eq!(Player::new(Piece::T, Rot::Right, Point::new(2, 4)), ghost);
+		// Follows the player and agrees with the hard drop
+		state.move_right();
+		assert_eq!(Point::new(3, 4), state.ghost().unwrap().pt);
+		state.rotate_ccw();
+		let ghost = state.ghost().unwrap();
+		state.set_hard_drop_lock(false);
+		state.hard_drop();
+		assert_eq!(Some(&ghost), state.player());
+	}
+
+	#[test]
+	fn garbage() {
+		// The garbage rises into the player resting on the floor and pushes it up
+		let mut state = State::new(10, 8);
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 1)));
+		assert_eq!(2, state.add_garbage(2, 0));
+		assert_eq!(Point::new(3, 3), state.player().unwrap().pt);
+		assert_eq!(&[0b0111111111 << 22, 0b0111111111 << 22], &state.well().lines()[..2]);
+		#[cfg(feature = "scene")]
+		{
+			assert_eq!(TILE_BG0, state.scene.tile(6, 0));
+			assert_eq!(TILE_GARBAGE, state.scene.tile(6, 9));
+		}
+		// A player clear of the garbage isn't moved
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 7)));
+		assert_eq!(1, state.add_garbage(1, 5));
+		assert_eq!(Point::new(3, 7), state.player().unwrap().pt);
+		assert!(!state.is_game_over());
+
+		// The garbage fits but the player can't be pushed up far enough
+		let mut state = State::new(10, 6);
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::I));
+		assert_eq!(6, state.add_garbage(6, 0));
+		assert_eq!(None, state.player());
+		assert!(state.is_game_over());
+
+		// Garbage overflowing the top of the well
+		let mut state = State::with_well(Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000110000,
+			0b0000111100,
+		]));
+		assert_eq!(2, state.add_garbage(4, 3));
+		assert!(state.is_game_over());
+	}
+
+	#[test]
+	fn game_end_reasons() {
+		// The O pieces stack up to their spawn rows, the fourth blocks out
+		let mut state = State::new(10, 7);
+		state.set_top_out_check(false);
+		state.record_events(true);
+		for _ in 0..3 {
+			assert_eq!(SpawnResult::Ok, state.spawn(Piece::O));
+			assert!(state.hard_drop());
+			assert_eq!(None, state.game_end());
+		}
+		assert!(state.spawn(Piece::O).is_blocked());
+		assert_eq!(Some(GameEnd::BlockOut), state.game_end());
+		// Replaying the events ends the game the same way
+		let replayed = State::replay_events(&state.take_events(), (10, 7)).unwrap();
+		assert_eq!(Some(GameEnd::BlockOut), replayed.game_end());
+
+		// The O locks entirely within the top two rows, it locks out
+		let mut state = State::new(10, 8);
+		state.set_top_out_check(false);
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(0, 8)));
+		state.lock();
+		assert_eq!(Some(GameEnd::LockOut), state.game_end());
+
+		// The T sticks into the top two rows, only the legacy check tops out
+		let mut state = State::new(10, 8);
+		state.set_top_out_check(false);
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 6)));
+		state.lock();
+		assert_eq!(None, state.game_end());
+		state.set_top_out_check(true);
+		assert_eq!(Some(GameEnd::TopOut), state.game_end());
+	}
+
+	#[test]
+	fn stats() {
+		fn hard_drop(state: &mut State) -> u32 {
+			let fall = state.player().unwrap().pt.y - state.ghost().unwrap().pt.y;
+			assert!(state.hard_drop());
+			fall as u32
+		}
+		let mut state = State::new(4, 10);
+		// The I fills the bottom row for a single
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::I));
+		let mut fallen = hard_drop(&mut state);
+		assert_eq!(1, state.clear_lines(|_| ()));
+		// Two Os side by side for a double
+		state.spawn(Piece::O);
+		while state.move_left() {}
+		assert_eq!(Step::Moved, state.soft_drop());
+		assert_eq!(Step::Moved, state.gravity());
+		fallen += hard_drop(&mut state);
+		assert_eq!(0, state.clear_lines(|_| ()));
+		state.spawn(Piece::O);
+		while state.move_right() {}
+		fallen += hard_drop(&mut state);
+		assert_eq!(2, state.clear_lines(|_| ()));
+
+		let stats = *state.stats();
+		assert_eq!(1, stats.spawned(Piece::I));
+		assert_eq!(2, stats.spawned(Piece::O));
+		assert_eq!(3, stats.total_spawned());
+		assert_eq!(3, stats.locked);
+		assert_eq!((1, 1, 0, 0), (stats.singles, stats.doubles, stats.triples, stats.tetrises));
+		assert_eq!(3, stats.lines());
+		assert_eq!(1, stats.soft_drop_rows);
+		assert_eq!(fallen, stats.hard_drop_rows);
+		assert_eq!(1, stats.gravity_ticks);
+		assert_eq!((100 + 300) * 2 + 1 + 2 * fallen, stats.guideline_score(2));
+
+		state.reset_stats();
+		assert_eq!(Stats::default(), *state.stats());
+	}
+
+	#[test]
+	fn soft_drop_lands() {
+		// Without a lock delay a landed player stays in play and can still slide
+		let mut state = State::new(10, 8);
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 2)));
+		assert_eq!(Step::Moved, state.soft_drop());
+		assert_eq!(Step::Landed, state.soft_drop());
+		assert_eq!(Step::Landed, state.gravity());
+		assert!(state.move_left());
+		assert_eq!(Point::new(2, 1), state.player().unwrap().pt);
+		assert_eq!(0, state.well().count_blocks());
+
+		// The locking variants lock where the player landed
+		assert!(!state.soft_drop_lock());
+		assert_eq!(None, state.player());
+		assert_eq!(4, state.well().count_blocks());
+		assert_eq!(Step::NoPlayer, state.soft_drop());
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(6, 3)));
+		assert!(state.gravity_lock());
+		assert!(!state.gravity_lock());
+		assert_eq!(None, state.player());
+	}
+
+	#[test]
+	fn firm_drop() {
+		// The T slides under the overhang after firm dropping next to it
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b1111110000,
+			0b0000000000,
+			0b0000000000,
+		]);
+		let target = Player::new(Piece::T, Rot::Two, Point::new(0, 2));
+		let mut state = State::with_well(well);
+		state.set_player(Player::new(Piece::T, Rot::Two, Point::new(6, 5)));
+		assert!(state.firm_drop());
+		assert_eq!(state.ghost(), state.player().cloned());
+		assert_eq!(0, state.stats().locked);
+		for _ in 0..6 {
+			assert!(state.move_left());
+		}
+		assert_eq!(Some(&target), state.player());
+		assert!(!state.gravity_lock());
+		assert_eq!(None, state.player());
+		assert_eq!(well.count_blocks() + 4, state.well().count_blocks());
+
+		// Hard dropping from above lands on the overhang
+		let mut state = State::with_well(well);
+		state.set_player(Player::new(Piece::T, Rot::Two, Point::new(0, 5)));
+		assert!(state.hard_drop());
+		assert_eq!(1, state.stats().locked);
+		assert!(!test_player(state.well(), target));
+		assert_eq!(Step::NoPlayer, state.soft_drop());
+		assert!(!state.firm_drop());
+	}
+
+	#[test]
+	fn lock_delay() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000111111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_lock_delay(5);
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 2)));
+		assert_eq!(Step::Landed, state.soft_drop());
+		for _ in 0..4 {
+			assert!(!state.tick_lock_delay());
+		}
+		// Sliding along the stack restarts the delay
+		assert!(state.move_left());
+		for _ in 0..4 {
+			assert!(!state.tick_lock_delay());
+		}
+		// Sliding off the ledge prevents the lock
+		assert!(state.move_left());
+		assert!(state.move_left());
+		assert!(!state.tick_lock_delay());
+		assert_eq!(Step::Moved, state.soft_drop());
+		assert_eq!(Point::new(0, 1), state.player().unwrap().pt);
+		for _ in 0..4 {
+			assert!(!state.tick_lock_delay());
+		}
+		assert!(state.tick_lock_delay());
+		assert_eq!(None, state.player());
+	}
+
+	#[test]
+	fn lock_delay_resets() {
+		let mut state = State::new(10, 6);
+		state.set_lock_delay(2);
+		state.set_player(Player::new(Piece::T, Rot::Zero, Point::new(3, 1)));
+		let mut ticks = 0;
+		while state.player().is_some() {
+			ticks += 1;
+			state.tick_lock_delay();
+			if ticks % 2 == 0 { state.move_left(); } else { state.move_right(); }
+		}
+		// Every reset buys another tick until they run out
+		assert_eq!(MAX_LOCK_RESETS as i32 + 2, ticks);
+	}
+
+	#[test]
+	fn spawn_grace() {
+		// The stack blocks the spawn row of the T but not the row above
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000010000,
+			0b0000111111,
+			0b0000111111,
+		]);
+		let mut state = State::with_well(well);
+		assert_eq!(SpawnResult::Blocked, state.spawn(Piece::T));
+		assert_eq!(Point::new(3, 3), state.player().unwrap().pt);
+		assert_eq!(0, state.spawn_nudge());
+
+		state.set_spawn_grace(SpawnGrace::ShiftUpOnce);
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::T));
+		assert_eq!(Point::new(3, 4), state.player().unwrap().pt);
+		assert_eq!(1, state.spawn_nudge());
+		// Nothing to nudge without overlap
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::I));
+		assert_eq!(0, state.spawn_nudge());
+
+		// Grace never shifts the lowest row of a piece out of the top of the well
+		let well = Well::from_data(10, &[
+			0b0000110000,
+			0b0000111111,
+			0b0000111111,
+			0b0000111111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_spawn_grace(SpawnGrace::ShiftUpN(10));
+		for &piece in &[Piece::T, Piece::I, Piece::O, Piece::S, Piece::L] {
+			assert_eq!(SpawnResult::Blocked, state.spawn(piece), "{:?}", piece);
+			assert_eq!(0, state.spawn_nudge());
+			let pt = state.player().unwrap().pt;
+			assert_eq!(Point::new(3, 4 - (piece != Piece::O && piece != Piece::I) as i8), pt);
+		}
+
+		// Any number of attempts spawns into an empty well
+		for &n in &[127, 128, 255] {
+			let mut state = State::standard();
+			state.set_spawn_grace(SpawnGrace::ShiftUpN(n));
+			assert_eq!(SpawnResult::Ok, state.spawn(Piece::T), "{}", n);
+			assert_eq!(0, state.spawn_nudge());
+		}
+	}
+
+	#[test]
+	fn try_spawn() {
+		// Nearly full, only the spawn rows of the T are free
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b1110001111,
+			0b1111111111,
+		]);
+		let mut state = State::with_well(well);
+		assert_eq!(Ok(SpawnResult::Ok), state.try_spawn(Piece::T));
+		let player = *state.player().unwrap();
+		// Spawning twice is refused and leaves the player alone
+		assert_eq!(Err(SpawnError { player: player }), state.try_spawn(Piece::I));
+		assert_eq!(player, *state.player().unwrap());
+		assert!(state.hard_drop());
+		assert!(state.player().is_none());
+
+		// The T now fills its own spawn rows, the O blocks out
+		assert_eq!(Ok(SpawnResult::Blocked), state.try_spawn(Piece::O));
+		assert!(state.spawn(Piece::O).is_blocked());
+	}
+
+	#[test]
+	fn rules_spawn() {
+		let mut state = State::standard();
+		state.set_rules::<::ClassicRules>();
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::T));
+		assert_eq!(Player::new(Piece::T, Rot::Two, Point::new(3, STANDARD_HEIGHT)), *state.player().unwrap());
+
+		// The spawn grace keeps the rotation of the rules
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000010000,
+			0b0000111111,
+			0b0000111111,
+		]);
+		let mut state = State::with_well(well);
+		state.set_rules::<::ClassicRules>();
+		state.set_spawn_grace(SpawnGrace::ShiftUpOnce);
+		assert_eq!(SpawnResult::Ok, state.spawn(Piece::T));
+		assert_eq!(Player::new(Piece::T, Rot::Two, Point::new(3, 5)), *state.player().unwrap());
+		assert_eq!(1, state.spawn_nudge());
+
+		// Rotating the upright I against the wall needs a kick
+		for &(classic, rotates) in &[(false, true), (true, false)] {
+			let mut state = State::standard();
+			if classic {
+				state.set_rules::<::ClassicRules>();
+			}
+			state.set_player(Player::new(Piece::I, Rot::Right, Point::new(-2, 5)));
+			assert_eq!(rotates, state.rotate_cw());
+		}
+
+		// Back to the standard spawn
+		let mut state = State::standard();
+		state.set_rules::<::ClassicRules>();
+		state.set_rules::<::TheRules>();
+		state.spawn(Piece::T);
+		assert_eq!(spawn_player(state.well(), Piece::T), *state.player().unwrap());
+	}
+
+	#[test]
+	fn move_clock_game_over() {
+		let mut state = State::standard();
+		state.set_move_budget(Some(MoveBudget { total: 10, expiry: ClockExpiry::GameOver }));
+		state.spawn(Piece::T);
+		let player = *state.player().unwrap();
+		for _ in 0..10 {
+			state.tick_move_clock();
+		}
+		assert!(!state.is_game_over());
+		state.record_occurrences(true);
+		state.tick_move_clock();
+		let occurrences: Vec<Occurrence> = state.take_occurrences().into_iter().map(|(_, occurrence)| occurrence).collect();
+		assert_eq!(vec![Occurrence::TimedOut(player)], occurrences);
+		assert!(state.is_timed_out());
+		assert!(state.is_game_over());
+		assert!(state.player().is_none());
+		assert_eq!(11, state.move_clock_used());
+		assert_eq!(0, state.well().count_blocks());
+		assert_eq!(Some(GameEnd::TimedOut), state.game_end());
+	}
+
+	#[test]
+	fn garbage_hole_range() {
+		let mut state = State::new(10, 8);
+		for &hole in &[-129, -1, 10, 128, i32::MAX] {
+			assert!(state.try_add_garbage(1, hole).is_err(), "{}", hole);
+		}
+		assert_eq!(Err(CoordError::Overflow { value: 128 }), state.try_add_garbage(1, 128));
+		assert_eq!(Err(CoordError::OutOfBounds { value: 10 }), state.try_add_garbage(1, 10));
+		assert_eq!(0, state.well().count_blocks());
+		assert_eq!(Ok(1), state.try_add_garbage(1, 9));
+		assert_eq!(9, state.well().count_blocks());
+	}
+
+	#[test]
+	fn finish() {
+		let mut state = State::standard();
+		state.spawn(Piece::T);
+		// The player in play still locks before the game ends
+		state.finish();
+		assert_eq!(None, state.game_end());
+		state.hard_drop();
+		assert_eq!(Some(GameEnd::OutOfPieces), state.game_end());
+		assert!(state.is_game_over());
+		assert_eq!(4, state.well().count_blocks());
+		// Topping out takes precedence
+		state.add_garbage(21, 0);
+		assert_eq!(Some(GameEnd::TopOut), state.game_end());
+	}
+
+	#[test]
+	fn step_equivalence() {
+		use ::rand::Rng;
+		// Plays the moves through the methods and through the actions, the states stay identical
+		let mut rng = ::garbage::xorshift(11);
+		let mut direct = State::standard();
+		let mut stepped = State::standard();
+		for _ in 0..2000 {
+			if direct.is_game_over() {
+				direct = State::standard();
+				stepped = State::standard();
+			}
+			if direct.player().is_none() {
+				let piece = Piece::ALL[rng.gen_range(0, 7)];
+				direct.clear_lines(|_| ());
+				direct.spawn(piece);
+				stepped.clear_lines(|_| ());
+				stepped.spawn(piece);
+			}
+			let play = Play::all()[rng.gen_range(0, Play::all().len())];
+			let expected = match play {
+				Play::MoveLeft => direct.move_left(),
+				Play::MoveRight => direct.move_right(),
+				Play::RotateCW => direct.rotate_cw(),
+				Play::RotateCCW => direct.rotate_ccw(),
+				Play::Rotate180 => direct.rotate_180(),
+				Play::SoftDrop => direct.soft_drop() == Step::Moved,
+				Play::HardDrop => direct.hard_drop(),
+				Play::FirmDrop => direct.firm_drop(),
+				_ => true,
+			};
+			assert_eq!(expected, Action::from_play(play).is_none_or(|action| stepped.step(action)), "{:?}", play);
+			assert_eq!(direct.checksum(), stepped.checksum());
+			assert!(direct == stepped, "{}", direct.diff(&stepped).unwrap());
+		}
+		// Locking in place
+		assert!(!State::standard().step(Action::Lock));
+		let mut state = State::standard();
+		state.spawn(Piece::O);
+		assert!(state.step(Action::Lock));
+		assert_eq!(1, state.stats().locked);
+		assert_eq!(4, state.well().count_blocks());
+	}
+
+	#[test]
+	fn run_steps() {
+		// The O is three columns away from the wall after the first move
+		let mut state = State::standard();
+		state.spawn(Piece::O);
+		assert!(state.move_left());
+		let x = state.player().unwrap().pt.x;
+		let plays = ::parse_play_sequence("left*5").unwrap();
+		let summary = state.run_steps(plays.into_iter().filter_map(Action::from_play));
+		assert_eq!((3, Some(Action::MoveLeft), 1), (summary.applied(), summary.rejected(), summary.skipped()));
+		assert_eq!(StepOutcome::Rejected, summary.outcomes[3].1);
+		assert!(!summary.is_complete());
+		assert_eq!(x - 3, state.player().unwrap().pt.x);
+		// Every action performed
+		let summary = state.run_steps(vec![Action::RotateCW, Action::RotateCW, Action::HardDrop]);
+		assert_eq!((3, None, 0), (summary.applied(), summary.rejected(), summary.skipped()));
+		assert!(summary.is_complete() && state.player().is_none());
+		assert!(state.run_steps(None).outcomes.is_empty());
+	}
+
+	#[test]
+	fn drive_suggestion() {
+		// The O moves four columns left before hitting the wall
+		let mut state = State::standard();
+		state.spawn(Piece::O);
+		let x = state.player().unwrap().pt.x;
+		let path = [Action::MoveLeft; 6];
+		let mut replay = PacedReplay::new(&path[..], 3, 1);
+		assert_eq!(Ok(0), state.drive_suggestion(&mut replay));
+		assert_eq!(Ok(3), state.drive_suggestion(&mut replay));
+		assert_eq!(Err(Action::MoveLeft), state.drive_suggestion(&mut replay));
+		assert_eq!(x - 4, state.player().unwrap().pt.x);
+		assert!(replay.is_done());
+		assert_eq!(Ok(0), state.drive_suggestion(&mut replay));
+		// Following the bot's path lands the player where it planned
+		#[cfg(feature = "bot")]
+		{
+			let mut state = State::standard();
+			state.spawn(Piece::T);
+			let bot = ::PlayI::play(&::Weights::default(), state.well(), *state.player().unwrap());
+			let mut replay = bot.paced(2, 0).every(2);
+			while !replay.is_done() {
+				assert!(state.drive_suggestion(&mut replay).is_ok());
+			}
+			assert_eq!(bot.player.as_ref(), state.player());
+		}
+	}
+
+	#[test]
+	fn move_rejection() {
+		let well = Well::from_data(10, &[
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+			0b0100000000,
+			0b0000000000,
+			0b0000000000,
+			0b0000000000,
+		]);
+		let mut state = State::with_well(well);
+		assert_eq!(Some(MoveRejectCause::NoPlayer), state.move_rejection(Dir::Left));
+		// The O piece occupies the middle two columns and rows of its sprite
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(1, 5)));
+		assert_eq!(Some(MoveRejectCause::Stack), state.move_rejection(Dir::Left));
+		assert_eq!(None, state.move_rejection(Dir::Right));
+		assert_eq!(None, state.move_rejection(Dir::Down));
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(-1, 2)));
+		assert_eq!(Some(MoveRejectCause::Wall), state.move_rejection(Dir::Left));
+		// The floor counts as a wall
+		assert_eq!(Some(MoveRejectCause::Wall), state.move_rejection(Dir::Down));
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(7, 5)));
+		assert_eq!(Some(MoveRejectCause::Wall), state.move_rejection(Dir::Right));
+
+		// Rejected moves are reported, gravity is not
+		state.record_occurrences(true);
+		assert!(!state.move_right());
+		state.set_player(Player::new(Piece::O, Rot::Zero, Point::new(1, 7)));
+		assert_eq!((Step::Moved, Step::Moved), (state.gravity(), state.gravity()));
+		assert!(!state.move_left());
+		while state.gravity() == Step::Moved {}
+		let occurrences: Vec<Occurrence> = state.take_occurrences().into_iter().map(|(_, occurrence)| occurrence).collect();
+		assert_eq!(Occurrence::MoveRejected { dir: Dir::Right, cause: MoveRejectCause::Wall }, occurrences[0]);
+		assert_eq!(Occurrence::MoveRejected { dir: Dir::Left, cause: MoveRejectCause::Stack }, occurrences[1]);
+		assert!(!occurrences[2..].iter().any(|occurrence| matches!(occurrence, Occurrence::MoveRejected { .. })));
+	}
+}
@@ -0,0 +1,243 @@
+/*!
+Saving and restoring game states as text.
+*/
+
+use ::std::{error, fmt};
+use ::std::fmt::Write;
+use ::std::convert::TryFrom;
+
+use super::State;
+use ::{Player, Piece, Rot, Point, Well, WellValidation, ValidationError, MAX_WIDTH, MAX_HEIGHT};
+#[cfg(feature = "scene")]
+use ::Tile;
+
+/// Errors when restoring a saved state.
+///
+/// Lines are numbered from zero in the text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseStateError {
+	/// The well dimensions on the first line are missing or out of range.
+	Dimensions,
+	/// The player on the second line is malformed or overlaps the well.
+	Player,
+	/// The text ends before the last row of the well.
+	Truncated,
+	/// The field of the row is malformed or not as wide as the well.
+	Field { line: usize },
+	/// The scene tiles of the row are malformed or not as wide as the well.
+	Tiles { line: usize },
+	/// The well breaks the validation rules, see `State::deserialize_validated`.
+	Invalid(ValidationError),
+}
+impl fmt::Display for ParseStateError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ParseStateError::Dimensions => f.write_str("line 0: expected the well dimensions"),
+			ParseStateError::Player => f.write_str("line 1: invalid player"),
+			ParseStateError::Truncated => f.write_str("truncated state"),
+			ParseStateError::Field { line } => write!(f, "line {}: invalid well row", line),
+			ParseStateError::Tiles { line } => write!(f, "line {}: invalid scene tiles", line),
+			ParseStateError::Invalid(err) => fmt::Display::fmt(&err, f),
+		}
+	}
+}
+impl error::Error for ParseStateError {}
+
+impl State {
+	/// Saves the well, the player and the scene as text.
+	///
+	/// The first line holds the well dimensions as `width height`, the second line the player as `piece rot x y` or `-` without a player.
+	/// Every row of the well follows starting with the top row, the blocks as `#` and the empty cells as `.`.
+	/// With the `scene` feature the row continues after a space with the raw scene tiles, three hex digits per tile.
+	///
+	/// The settings, counters and recordings are not saved, see `deserialize`.
+	pub fn serialize(&self) -> String {
+		let (width, height) = self.dimensions();
+		let mut s = String::new();
+		let _ = writeln!(s, "{} {}", width, height);
+		match self.player {
+			Some(player) => { let _ = writeln!(s, "{} {} {} {}", player.piece, player.rot as u8, player.pt.x, player.pt.y); },
+			None => s.push_str("-\n"),
+		}
+		for row in (0..height).rev() {
+			let line = self.well.line(row);
+			for col in self.well.col_range() {
+				s.push(if line & col != 0 { '#' } else { '.' });
+			}
+			#[cfg(feature = "scene")]
+			{
+				s.push(' ');
+				for col in 0..width {
+					let _ = write!(s, "{:03x}", self.scene.tile(height - 1 - row, col).raw());
+				}
+			}
+			s.push('\n');
+		}
+		s
+	}
+	/// Restores a state saved with `serialize`.
+	///
+	/// Everything not saved starts out as in `with_well`, restoring the saved text of a restored state gives an equal state.
+	/// Without the scene tiles in the text the scene is drawn from the well, see `Scene::from_well`.
+	pub fn deserialize(s: &str) -> Result<State, ParseStateError> {
+		State::deserialize_validated(s, WellValidation::ALLOW_ANY)
+	}
+	/// Restores a state saved with `serialize` like `deserialize`, the well must pass the validation rules.
+	pub fn deserialize_validated(s: &str, flags: WellValidation) -> Result<State, ParseStateError> {
+		let mut lines = s.lines();
+
+		let dims: Vec<i8> = lines.next().ok_or(ParseStateError::Dimensions)?
+			.split_whitespace().map(|dim| dim.parse()).collect::<Result<_, _>>().map_err(|_| ParseStateError::Dimensions)?;
+		let (width, height) = match dims[..] {
+			[width, height] if width >= 4 && width as usize <= MAX_WIDTH && height >= 4 && height as usize <= MAX_HEIGHT => (width, height),
+			_ => return Err(ParseStateError::Dimensions),
+		};
+
+		let player = match lines.next().ok_or(ParseStateError::Truncated)?.trim() {
+			"-" => None,
+			line => Some(parse_player(line).ok_or(ParseStateError::Player)?),
+		};
+
+		let mut well = Well::new(width, height);
+		#[cfg(feature = "scene")]
+		let mut tiles = Vec::new();
+		for row in (0..height).rev() {
+			let index = (height - row) as usize + 1;
+			let line = lines.next().ok_or(ParseStateError::Truncated)?;
+			let mut parts = line.split(' ');
+			let field = parts.next().unwrap_or("");
+			if field.len() != width as usize {
+				return Err(ParseStateError::Field { line: index });
+			}
+			let mut bits = 0;
+			for (c, col) in field.chars().zip(well.col_range()) {
+				match c {
+					'#' => bits |= col,
+					'.' => (),
+					_ => return Err(ParseStateError::Field { line: index }),
+				}
+			}
+			well.set_line(row, bits);
+			#[cfg(feature = "scene")]
+			{
+				if let Some(hex) = parts.next() {
+					tiles.push((row, parse_tiles(hex, width).ok_or(ParseStateError::Tiles { line: index })?));
+				}
+			}
+		}
+
+		well.validate(flags).map_err(ParseStateError::Invalid)?;
+		let mut state = State::with_well(well);
+		if let Some(player) = player {
+			if state.well.test(player.sprite(), player.pt) {
+				return Err(ParseStateError::Player);
+			}
+			state.player = Some(player);
+		}
+		#[cfg(feature = "scene")]
+		for (row, tiles) in tiles {
+			for (col, tile) in tiles.into_iter().enumerate() {
+				state.scene.set_tile(row, col as i8, tile);
+			}
+		}
+		Ok(state)
+	}
+}
+
+fn parse_player(line: &str) -> Option<Player> {
+	let mut parts = line.split_whitespace();
+	let piece: Piece = parts.next()?.parse().ok()?;
+	let rot = Rot::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+	let x = parts.next()?.parse().ok()?;
+	let y = parts.next()?.parse().ok()?;
+	if parts.next().is_some() {
+		return None;
+	}
+	Some(Player::new(piece, rot, Point::new(x, y)))
+}
+
+#[cfg(feature = "scene")]
+fn parse_tiles(hex: &str, width: i8) -> Option<Vec<Tile>> {
+	if hex.len() != width as usize * 3 || !hex.is_ascii() {
+		return None;
+	}
+	(0..width as usize).map(|col| u16::from_str_radix(&hex[col * 3..col * 3 + 3], 16).ok().map(Into::into)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn played() -> State {
+		let mut state = State::standard();
+		for &piece in &[Piece::T, Piece::S, Piece::I, Piece::L] {
+			state.spawn(piece);
+			assert!(match piece {
+				Piece::T => state.rotate_cw(),
+				Piece::S => state.move_left(),
+				Piece::I => state.move_right(),
+				_ => state.rotate_ccw(),
+			});
+			assert!(state.hard_drop());
+			state.clear_lines(|_| ());
+		}
+		state.spawn(Piece::J);
+		state
+	}
+
+	#[test]
+	fn round_trip() {
+		let state = played();
+		let saved = state.serialize();
+		let restored = State::deserialize(&saved).unwrap();
+		assert_eq!(state.well(), restored.well());
+		assert_eq!(state.player(), restored.player());
+		#[cfg(feature = "scene")]
+		assert_eq!(state.scene(), restored.scene());
+		// The counters start fresh, the restored state saves the same text
+		assert_ne!(state, restored);
+		assert_eq!(saved, restored.serialize());
+		assert_eq!(restored, State::deserialize(&saved).unwrap());
+		// Without a player
+		let empty = State::new(6, 8);
+		assert_eq!("6 8\n-\n", &empty.serialize()[..6]);
+		assert_eq!(empty, State::deserialize(&empty.serialize()).unwrap());
+	}
+
+	#[test]
+	fn without_tiles() {
+		let restored = State::deserialize("4 4\nO 0 0 3\n....\n....\n#...\n##.#\n").unwrap();
+		assert_eq!(Some(&Player::new(Piece::O, Rot::Zero, Point::new(0, 3))), restored.player());
+		assert_eq!(4, restored.well().count_blocks());
+		#[cfg(feature = "scene")]
+		assert_eq!(::Scene::from_well(restored.well()), restored.scene);
+	}
+
+	#[test]
+	fn errors() {
+		assert_eq!(Err(ParseStateError::Dimensions), State::deserialize("").map(|_| ()));
+		assert_eq!(Err(ParseStateError::Dimensions), State::deserialize("3 4\n-\n").map(|_| ()));
+		assert_eq!(Err(ParseStateError::Player), State::deserialize("4 4\nO 4 0 3\n").map(|_| ()));
+		assert_eq!(Err(ParseStateError::Truncated), State::deserialize("4 4\n-\n....\n").map(|_| ()));
+		assert_eq!(Err(ParseStateError::Field { line: 3 }), State::deserialize("4 4\n-\n....\n...\n").map(|_| ()));
+		assert_eq!(Err(ParseStateError::Field { line: 2 }), State::deserialize("4 4\n-\n..x.\n").map(|_| ()));
+		// The player may not overlap the blocks
+		assert_eq!(Err(ParseStateError::Player), State::deserialize("4 4\nO 0 0 1\n....\n....\n....\n.#..\n").map(|_| ()));
+		#[cfg(feature = "scene")]
+		assert_eq!(Err(ParseStateError::Tiles { line: 2 }), State::deserialize("4 4\n-\n.... 0c0\n").map(|_| ()));
+		assert_eq!("line 3: invalid well row", ParseStateError::Field { line: 3 }.to_string());
+	}
+
+	#[test]
+	fn validated() {
+		let floating = "4 4\n-\n....\n.#..\n....\n##.#\n";
+		assert!(State::deserialize(floating).is_ok());
+		assert_eq!(Ok(State::deserialize(floating).unwrap()), State::deserialize_validated(floating, WellValidation::ALLOW_ANY));
+		let err = ValidationError::Unsupported { cell: Point::new(1, 2) };
+		assert_eq!(Err(ParseStateError::Invalid(err)), State::deserialize_validated(floating, WellValidation::REQUIRE_SUPPORTED).map(|_| ()));
+		let full = "4 4\n-\n....\n....\n####\n##.#\n";
+		assert!(State::deserialize_validated(full, WellValidation::REQUIRE_SUPPORTED).is_ok());
+		assert_eq!(Err(ParseStateError::Invalid(ValidationError::FullRow { row: 1 })), State::deserialize_validated(full, WellValidation::NO_FULL_ROWS).map(|_| ()));
+	}
+}
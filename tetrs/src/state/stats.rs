@@ -0,0 +1,55 @@
+/*!
+Game statistics.
+*/
+
+use ::Piece;
+
+/// Game statistics maintained by the `State`.
+///
+/// Counted since the state was created or since the last `State::reset_stats`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+	/// Spawned pieces by piece, indexed by the piece's discriminant in the order of `Piece::ALL`.
+	pub spawned: [u32; 7],
+	/// Locked pieces.
+	pub locked: u32,
+	/// Checks clearing a single line.
+	pub singles: u32,
+	/// Checks clearing two lines.
+	pub doubles: u32,
+	/// Checks clearing three lines.
+	pub triples: u32,
+	/// Checks clearing four lines.
+	pub tetrises: u32,
+	/// Rows moved down by `soft_drop`.
+	pub soft_drop_rows: u32,
+	/// Rows fallen by `hard_drop`.
+	pub hard_drop_rows: u32,
+	/// Gravity applied to a live player.
+	pub gravity_ticks: u32,
+}
+
+impl Stats {
+	/// Returns the number of spawned pieces of the given kind.
+	pub fn spawned(&self, piece: Piece) -> u32 {
+		self.spawned[piece as usize]
+	}
+	/// Returns the total number of spawned pieces.
+	pub fn total_spawned(&self) -> u32 {
+		self.spawned.iter().sum()
+	}
+	/// Returns the total number of cleared lines.
+	pub fn lines(&self) -> u32 {
+		self.singles + 2 * self.doubles + 3 * self.triples + 4 * self.tetrises
+	}
+	/// Returns the score by the guideline scoring formula.
+	///
+	/// Singles, doubles, triples and tetrises score 100, 300, 500 and 800 times the level.
+	/// Every soft dropped row scores 1 point and every hard dropped row 2 points.
+	///
+	/// The level applies to every clear, T-spins, combos and back-to-back bonuses are not scored.
+	pub fn guideline_score(&self, level: u32) -> u32 {
+		let clears = 100 * self.singles + 300 * self.doubles + 500 * self.triples + 800 * self.tetrises;
+		clears * level + self.soft_drop_rows + 2 * self.hard_drop_rows
+	}
+}
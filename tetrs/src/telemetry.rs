@@ -0,0 +1,192 @@
+/*!
+Live game statistics.
+*/
+
+/// Snapshot of the statistics.
+///
+/// Rates are zero when no time has passed.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TelemetryStats {
+	/// Ticks elapsed over the whole game.
+	pub ticks: u64,
+	/// Pieces locked over the whole game.
+	pub pieces: u32,
+	/// Garbage lines sent over the whole game.
+	pub attack: u32,
+	/// Longest combo.
+	pub max_combo: u32,
+	/// Attack per minute over the whole game.
+	pub apm: f64,
+	/// Pieces per second over the whole game.
+	pub pps: f64,
+	/// Attack per piece over the whole game.
+	pub efficiency: f64,
+	/// Attack per minute over the rolling window.
+	pub window_apm: f64,
+	/// Pieces per second over the rolling window.
+	pub window_pps: f64,
+	/// Attack per piece over the rolling window.
+	pub window_efficiency: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct Bucket {
+	pieces: u32,
+	attack: u32,
+}
+
+/// Accumulates APM, PPS and attack efficiency.
+///
+/// Feed it every tick with `tick` and every locked piece with `lock`.
+///
+/// The rolling window is a ring of per-second buckets and covers the current (partial) second plus the preceding full seconds.
+#[derive(Clone, Debug)]
+pub struct Telemetry {
+	ticks_per_second: u32,
+	buckets: Vec<Bucket>,
+	ticks: u64,
+	pieces: u32,
+	attack: u32,
+	max_combo: u32,
+}
+
+impl Telemetry {
+	/// Creates a new accumulator.
+	///
+	/// The rolling window is `window` seconds long, the engine runs at `ticks_per_second`.
+	pub fn new(ticks_per_second: u32, window: u32) -> Telemetry {
+		assert!(ticks_per_second > 0 && window > 0);
+		Telemetry {
+			ticks_per_second: ticks_per_second,
+			buckets: vec![Bucket::default(); window as usize],
+			ticks: 0,
+			pieces: 0,
+			attack: 0,
+			max_combo: 0,
+		}
+	}
+	/// Advances time by one tick.
+	pub fn tick(&mut self) {
+		self.ticks += 1;
+		// Entering a new second, recycle its bucket
+		if self.ticks.is_multiple_of(self.ticks_per_second as u64) {
+			let i = self.second() % self.buckets.len() as u64;
+			self.buckets[i as usize] = Bucket::default();
+		}
+	}
+	/// Advances time by the given number of ticks.
+	pub fn advance(&mut self, ticks: u32) {
+		for _ in 0..ticks {
+			self.tick();
+		}
+	}
+	/// Records a locked piece with the garbage lines it sent and the current combo.
+	pub fn lock(&mut self, attack: u32, combo: u32) {
+		let i = (self.second() % self.buckets.len() as u64) as usize;
+		self.buckets[i].pieces += 1;
+		self.buckets[i].attack += attack;
+		self.pieces += 1;
+		self.attack += attack;
+		self.max_combo = self.max_combo.max(combo);
+	}
+	/// Returns the statistics.
+	pub fn snapshot(&self) -> TelemetryStats {
+		let window = self.buckets.iter().fold(Bucket::default(), |acc, bucket| Bucket {
+			pieces: acc.pieces + bucket.pieces,
+			attack: acc.attack + bucket.attack,
+		});
+		// The window starts at the oldest second still in the ring
+		let window_start = (self.second() + 1).saturating_sub(self.buckets.len() as u64) * self.ticks_per_second as u64;
+		let window_ticks = self.ticks - window_start;
+		TelemetryStats {
+			ticks: self.ticks,
+			pieces: self.pieces,
+			attack: self.attack,
+			max_combo: self.max_combo,
+			apm: self.per_minute(self.attack, self.ticks),
+			pps: self.per_minute(self.pieces, self.ticks) / 60.0,
+			efficiency: ratio(self.attack, self.pieces),
+			window_apm: self.per_minute(window.attack, window_ticks),
+			window_pps: self.per_minute(window.pieces, window_ticks) / 60.0,
+			window_efficiency: ratio(window.attack, window.pieces),
+		}
+	}
+	fn second(&self) -> u64 {
+		self.ticks / self.ticks_per_second as u64
+	}
+	fn per_minute(&self, count: u32, ticks: u64) -> f64 {
+		if ticks == 0 { 0.0 } else { count as f64 * self.ticks_per_second as f64 * 60.0 / ticks as f64 }
+	}
+}
+
+fn ratio(attack: u32, pieces: u32) -> f64 {
+	if pieces == 0 { 0.0 } else { attack as f64 / pieces as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn scripted() {
+		let mut telemetry = Telemetry::new(60, 60);
+		assert_eq!(TelemetryStats::default(), telemetry.snapshot());
+		// One piece per second, every other piece sends 4 lines
+		for i in 0..10 {
+			telemetry.advance(60);
+			telemetry.lock(if i % 2 == 0 { 4 } else { 0 }, i % 3);
+		}
+		let stats = telemetry.snapshot();
+		assert_eq!(600, stats.ticks);
+		assert_eq!(10, stats.pieces);
+		assert_eq!(20, stats.attack);
+		assert_eq!(2, stats.max_combo);
+		assert_eq!(120.0, stats.apm);
+		assert_eq!(1.0, stats.pps);
+		assert_eq!(2.0, stats.efficiency);
+		// Everything is still inside the window
+		assert_eq!(stats.apm, stats.window_apm);
+		assert_eq!(stats.pps, stats.window_pps);
+		assert_eq!(stats.efficiency, stats.window_efficiency);
+	}
+
+	#[test]
+	fn window_expiry() {
+		// Two second window at 10 ticks per second
+		let mut telemetry = Telemetry::new(10, 2);
+		telemetry.lock(6, 0);
+		telemetry.advance(5);
+		telemetry.lock(0, 0);
+
+		// Last tick of the second second, both pieces are still in the window
+		telemetry.advance(14);
+		let stats = telemetry.snapshot();
+		assert_eq!(19, stats.ticks);
+		assert_eq!(2.0 * 10.0 / 19.0, stats.window_pps);
+		assert_eq!(6.0 * 600.0 / 19.0, stats.window_apm);
+
+		// First tick of the third second drops the first second's bucket
+		telemetry.tick();
+		let stats = telemetry.snapshot();
+		assert_eq!(0.0, stats.window_pps);
+		assert_eq!(0.0, stats.window_apm);
+		assert_eq!(0.0, stats.window_efficiency);
+		assert_eq!(2, stats.pieces);
+		assert_eq!(6, stats.attack);
+	}
+
+	#[test]
+	fn bucket_rollover() {
+		let mut telemetry = Telemetry::new(10, 3);
+		// Lock a piece sending `second` lines at the start of every second
+		for second in 0..10 {
+			telemetry.lock(second, 0);
+			telemetry.advance(10);
+			// Just rolled over into a new second, the window holds the two previous seconds and the empty current second
+			let stats = telemetry.snapshot();
+			assert_eq!(1.0, stats.window_pps, "second {}", second);
+			let expected_apm = if second == 0 { 0.0 } else { (2 * second - 1) as f64 * 600.0 / 20.0 };
+			assert_eq!(expected_apm, stats.window_apm, "second {}", second);
+		}
+	}
+}
@@ -7,6 +7,10 @@ use ::std::mem;
 use ::Piece;
 
 /// The tile type.
+///
+/// Unlike `Play` this enum is not `#[non_exhaustive]`: it is stored in the top 2 bits of the `Tile` byte
+/// and all 4 encodings are taken. The variants and their discriminants are part of the stable byte encoding,
+/// adding a tile type requires a new encoding and will be a breaking change.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum TileTy {
@@ -20,45 +24,135 @@ pub enum TileTy {
 	Background,
 }
 
+impl TileTy {
+	/// Returns every tile type in encoding order.
+	pub fn all() -> &'static [TileTy] {
+		&TILE_TYS
+	}
+}
+
+static TILE_TYS: [TileTy; 4] = [TileTy::Player, TileTy::Ghost, TileTy::Field, TileTy::Background];
+
+/// The mino connects to the mino of the same piece on its left.
+pub const CONNECTS_LEFT: u8 = 0b0001;
+/// The mino connects to the mino of the same piece on its right.
+pub const CONNECTS_RIGHT: u8 = 0b0010;
+/// The mino connects to the mino of the same piece above it.
+pub const CONNECTS_UP: u8 = 0b0100;
+/// The mino connects to the mino of the same piece below it.
+pub const CONNECTS_DOWN: u8 = 0b1000;
+
 /// Graphics tile.
+///
+/// The raw encoding is `0b0000_CCCC_TT_PPP_RRR` with the connections in `C`, the tile type in `T`,
+/// the piece in `P` (`0b111` for none) and the part in `R`.
+/// The low byte is the original byte encoding, converting to `u8` drops the connections.
+/// Both encodings are stable and may be stored or sent over the wire.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct Tile(u8);
+pub struct Tile(u16);
 impl Tile {
 	pub fn from(ty: TileTy, part: u8, piece: Option<Piece>) -> Tile {
-		let ty = ty as u8;
-		let piece = piece.map(|p| p as u8).unwrap_or(0b111);
-		Tile(ty << 6 | piece << 3 | part)
+		let ty = ty as u16;
+		let piece = piece.map(|p| p as u16).unwrap_or(0b111);
+		Tile(ty << 6 | piece << 3 | part as u16)
+	}
+	/// Returns the tile with the connections mask, any of the `CONNECTS_*` flags.
+	pub fn with_connections(self, connections: u8) -> Tile {
+		Tile(self.0 & 0xff | (connections as u16 & 0b1111) << 8)
 	}
 	pub fn tile_ty(self) -> TileTy {
-		unsafe { mem::transmute(self.0 >> 6) }
+		unsafe { mem::transmute((self.0 >> 6) as u8 & 0b11) }
 	}
 	pub fn part(self) -> u8 {
-		self.0 & 0b00_000_111
+		(self.0 & 0b00_000_111) as u8
 	}
 	pub fn piece(self) -> Option<Piece> {
-		match (self.0 & 0b00_111_000) >> 3 {
-			0b000 => Some(Piece::O),
-			0b001 => Some(Piece::I),
-			0b010 => Some(Piece::S),
-			0b011 => Some(Piece::Z),
-			0b100 => Some(Piece::L),
-			0b101 => Some(Piece::J),
-			0b110 => Some(Piece::T),
-			_ => None,
-		}
+		Piece::from_u8(((self.0 & 0b00_111_000) >> 3) as u8)
+	}
+	/// Returns which of the neighboring minos belong to the same piece, a mask of the `CONNECTS_*` flags.
+	///
+	/// Only the player, ghost and field tiles drawn by `Scene::draw` have connections.
+	/// Clearing a line cuts the connections across it.
+	pub fn connections(self) -> u8 {
+		(self.0 >> 8) as u8 & 0b1111
+	}
+	pub fn connects_left(self) -> bool {
+		self.connections() & CONNECTS_LEFT != 0
+	}
+	pub fn connects_right(self) -> bool {
+		self.connections() & CONNECTS_RIGHT != 0
+	}
+	pub fn connects_up(self) -> bool {
+		self.connections() & CONNECTS_UP != 0
+	}
+	pub fn connects_down(self) -> bool {
+		self.connections() & CONNECTS_DOWN != 0
+	}
+	/// Returns the raw encoding with the connections.
+	pub fn raw(self) -> u16 {
+		self.0
 	}
 }
 impl From<u8> for Tile {
 	fn from(byte: u8) -> Tile {
-		Tile(byte)
+		Tile(byte as u16)
 	}
 }
-impl Into<u8> for Tile {
-	fn into(self) -> u8 {
-		self.0
+impl From<Tile> for u8 {
+	fn from(tile: Tile) -> u8 {
+		tile.0 as u8
+	}
+}
+impl From<u16> for Tile {
+	fn from(raw: u16) -> Tile {
+		Tile(raw & 0x0fff)
 	}
 }
+impl From<Tile> for u16 {
+	fn from(tile: Tile) -> u16 {
+		tile.0
+	}
+}
+
+/// Garbage block in the playing field, a field tile without a piece.
+pub const TILE_GARBAGE: Tile = Tile(0b10_111_000);
 
 pub const TILE_BG0: Tile = Tile(0b11_000_000);
 pub const TILE_BG1: Tile = Tile(0b11_001_000);
 pub const TILE_BG2: Tile = Tile(0b11_010_000);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tile_ty_all() {
+		// Exhaustive match, adding a variant fails to compile here as a reminder to update `TileTy::all()`.
+		fn index(tile_ty: TileTy) -> usize {
+			match tile_ty {
+				TileTy::Player => 0,
+				TileTy::Ghost => 1,
+				TileTy::Field => 2,
+				TileTy::Background => 3,
+			}
+		}
+		const COUNT: usize = 4;
+		assert_eq!(COUNT, TileTy::all().len());
+		for (i, &tile_ty) in TileTy::all().iter().enumerate() {
+			assert_eq!(i, index(tile_ty));
+			assert_eq!(tile_ty, Tile::from(tile_ty, 0, None).tile_ty());
+		}
+	}
+
+	#[test]
+	fn connections() {
+		let tile = Tile::from(TileTy::Field, 2, Some(Piece::T)).with_connections(CONNECTS_LEFT | CONNECTS_DOWN);
+		assert!(tile.connects_left() && tile.connects_down());
+		assert!(!tile.connects_right() && !tile.connects_up());
+		assert_eq!((TileTy::Field, 2, Some(Piece::T)), (tile.tile_ty(), tile.part(), tile.piece()));
+		// The byte drops the connections, the raw encoding keeps them
+		assert_eq!(Tile::from(TileTy::Field, 2, Some(Piece::T)), <Tile as From<u8>>::from(u8::from(tile)));
+		assert_eq!(tile, <Tile as From<u16>>::from(tile.raw()));
+		assert_eq!(0b1001_1011_0010, tile.raw());
+	}
+}
@@ -0,0 +1,259 @@
+/*!
+Versus matches between live game states.
+*/
+
+use ::{Action, Bag, GarbageRng, Play, State};
+use ::scoring::attack;
+
+/// Outcome of a match.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MatchStatus {
+	/// More than one player is still in the game.
+	Playing,
+	/// The player with the given index is the last one standing.
+	Won(usize),
+	/// The last players were knocked out at the same time.
+	Draw,
+}
+
+/// Single player in a match.
+#[derive(Clone, Debug)]
+pub struct Contender<B> {
+	state: State,
+	bag: B,
+	pending: u32,
+	sent: u32,
+	received: u32,
+	alive: bool,
+}
+
+impl<B> Contender<B> {
+	/// Returns the game state.
+	pub fn state(&self) -> &State {
+		&self.state
+	}
+	/// Returns the bag.
+	pub fn bag(&self) -> &B {
+		&self.bag
+	}
+	/// Returns the garbage lines waiting to be received.
+	pub fn pending(&self) -> u32 {
+		self.pending
+	}
+	/// Returns the garbage lines sent.
+	pub fn sent(&self) -> u32 {
+		self.sent
+	}
+	/// Returns the garbage lines received.
+	pub fn received(&self) -> u32 {
+		self.received
+	}
+	/// Returns if the player is still in the game.
+	pub fn is_alive(&self) -> bool {
+		self.alive
+	}
+}
+
+/// Real-time match between two or more players, each with their own state and bag.
+///
+/// Every lock clears the lines and sends the `scoring::attack` lines without back-to-back and perfect clear bonuses,
+/// first cancelling the player's own pending garbage.
+/// The rest is queued against the next player still in the game in index order, with two players the opponent.
+/// A player whose lock cleared no lines receives all its pending garbage, the hole columns are drawn in arrival order
+/// from the garbage sequence shared by all players. Then the next piece spawns from the player's bag.
+///
+/// A player is knocked out when its game ends: topping out, blocking out, running out of pieces or any other `GameEnd`.
+/// Its pending garbage is discarded and its state is never touched again. The match is over once at most one player is left.
+#[derive(Clone, Debug)]
+pub struct Match<B> {
+	players: Vec<Contender<B>>,
+	garbage: GarbageRng,
+}
+
+impl<B: Bag> Match<B> {
+	/// Creates a new match and spawns the first piece of every player.
+	///
+	/// The garbage hole columns are drawn from the seed.
+	///
+	/// # Panics
+	///
+	/// Panics if fewer than two players are given or their wells differ in width.
+	pub fn new(seed: u64, players: Vec<(State, B)>) -> Match<B> {
+		assert!(players.len() >= 2, "a match needs at least 2 players");
+		let width = players[0].0.well().width();
+		assert!(players.iter().all(|player| player.0.well().width() == width), "the wells of a match must be equally wide");
+		let players = players.into_iter().map(|(state, bag)| Contender {
+			state: state,
+			bag: bag,
+			pending: 0,
+			sent: 0,
+			received: 0,
+			alive: true,
+		}).collect();
+		let mut game = Match {
+			players: players,
+			garbage: GarbageRng::new(seed, width),
+		};
+		for i in 0..game.players.len() {
+			let player = &mut game.players[i];
+			if player.state.player().is_none() && player.state.spawn_from(&mut player.bag) {
+				game.knock_out(i);
+			}
+		}
+		game
+	}
+	/// Returns the players.
+	pub fn players(&self) -> &[Contender<B>] {
+		&self.players
+	}
+	/// Returns the player.
+	pub fn player(&self, player_idx: usize) -> &Contender<B> {
+		&self.players[player_idx]
+	}
+	/// Returns the outcome of the match.
+	pub fn status(&self) -> MatchStatus {
+		let mut alive = self.players.iter().enumerate().filter(|&(_, player)| player.alive).map(|(i, _)| i);
+		match (alive.next(), alive.next()) {
+			(None, _) => MatchStatus::Draw,
+			(Some(i), None) => MatchStatus::Won(i),
+			(Some(_), Some(_)) => MatchStatus::Playing,
+		}
+	}
+	/// Applies gravity and the lock delay to every player in the game.
+	///
+	/// Does nothing once the match is over.
+	pub fn tick(&mut self) {
+		if self.status() != MatchStatus::Playing {
+			return;
+		}
+		for i in 0..self.players.len() {
+			if self.players[i].alive {
+				let state = &mut self.players[i].state;
+				let locked = state.stats().locked;
+				state.gravity_lock();
+				state.tick_lock_delay();
+				self.settle(i, locked);
+			}
+		}
+	}
+	/// Plays a move for the player.
+	///
+	/// Soft drops leave a landed player resting on the stack, it locks by gravity or the lock delay in `tick`.
+	///
+	/// Returns `false` if the move failed, the player is out of the game or the match is over.
+	pub fn play(&mut self, player_idx: usize, play: Play) -> bool {
+		if self.status() != MatchStatus::Playing || !self.players[player_idx].alive {
+			return false;
+		}
+		let state = &mut self.players[player_idx].state;
+		let locked = state.stats().locked;
+		// Waiting always succeeds
+		let result = Action::from_play(play).is_none_or(|action| state.step(action));
+		self.settle(player_idx, locked);
+		result
+	}
+	// Clears the lines, routes the garbage and spawns the next piece after the player locked
+	fn settle(&mut self, i: usize, locked: u32) {
+		if self.players[i].state.stats().locked == locked {
+			if self.players[i].state.is_game_over() {
+				self.knock_out(i);
+			}
+			return;
+		}
+		let info = self.players[i].state.clear_lines_ex();
+
+		// Cancel pending garbage before sending the rest
+		let mut attack = attack(info.lines, info.tspin, info.combo, false, false);
+		let cancel = attack.min(self.players[i].pending);
+		self.players[i].pending -= cancel;
+		attack -= cancel;
+		if attack > 0 {
+			if let Some(target) = self.target(i) {
+				self.players[target].pending += attack;
+				self.players[i].sent += attack;
+			}
+		}
+
+		// Receive the garbage when no lines were cleared
+		let player = &mut self.players[i];
+		let mut topped_out = false;
+		if info.lines == 0 {
+			for _ in 0..player.pending {
+				topped_out |= player.state.add_garbage(1, self.garbage.next_column()) < 1;
+			}
+			player.received += player.pending;
+			player.pending = 0;
+		}
+		if topped_out || player.state.is_game_over() || player.state.spawn_from(&mut player.bag) {
+			self.knock_out(i);
+		}
+	}
+	// The next player still in the game after the attacker
+	fn target(&self, attacker: usize) -> Option<usize> {
+		let n = self.players.len();
+		(1..n).map(|offset| (attacker + offset) % n).find(|&i| self.players[i].alive)
+	}
+	fn knock_out(&mut self, i: usize) {
+		let player = &mut self.players[i];
+		player.alive = false;
+		// Pending garbage is discarded
+		player.pending = 0;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::{Piece, SequenceBag, Well};
+
+	#[test]
+	fn tetris_tops_out() {
+		// Player A has four lines ready for an I piece in the leftmost column
+		let mut well = Well::new(10, 22);
+		for row in 0..4 {
+			well.set_line(row, 0b0111111111 << 22);
+		}
+		let a = (State::with_well(well), SequenceBag::new(&[Piece::I, Piece::O]));
+		// Player B has a tall stack, a few more lines push it out of the well
+		let mut well = Well::new(10, 22);
+		for row in 0..18 {
+			well.set_line(row, 0b1111111110 << 22);
+		}
+		let b = (State::with_well(well), SequenceBag::new(&[Piece::O, Piece::O]));
+		let mut game = Match::new(7, vec![a, b]);
+		assert_eq!(MatchStatus::Playing, game.status());
+
+		// A stands the I up against the left wall and sends a tetris
+		assert!(game.play(0, Play::RotateCW));
+		while game.play(0, Play::MoveLeft) {}
+		assert!(game.play(0, Play::HardDrop));
+		assert_eq!(4, game.player(0).sent());
+		assert_eq!(4, game.player(1).pending());
+		assert_eq!(0, game.player(0).state().well().stack_height());
+		assert_eq!(MatchStatus::Playing, game.status());
+
+		// B clears nothing, takes the garbage and tops out
+		assert!(game.play(1, Play::HardDrop));
+		assert_eq!(4, game.player(1).received());
+		assert!(!game.player(1).is_alive());
+		assert_eq!(Some(::GameEnd::TopOut), game.player(1).state().game_end());
+		assert_eq!(MatchStatus::Won(0), game.status());
+		// The match is over
+		assert!(!game.play(0, Play::HardDrop));
+	}
+
+	#[test]
+	fn ticks_lock() {
+		let bag = || SequenceBag::new(&[Piece::O; 8]);
+		let mut game = Match::new(1, vec![(State::standard(), bag()), (State::standard(), bag())]);
+		for _ in 0..30 {
+			game.tick();
+		}
+		// Both players dropped the first O to the floor by gravity and spawned the next
+		for player in game.players() {
+			assert_eq!(1, player.state().stats().locked);
+			assert_eq!(2, player.state().stats().total_spawned());
+		}
+		assert_eq!(2, game.player(0).state().well().stack_height());
+	}
+}
@@ -2,23 +2,23 @@
 Playing field.
 */
 
-use ::std::{fmt};
+use ::std::{cmp, error, fmt, ops};
 use ::std::str::{FromStr};
 
-use ::{Point, Sprite};
+use ::{Piece, Point, Rot, Sprite};
 
 /// Row in the well.
 ///
 /// The well represents its internal structure in bit masks.
 //
 // Keep in sync with `SIZE_OF_WIDTH` and `MAX_WIDTH`.
-pub type Line = u16;
-const SIZE_OF_WIDTH: usize = 16;
+pub type Line = u32;
+pub(crate) const SIZE_OF_WIDTH: usize = 32;
 
 /// Maximum well height.
 ///
 /// The well uses a fixed size array to store its field making it very cheap to copy.
-// This height was chosen to make the size of `Well` equal to 80 bytes, which is 5 times size of xmm register.
+// This height was chosen to make the size of `Well` equal to 96 bytes, which is 6 times size of xmm register.
 //
 // If this is changed, don't forget to update the documentation for `Well::new`.
 //
@@ -31,7 +31,15 @@ pub const MAX_HEIGHT: usize = 23;
 //
 // This should be equal to `size_of(Line) - 4`.
 // Subtract 4 is needed to avoid handling some sprite test edge cases (sprites are 4x4).
-pub const MAX_WIDTH: usize = 12;
+pub const MAX_WIDTH: usize = 28;
+
+/// Standard well width.
+pub const STANDARD_WIDTH: i8 = 10;
+
+/// Standard well height.
+///
+/// The guideline well is 20 rows high with 2 extra rows for spawning pieces.
+pub const STANDARD_HEIGHT: i8 = 22;
 
 /// Playing field.
 ///
@@ -43,17 +51,48 @@ pub struct Well {
 	field: [Line; MAX_HEIGHT],
 }
 
-const MINOS_STR: &'static str = "□";
+const MINOS_STR: &str = "□";
+
+// Sprites can stick out up to 3 columns left of the well
+const RENDER_COLS: usize = MAX_WIDTH + 3;
+
+// The standard sprites rendered at every column by piece, rotation and column plus 3, see `Well::render`
+static RENDERED: [[[[Line; 4]; RENDER_COLS]; 4]; 7] = render_all();
+
+const fn render_all() -> [[[[Line; 4]; RENDER_COLS]; 4]; 7] {
+	let mut rendered = [[[[0; 4]; RENDER_COLS]; 4]; 7];
+	let mut piece = 0;
+	while piece < 7 {
+		let mut rot = 0;
+		while rot < 4 {
+			let pix = ::rules::sprite_pix(piece, rot);
+			let mut col = 0;
+			while col < RENDER_COLS {
+				let mut y = 0;
+				while y < 4 {
+					rendered[piece][rot][col][y] = (pix[y] as Line).rotate_right(col as u32 + 1);
+					y += 1;
+				}
+				col += 1;
+			}
+			rot += 1;
+		}
+		piece += 1;
+	}
+	rendered
+}
 
 impl Well {
 	/// Creates an empty well with the given dimensions.
 	///
 	/// # Panics
 	///
-	/// The width must be ∈ [4, 12] and the height must be ∈ [4, 23].
-	pub fn new(width: i8, height: i8) -> Well {
-		assert!(width >= 4 && width <= MAX_WIDTH as i8, "width must be ∈ [4, {}]", MAX_WIDTH);
-		assert!(height >= 4 && height <= MAX_HEIGHT as i8, "height must be ∈ [4, {}]", MAX_HEIGHT);
+	/// The width must be ∈ [4, 28] and the height must be ∈ [4, 23].
+	///
+	/// Usable in constants, eg. for a `static` well on targets without an allocator.
+	pub const fn new(width: i8, height: i8) -> Well {
+		assert!(width >= 4 && width <= MAX_WIDTH as i8, "width must be ∈ [4, 28]");
+		assert!(height >= 4 && height <= MAX_HEIGHT as i8, "height must be ∈ [4, 23]");
 		Well {
 			width: width,
 			height: height,
@@ -63,15 +102,18 @@ impl Well {
 	/// Creates a new well with the given data.
 	///
 	/// Note that the input lines are in 'visual' order. Internally the lines are stored bottom line first.
+	/// Usable in constants like `new`.
 	///
 	/// # Panics
 	///
 	/// No minos may be found outside the well's width.
-	pub fn from_data(width: i8, lines: &[Line]) -> Well {
+	pub const fn from_data(width: i8, lines: &[Line]) -> Well {
 		let mut well = Well::new(width, lines.len() as i8);
-		let shift = SIZE_OF_WIDTH as usize - width as usize;
-		for (lhs, &rhs) in Iterator::zip(well.field[..lines.len()].iter_mut(), lines.iter().rev()) {
-			*lhs = rhs << shift;
+		let shift = SIZE_OF_WIDTH - width as usize;
+		let mut i = 0;
+		while i < lines.len() {
+			well.field[i] = lines[lines.len() - 1 - i] << shift;
+			i += 1;
 		}
 		well
 	}
@@ -95,25 +137,34 @@ impl Well {
 			end: 1 << (SIZE_OF_WIDTH - self.width() as usize - 1),
 		}
 	}
-	fn render(sprite: &Sprite, x: i8) -> [Line; 4] {
+	pub(crate) fn render(sprite: &Sprite, x: i8) -> [Line; 4] {
 		let mut result = [0; 4];
-		for y in 0..4 {
-			result[y] = (sprite.pix[y] as Line).rotate_right((x + 4) as u32);
+		for (line, &pix) in result.iter_mut().zip(sprite.pix.iter()) {
+			*line = (pix as Line).rotate_right((x + 4) as u32);
 		}
 		result
 	}
 	/// Tests if the sprite collides with with the well.
+	///
+	/// Only the occupied cells of the sprite matter, the empty parts of its 4x4 box may stick out of the well.
+	/// Cells above the well collide with the walls but not with the field.
 	pub fn test(&self, sprite: &Sprite, pt: Point) -> bool {
-		// Early reject out of bounds
+		// Early reject when every cell is out of bounds, any cell of a sprite at these points is left, right or below the well
 		if pt.x <= -4 || pt.x >= self.width || pt.y < 0 {
 			return true;
 		}
-		if pt.y >= self.height + 4 {
-			return false;
+		self.test_rendered(&Self::render(sprite, pt.x), pt)
+	}
+	/// Tests if the standard sprite of the piece collides with the well.
+	///
+	/// Same as `test` with the sprite from `Piece::sprite`, looks up the sprite prerendered at every column.
+	pub fn test_fast(&self, piece: Piece, rot: Rot, pt: Point) -> bool {
+		if pt.x <= -4 || pt.x >= self.width || pt.y < 0 {
+			return true;
 		}
-
-		// Render the sprite
-		let sprite = Self::render(sprite, pt.x);
+		self.test_rendered(&RENDERED[piece as usize][rot as usize][(pt.x + 3) as usize], pt)
+	}
+	fn test_rendered(&self, sprite: &[Line; 4], pt: Point) -> bool {
 		let line_mask = self.line_mask();
 
 		for y in 0..4 {
@@ -136,7 +187,7 @@ impl Well {
 				}
 			}
 		}
-		return false;
+		false
 	}
 	/// Tests a list of kicks and returns the first point where the sprite doesn't collide with the well.
 	///
@@ -150,7 +201,7 @@ impl Well {
 	/// Traces the sprite down and returns the lowest point where it does not collide with the well.
 	pub fn trace_down(&self, sprite: &Sprite, mut pt: Point) -> Point {
 		loop {
-			let next = Point::new(pt.x, pt.y - 1);
+			let next = pt.offset(0, -1);
 			if self.test(sprite, next) {
 				return pt;
 			}
@@ -159,11 +210,23 @@ impl Well {
 	}
 	/// Etches the sprite into the well.
 	pub fn etch(&mut self, sprite: &Sprite, pt: Point) {
-		let sprite = Self::render(sprite, pt.x);
+		self.etch_rendered(&Self::render(sprite, pt.x), pt);
+	}
+	/// Etches the standard sprite of the piece into the well.
+	///
+	/// Same as `etch` with the sprite from `Piece::sprite`, looks up the sprite prerendered at every column.
+	///
+	/// # Panics
+	///
+	/// Panics if the point is more than 3 columns left of the well or at least `MAX_WIDTH` columns right of it.
+	pub fn etch_fast(&mut self, piece: Piece, rot: Rot, pt: Point) {
+		self.etch_rendered(&RENDERED[piece as usize][rot as usize][(pt.x + 3) as usize], pt);
+	}
+	fn etch_rendered(&mut self, sprite: &[Line; 4], pt: Point) {
 		// Etch the sprite into the field
 		for y in 0..4 {
-			// Clip the affected row to the field
-			let row = pt.y - y;
+			// Clip the affected row to the field, saturates for points far below it
+			let row = pt.y.saturating_sub(y);
 			if row >= 0 && row < self.height {
 				self.field[row as usize] |= sprite[y as usize];
 			}
@@ -178,6 +241,10 @@ impl Well {
 	pub fn line(&self, row: i8) -> Line {
 		self.field[row as usize]
 	}
+	/// Returns if every column of the line has a block.
+	pub fn is_line_full(&self, row: i8) -> bool {
+		self.line(row) == self.line_mask()
+	}
 	/// Sets a line.
 	///
 	/// Returns the erased line.
@@ -198,6 +265,32 @@ impl Well {
 		}
 		line
 	}
+	/// Empties a line without shifting the lines above.
+	///
+	/// Returns the erased line.
+	pub fn clear_line(&mut self, row: i8) -> Line {
+		self.set_line(row, 0)
+	}
+	/// Removes all full lines, see `is_line_full`.
+	///
+	/// Returns the number of removed lines.
+	///
+	/// The lines above the removed lines are shifted down in one pass from the bottom up and empty lines are inserted at the top.
+	pub fn clear_full_lines(&mut self) -> u32 {
+		let mut dest = 0;
+		for row in 0..self.height() as usize {
+			// Keeps the lines which aren't full, moving them down over the removed lines
+			if !self.is_line_full(row as i8) {
+				self.field[dest] = self.field[row];
+				dest += 1;
+			}
+		}
+		let height = self.height() as usize;
+		for line in &mut self.field[dest..height] {
+			*line = 0;
+		}
+		(height - dest) as u32
+	}
 	/// Inserts a line.
 	///
 	/// The existing lines are shifted up and the top line that got bumped out is returned.
@@ -214,83 +307,259 @@ impl Well {
 impl Well {
 	/// Counts the number of holes.
 	///
-	/// A hole is defined as an empty block that is not reachable from the top of the well.
+	/// A hole is defined as an empty block that is not reachable from the top of the well, see `reachable_mask`.
 	pub fn count_holes(&self) -> i32 {
-		let mut well = *self;
-		let seed = Point::new(self.width >> 1, self.height - 1);
-		well.flood_fill(seed);
-		well.width as i32 * well.height as i32 - well.count_blocks() as i32
+		let empty = self.width as i32 * self.height as i32 - self.count_blocks() as i32;
+		let reachable: u32 = self.reachable_mask().iter().map(|line| line.count_ones()).sum();
+		empty - reachable as i32
+	}
+	/// Returns the empty blocks reachable from above the well, bottom line first.
+	///
+	/// Every empty block in the top row is reachable, from there the empty blocks connected left, right, up or down.
+	/// The lines past the height of the well are empty.
+	pub fn reachable_mask(&self) -> [Line; MAX_HEIGHT] {
+		// The lines above the stack are empty and connected to the top row, seed them all in one fill
+		let mut fill = [0; MAX_HEIGHT];
+		let top = cmp::min(self.stack_height(), self.height as i32 - 1) as usize;
+		for (fill, &line) in fill[top..self.height as usize].iter_mut().zip(&self.field[top..]) {
+			*fill = !line & self.line_mask();
+		}
+		self.grow_fill(&mut fill);
+		fill
+	}
+	/// Returns if the field has no blocks, eg. after a perfect clear.
+	pub fn is_empty(&self) -> bool {
+		self.lines().iter().all(|&line| line == 0)
 	}
 	/// Returns the number of blocks in the field.
 	pub fn count_blocks(&self) -> u32 {
 		self.lines().iter().map(|&line| line.count_ones()).sum()
 	}
+	/// Returns the rows which differ from the other well, with the line of this well.
+	///
+	/// Diff the well after a placement against the well before to find the rows to redraw.
+	///
+	/// # Panics
+	///
+	/// Panics if the wells differ in dimensions.
+	pub fn diff_lines<'a>(&'a self, other: &'a Well) -> impl Iterator<Item = (i8, Line)> + 'a {
+		self.assert_same_dims(other);
+		self.lines().iter().zip(other.lines()).enumerate()
+			.filter(|&(_, (line, other))| line != other)
+			.map(|(row, (&line, _))| (row as i8, line))
+	}
+	fn assert_same_dims(&self, other: &Well) {
+		assert_eq!((self.width, self.height), (other.width, other.height), "the wells must have the same dimensions");
+	}
+	/// Returns the height of the highest column.
+	pub fn stack_height(&self) -> i32 {
+		// Lines are stored bottom first, the highest non-empty line is the stack height
+		self.lines().iter().rposition(|&line| line != 0).map(|row| row as i32 + 1).unwrap_or(0)
+	}
+	/// Returns the height of every column, one above its highest block.
+	///
+	/// Full lines count towards the heights, columns past the width of the well are zero.
+	pub fn heights(&self) -> [i8; MAX_WIDTH] {
+		let mut heights = [0; MAX_WIDTH];
+		for (row, &line) in self.lines().iter().enumerate() {
+			if line != 0 {
+				for (height, col_mask) in heights.iter_mut().zip(self.col_range()) {
+					if line & col_mask != 0 {
+						*height = row as i8 + 1;
+					}
+				}
+			}
+		}
+		heights
+	}
 	/// Flood fills the field from the given seeding point.
+	///
+	/// Fills the empty blocks connected left, right, up or down to the seed, filling from a block fills nothing.
 	pub fn flood_fill(&mut self, seed: Point) {
-		let x = self.col_range().nth(seed.x as usize).unwrap();
-		self._flood_fill(seed.y as usize, x);
-	}
-	fn _flood_fill(&mut self, y: usize, x: Line) {
-		// Bounds check it early (optimizer is dumb...)
-		//let _ = self.lines()[y];
-		let mut range = self.col_range();
-		// Since the top of the well is most likely open, optimize for this case
-		if self.field[y] != 0 {
-			// Find the left edge
-			let mut left = x;
-			while left < range.start && self.field[y] & (left << 1) == 0 {
-				left <<= 1;
-			}
-			// Find the right edge (+ 1)
-			let mut right = x;
-			while right > range.end && self.field[y] & right == 0 {
-				right >>= 1;
-			}
-			range = ColRange {
-				start: left,
-				end: right,
-			};
-		}
-		// Mask all the blocks between left and right
-		let mask = range.mask();
-		self.field[y] |= mask;
-
-		// Let's do some tail call optimization first
-		if y >= 1 {
-			if self.field[y - 1] & mask == 0 {
-				return self._flood_fill(y - 1, range.start);
-			}
-			// Recursively flood the rest
-			for it in range.clone() {
-				if self.field[y - 1] & it == 0 {
-					self._flood_fill(y - 1, it);
+		let mut fill = [0; MAX_HEIGHT];
+		fill[seed.y as usize] = self.col_range().nth(seed.x as usize).unwrap() & !self.line(seed.y);
+		self.grow_fill(&mut fill);
+		let height = self.height as usize;
+		for (line, &fill) in self.field.iter_mut().zip(&fill[..height]) {
+			*line |= fill;
+		}
+	}
+	/// Grows the fill through the empty blocks connected left, right, up or down.
+	///
+	/// The fill must only contain empty blocks.
+	fn grow_fill(&self, fill: &mut [Line; MAX_HEIGHT]) {
+		let mut empty = [0; MAX_HEIGHT];
+		for (empty, &line) in empty.iter_mut().zip(self.lines()) {
+			*empty = !line & self.line_mask();
+		}
+		self.grow(fill, &empty);
+	}
+	/// Grows the fill through the given cells connected left, right, up or down.
+	///
+	/// The fill must only contain the given cells.
+	fn grow(&self, fill: &mut [Line; MAX_HEIGHT], cells: &[Line; MAX_HEIGHT]) {
+		let height = self.height as usize;
+		// Grow the fill until nothing changes, without recursion or allocation
+		// Alternate sweeping down and up, flooding into a pocket under an overhang takes an extra sweep
+		let mut changed = true;
+		let mut down = true;
+		while changed {
+			changed = false;
+			for i in 0..height {
+				let y = if down { height - 1 - i } else { i };
+				let mut line = fill[y];
+				if y + 1 < height {
+					line |= fill[y + 1] & cells[y];
+				}
+				if y >= 1 {
+					line |= fill[y - 1] & cells[y];
+				}
+				// Spread along the row through the cells
+				loop {
+					let next = line | ((line << 1 | line >> 1) & cells[y]);
+					if next == line {
+						break;
+					}
+					line = next;
+				}
+				if line != fill[y] {
+					fill[y] = line;
+					changed = true;
 				}
 			}
+			down = !down;
+		}
+	}
+	/// Checks the well against the validation rules, see `WellValidation`.
+	///
+	/// Full rows are checked first, bottom row first. Then the unsupported blocks, bottom row first and left to right.
+	/// The error points at the first offending cell.
+	pub fn validate(&self, flags: WellValidation) -> Result<(), ValidationError> {
+		if flags.contains(WellValidation::NO_FULL_ROWS) {
+			if let Some(row) = self.lines().iter().position(|&line| line == self.line_mask()) {
+				return Err(ValidationError::FullRow { row: row as i8 });
+			}
 		}
-		// Since we're flooding top to bottom first, this case is considerably more rare
-		if y + 1 < self.height as usize && self.field[y + 1] & mask != mask {
-			for it in range.clone() {
-				if self.field[y + 1] & it == 0 {
-					self._flood_fill(y + 1, it);
+		if flags.contains(WellValidation::REQUIRE_SUPPORTED) {
+			// Grow through the blocks from the ones on the floor
+			let mut fill = [0; MAX_HEIGHT];
+			fill[0] = self.field[0];
+			self.grow(&mut fill, &self.field);
+			for (row, (&line, &fill)) in self.lines().iter().zip(&fill).enumerate() {
+				let floating = line & !fill;
+				if floating != 0 {
+					let cell = Point::new(floating.leading_zeros() as i8, row as i8);
+					return Err(ValidationError::Unsupported { cell: cell });
 				}
 			}
 		}
+		Ok(())
+	}
+	/// Parses the well from text like `FromStr` and checks it against the validation rules.
+	pub fn parse_validated(s: &str, flags: WellValidation) -> Result<Well, ParseWellError> {
+		let well: Well = s.parse()?;
+		well.validate(flags).map_err(ParseWellError::Invalid)?;
+		Ok(well)
+	}
+}
+
+/// Validation rules for wells from untrusted sources, see `Well::validate`.
+///
+/// The rules combine with `|`, no rules accept any well.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WellValidation(u8);
+impl WellValidation {
+	/// Accepts any well.
+	pub const ALLOW_ANY: WellValidation = WellValidation(0x00);
+	/// Every block rests on the floor or is connected left, right, up or down through other blocks to a block on the floor.
+	///
+	/// Rejects floating blocks for modes which guarantee gravity-compacted garbage, the holes of cheese rows are fine.
+	pub const REQUIRE_SUPPORTED: WellValidation = WellValidation(0x01);
+	/// No row is complete, the lines are cleared as soon as they complete.
+	pub const NO_FULL_ROWS: WellValidation = WellValidation(0x02);
+
+	/// Returns if all the given rules are enabled.
+	pub fn contains(self, flags: WellValidation) -> bool {
+		self.0 & flags.0 == flags.0
+	}
+}
+impl ops::BitOr for WellValidation {
+	type Output = WellValidation;
+	fn bitor(self, rhs: WellValidation) -> WellValidation {
+		WellValidation(self.0 | rhs.0)
 	}
 }
 
+/// Errors when validating a well, see `Well::validate`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ValidationError {
+	/// The block at the cell isn't supported, see `WellValidation::REQUIRE_SUPPORTED`.
+	Unsupported { cell: Point },
+	/// The row is complete, see `WellValidation::NO_FULL_ROWS`.
+	FullRow { row: i8 },
+}
+impl ValidationError {
+	/// Returns the offending cell, the leftmost cell of a full row.
+	pub fn cell(self) -> Point {
+		match self {
+			ValidationError::Unsupported { cell } => cell,
+			ValidationError::FullRow { row } => Point::new(0, row),
+		}
+	}
+}
+impl fmt::Display for ValidationError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ValidationError::Unsupported { cell } => write!(f, "unsupported block at ({}, {})", cell.x, cell.y),
+			ValidationError::FullRow { row } => write!(f, "row {} is complete", row),
+		}
+	}
+}
+impl error::Error for ValidationError {}
+
 /// Errors when parsing a well from text.
+///
+/// Lines are numbered from zero in the text, floor lines included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum ParseWellError {
 	/// The string is empty.
 	Empty,
-	/// The well has no walls.
-	BadWalls,
-	/// The well has inconsistent width.
-	InWidth,
-	/// The well is too wide.
-	OutWidth,
-	/// The well is too high.
-	OutHeight,
+	/// The line is not enclosed in walls.
+	BadWalls { line: usize },
+	/// The line is not as wide as the first line of the well.
+	InWidth { line: usize, expected: usize, found: usize },
+	/// The line is too wide.
+	OutWidth { line: usize },
+	/// The well is too high at the line.
+	OutHeight { line: usize },
+	/// The well breaks the validation rules, see `Well::parse_validated`.
+	Invalid(ValidationError),
+}
+impl ParseWellError {
+	/// Returns the line of the error, `None` if the well is empty or invalid.
+	pub fn line(self) -> Option<usize> {
+		match self {
+			ParseWellError::Empty | ParseWellError::Invalid(_) => None,
+			ParseWellError::BadWalls { line } | ParseWellError::InWidth { line, .. } |
+			ParseWellError::OutWidth { line } | ParseWellError::OutHeight { line } => Some(line),
+		}
+	}
 }
+impl fmt::Display for ParseWellError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ParseWellError::Empty => f.write_str("empty well"),
+			ParseWellError::BadWalls { line } => write!(f, "line {}: well lines must be enclosed in walls", line),
+			ParseWellError::InWidth { line, expected, found } => write!(f, "line {}: well line is {} wide, expected {}", line, found, expected),
+			ParseWellError::OutWidth { line } => write!(f, "line {}: well is too wide", line),
+			ParseWellError::OutHeight { line } => write!(f, "line {}: well is too high", line),
+			ParseWellError::Invalid(err) => fmt::Display::fmt(&err, f),
+		}
+	}
+}
+impl error::Error for ParseWellError {}
 impl FromStr for Well {
 	type Err = ParseWellError;
 	fn from_str(s: &str) -> Result<Well, ParseWellError> {
@@ -298,43 +567,45 @@ impl FromStr for Well {
 		let mut height = 0;
 		let mut field = [0; MAX_HEIGHT];
 
-		for line in s.lines() {
-			let line = line.trim_right();
+		// Lines in visual order, the floor is optional
+		for (index, line) in s.lines().enumerate().filter(|&(_, line)| !line.starts_with('+')) {
+			let line = line.trim_end();
 			if line.len() < 3 {
-				return Err(ParseWellError::BadWalls);
+				return Err(ParseWellError::BadWalls { line: index });
 			}
 			let bline = line.as_bytes();
 			if bline[0] != b'|' || bline[bline.len() - 1] != b'|' {
-				return Err(ParseWellError::BadWalls);
+				return Err(ParseWellError::BadWalls { line: index });
 			}
 			let mut w = 0;
 			let mut row = 0;
 			let line = &line[1..line.len() - 1];
 			for c in line.chars() {
-				let bit = if c == ' ' { 0 } else { 1 };
-				row |= bit << w;
-				w += 1;
 				if w >= MAX_WIDTH {
-					return Err(ParseWellError::OutWidth);
+					return Err(ParseWellError::OutWidth { line: index });
 				}
+				let bit = if c == ' ' { 0 } else { 1 };
+				row |= bit << (SIZE_OF_WIDTH - 1 - w);
+				w += 1;
 			}
 
 			if let Some(prev_width) = width {
 				if prev_width != w {
-					return Err(ParseWellError::InWidth);
+					return Err(ParseWellError::InWidth { line: index, expected: prev_width, found: w });
 				}
 			}
 			else {
 				width = Some(w);
 			}
 
-			field[height] = row;
-
-			height += 1;
 			if height >= MAX_HEIGHT {
-				return Err(ParseWellError::OutHeight);
+				return Err(ParseWellError::OutHeight { line: index });
 			}
+			field[height] = row;
+			height += 1;
 		}
+		// Stored bottom line first
+		field[..height].reverse();
 
 		if let Some(width) = width {
 			Ok(Well {
@@ -344,7 +615,7 @@ impl FromStr for Well {
 			})
 		}
 		else {
-			return Err(ParseWellError::Empty);
+			Err(ParseWellError::Empty)
 		}
 	}
 }
@@ -374,6 +645,38 @@ impl fmt::Display for Well {
 	}
 }
 
+/// Union of the blocks in both wells.
+///
+/// # Panics
+///
+/// Panics if the wells differ in dimensions.
+impl ops::BitOr for Well {
+	type Output = Well;
+	fn bitor(mut self, rhs: Well) -> Well {
+		self.assert_same_dims(&rhs);
+		for (line, &other) in self.field.iter_mut().zip(rhs.field.iter()) {
+			*line |= other;
+		}
+		self
+	}
+}
+
+/// Difference, the blocks in the left well which are not in the right well.
+///
+/// # Panics
+///
+/// Panics if the wells differ in dimensions.
+impl ops::Sub for Well {
+	type Output = Well;
+	fn sub(mut self, rhs: Well) -> Well {
+		self.assert_same_dims(&rhs);
+		for (line, &other) in self.field.iter_mut().zip(rhs.field.iter()) {
+			*line &= !other;
+		}
+		self
+	}
+}
+
 //----------------------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -419,6 +722,7 @@ mod tests {
 	use super::*;
 
 	#[test]
+	#[allow(clippy::assertions_on_constants)]
 	fn static_assert() {
 		// These shouldn't need to be tests but for the lack of const fn.
 		let size_of_line = ::std::mem::size_of::<Line>() * 8;
@@ -427,15 +731,38 @@ mod tests {
 		assert!(MAX_HEIGHT < 123);
 	}
 
+
+	#[test]
+	fn difference() {
+		let well: Well = "|          |\n|          |\n|   #      |\n|## ##  ###|\n|##### ####|".parse().unwrap();
+		let mut placed = well;
+		placed.etch_fast(Piece::T, Rot::Two, Point::new(4, 3));
+		let added = placed - well;
+		assert_eq!(4, added.count_blocks());
+		assert_eq!(Well::new(10, 5), well - placed - well);
+		assert_eq!(placed, well | added);
+		assert_eq!(placed, added | well);
+		// The changed rows carry the lines after the placement
+		let rows: Vec<_> = placed.diff_lines(&well).collect();
+		assert_eq!(vec![(1, placed.line(1)), (2, placed.line(2))], rows);
+		assert_eq!(4, rows.iter().map(|&(row, line)| (line ^ well.line(row)).count_ones()).sum::<u32>());
+		assert_eq!(0, well.diff_lines(&well).count());
+	}
+
+	#[test]
+	#[should_panic]
+	fn difference_dims() {
+		let _ = Well::new(10, 22) - Well::new(10, 20);
+	}
 	#[test]
 	fn render() {
 		let sprite = Sprite { pix: [ 0b1000, 0b0111, 0b1110, 0b0001 ] };
 		let rendered = Well::render(&sprite, 1);
 		assert_eq!(rendered, [
-			0b1000 << 11,
-			0b0111 << 11,
-			0b1110 << 11,
-			0b0001 << 11,
+			0b1000 << 27,
+			0b0111 << 27,
+			0b1110 << 27,
+			0b0001 << 27,
 		]);
 	}
 
@@ -444,18 +771,18 @@ mod tests {
 		let well = Well::new(4, 4);
 		let mut range = well.col_range();
 
-		assert_eq!(     0b1111 << 12 , range.mask());
+		assert_eq!(     0b1111 << 28 , range.mask());
 
-		assert_eq!(Some(0b1000 << 12), range.next());
-		assert_eq!(     0b0111 << 12 , range.mask());
+		assert_eq!(Some(0b1000 << 28), range.next());
+		assert_eq!(     0b0111 << 28 , range.mask());
 
-		assert_eq!(Some(0b0100 << 12), range.next());
-		assert_eq!(     0b0011 << 12 , range.mask());
+		assert_eq!(Some(0b0100 << 28), range.next());
+		assert_eq!(     0b0011 << 28 , range.mask());
 
-		assert_eq!(Some(0b0001 << 12), range.next_back());
-		assert_eq!(     0b0010 << 12 , range.mask());
+		assert_eq!(Some(0b0001 << 28), range.next_back());
+		assert_eq!(     0b0010 << 28 , range.mask());
 
-		assert_eq!(Some(0b0010 << 12), range.next());
+		assert_eq!(Some(0b0010 << 28), range.next());
 
 		assert_eq!(None, range.next());
 		assert_eq!(None, range.next_back());
@@ -505,6 +832,406 @@ mod tests {
 		]);
 		assert_eq!(result, well);
 	}
+
+	#[test]
+	fn heights() {
+		assert_eq!([0; MAX_WIDTH], Well::new(10, 6).heights());
+		assert_eq!(0, Well::new(10, 6).stack_height());
+		// The fixture of the flood fill test
+		let well = Well::from_data(10, &[
+			0b0000000011,
+			0b0000011011,
+			0b0001100100,
+			0b1000000100,
+			0b0100101000,
+			0b0011010000,
+		]);
+		assert_eq!([3, 2, 1, 4, 4, 5, 5, 4, 6, 6], well.heights()[..10]);
+		assert!(well.heights()[10..].iter().all(|&height| height == 0));
+		assert_eq!(6, well.stack_height());
+		// The result of the etch test
+		let well = Well::from_data(10, &[
+			0b0000000001,
+			0b0000000001,
+			0b1100010001,
+			0b1101110001,
+		]);
+		assert_eq!([2, 2, 0, 1, 1, 2, 0, 0, 0, 4], well.heights()[..10]);
+		assert_eq!(4, well.stack_height());
+		// Full lines count towards the heights
+		let well = Well::from_data(4, &[0b0000, 0b0100, 0b1111, 0b1001]);
+		assert_eq!([2, 3, 2, 2, 0], well.heights()[..5]);
+		assert_eq!(3, well.stack_height());
+	}
+
+	#[test]
+	fn clear_lines() {
+		// Full rows 0, 2 and 4 with a block left between them
+		let mut well = Well::from_data(6, &[
+			0b000000,
+			0b111111,
+			0b100000,
+			0b111111,
+			0b011111,
+			0b111111,
+		]);
+		assert_eq!((true, false, true), (well.is_line_full(0), well.is_line_full(1), well.is_line_full(4)));
+		let expected = Well::from_data(6, &[
+			0b000000,
+			0b000000,
+			0b000000,
+			0b000000,
+			0b100000,
+			0b011111,
+		]);
+		let mut removed = well;
+		for row in [4, 2, 0] {
+			removed.remove_line(row);
+		}
+		assert_eq!(3, well.clear_full_lines());
+		assert_eq!(expected, well);
+		assert_eq!(removed, well);
+		assert_eq!(0, well.clear_full_lines());
+		// Clearing a line leaves the lines above in place
+		assert_eq!(0b011111 << 26, well.clear_line(0));
+		assert_eq!(0, well.line(0));
+		assert_eq!(0b100000 << 26, well.line(1));
+	}
+
+	#[test]
+	fn validate() {
+		let any = WellValidation::ALLOW_ANY;
+		let supported = WellValidation::REQUIRE_SUPPORTED;
+		let no_full = WellValidation::NO_FULL_ROWS;
+		assert_eq!(WellValidation::default(), any);
+		// Cheese rows hold up the blocks above their holes, the overhang hangs on to the stack
+		let cheese = Well::from_data(6, &[
+			0b000000,
+			0b011100,
+			0b010000,
+			0b111011,
+			0b101111,
+			0b110111,
+		]);
+		for &flags in &[any, supported, no_full, supported | no_full] {
+			assert_eq!(Ok(()), cheese.validate(flags));
+		}
+		// A block floating above an empty row, and one only touching the stack diagonally
+		let floating = Well::from_data(6, &[
+			0b000000,
+			0b000100,
+			0b000000,
+			0b000010,
+			0b111101,
+			0b111101,
+		]);
+		assert_eq!(Ok(()), floating.validate(any));
+		assert_eq!(Ok(()), floating.validate(no_full));
+		assert_eq!(Err(ValidationError::Unsupported { cell: Point::new(4, 2) }), floating.validate(supported));
+		assert_eq!(Point::new(4, 2), floating.validate(supported).unwrap_err().cell());
+		// Full rows, the lowest is reported before the floating blocks
+		let mut full = floating;
+		full.set_line(3, full.line_mask());
+		full.set_line(1, full.line_mask());
+		assert_eq!(Ok(()), full.validate(any));
+		assert_eq!(Ok(()), full.validate(supported));
+		assert_eq!(Err(ValidationError::FullRow { row: 1 }), full.validate(no_full | supported));
+		assert_eq!("row 1 is complete", ValidationError::FullRow { row: 1 }.to_string());
+		// Parsing text enforces the rules
+		let text = floating.to_string();
+		assert_eq!(Ok(floating), Well::parse_validated(&text, any));
+		assert_eq!(text.parse::<Well>(), Well::parse_validated(&text, no_full));
+		let err = Well::parse_validated(&text, supported).unwrap_err();
+		assert_eq!(ParseWellError::Invalid(ValidationError::Unsupported { cell: Point::new(4, 2) }), err);
+		assert_eq!((None, "unsupported block at (4, 2)"), (err.line(), &*err.to_string()));
+	}
+
+	#[test]
+	fn parse() {
+		let well = Well::from_data(10, &[
+			0b0000000001,
+			0b0000000001,
+			0b1100010001,
+			0b1101110001,
+		]);
+		let text = format!("{}", well);
+		assert_eq!(Ok(well), text.parse::<Well>().map_err(|_| ()));
+		// The floor is optional
+		assert_eq!(Ok(well), text.lines().take(4).collect::<Vec<_>>().join("\n").parse::<Well>().map_err(|_| ()));
+		let wide = Well::from_data(MAX_WIDTH as i8, &[1, 1 << (MAX_WIDTH - 1), 0, 0]);
+		assert_eq!(Ok(wide), format!("{}", wide).parse::<Well>().map_err(|_| ()));
+		assert!(format!("|{}|", " ".repeat(MAX_WIDTH + 1)).parse::<Well>().is_err());
+	}
+
+	#[test]
+	fn const_wells() {
+		const EMPTY: Well = Well::new(STANDARD_WIDTH, STANDARD_HEIGHT);
+		static CUP: Well = Well::from_data(4, &[
+			0b0000,
+			0b0000,
+			0b1001,
+			0b1111,
+		]);
+		assert_eq!(Well::new(STANDARD_WIDTH, STANDARD_HEIGHT), EMPTY);
+		assert_eq!(Well::from_data(4, &[0, 0, 0b1001, 0b1111]), CUP);
+		assert_eq!(&[0b1111 << 28, 0b1001 << 28, 0, 0], CUP.lines());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn parse_round_trip() {
+		use ::rand::Rng;
+		let mut rng = ::garbage::xorshift(0x5eed);
+		for &width in &[4, 5, 7, 10, 16, MAX_WIDTH as i8] {
+			for height in 4..MAX_HEIGHT as i8 + 1 {
+				let lines: Vec<Line> = (0..height).map(|_| rng.gen::<Line>() & ((1 << width) - 1)).collect();
+				let well = Well::from_data(width, &lines);
+				let text = well.to_string();
+				assert_eq!(Ok(well), text.parse::<Well>(), "\n{}", text);
+			}
+		}
+		// The left column is the high bit of the line and the top line is printed first
+		let well: Well = "|■   |\n|    |\n|    |\n|   ■|".parse().unwrap();
+		assert_eq!(Well::from_data(4, &[0b1000, 0, 0, 0b0001]), well);
+		assert!(well.test(::Player::new(::Piece::O, ::Rot::Zero, Point::new(-1, 4)).sprite(), Point::new(-1, 4)));
+		assert!(!well.test(::Player::new(::Piece::O, ::Rot::Zero, Point::new(1, 4)).sprite(), Point::new(1, 4)));
+	}
+
+	#[test]
+	fn parse_errors() {
+		let parse = |text: &str| text.parse::<Well>().unwrap_err();
+		assert_eq!(ParseWellError::Empty, parse(""));
+		assert_eq!(ParseWellError::Empty, parse("+----+"));
+		assert_eq!(None, ParseWellError::Empty.line());
+		assert_eq!(ParseWellError::BadWalls { line: 0 }, parse("||"));
+		assert_eq!(ParseWellError::BadWalls { line: 2 }, parse("|    |\n|    |\n|     \n|    |"));
+		// Empty lines are not part of the well
+		assert_eq!(ParseWellError::BadWalls { line: 1 }, parse("|    |\n\n|    |"));
+		let err = parse("|    |\n|    |\n|    |\n| ■ |\n+----+");
+		assert_eq!(ParseWellError::InWidth { line: 3, expected: 4, found: 3 }, err);
+		assert_eq!(Some(3), err.line());
+		assert_eq!("line 3: well line is 3 wide, expected 4", err.to_string());
+		// Floor lines count towards the line numbers
+		assert_eq!(ParseWellError::InWidth { line: 2, expected: 4, found: 5 }, parse("+----+\n|    |\n|     |"));
+		assert_eq!(ParseWellError::OutWidth { line: 1 }, parse(&format!("|  |\n|{}|", " ".repeat(MAX_WIDTH + 1))));
+		let tall = vec!["|    |"; MAX_HEIGHT + 1].join("\n");
+		assert_eq!(ParseWellError::OutHeight { line: MAX_HEIGHT }, parse(&tall));
+		assert_eq!("line 23: well is too high", parse(&tall).to_string());
+	}
+
+	#[test]
+	fn wide_wells() {
+		use ::{Piece, Rot, Player};
+		for &width in &[16, 20, MAX_WIDTH as i8] {
+			let mut well = Well::new(width, 22);
+			assert_eq!(width as u32, well.line_mask().count_ones());
+			assert_eq!(width as usize, well.col_range().count());
+			// Flat I pieces against either wall, one more step collides
+			let left = Player::new(Piece::I, Rot::Zero, Point::new(0, 1));
+			let right = Player::new(Piece::I, Rot::Zero, Point::new(width - 4, 1));
+			assert!(!well.test(left.sprite(), left.pt) && !well.test(right.sprite(), right.pt));
+			assert!(well.test(left.sprite(), Point::new(-1, 1)) && well.test(right.sprite(), Point::new(width - 3, 1)));
+			well.etch(left.sprite(), left.pt);
+			well.etch(right.sprite(), right.pt);
+			let heights = well.heights();
+			assert!(heights.iter().enumerate().all(|(col, &height)| height == (col < 4 || (col >= width as usize - 4 && col < width as usize)) as i8));
+			// Filling the gap completes the line
+			let gap = well.line_mask() & !well.line(0);
+			assert_eq!(width as u32 - 8, gap.count_ones());
+			well.set_line(0, well.line(0) | gap);
+			assert_eq!(well.line_mask(), well.line(0));
+			assert_eq!(0, well.count_holes());
+		}
+	}
+
+	// Reference flood fill one block at a time
+	fn naive_holes(well: &Well) -> i32 {
+		let (width, height) = (well.width() as usize, well.height() as usize);
+		let blocked = |x: usize, y: usize| well.line(y as i8) & well.col_range().nth(x).unwrap() != 0;
+		let mut reached = vec![false; width * height];
+		let mut stack: Vec<(usize, usize)> = (0..width).filter(|&x| !blocked(x, height - 1)).map(|x| (x, height - 1)).collect();
+		while let Some((x, y)) = stack.pop() {
+			if reached[y * width + x] || blocked(x, y) {
+				continue;
+			}
+			reached[y * width + x] = true;
+			if x > 0 { stack.push((x - 1, y)); }
+			if x + 1 < width { stack.push((x + 1, y)); }
+			if y > 0 { stack.push((x, y - 1)); }
+			if y + 1 < height { stack.push((x, y + 1)); }
+		}
+		(0..width * height).filter(|&i| !reached[i] && !blocked(i % width, i / width)).count() as i32
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn flood_fill_matches_naive() {
+		use ::rand::Rng;
+		let mut rng = ::garbage::xorshift(3);
+		for _ in 0..200 {
+			let width = rng.gen_range(4, MAX_WIDTH as i8 + 1);
+			let height = rng.gen_range(4, MAX_HEIGHT as i8 + 1);
+			// Leave the lines above the stack empty, a full well is as likely as an empty one
+			let stack = rng.gen_range(0, height + 1);
+			let mut well = Well::new(width, height);
+			for row in 0..stack {
+				well.set_line(row, rng.gen::<Line>() & well.line_mask());
+			}
+			assert_eq!(naive_holes(&well), well.count_holes(), "{}", well);
+		}
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn test_fast_matches_test() {
+		use ::rand::Rng;
+		let mut rng = ::garbage::xorshift(7);
+		for _ in 0..20 {
+			let width = rng.gen_range(4, MAX_WIDTH as i8 + 1);
+			let height = rng.gen_range(4, MAX_HEIGHT as i8 + 1);
+			let stack = rng.gen_range(0, height + 1);
+			let mut well = Well::new(width, height);
+			for row in 0..stack {
+				well.set_line(row, rng.gen::<Line>() & well.line_mask());
+			}
+			for &piece in &Piece::ALL {
+				for &rot in &Rot::ALL {
+					let sprite = piece.sprite(rot);
+					for x in -5..width + 2 {
+						for y in -2..height + 4 {
+							let pt = Point::new(x, y);
+							assert_eq!(well.test(sprite, pt), well.test_fast(piece, rot, pt), "{:?} {:?} {:?}\n{}", piece, rot, pt, well);
+							if x >= -3 && x < MAX_WIDTH as i8 {
+								let (mut slow, mut fast) = (well, well);
+								slow.etch(sprite, pt);
+								fast.etch_fast(piece, rot, pt);
+								assert_eq!(slow, fast, "{:?} {:?} {:?}", piece, rot, pt);
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn flood_fill_maze() {
+		// A winding corridor through every row, a single gap alternating between the walls
+		let mut well = Well::new(MAX_WIDTH as i8, MAX_HEIGHT as i8);
+		let (first, last) = (well.col_range().next().unwrap(), well.col_range().next_back().unwrap());
+		for row in (1..MAX_HEIGHT as i8 - 1).step_by(2) {
+			let gap = if row % 4 == 1 { first } else { last };
+			well.set_line(row, well.line_mask() & !gap);
+		}
+		assert_eq!(0, well.count_holes());
+		assert_eq!(naive_holes(&well), well.count_holes());
+		// A checkerboard only reaches into the top row
+		let mut well = Well::new(12, MAX_HEIGHT as i8);
+		for row in 0..MAX_HEIGHT as i8 {
+			let line = well.col_range().enumerate().filter(|&(col, _)| (col + row as usize).is_multiple_of(2)).fold(0, |line, (_, col)| line | col);
+			well.set_line(row, line);
+		}
+		assert_eq!(6 * (MAX_HEIGHT as i32 - 1), well.count_holes());
+		assert_eq!(naive_holes(&well), well.count_holes());
+		// Filling from a block fills nothing
+		let mut filled = well;
+		filled.flood_fill(Point::new(0, 0));
+		assert_eq!(well, filled);
+	}
+
+	#[test]
+	fn holes_with_full_center_column() {
+		// The seed of the old flood fill sat in the middle column at the top
+		let mut well = Well::new(10, 22);
+		let center = well.col_range().nth(5).unwrap();
+		for row in 0..22 {
+			well.set_line(row, center);
+		}
+		// A single covered hole in the bottom left corner
+		let (col0, col1) = (well.col_range().next().unwrap(), well.col_range().nth(1).unwrap());
+		well.set_line(1, center | col0);
+		well.set_line(0, center | col1);
+		assert_eq!(1, well.count_holes());
+		let reachable = well.reachable_mask();
+		assert_eq!(well.line_mask() & !center, reachable[21]);
+		assert_eq!(well.line_mask() & !center & !col0 & !col1, reachable[0]);
+		assert!(reachable[22..].iter().all(|&line| line == 0));
+
+		// A full top row seals the well
+		well.set_line(21, well.line_mask());
+		assert_eq!(0, well.reachable_mask().iter().map(|line| line.count_ones()).sum::<u32>());
+		assert_eq!(10 * 22 - well.count_blocks() as i32, well.count_holes());
+	}
+
+	// Reference collision test cell by cell
+	fn naive_test(well: &Well, sprite: &Sprite, pt: Point) -> bool {
+		for y in 0..4 {
+			for k in 0..4 {
+				if sprite.pix[y as usize] >> k & 1 == 0 {
+					continue;
+				}
+				let (col, row) = (pt.x + 3 - k, pt.y - y);
+				if col < 0 || col >= well.width() || row < 0 {
+					return true;
+				}
+				if row < well.height() && well.line(row) & well.col_range().nth(col as usize).unwrap() != 0 {
+					return true;
+				}
+			}
+		}
+		false
+	}
+
+	#[test]
+	fn narrow_wells() {
+		use ::{Piece, Rot, Player, srs_cw, srs_ccw, srs_data_cw, srs_data_ccw};
+		let wells = [
+			Well::new(4, 6),
+			Well::from_data(4, &[0b0000, 0b0000, 0b0000, 0b0000, 0b1001, 0b1101]),
+			Well::new(5, 6),
+			Well::from_data(5, &[0b00000, 0b00000, 0b00000, 0b00000, 0b10001, 0b11011]),
+		];
+		for well in &wells {
+			for &piece in &Piece::ALL {
+				for &rot in &Rot::ALL {
+					let sprite = Player::new(piece, rot, Point::new(0, 0)).sprite();
+					// Including points high above the well where only the walls matter
+					for x in -6..well.width() + 2 {
+						for y in -2..well.height() + 8 {
+							let pt = Point::new(x, y);
+							assert_eq!(naive_test(well, sprite, pt), well.test(sprite, pt), "{:?} {:?} {:?}\n{}", piece, rot, pt, well);
+						}
+					}
+					// Rotations of every player resting against a wall
+					for x in -3..well.width() {
+						for y in 0..well.height() + 2 {
+							let player = Player::new(piece, rot, Point::new(x, y));
+							let touches_wall = naive_test(well, sprite, Point::new(x - 1, y)) || naive_test(well, sprite, Point::new(x + 1, y));
+							if naive_test(well, sprite, player.pt) || !touches_wall {
+								continue;
+							}
+							let reference = |rotated: Player, kicks: &[Point]| {
+								kicks.iter().map(|&offset| rotated.pt + offset)
+									.find(|&pt| !naive_test(well, rotated.sprite(), pt))
+									.map(|pt| Player::new(piece, rotated.rot, pt))
+									.unwrap_or(player)
+							};
+							assert_eq!(reference(player.rotate_cw(), srs_data_cw(piece, rot)), srs_cw(well, player), "{:?}", player);
+							assert_eq!(reference(player.rotate_ccw(), srs_data_ccw(piece, rot)), srs_ccw(well, player), "{:?}", player);
+						}
+					}
+				}
+			}
+		}
+		// The vertical I fits against either wall of the narrowest well, its sprite box sticking out
+		let well = Well::new(4, 6);
+		let flat = Player::new(Piece::I, Rot::Zero, Point::new(0, 3));
+		assert!(!well.test(flat.sprite(), flat.pt));
+		for &x in &[-2, 1] {
+			let upright = Player::new(Piece::I, Rot::Right, Point::new(x, 3));
+			assert!(!well.test(upright.sprite(), upright.pt), "{:?}", upright);
+		}
+	}
 /*
 	#[test]
 	fn test_player_test() {
@@ -0,0 +1,83 @@
+/*!
+Feature matrix smoke tests.
+
+Every optional feature exercises at least one of its public APIs when enabled, the core always runs.
+Run under each entry of `SUPPORTED_FEATURE_SETS`, eg. `cargo test --no-default-features --features testing,log`.
+*/
+
+extern crate tetrs;
+
+use tetrs::{Bag, OfficialBag, State};
+
+#[test]
+fn enabled_features() {
+	let expected: Vec<&str> = [
+		("std", cfg!(feature = "std")),
+		("bot", cfg!(feature = "bot")),
+		("scene", cfg!(feature = "scene")),
+		("testing", cfg!(feature = "testing")),
+		("log", cfg!(feature = "log")),
+		("soak", cfg!(feature = "soak")),
+	].iter().filter(|&&(_, on)| on).map(|&(name, _)| name).collect();
+	assert_eq!(expected, tetrs::enabled_features());
+	assert_eq!(expected.len(), tetrs::FEATURES.iter().filter(|name| expected.contains(name)).count());
+	assert!(tetrs::is_supported_feature_set(&expected), "unsupported feature set {:?}", expected);
+}
+
+#[test]
+fn core() {
+	let mut state = State::standard();
+	let mut bag = OfficialBag::from_seed(7);
+	assert_eq!(tetrs::SpawnResult::Ok, state.spawn(bag.next(state.well()).unwrap()));
+	assert!(state.move_left());
+	state.hard_drop();
+	assert!(state.player().is_none());
+	assert_eq!(4, state.well().count_blocks());
+}
+
+#[cfg(feature = "bot")]
+#[test]
+fn bot() {
+	use tetrs::{PlayI, Weights, WorstBag};
+	let mut state = State::standard();
+	let mut bag = WorstBag::new(Weights::default());
+	for _ in 0..10 {
+		let piece = bag.next(state.well()).unwrap();
+		assert_eq!(tetrs::SpawnResult::Ok, state.spawn(piece));
+		let play = PlayI::play(&Weights::default(), state.well(), *state.player().unwrap());
+		assert!(!play.actions.is_empty());
+		state.hard_drop();
+		state.clear_lines(|_| ());
+	}
+}
+
+#[cfg(feature = "scene")]
+#[test]
+fn scene() {
+	let mut state = State::standard();
+	state.spawn(tetrs::Piece::O);
+	state.hard_drop();
+	let scene = state.scene();
+	let (width, height) = state.dimensions();
+	assert_eq!((width, height), (scene.width(), scene.height()));
+	let filled = (0..height).map(|row| scene.line(row).iter().filter(|tile| tile.tile_ty() == tetrs::TileTy::Field).count()).sum::<usize>();
+	assert_eq!(4, filled);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn testing() {
+	tetrs::assert_bag_conformance(|| OfficialBag::from_seed(11));
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn log() {
+	tetrs::set_log_sink(Some(tetrs::collect_sink));
+	let _ = tetrs::take_collected();
+	let mut state = State::standard();
+	state.spawn(tetrs::Piece::T);
+	state.hard_drop();
+	tetrs::set_log_sink(None);
+	assert!(tetrs::take_collected().iter().any(|record| matches!(*record, tetrs::LogRecord::Lock { .. })));
+}
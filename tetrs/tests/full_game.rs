@@ -0,0 +1,345 @@
+/*!
+Plays one full game through every public subsystem as a compatibility check.
+
+A seeded bag feeds a `NextQueue`, the bot's plans are turned into a key timeline for `Input`,
+the locks feed the line clears and the `Telemetry`, the state records its events for the replay
+and the scene is rendered and published through a `SnapshotBuffer` at fixed ticks.
+Halfway through the game is saved and loaded again, the loaded game must stay in lockstep with the original.
+
+Every artifact is pinned by a golden file in `tests/full_game/`.
+Changes to gameplay show up as a diff against these, rerun with `TETRS_BLESS=1` to accept them.
+*/
+
+extern crate rand;
+extern crate tetrs;
+
+use std::{env, fs};
+use std::path::PathBuf;
+
+use rand::XorShiftRng;
+use tetrs::{Action, Actions, Bag, Clock, CompactStyle, Dir, Event, Input, NextQueue, OfficialBag, Occurrence, PlayI, SnapshotBuffer, State, Telemetry, Weights, srs_180, srs_ccw, srs_cw, test_player, verify_queue};
+use tetrs::scoring::attack_lines;
+
+//----------------------------------------------------------------
+
+const SEED: u64 = 2024;
+const CLOCK: Clock = Clock { gravity: 20, player: 3, lock_delay: 12 };
+// Entry delay between locking and spawning the next piece
+const ARE: u32 = 6;
+const TICKS: u64 = 2400;
+const SAVE_AT: u64 = 1200;
+const SCENES_AT: [u64; 4] = [300, 900, 1800, TICKS - 1];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Key {
+	Left,
+	Right,
+	RotateCW,
+	RotateCCW,
+	Rotate180,
+	/// Held until the player reaches the row.
+	SoftDrop(i8),
+	HardDrop,
+}
+
+#[derive(Clone)]
+struct Game {
+	state: State,
+	queue: NextQueue<OfficialBag<XorShiftRng>>,
+	input: Input,
+	telemetry: Telemetry,
+	// The bot's plan for the current piece as keys, the held key and the next key to press
+	script: Vec<Key>,
+	held: Option<Key>,
+	step: usize,
+	are: u32,
+	tick: u64,
+	// Recorded artifacts
+	events: Vec<Event>,
+	keys: String,
+	pieces: u32,
+	lines: u32,
+	rejected: u32,
+}
+
+impl Game {
+	fn new() -> Game {
+		let mut game = Game {
+			state: State::standard(),
+			queue: NextQueue::new(OfficialBag::from_seed(SEED)),
+			input: Input::new(CLOCK),
+			telemetry: Telemetry::new(60, 10),
+			script: Vec::new(),
+			held: None,
+			step: 0,
+			are: 0,
+			tick: 0,
+			events: Vec::new(),
+			keys: String::new(),
+			pieces: 0,
+			lines: 0,
+			rejected: 0,
+		};
+		game.state.record_events(true);
+		game
+	}
+	// Saves the game and loads it again, the state goes through a diff against a blank state
+	fn reload(&self) -> Game {
+		let (width, height) = self.state.dimensions();
+		let save = State::new(width, height).diff(&self.state).unwrap();
+		let mut game = self.clone();
+		game.state = State::new(width, height);
+		save.apply(&mut game.state).unwrap();
+		game.state.record_events(true);
+		game
+	}
+	fn tick(&mut self) {
+		if self.state.player().is_none() && !self.state.is_game_over() {
+			if self.are > 0 {
+				self.are -= 1;
+			}
+			else {
+				self.spawn();
+			}
+		}
+		self.press();
+		let report = self.input.tick_report(&mut self.state);
+		self.release();
+		self.rejected += report.occurrences.iter().filter(|entry| matches!(entry.occurrence, Occurrence::MoveRejected { .. })).count() as u32;
+		if report.actions.contains(Actions::LOCK) {
+			let info = self.state.clear_lines_ex();
+			self.pieces += 1;
+			self.lines += info.lines as u32;
+			self.telemetry.lock(attack_lines(info.lines), info.combo);
+			self.are = ARE;
+		}
+		self.telemetry.tick();
+		self.events.extend(self.state.take_events());
+		self.tick += 1;
+	}
+	fn spawn(&mut self) {
+		let piece = match self.queue.next(self.state.well()) { Some(piece) => piece, None => return };
+		if self.state.spawn(piece).is_blocked() {
+			return;
+		}
+		// The bot plans one row at a time, runs of drops are held down to the row they end on
+		let well = *self.state.well();
+		let mut player = *self.state.player().unwrap();
+		let bot = PlayI::play(&Weights::default(), &well, player);
+		self.script.clear();
+		for &action in &bot.actions {
+			let key = match action {
+				Action::MoveLeft => { player = player.move_left(); Key::Left },
+				Action::MoveRight => { player = player.move_right(); Key::Right },
+				Action::RotateCW => { player = srs_cw(&well, player); Key::RotateCW },
+				Action::RotateCCW => { player = srs_ccw(&well, player); Key::RotateCCW },
+				Action::Rotate180 => { player = srs_180(&well, player); Key::Rotate180 },
+				Action::SoftDrop if !test_player(&well, player.move_down()) => { player = player.move_down(); Key::SoftDrop(player.pt.y) },
+				_ => continue,
+			};
+			match (self.script.last_mut(), key) {
+				(Some(&mut Key::SoftDrop(ref mut row)), Key::SoftDrop(to)) => *row = to,
+				_ => self.script.push(key),
+			}
+		}
+		assert_eq!(bot.player, Some(player));
+		self.script.push(Key::HardDrop);
+		self.step = 0;
+		self.log(format_args!("spawn {:?}", piece));
+	}
+	// Moves are tapped for a single tick, soft drop is held until the player reaches the row
+	fn press(&mut self) {
+		if self.state.player().is_none() {
+			return;
+		}
+		if let Some(Key::SoftDrop(row)) = self.held {
+			let player = self.state.player().unwrap();
+			if player.pt.y > row && self.state.move_rejection(Dir::Down).is_none() {
+				return;
+			}
+			self.input.soft_drop_up();
+			self.held = None;
+			self.step += 1;
+			self.log(format_args!("-SoftDrop"));
+		}
+		let key = match self.script.get(self.step) { Some(&key) => key, None => return };
+		match key {
+			Key::Left => self.input.move_left_down(),
+			Key::Right => self.input.move_right_down(),
+			Key::SoftDrop(_) => self.input.soft_drop_down(),
+			Key::RotateCW => self.input.rotate_cw(),
+			Key::RotateCCW => self.input.rotate_ccw(),
+			Key::Rotate180 => self.input.rotate_180(),
+			Key::HardDrop => self.input.hard_drop(),
+		}
+		match key {
+			Key::Left | Key::Right | Key::SoftDrop(_) => {
+				self.held = Some(key);
+				self.log(format_args!("+{:?}", key));
+			},
+			_ => {
+				self.step += 1;
+				self.log(format_args!("{:?}", key));
+			},
+		}
+	}
+	fn release(&mut self) {
+		match self.held {
+			Some(Key::Left) => self.input.move_left_up(),
+			Some(Key::Right) => self.input.move_right_up(),
+			_ => return,
+		}
+		self.log(format_args!("-{:?}", self.held.unwrap()));
+		self.held = None;
+		self.step += 1;
+	}
+	fn log(&mut self, args: std::fmt::Arguments) {
+		self.keys += &format!("{:5} {}\n", self.tick, args);
+	}
+	// Renders the scene and checks the render thread's copy of it
+	fn scene(&self, buffer: &SnapshotBuffer) -> String {
+		let scene = self.state.scene_view().to_scene();
+		let sequence = buffer.publish_scene(&scene);
+		let mut copy = vec![0u8; buffer.len()];
+		assert_eq!(sequence, buffer.snapshot(&mut copy));
+		let tiles: Vec<u8> = (0..scene.height()).flat_map(|row| scene.line(row).iter().map(|&tile| u8::from(tile))).collect();
+		assert_eq!(tiles, copy, "published scene at tick {}", self.tick);
+		format!("ticks {} checksum {:016x}\n{}\n", self.tick, self.state.checksum(), scene.render_compact(CompactStyle::HalfBlock, true))
+	}
+	fn stats(&self) -> String {
+		format!("ticks: {}\npieces: {}\nlines: {}\nrejected moves: {}\ngame end: {:?}\nchecksum: {:016x}\n{:#?}\n",
+			self.tick, self.pieces, self.lines, self.rejected, self.state.game_end(), self.state.checksum(), self.telemetry.snapshot())
+	}
+}
+
+//----------------------------------------------------------------
+
+// Compact encoding of the events, one event per line in hex
+fn replay_bytes(events: &[Event]) -> String {
+	let mut out = String::new();
+	for (index, event) in events.iter().enumerate() {
+		let bytes = match *event {
+			Event::Spawned(pl) => vec![0, pl.piece as u8, pl.rot as u8, pl.pt.x as u8, pl.pt.y as u8],
+			Event::Locked { player: pl, kick } => vec![1, pl.piece as u8, pl.rot as u8, pl.pt.x as u8, pl.pt.y as u8, kick.unwrap_or(0xff)],
+			Event::LinesCleared(rows) => {
+				let mut bytes = vec![2, rows.len() as u8];
+				bytes.extend(rows.iter().map(|row| row as u8));
+				bytes
+			},
+			Event::GarbageReceived { count, hole, player } => {
+				let mut bytes = vec![3, count, hole as u8];
+				if let Some(pl) = player {
+					bytes.extend(&[pl.piece as u8, pl.rot as u8, pl.pt.x as u8, pl.pt.y as u8]);
+				}
+				bytes
+			},
+			Event::OutOfPieces => vec![4],
+			_ => panic!("unexpected event {:?}", event),
+		};
+		out += &format!("{:04}:", index);
+		for byte in bytes {
+			out += &format!(" {:02x}", byte);
+		}
+		out.push('\n');
+	}
+	out
+}
+
+// Lists the differing lines with their line numbers
+fn diff_lines(expected: &str, actual: &str) -> String {
+	let expected: Vec<&str> = expected.lines().collect();
+	let actual: Vec<&str> = actual.lines().collect();
+	let mut out = String::new();
+	let mut shown = 0;
+	for line in 0..expected.len().max(actual.len()) {
+		let (lhs, rhs) = (expected.get(line), actual.get(line));
+		if lhs == rhs {
+			continue;
+		}
+		if shown == 20 {
+			out += "  ...\n";
+			break;
+		}
+		shown += 1;
+		out += &format!("{:5} - {}\n{:5} + {}\n", line + 1, lhs.unwrap_or(&"<missing>"), line + 1, rhs.unwrap_or(&"<missing>"));
+	}
+	if expected.len() != actual.len() {
+		out += &format!("expected {} lines, found {}\n", expected.len(), actual.len());
+	}
+	out
+}
+
+// Shows the first differing scene next to the expected one
+fn diff_scenes(expected: &str, actual: &str) -> String {
+	let split = |text: &str| -> Vec<String> { text.split("ticks ").filter(|scene| !scene.is_empty()).map(|scene| format!("ticks {}", scene)).collect() };
+	let (expected, actual) = (split(expected), split(actual));
+	for (lhs, rhs) in expected.iter().zip(&actual) {
+		if lhs != rhs {
+			let width = lhs.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+			let mut out = String::from("expected | actual\n");
+			let (lhs, rhs): (Vec<&str>, Vec<&str>) = (lhs.lines().collect(), rhs.lines().collect());
+			for line in 0..lhs.len().max(rhs.len()) {
+				let (l, r) = (lhs.get(line).cloned().unwrap_or(""), rhs.get(line).cloned().unwrap_or(""));
+				let mark = if l == r { ' ' } else { '*' };
+				out += &format!("{}{:width$} | {}\n", mark, l, r, width = width);
+			}
+			return out;
+		}
+	}
+	format!("expected {} scenes, found {}\n", expected.len(), actual.len())
+}
+
+fn check_golden(name: &str, actual: &str, diff: fn(&str, &str) -> String) {
+	let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "full_game", name].iter().collect();
+	if env::var_os("TETRS_BLESS").is_some() {
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(&path, actual).unwrap();
+		return;
+	}
+	let expected = fs::read_to_string(&path).unwrap_or_else(|err| panic!("golden {}: {}, run with TETRS_BLESS=1 to create it", path.display(), err));
+	if expected != actual {
+		panic!("{} differs from the golden, run with TETRS_BLESS=1 if the change is intended\n{}", name, diff(&expected, actual));
+	}
+}
+
+//----------------------------------------------------------------
+
+#[test]
+fn full_game() {
+	let mut game = Game::new();
+	let buffer = SnapshotBuffer::for_scene(game.state.dimensions().0, game.state.dimensions().1);
+	let mut scenes = String::new();
+	let mut loaded = None;
+
+	for tick in 0..TICKS {
+		if tick == SAVE_AT {
+			loaded = Some(game.reload());
+		}
+		game.tick();
+		if let Some(ref mut loaded) = loaded {
+			loaded.tick();
+			assert_eq!(game.state.checksum(), loaded.state.checksum(), "loaded game drifted at tick {}\n{}", tick, diff_lines(&game.keys, &loaded.keys));
+		}
+		if SCENES_AT.contains(&tick) {
+			scenes += &game.scene(&buffer);
+		}
+		if game.state.is_game_over() {
+			break;
+		}
+	}
+	let loaded = loaded.unwrap();
+	assert_eq!(game.keys, loaded.keys);
+	assert_eq!(replay_bytes(&game.events), replay_bytes(&loaded.events));
+	assert_eq!(game.stats(), loaded.stats());
+	assert!(game.pieces > 50 && game.lines > 0, "{}", game.stats());
+
+	// The replay rebuilds the well, the queue replays on a fresh bag
+	let replayed = State::replay_events(&game.events, game.state.dimensions()).unwrap();
+	assert_eq!(game.state.well(), replayed.well());
+	assert_eq!(Ok(()), verify_queue(OfficialBag::from_seed(SEED), game.queue.events()));
+
+	check_golden("keys.txt", &game.keys, diff_lines);
+	check_golden("replay.txt", &replay_bytes(&game.events), diff_lines);
+	check_golden("scenes.txt", &scenes, diff_scenes);
+	check_golden("stats.txt", &game.stats(), diff_lines);
+}
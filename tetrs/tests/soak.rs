@@ -0,0 +1,182 @@
+/*!
+Soak test for the long-lived engine types.
+
+Plays seeded bot sessions tick by tick and checks that memory in use stays flat, that a spectator fed with state diffs
+never drifts and that a reference session with the same seed stays in lockstep.
+
+The short variant runs with the regular tests, enable the `soak` feature for the long run.
+`TETRS_SOAK_TICKS` overrides the number of ticks per session of the long run.
+*/
+
+extern crate rand;
+extern crate tetrs;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{SeedableRng, XorShiftRng};
+use tetrs::{Action, Bag, Clock, Input, OfficialBag, PlayI, State, Telemetry, Weights};
+use tetrs::scoring::attack_lines;
+
+//----------------------------------------------------------------
+
+// Counts the bytes currently allocated by the whole test binary
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let ptr = System.alloc(layout);
+		if !ptr.is_null() {
+			ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+		}
+		ptr
+	}
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout);
+		ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+	}
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		let new_ptr = System.realloc(ptr, layout, new_size);
+		if !new_ptr.is_null() {
+			ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+			ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+		}
+		new_ptr
+	}
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+fn allocated() -> usize {
+	ALLOCATED.load(Ordering::Relaxed)
+}
+
+//----------------------------------------------------------------
+
+const CLOCK: Clock = Clock { gravity: 40, player: 8, lock_delay: 0 };
+
+// Bot driven game session, restarts on game over
+struct Session {
+	state: State,
+	input: Input,
+	bag: OfficialBag<XorShiftRng>,
+	telemetry: Telemetry,
+	plan: Vec<Action>,
+	step: usize,
+	combo: u32,
+	games: u32,
+}
+
+impl Session {
+	fn new(seed: u32) -> Session {
+		Session {
+			state: State::new(10, 16),
+			input: Input::new(CLOCK),
+			bag: OfficialBag::with_rng(XorShiftRng::from_seed([seed, 0x9E3779B9, 0x243F6A88, 0xB7E15162])),
+			telemetry: Telemetry::new(60, 60),
+			plan: Vec::new(),
+			step: 0,
+			combo: 0,
+			games: 0,
+		}
+	}
+	fn tick(&mut self) {
+		if self.state.player().is_none() {
+			if self.state.is_game_over() {
+				self.state = State::new(10, 16);
+				self.games += 1;
+			}
+			let piece = self.bag.next(self.state.well()).unwrap();
+			// Spawn returns `true` when the spawned player overlaps the well
+			if self.state.spawn(piece).is_blocked() {
+				self.state = State::new(10, 16);
+				self.games += 1;
+				return;
+			}
+			let bot = PlayI::play(&Weights::default(), self.state.well(), *self.state.player().unwrap());
+			// Reuse the plan's allocation for every piece
+			self.plan.clear();
+			self.plan.extend_from_slice(&bot.actions);
+			self.step = 0;
+		}
+
+		// One move of the plan per tick, drop once the plan runs out
+		match self.plan.get(self.step) {
+			Some(&Action::SoftDrop) => { self.state.soft_drop_lock(); },
+			Some(&action) => { self.state.step(action); },
+			None => { self.state.hard_drop(); },
+		}
+		self.step += 1;
+		self.input.tick(&mut self.state);
+		self.telemetry.tick();
+
+		if self.state.player().is_none() {
+			let cleared = self.state.clear_lines(|_| ());
+			self.combo = if cleared > 0 { self.combo + 1 } else { 0 };
+			self.telemetry.lock(attack_lines(cleared as u8), self.combo);
+		}
+	}
+	fn checksum(&self) -> u64 {
+		// FNV-1a over the debug representation of everything observable
+		let text = format!("{:?}{:?}{:?}", self.state, self.telemetry.snapshot(), self.games);
+		text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+	}
+}
+
+//----------------------------------------------------------------
+
+fn soak(sessions: u32, ticks: u64) {
+	let check_every = (ticks / 20).max(1);
+	let mut live: Vec<Session> = (0..sessions).map(|seed| Session::new(seed + 1)).collect();
+	let mut reference: Vec<Session> = (0..sessions).map(|seed| Session::new(seed + 1)).collect();
+	let mut spectators: Vec<State> = live.iter().map(|session| session.state.clone()).collect();
+	let mut baseline = None;
+
+	for tick in 1..ticks + 1 {
+		for i in 0..live.len() {
+			live[i].tick();
+			reference[i].tick();
+			// The spectator only ever sees diffs, restarts included
+			let diff = spectators[i].diff(&live[i].state).unwrap();
+			diff.apply(&mut spectators[i]).unwrap();
+		}
+
+		if tick % check_every == 0 {
+			for (i, session) in live.iter().enumerate() {
+				assert_eq!(reference[i].checksum(), session.checksum(), "session {} drifted at tick {}", i, tick);
+				assert!(spectators[i].diff(&session.state).unwrap().is_empty(), "spectator {} drifted at tick {}", i, tick);
+				// Full catch-up from a blank state reproduces the session
+				let (width, height) = session.state.dimensions();
+				let mut fresh = State::new(width, height);
+				State::new(width, height).diff(&session.state).unwrap().apply(&mut fresh).unwrap();
+				assert_eq!(format!("{:?}", session.state), format!("{:?}", fresh));
+				assert_eq!(session.telemetry.snapshot().ticks, tick);
+			}
+			// Memory in use settles after the first check and must not grow from there
+			let in_use = allocated();
+			match baseline {
+				None => baseline = Some(in_use),
+				Some(baseline) => assert!(in_use <= baseline + 16 * 1024, "{} bytes in use at tick {}, {} at first check", in_use, tick, baseline),
+			}
+		}
+	}
+	assert!(live.iter().all(|session| session.telemetry.snapshot().pieces > 0));
+}
+
+#[test]
+fn soak_short() {
+	soak(2, 4000);
+}
+
+#[cfg(feature = "soak")]
+#[test]
+fn soak_long() {
+	let ticks = std::env::var("TETRS_SOAK_TICKS").ok().and_then(|ticks| ticks.parse().ok()).unwrap_or(2_000_000);
+	let features = tetrs::enabled_features();
+	assert!(tetrs::is_supported_feature_set(&features), "unsupported feature set {:?}", features);
+	eprintln!("soak: {} ticks, features {:?}", ticks, features);
+	soak(4, ticks);
+}